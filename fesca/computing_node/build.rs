@@ -1,4 +1,6 @@
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     tonic_build::compile_protos("proto/share_service.proto")?;
+    tonic_build::compile_protos("proto/node_service.proto")?;
+    tonic_build::compile_protos("proto/query_service.proto")?;
     Ok(())
-} 
\ No newline at end of file
+}
@@ -0,0 +1,59 @@
+// Configuration Module
+// ====================
+// This module handles reading the computing node configuration file
+// (config_computing_node.json), which identifies the node within the
+// three-party ring and tells it where its neighbours are reachable.
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+
+use crate::exchange::channel::NUM_NODES;
+
+/// URLs of the other two computing nodes' exchange servers
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ComputationUrls {
+    /// Next node in the ring (node_id + 1 mod 3), receives this node's batches
+    pub url1: String,
+    /// Previous node in the ring (node_id - 1 mod 3), sends batches to this node
+    pub url2: String,
+}
+
+/// Configuration structure for a computing node
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ComputingNodeConfig {
+    pub computation_urls: ComputationUrls,
+    pub node_id: String,
+    pub storage_path: String,
+}
+
+impl ComputingNodeConfig {
+    /// Parse the configured node id, which must be 0, 1 or 2
+    pub fn node_id(&self) -> Result<u32> {
+        let node_id: u32 = self
+            .node_id
+            .trim()
+            .parse()
+            .map_err(|_| anyhow!("Invalid node_id '{}' in configuration", self.node_id))?;
+        if node_id >= NUM_NODES {
+            return Err(anyhow!("node_id must be 0, 1 or 2, got {}", node_id));
+        }
+        Ok(node_id)
+    }
+
+    /// Whether the ring neighbours are configured, i.e. the node can take
+    /// part in interactive protocols
+    pub fn has_peers(&self) -> bool {
+        !self.node_id.trim().is_empty() && !self.computation_urls.url1.trim().is_empty()
+    }
+}
+
+/// Loads the computing node configuration
+///
+/// # Arguments
+/// * `config_path` - Path to the config file (e.g., "config_computing_node.json")
+pub fn load_computing_node_config(config_path: &str) -> Result<ComputingNodeConfig> {
+    let file = File::open(config_path)?;
+    let config: ComputingNodeConfig = serde_json::from_reader(file)?;
+    Ok(config)
+}
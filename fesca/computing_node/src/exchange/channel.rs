@@ -0,0 +1,84 @@
+// Share Exchange Channel
+// ======================
+// Abstraction over the ring topology used by the replicated secret sharing
+// protocols: in every round node i sends one batch to node i+1 and receives
+// one batch from node i-1 (indices mod 3).
+
+use anyhow::Result;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::mailbox::Mailbox;
+use crate::helpers::secret_share::SecretShareSend;
+
+/// Number of computing nodes taking part in the protocol
+pub const NUM_NODES: u32 = 3;
+
+/// Default time to wait for the predecessor's batch of a round
+pub const DEFAULT_ROUND_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// One communication round with the neighbouring computing nodes
+#[tonic::async_trait]
+pub trait ShareExchange: Send + Sync {
+    /// Id of the local computing node (0, 1 or 2)
+    fn node_id(&self) -> u32;
+
+    /// Send `shares` to the next node and return the batch the previous node
+    /// sent for the same round
+    async fn exchange(
+        &self,
+        round_id: u64,
+        shares: Vec<SecretShareSend>,
+    ) -> Result<Vec<SecretShareSend>>;
+}
+
+/// Id of the node that receives this node's batches
+pub fn next_node(node_id: u32) -> u32 {
+    (node_id + 1) % NUM_NODES
+}
+
+/// Id of the node whose batches this node receives
+pub fn previous_node(node_id: u32) -> u32 {
+    (node_id + NUM_NODES - 1) % NUM_NODES
+}
+
+/// In-process exchange connecting three nodes through shared mailboxes.
+/// Used to run all parties in one process without gRPC.
+#[derive(Debug, Clone)]
+pub struct LocalExchange {
+    node_id: u32,
+    inbox: Arc<Mailbox>,
+    next_inbox: Arc<Mailbox>,
+}
+
+impl LocalExchange {
+    /// Create the three connected endpoints of a ring, indexed by node id
+    pub fn ring() -> [LocalExchange; 3] {
+        let inboxes = [
+            Arc::new(Mailbox::new()),
+            Arc::new(Mailbox::new()),
+            Arc::new(Mailbox::new()),
+        ];
+        [0, 1, 2].map(|node_id: u32| LocalExchange {
+            node_id,
+            inbox: inboxes[node_id as usize].clone(),
+            next_inbox: inboxes[next_node(node_id) as usize].clone(),
+        })
+    }
+}
+
+#[tonic::async_trait]
+impl ShareExchange for LocalExchange {
+    fn node_id(&self) -> u32 {
+        self.node_id
+    }
+
+    async fn exchange(
+        &self,
+        round_id: u64,
+        shares: Vec<SecretShareSend>,
+    ) -> Result<Vec<SecretShareSend>> {
+        self.next_inbox.deliver(round_id, shares)?;
+        self.inbox.receive_timeout(round_id, DEFAULT_ROUND_TIMEOUT).await
+    }
+}
@@ -0,0 +1,171 @@
+// Peer Exchange Client
+// ====================
+// gRPC client pushing share batches to the next computing node. Combined with
+// the local mailbox it implements one protocol round over the network.
+
+use anyhow::{anyhow, Result};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tonic::transport::Channel;
+use log::{debug, warn};
+
+use super::channel::{ShareExchange, DEFAULT_ROUND_TIMEOUT};
+use super::mailbox::Mailbox;
+use super::server::node_service::{
+    node_service_client::NodeServiceClient, SecretShareSend as ProtoSecretShareSend,
+    SendSharesRequest,
+};
+use crate::helpers::secret_share::SecretShareSend;
+
+/// Number of attempts to reach the next node before a round fails
+const CONNECT_ATTEMPTS: u32 = 20;
+
+/// Delay between two connection attempts
+const CONNECT_BACKOFF: Duration = Duration::from_millis(250);
+
+/// Network exchange with the neighbouring computing nodes over gRPC
+#[derive(Debug)]
+pub struct PeerExchange {
+    node_id: u32,
+    next_url: String,
+    mailbox: Arc<Mailbox>,
+    client: Mutex<Option<NodeServiceClient<Channel>>>,
+    round_timeout: Duration,
+}
+
+impl PeerExchange {
+    /// Create an exchange for `node_id` that pushes to `next_url` and reads
+    /// the previous node's batches from `mailbox`
+    pub fn new(node_id: u32, next_url: String, mailbox: Arc<Mailbox>) -> Self {
+        Self {
+            node_id,
+            next_url,
+            mailbox,
+            client: Mutex::new(None),
+            round_timeout: DEFAULT_ROUND_TIMEOUT,
+        }
+    }
+
+    /// Override how long to wait for the previous node's batch
+    pub fn with_round_timeout(mut self, round_timeout: Duration) -> Self {
+        self.round_timeout = round_timeout;
+        self
+    }
+
+    /// Connect to the next node, retrying while it is still starting up
+    async fn connect(&self) -> Result<NodeServiceClient<Channel>> {
+        let mut last_error = None;
+        for attempt in 1..=CONNECT_ATTEMPTS {
+            match Channel::from_shared(self.next_url.clone())?.connect().await {
+                Ok(channel) => return Ok(NodeServiceClient::new(channel)),
+                Err(e) => {
+                    debug!("Connection attempt {} to {} failed: {}", attempt, self.next_url, e);
+                    last_error = Some(e);
+                    tokio::time::sleep(CONNECT_BACKOFF).await;
+                }
+            }
+        }
+        Err(anyhow!(
+            "Could not connect to next node at {}: {:?}",
+            self.next_url,
+            last_error
+        ))
+    }
+
+    /// Push a batch of shares for `round_id` to the next node
+    pub async fn send_to_next(&self, round_id: u64, shares: Vec<SecretShareSend>) -> Result<()> {
+        let mut guard = self.client.lock().await;
+        if guard.is_none() {
+            *guard = Some(self.connect().await?);
+        }
+        let client = guard.as_mut().expect("client connected above");
+
+        let request = tonic::Request::new(SendSharesRequest {
+            from_node: self.node_id,
+            round_id,
+            shares: shares
+                .into_iter()
+                .map(|s| ProtoSecretShareSend { id: s.id, share: s.share })
+                .collect(),
+        });
+
+        match client.send_shares(request).await {
+            Ok(response) if response.get_ref().success => Ok(()),
+            Ok(response) => Err(anyhow!(
+                "Next node rejected round {}: {}",
+                round_id,
+                response.into_inner().message
+            )),
+            Err(status) => {
+                // Drop the connection so the next round reconnects
+                warn!("Sending round {} to {} failed: {}", round_id, self.next_url, status);
+                *guard = None;
+                Err(status.into())
+            }
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl ShareExchange for PeerExchange {
+    fn node_id(&self) -> u32 {
+        self.node_id
+    }
+
+    async fn exchange(
+        &self,
+        round_id: u64,
+        shares: Vec<SecretShareSend>,
+    ) -> Result<Vec<SecretShareSend>> {
+        // A batch the next node rejects fails the round right away instead of
+        // after the receive timeout
+        let (_, received) = tokio::try_join!(
+            self.send_to_next(round_id, shares),
+            self.mailbox.receive_timeout(round_id, self.round_timeout)
+        )?;
+        Ok(received)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    use crate::exchange::server::serve_exchange;
+    use crate::handshake;
+
+    #[tokio::test]
+    async fn test_handshake_over_grpc() {
+        // Port 0 lets the system pick free ports, so parallel test runs do not collide
+        let mut addresses = Vec::new();
+        let mailboxes: Vec<Arc<Mailbox>> = (0..3).map(|_| Arc::new(Mailbox::new())).collect();
+        for (node_id, mailbox) in (0..3u32).zip(&mailboxes) {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            addresses.push(listener.local_addr().unwrap());
+            tokio::spawn(serve_exchange(listener, node_id, mailbox.clone()));
+        }
+
+        let exchanges: Vec<PeerExchange> = (0..3u32)
+            .map(|node_id| {
+                let next = addresses[((node_id + 1) % 3) as usize];
+                PeerExchange::new(
+                    node_id,
+                    format!("http://{}", next),
+                    mailboxes[node_id as usize].clone(),
+                )
+                .with_round_timeout(Duration::from_secs(10))
+            })
+            .collect();
+
+        let (r0, r1, r2) = tokio::join!(
+            handshake(&exchanges[0]),
+            handshake(&exchanges[1]),
+            handshake(&exchanges[2]),
+        );
+        r0.unwrap();
+        r1.unwrap();
+        r2.unwrap();
+    }
+}
@@ -0,0 +1,128 @@
+// Share Mailbox
+// =============
+// Buffers share batches pushed by the previous computing node until the
+// local protocol asks for them. Batches are keyed by round / gate identifier,
+// so a batch that arrives before the local node reaches that round is kept.
+// Every round carries exactly one batch: a second batch for a round that is
+// still waiting is rejected rather than replacing the first.
+
+use anyhow::{anyhow, Result};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::Notify;
+
+use crate::helpers::secret_share::SecretShareSend;
+
+/// Buffer of share batches received from the previous computing node
+#[derive(Debug, Default)]
+pub struct Mailbox {
+    batches: Mutex<HashMap<u64, Vec<SecretShareSend>>>,
+    notify: Notify,
+}
+
+impl Mailbox {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Store a batch for the given round and wake up any waiting receiver.
+    /// Fails, keeping the first batch, if a batch for this round is already waiting.
+    pub fn deliver(&self, round_id: u64, shares: Vec<SecretShareSend>) -> Result<()> {
+        {
+            let mut batches = self.batches.lock().expect("mailbox lock poisoned");
+            match batches.entry(round_id) {
+                Entry::Occupied(_) => return Err(anyhow!("Duplicate batch for round {}", round_id)),
+                Entry::Vacant(entry) => entry.insert(shares),
+            };
+        }
+        self.notify.notify_waiters();
+        Ok(())
+    }
+
+    /// Remove and return the batch for the given round if it has arrived
+    pub fn take(&self, round_id: u64) -> Option<Vec<SecretShareSend>> {
+        self.batches
+            .lock()
+            .expect("mailbox lock poisoned")
+            .remove(&round_id)
+    }
+
+    /// Wait until the batch for the given round arrives
+    pub async fn receive(&self, round_id: u64) -> Vec<SecretShareSend> {
+        loop {
+            // Register for notification before checking to avoid missing a delivery
+            let notified = self.notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            if let Some(shares) = self.take(round_id) {
+                return shares;
+            }
+            notified.await;
+        }
+    }
+
+    /// Wait for the batch of the given round, failing after `timeout`
+    pub async fn receive_timeout(
+        &self,
+        round_id: u64,
+        timeout: Duration,
+    ) -> Result<Vec<SecretShareSend>> {
+        tokio::time::timeout(timeout, self.receive(round_id))
+            .await
+            .map_err(|_| anyhow!("Timed out waiting for shares of round {}", round_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_batch_delivered_before_receive() {
+        let mailbox = Mailbox::new();
+        mailbox.deliver(7, vec![SecretShareSend { id: 1, share: 42 }]).unwrap();
+
+        let shares = mailbox.receive(7).await;
+        assert_eq!(shares, vec![SecretShareSend { id: 1, share: 42 }]);
+        assert!(mailbox.take(7).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_receive_waits_for_delivery() {
+        let mailbox = Arc::new(Mailbox::new());
+        let waiter = {
+            let mailbox = mailbox.clone();
+            tokio::spawn(async move { mailbox.receive(3).await })
+        };
+
+        // A batch for another round must not wake the waiter with wrong data
+        mailbox.deliver(2, vec![SecretShareSend { id: 0, share: 1 }]).unwrap();
+        mailbox.deliver(3, vec![SecretShareSend { id: 0, share: 9 }]).unwrap();
+
+        let shares = waiter.await.unwrap();
+        assert_eq!(shares[0].share, 9);
+        assert!(mailbox.take(2).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_batch_is_rejected() {
+        let mailbox = Mailbox::new();
+        mailbox.deliver(5, vec![SecretShareSend { id: 0, share: 1 }]).unwrap();
+        assert!(mailbox.deliver(5, vec![SecretShareSend { id: 0, share: 2 }]).is_err());
+        assert_eq!(mailbox.receive(5).await[0].share, 1);
+
+        // Once taken, the round can be used again
+        mailbox.deliver(5, vec![SecretShareSend { id: 0, share: 3 }]).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_receive_timeout() {
+        let mailbox = Mailbox::new();
+        let result = mailbox.receive_timeout(1, Duration::from_millis(10)).await;
+        assert!(result.is_err());
+    }
+}
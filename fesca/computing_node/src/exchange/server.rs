@@ -0,0 +1,127 @@
+// Peer Exchange Server
+// ====================
+// gRPC server implementation for receiving share batches from the previous
+// computing node during protocol execution
+
+use anyhow::{anyhow, Result};
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tonic::transport::server::TcpIncoming;
+use tonic::{transport::Server, Request, Response, Status};
+use log::info;
+
+// Include the generated protobuf code
+pub mod node_service {
+    tonic::include_proto!("node_service");
+}
+
+use node_service::{
+    node_service_server::{NodeService, NodeServiceServer},
+    SendSharesRequest, SendSharesResponse,
+};
+
+use super::channel::{previous_node, NUM_NODES};
+use super::mailbox::Mailbox;
+use crate::helpers::secret_share::SecretShareSend;
+
+/// gRPC service implementation that puts incoming batches into the mailbox.
+/// Only the previous node in the ring may deliver, so no other node can
+/// claim a round before it.
+#[derive(Debug)]
+pub struct PeerReceiver {
+    previous_node: u32,
+    mailbox: Arc<Mailbox>,
+}
+
+impl PeerReceiver {
+    /// Receiver accepting batches from `previous_node` only
+    pub fn new(previous_node: u32, mailbox: Arc<Mailbox>) -> Self {
+        Self { previous_node, mailbox }
+    }
+}
+
+#[tonic::async_trait]
+impl NodeService for PeerReceiver {
+    /// Receive a batch of shares from the previous computing node
+    async fn send_shares(
+        &self,
+        request: Request<SendSharesRequest>,
+    ) -> Result<Response<SendSharesResponse>, Status> {
+        let req = request.into_inner();
+
+        if req.from_node >= NUM_NODES {
+            return Err(Status::invalid_argument(format!(
+                "Invalid sender node id: {}",
+                req.from_node
+            )));
+        }
+        if req.from_node != self.previous_node {
+            return Err(Status::permission_denied(format!(
+                "Node {} may not send batches to this node, only node {} may",
+                req.from_node, self.previous_node
+            )));
+        }
+
+        let shares: Vec<SecretShareSend> = req
+            .shares
+            .into_iter()
+            .map(|s| SecretShareSend { id: s.id, share: s.share })
+            .collect();
+        let count = shares.len();
+
+        self.mailbox
+            .deliver(req.round_id, shares)
+            .map_err(|e| Status::already_exists(format!("{} (from node {})", e, req.from_node)))?;
+
+        Ok(Response::new(SendSharesResponse {
+            success: true,
+            message: format!("Stored {} shares for round {}", count, req.round_id),
+        }))
+    }
+}
+
+/// Start the exchange server of node `node_id` delivering into `mailbox`
+pub async fn start_exchange_server(port: u16, node_id: u32, mailbox: Arc<Mailbox>) -> Result<()> {
+    let listener = TcpListener::bind(("0.0.0.0", port)).await?;
+    serve_exchange(listener, node_id, mailbox).await
+}
+
+/// Run the exchange server of node `node_id` on an already bound listener,
+/// e.g. one bound to port 0 so the system picks a free port
+pub async fn serve_exchange(listener: TcpListener, node_id: u32, mailbox: Arc<Mailbox>) -> Result<()> {
+    info!("Starting computing node exchange server on {}", listener.local_addr()?);
+
+    Server::builder()
+        .add_service(NodeServiceServer::new(PeerReceiver::new(previous_node(node_id), mailbox)))
+        .serve_with_incoming(TcpIncoming::from_listener(listener, true, None).map_err(|e| anyhow!(e))?)
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use node_service::SecretShareSend as ShareMessage;
+
+    fn batch(from_node: u32, round_id: u64, share: u64) -> Request<SendSharesRequest> {
+        Request::new(SendSharesRequest {
+            from_node,
+            round_id,
+            shares: vec![ShareMessage { id: 0, share }],
+        })
+    }
+
+    #[tokio::test]
+    async fn test_only_previous_node_may_deliver() {
+        let mailbox = Arc::new(Mailbox::new());
+        let receiver = PeerReceiver::new(previous_node(1), mailbox.clone());
+
+        let status = receiver.send_shares(batch(2, 4, 7)).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
+        assert!(mailbox.take(4).is_none());
+
+        receiver.send_shares(batch(0, 4, 9)).await.unwrap();
+        assert_eq!(mailbox.take(4).unwrap()[0].share, 9);
+    }
+}
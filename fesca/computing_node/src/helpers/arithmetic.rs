@@ -0,0 +1,327 @@
+// Arithmetic Shares
+// =================
+// Replicated additive sharing over Z_2^64: a value x = x_0 + x_1 + x_2
+// (wrapping) and node i holds (x_i, x_{i-1}), the same layout as the boolean
+// shares. Addition and multiplication by a public constant are local; the
+// product of two shared values takes one round:
+//     z_i = x_i y_i + x_i y_{i-1} + x_{i-1} y_i + alpha_i
+// with an arithmetic zero-sharing alpha, after which node i sends z_i to the
+// next node, exactly like the boolean AND.
+//
+// Columns shared arithmetically by the data owner are stored as 64-bit
+// fields, so the stored share bits of such a column are the two u64 shares.
+
+use anyhow::{anyhow, Result};
+use std::path::Path;
+
+use super::bit_column::SharedBitColumn;
+use super::context::ProtocolContext;
+
+/// Arithmetic shares of a whole column, one share pair per row
+#[derive(Debug, Clone, PartialEq)]
+pub struct SharedArithColumn {
+    pub party_id: u32,
+    pub own: Vec<u64>,
+    pub prev: Vec<u64>,
+}
+
+impl SharedArithColumn {
+    /// Sharing of zero in every row
+    pub fn zeros(party_id: u32, rows: usize) -> Self {
+        Self {
+            party_id,
+            own: vec![0; rows],
+            prev: vec![0; rows],
+        }
+    }
+
+    /// Sharing of a public constant in every row: x_0 = value, x_1 = x_2 = 0
+    pub fn constant(party_id: u32, rows: usize, value: u64) -> Self {
+        Self::zeros(party_id, rows).add_const(value)
+    }
+
+    /// Number of rows
+    pub fn rows(&self) -> usize {
+        self.own.len()
+    }
+
+    fn check_compatible(&self, other: &SharedArithColumn) -> Result<()> {
+        if self.rows() != other.rows() {
+            return Err(anyhow!("Column length mismatch: {} vs {}", self.rows(), other.rows()));
+        }
+        Ok(())
+    }
+
+    fn zip_with(&self, other: &SharedArithColumn, f: impl Fn(u64, u64) -> u64) -> Result<SharedArithColumn> {
+        self.check_compatible(other)?;
+        Ok(SharedArithColumn {
+            party_id: self.party_id,
+            own: self.own.iter().zip(&other.own).map(|(a, b)| f(*a, *b)).collect(),
+            prev: self.prev.iter().zip(&other.prev).map(|(a, b)| f(*a, *b)).collect(),
+        })
+    }
+
+    /// Local addition
+    pub fn add(&self, other: &SharedArithColumn) -> Result<SharedArithColumn> {
+        self.zip_with(other, u64::wrapping_add)
+    }
+
+    /// Local subtraction
+    pub fn sub(&self, other: &SharedArithColumn) -> Result<SharedArithColumn> {
+        self.zip_with(other, u64::wrapping_sub)
+    }
+
+    /// Local negation
+    pub fn neg(&self) -> SharedArithColumn {
+        self.mul_const(u64::MAX)
+    }
+
+    /// Add a public constant: only x_0 changes, held by node 0 (own) and node 1 (prev)
+    pub fn add_const(&self, value: u64) -> SharedArithColumn {
+        let mut result = self.clone();
+        match self.party_id {
+            0 => result.own.iter_mut().for_each(|x| *x = x.wrapping_add(value)),
+            1 => result.prev.iter_mut().for_each(|x| *x = x.wrapping_add(value)),
+            _ => {}
+        }
+        result
+    }
+
+    /// Rows `start..start + count`; local
+    pub fn slice_rows(&self, start: usize, count: usize) -> SharedArithColumn {
+        self.slice_blocks(start, count, count, 1)
+    }
+
+    /// `blocks` blocks of `block` rows, block q starting at row
+    /// `start + q * stride`; local
+    pub fn slice_blocks(&self, start: usize, block: usize, stride: usize, blocks: usize) -> SharedArithColumn {
+        let pick = |shares: &[u64]| -> Vec<u64> {
+            (0..blocks)
+                .flat_map(|q| shares[start + q * stride..start + q * stride + block].iter().copied())
+                .collect()
+        };
+        SharedArithColumn {
+            party_id: self.party_id,
+            own: pick(&self.own),
+            prev: pick(&self.prev),
+        }
+    }
+
+    /// Columns one after the other; local
+    pub fn concat_rows(party_id: u32, parts: &[&SharedArithColumn]) -> SharedArithColumn {
+        SharedArithColumn {
+            party_id,
+            own: parts.iter().flat_map(|part| part.own.iter().copied()).collect(),
+            prev: parts.iter().flat_map(|part| part.prev.iter().copied()).collect(),
+        }
+    }
+
+    /// Blocks of `block` rows taken from each column in turn; local
+    pub fn interleave_rows(party_id: u32, parts: &[&SharedArithColumn], block: usize) -> SharedArithColumn {
+        let rows = parts.first().map_or(0, |part| part.rows());
+        let pick = |shares: fn(&SharedArithColumn) -> &Vec<u64>| -> Vec<u64> {
+            (0..rows / block)
+                .flat_map(|q| parts.iter().flat_map(move |part| shares(part)[q * block..(q + 1) * block].iter().copied()))
+                .collect()
+        };
+        SharedArithColumn {
+            party_id,
+            own: pick(|part| &part.own),
+            prev: pick(|part| &part.prev),
+        }
+    }
+
+    /// Local multiplication by a public constant
+    pub fn mul_const(&self, value: u64) -> SharedArithColumn {
+        SharedArithColumn {
+            party_id: self.party_id,
+            own: self.own.iter().map(|x| x.wrapping_mul(value)).collect(),
+            prev: self.prev.iter().map(|x| x.wrapping_mul(value)).collect(),
+        }
+    }
+
+    /// Row-wise product in one communication round
+    pub async fn mul(&self, other: &SharedArithColumn, ctx: &mut ProtocolContext) -> Result<SharedArithColumn> {
+        let mut result = mul_columns(ctx, &[(self, other)]).await?;
+        Ok(result.remove(0))
+    }
+
+    /// Local part of the multiplication protocol: this node's new share before resharing
+    pub(crate) fn mul_local(&self, other: &SharedArithColumn, masks: &[u64]) -> Vec<u64> {
+        (0..self.rows())
+            .map(|r| {
+                self.own[r].wrapping_mul(other.own[r])
+                    .wrapping_add(self.own[r].wrapping_mul(other.prev[r]))
+                    .wrapping_add(self.prev[r].wrapping_mul(other.own[r]))
+                    .wrapping_add(masks[r])
+            })
+            .collect()
+    }
+
+    /// Local sum over all rows, as a single-row column
+    pub fn sum(&self) -> SharedArithColumn {
+        let total = |shares: &[u64]| shares.iter().fold(0u64, |acc, x| acc.wrapping_add(*x));
+        SharedArithColumn {
+            party_id: self.party_id,
+            own: vec![total(&self.own)],
+            prev: vec![total(&self.prev)],
+        }
+    }
+
+    /// Reinterpret the stored share bits of an arithmetically shared column
+    /// (64 bits per share, LSB first) as u64 shares
+    pub fn from_stored(column: &SharedBitColumn) -> Result<SharedArithColumn> {
+        if column.width() != 64 {
+            return Err(anyhow!(
+                "Arithmetically shared columns are 64 bits wide, got {}",
+                column.width()
+            ));
+        }
+        let mut result = SharedArithColumn::zeros(column.party_id, column.rows);
+        for (j, plane) in column.planes.iter().enumerate() {
+            for r in 0..column.rows {
+                let (own, prev) = plane.get(r);
+                result.own[r] |= (own as u64) << j;
+                result.prev[r] |= (prev as u64) << j;
+            }
+        }
+        Ok(result)
+    }
+
+    /// Load an arithmetically shared column from a `party{N}_data.bin` file
+    pub fn load(
+        path: &Path,
+        party_id: u32,
+        table_id: u32,
+        schema_hash: u64,
+        column_index: usize,
+    ) -> Result<SharedArithColumn> {
+        Self::from_stored(&SharedBitColumn::load(path, party_id, table_id, schema_hash, column_index)?)
+    }
+}
+
+/// Row-wise products of several column pairs, all in one communication round
+pub async fn mul_columns(
+    ctx: &mut ProtocolContext,
+    pairs: &[(&SharedArithColumn, &SharedArithColumn)],
+) -> Result<Vec<SharedArithColumn>> {
+    for (a, b) in pairs {
+        a.check_compatible(b)?;
+    }
+    let total_rows: usize = pairs.iter().map(|(a, _)| a.rows()).sum();
+    let masks = ctx.randomness().next_arith_masks(total_rows);
+
+    let mut local = Vec::with_capacity(total_rows);
+    for (a, b) in pairs {
+        let offset = local.len();
+        local.extend(a.mul_local(b, &masks[offset..offset + a.rows()]));
+    }
+
+    let received = ctx.exchange_words(local.clone()).await?;
+
+    let mut result = Vec::with_capacity(pairs.len());
+    let mut offset = 0;
+    for (a, _) in pairs {
+        let rows = a.rows();
+        result.push(SharedArithColumn {
+            party_id: a.party_id,
+            own: local[offset..offset + rows].to_vec(),
+            prev: received[offset..offset + rows].to_vec(),
+        });
+        offset += rows;
+    }
+    Ok(result)
+}
+
+/// Reveal an arithmetic column to this node in one round
+pub async fn open_arith(ctx: &mut ProtocolContext, column: &SharedArithColumn) -> Result<Vec<u64>> {
+    let received = ctx.exchange_words(column.prev.clone()).await?;
+    Ok((0..column.rows())
+        .map(|r| column.own[r].wrapping_add(column.prev[r]).wrapping_add(received[r]))
+        .collect())
+}
+
+#[cfg(test)]
+pub(crate) mod testing {
+    use super::*;
+
+    /// Dealer-side additive sharing of plaintext values
+    pub fn share_arith_values(values: &[u64]) -> [SharedArithColumn; 3] {
+        let mut parts = [0, 1, 2].map(|p| SharedArithColumn::zeros(p, values.len()));
+        for (r, value) in values.iter().enumerate() {
+            let x0: u64 = rand::random();
+            let x1: u64 = rand::random();
+            let x = [x0, x1, value.wrapping_sub(x0).wrapping_sub(x1)];
+            for (p, part) in parts.iter_mut().enumerate() {
+                part.own[r] = x[p];
+                part.prev[r] = x[(p + 2) % 3];
+            }
+        }
+        parts
+    }
+
+    /// Reconstruct values from the three nodes' columns
+    pub fn reveal_arith(parts: &[SharedArithColumn]) -> Vec<u64> {
+        (0..parts[0].rows())
+            .map(|r| parts.iter().fold(0u64, |acc, p| acc.wrapping_add(p.own[r])))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::*;
+    use super::*;
+    use crate::helpers::bit_column::testing::{run_parties, share_values};
+
+    #[tokio::test]
+    async fn test_arithmetic_operations() {
+        let a = [3u64, 5000, u64::MAX, 0, 123_456_789];
+        let b = [4u64, 2, 2, 77, 987_654_321];
+        let (sa, sb) = (share_arith_values(&a), share_arith_values(&b));
+
+        let results = run_parties(|mut ctx, p| {
+            let (a, b) = (sa[p].clone(), sb[p].clone());
+            Box::pin(async move {
+                let linear = a.add(&b).unwrap().mul_const(3).add_const(10).sub(&b.neg()).unwrap();
+                let product = a.mul(&b, &mut ctx).await.unwrap();
+                let total = product.sum();
+                let opened = open_arith(&mut ctx, &total).await.unwrap();
+                assert_eq!(ctx.stats().rounds, 2);
+                (linear, product, opened)
+            })
+        })
+        .await;
+
+        let column = |i: usize| -> Vec<SharedArithColumn> {
+            results.iter().map(|r| if i == 0 { r.0.clone() } else { r.1.clone() }).collect()
+        };
+        let linear: Vec<u64> = a
+            .iter()
+            .zip(&b)
+            .map(|(x, y)| x.wrapping_add(*y).wrapping_mul(3).wrapping_add(10).wrapping_add(*y))
+            .collect();
+        let products: Vec<u64> = a.iter().zip(&b).map(|(x, y)| x.wrapping_mul(*y)).collect();
+        assert_eq!(reveal_arith(&column(0)), linear);
+        assert_eq!(reveal_arith(&column(1)), products);
+        let total = products.iter().fold(0u64, |acc, x| acc.wrapping_add(*x));
+        for r in &results {
+            assert_eq!(r.2, vec![total]);
+        }
+    }
+
+    #[test]
+    fn test_from_stored_bits() {
+        // A 64-bit boolean layout of additive shares reads back as the same shares
+        let values = [1u64, 1 << 63, 0xDEAD_BEEF_0123_4567];
+        let stored = share_values(&values, 64);
+        let parts = stored.clone().map(|s| SharedArithColumn::from_stored(&s).unwrap());
+        for (part, column) in parts.iter().zip(&stored) {
+            for r in 0..values.len() {
+                let own = (0..64).fold(0u64, |acc, j| acc | ((column.planes[j].get(r).0 as u64) << j));
+                assert_eq!(part.own[r], own);
+            }
+        }
+        assert!(SharedArithColumn::from_stored(&SharedBitColumn::zeros(0, 3, 32)).is_err());
+    }
+}
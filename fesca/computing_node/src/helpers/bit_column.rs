@@ -0,0 +1,622 @@
+// Bit-Sliced Shared Columns
+// =========================
+// Vectorized boolean shares for whole table columns. A column of `width`-bit
+// values over `rows` rows is stored as `width` bit-planes; plane j packs bit j
+// of every row into u64 words (row r lives in word r / 64, bit r % 64). Each
+// plane carries the node's replicated share pair (own, prev), so one gate on a
+// column evaluates the gate for every row and every bit at once, and an AND
+// over any number of planes costs a single communication round.
+
+use anyhow::{anyhow, Result};
+use std::path::Path;
+
+use crate::receive::server::share_service::BinaryPartyData;
+use crate::receive::columnar::{self, ColumnarFile};
+use crate::receive::storage::{extract_column_bits, BinaryShareStorage};
+use super::context::ProtocolContext;
+
+/// Number of rows packed into one word of a bit-plane
+pub const WORD_BITS: usize = 64;
+
+/// Number of words needed to pack `rows` bits
+pub fn words_for(rows: usize) -> usize {
+    rows.div_ceil(WORD_BITS)
+}
+
+/// Replicated shares of one bit position across all rows
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SharedBits {
+    pub own: Vec<u64>,
+    pub prev: Vec<u64>,
+}
+
+impl SharedBits {
+    /// Plane of `words` zero words (a valid sharing of all-zero bits)
+    pub fn zeros(words: usize) -> Self {
+        Self {
+            own: vec![0; words],
+            prev: vec![0; words],
+        }
+    }
+
+    /// Local XOR of two planes
+    pub fn xor(&self, other: &SharedBits) -> SharedBits {
+        SharedBits {
+            own: self.own.iter().zip(&other.own).map(|(a, b)| a ^ b).collect(),
+            prev: self.prev.iter().zip(&other.prev).map(|(a, b)| a ^ b).collect(),
+        }
+    }
+
+    /// Local NOT of a plane of `rows` rows: only share x_0 is flipped, held by
+    /// node 0 (own) and node 1 (prev). The padding bits past `rows` stay zero.
+    pub fn not(&self, party_id: u32, rows: usize) -> SharedBits {
+        let mut result = self.clone();
+        match party_id {
+            0 => result.own.iter_mut().for_each(|w| *w = !*w),
+            1 => result.prev.iter_mut().for_each(|w| *w = !*w),
+            _ => {}
+        }
+        result.clear_padding(rows);
+        result
+    }
+
+    /// Zero the bits past the last of `rows` rows
+    pub fn clear_padding(&mut self, rows: usize) {
+        let used = rows % WORD_BITS;
+        if used != 0 {
+            let mask = (1u64 << used) - 1;
+            if let (Some(own), Some(prev)) = (self.own.last_mut(), self.prev.last_mut()) {
+                *own &= mask;
+                *prev &= mask;
+            }
+        }
+    }
+
+    /// Local part of the AND protocol: this node's new share before resharing
+    pub(crate) fn and_local(&self, other: &SharedBits, masks: &[u64]) -> Vec<u64> {
+        (0..self.own.len())
+            .map(|w| {
+                (self.own[w] & other.own[w])
+                    ^ (self.own[w] & other.prev[w])
+                    ^ (self.prev[w] & other.own[w])
+                    ^ masks[w]
+            })
+            .collect()
+    }
+
+    /// Shares (own, prev) of the bit in `row`
+    pub fn get(&self, row: usize) -> (bool, bool) {
+        let (word, bit) = (row / WORD_BITS, row % WORD_BITS);
+        ((self.own[word] >> bit) & 1 == 1, (self.prev[word] >> bit) & 1 == 1)
+    }
+
+    /// Set the shares (own, prev) of the bit in `row`
+    pub fn set(&mut self, row: usize, own: bool, prev: bool) {
+        let (word, bit) = (row / WORD_BITS, row % WORD_BITS);
+        self.own[word] = (self.own[word] & !(1 << bit)) | ((own as u64) << bit);
+        self.prev[word] = (self.prev[word] & !(1 << bit)) | ((prev as u64) << bit);
+    }
+
+    /// Rows `start..start + count` as a plane of their own; local
+    pub fn slice_rows(&self, start: usize, count: usize) -> SharedBits {
+        self.slice_blocks(start, count, count, 1)
+    }
+
+    /// `blocks` blocks of `block` rows, block q starting at row
+    /// `start + q * stride`; local
+    pub fn slice_blocks(&self, start: usize, block: usize, stride: usize, blocks: usize) -> SharedBits {
+        let mut result = SharedBits::zeros(words_for(block * blocks));
+        for q in 0..blocks {
+            result.copy_rows(q * block, self, start + q * stride, block);
+        }
+        result
+    }
+
+    /// Planes one after the other, each given with its number of rows; local
+    pub fn concat_rows(parts: &[(&SharedBits, usize)]) -> SharedBits {
+        let rows = parts.iter().map(|(_, count)| count).sum();
+        let mut result = SharedBits::zeros(words_for(rows));
+        let mut offset = 0;
+        for (plane, count) in parts {
+            result.copy_rows(offset, plane, 0, *count);
+            offset += count;
+        }
+        result
+    }
+
+    /// Blocks of `block` rows taken from each plane in turn; every plane has
+    /// `rows` rows; local
+    pub fn interleave_rows(planes: &[&SharedBits], rows: usize, block: usize) -> SharedBits {
+        let mut result = SharedBits::zeros(words_for(rows * planes.len()));
+        let mut offset = 0;
+        for q in 0..rows / block {
+            for plane in planes {
+                result.copy_rows(offset, plane, q * block, block);
+                offset += block;
+            }
+        }
+        result
+    }
+
+    /// Copy `count` rows of `source` starting at row `from` to the rows
+    /// starting at `to`, which must still be zero
+    fn copy_rows(&mut self, to: usize, source: &SharedBits, from: usize, count: usize) {
+        if count < WORD_BITS {
+            for k in 0..count {
+                let (own, prev) = source.get(from + k);
+                self.set(to + k, own, prev);
+            }
+        } else {
+            or_bits(&mut self.own, to, &extract_bits(&source.own, from, count));
+            or_bits(&mut self.prev, to, &extract_bits(&source.prev, from, count));
+        }
+    }
+}
+
+/// `count` bits of a packed bit vector starting at bit `start`, packed from
+/// bit 0 with the bits past `count` cleared
+fn extract_bits(words: &[u64], start: usize, count: usize) -> Vec<u64> {
+    let (first, shift) = (start / WORD_BITS, start % WORD_BITS);
+    let word = |i: usize| words.get(i).copied().unwrap_or(0);
+    let mut result: Vec<u64> = (0..words_for(count))
+        .map(|k| {
+            let low = word(first + k) >> shift;
+            let high = if shift == 0 { 0 } else { word(first + k + 1) << (WORD_BITS - shift) };
+            low | high
+        })
+        .collect();
+    if !count.is_multiple_of(WORD_BITS) {
+        if let Some(last) = result.last_mut() {
+            *last &= (1u64 << (count % WORD_BITS)) - 1;
+        }
+    }
+    result
+}
+
+/// OR packed bits into a packed bit vector from bit `offset` on
+fn or_bits(words: &mut [u64], offset: usize, bits: &[u64]) {
+    let shift = offset % WORD_BITS;
+    for (k, bit_word) in bits.iter().enumerate() {
+        let index = offset / WORD_BITS + k;
+        words[index] |= bit_word << shift;
+        if shift != 0 && index + 1 < words.len() {
+            words[index + 1] |= bit_word >> (WORD_BITS - shift);
+        }
+    }
+}
+
+/// Boolean shares of a whole column, one bit-plane per bit (LSB first)
+#[derive(Debug, Clone, PartialEq)]
+pub struct SharedBitColumn {
+    pub party_id: u32,
+    pub rows: usize,
+    pub planes: Vec<SharedBits>,
+}
+
+impl SharedBitColumn {
+    /// Sharing of all-zero values
+    pub fn zeros(party_id: u32, rows: usize, width: usize) -> Self {
+        Self {
+            party_id,
+            rows,
+            planes: vec![SharedBits::zeros(words_for(rows)); width],
+        }
+    }
+
+    /// Sharing of a public constant in every row: x_0 = value, x_1 = x_2 = 0
+    pub fn constant(party_id: u32, rows: usize, width: usize, value: u64) -> Self {
+        let mut column = Self::zeros(party_id, rows, width);
+        for (j, plane) in column.planes.iter_mut().enumerate() {
+            let bit_set = j < 64 && (value >> j) & 1 == 1;
+            if !bit_set {
+                continue;
+            }
+            *plane = plane.not(party_id, rows);
+        }
+        column
+    }
+
+    /// Number of bits per value
+    pub fn width(&self) -> usize {
+        self.planes.len()
+    }
+
+    /// Single-bit column holding plane `j`
+    pub fn bit(&self, j: usize) -> SharedBitColumn {
+        SharedBitColumn {
+            party_id: self.party_id,
+            rows: self.rows,
+            planes: vec![self.planes[j].clone()],
+        }
+    }
+
+    /// Column repeating the single plane of a 1-bit column `width` times,
+    /// e.g. to AND a predicate bit into every bit of a value
+    pub fn broadcast(&self, width: usize) -> SharedBitColumn {
+        SharedBitColumn {
+            party_id: self.party_id,
+            rows: self.rows,
+            planes: vec![self.planes[0].clone(); width],
+        }
+    }
+
+    fn check_compatible(&self, other: &SharedBitColumn) -> Result<()> {
+        if self.rows != other.rows || self.width() != other.width() {
+            return Err(anyhow!(
+                "Column shape mismatch: {}x{} vs {}x{}",
+                self.rows,
+                self.width(),
+                other.rows,
+                other.width()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Local XOR of two columns of the same shape
+    pub fn xor(&self, other: &SharedBitColumn) -> Result<SharedBitColumn> {
+        self.check_compatible(other)?;
+        Ok(SharedBitColumn {
+            party_id: self.party_id,
+            rows: self.rows,
+            planes: self.planes.iter().zip(&other.planes).map(|(a, b)| a.xor(b)).collect(),
+        })
+    }
+
+    /// Local bitwise NOT
+    pub fn not(&self) -> SharedBitColumn {
+        SharedBitColumn {
+            party_id: self.party_id,
+            rows: self.rows,
+            planes: self.planes.iter().map(|p| p.not(self.party_id, self.rows)).collect(),
+        }
+    }
+
+    /// Bitwise AND of two columns in one communication round
+    pub async fn and(&self, other: &SharedBitColumn, ctx: &mut ProtocolContext) -> Result<SharedBitColumn> {
+        let mut result = and_columns(ctx, &[(self, other)]).await?;
+        Ok(result.remove(0))
+    }
+
+    /// Build the column at `column_index` from the rows received from a data owner
+    pub fn from_party_data(data: &BinaryPartyData, column_index: usize) -> Result<SharedBitColumn> {
+        let rows = data.rows.len();
+        let width = match data.rows.first() {
+            Some(row) => extract_column_bits(row, column_index)?.bits_a.len(),
+            None => 0,
+        };
+
+        let mut column = SharedBitColumn::zeros(data.party_id, rows, width);
+        for (r, row) in data.rows.iter().enumerate() {
+            let bits = extract_column_bits(row, column_index)?;
+            if bits.bits_a.len() != width {
+                return Err(anyhow!("Row {} has a different width for column {}", r, column_index));
+            }
+            for (j, plane) in column.planes.iter_mut().enumerate() {
+                plane.set(r, bits.bits_a[j], bits.bits_b[j]);
+            }
+        }
+        Ok(column)
+    }
+
+    /// Load one column of table `table_id` from a `party{N}_data.bin` file
+    /// written by `BinaryShareStorage`. Columnar (v2) files are read without
+    /// touching the other columns and must carry `schema_hash`, the hash of
+    /// the schema the table was shared with; v1 files carry no hash.
+    pub fn load(
+        path: &Path,
+        party_id: u32,
+        table_id: u32,
+        schema_hash: u64,
+        column_index: usize,
+    ) -> Result<SharedBitColumn> {
+        if columnar::is_columnar(path)? {
+            let mut file = ColumnarFile::open(path)?;
+            let header = &file.header;
+            if (header.party_id, header.table_id, header.schema_hash) != (party_id, table_id, schema_hash) {
+                return Err(anyhow!(
+                    "{} holds table {} (schema {:016x}) of party {}, expected table {} (schema {:016x}) of party {}",
+                    path.display(),
+                    header.table_id,
+                    header.schema_hash,
+                    header.party_id,
+                    table_id,
+                    schema_hash,
+                    party_id
+                ));
+            }
+            return Ok(file.read_column(column_index)?);
+        }
+        let data = BinaryShareStorage::read_binary_data(path, party_id, table_id)?;
+        Self::from_party_data(&data, column_index)
+    }
+}
+
+/// Bitwise AND of several column pairs, all planes batched into one round
+pub async fn and_columns(
+    ctx: &mut ProtocolContext,
+    pairs: &[(&SharedBitColumn, &SharedBitColumn)],
+) -> Result<Vec<SharedBitColumn>> {
+    for (a, b) in pairs {
+        a.check_compatible(b)?;
+    }
+    let plane_pairs: Vec<(&SharedBits, &SharedBits)> = pairs
+        .iter()
+        .flat_map(|(a, b)| a.planes.iter().zip(&b.planes))
+        .collect();
+    let mut planes = and_planes(ctx, &plane_pairs).await?.into_iter();
+
+    Ok(pairs
+        .iter()
+        .map(|(a, _)| SharedBitColumn {
+            party_id: a.party_id,
+            rows: a.rows,
+            planes: planes.by_ref().take(a.width()).collect(),
+        })
+        .collect())
+}
+
+/// AND of many plane pairs in one communication round: every node computes
+/// its masked local share, sends it to the next node and keeps the previous
+/// node's share as its new `prev`
+pub async fn and_planes(
+    ctx: &mut ProtocolContext,
+    pairs: &[(&SharedBits, &SharedBits)],
+) -> Result<Vec<SharedBits>> {
+    let total_words: usize = pairs.iter().map(|(a, _)| a.own.len()).sum();
+    let masks = ctx.randomness().next_masks(total_words);
+
+    let mut local = Vec::with_capacity(total_words);
+    let mut offset = 0;
+    for (a, b) in pairs {
+        let words = a.own.len();
+        local.extend(a.and_local(b, &masks[offset..offset + words]));
+        offset += words;
+    }
+
+    let received = ctx.exchange_words(local.clone()).await?;
+
+    let mut result = Vec::with_capacity(pairs.len());
+    let mut offset = 0;
+    for (a, _) in pairs {
+        let words = a.own.len();
+        result.push(SharedBits {
+            own: local[offset..offset + words].to_vec(),
+            prev: received[offset..offset + words].to_vec(),
+        });
+        offset += words;
+    }
+    Ok(result)
+}
+
+/// Reveal a column (width up to 64 bits) to this node in one round: every node
+/// sends its `prev` share, which is the share the next node is missing
+pub async fn open_column(ctx: &mut ProtocolContext, column: &SharedBitColumn) -> Result<Vec<u64>> {
+    let sent: Vec<u64> = column.planes.iter().flat_map(|p| p.prev.iter().copied()).collect();
+    let received = ctx.exchange_words(sent).await?;
+
+    let words = words_for(column.rows);
+    let mut values = vec![0u64; column.rows];
+    for (j, plane) in column.planes.iter().enumerate().take(64) {
+        for (r, value) in values.iter_mut().enumerate() {
+            let (word, bit) = (r / WORD_BITS, r % WORD_BITS);
+            let full = plane.own[word] ^ plane.prev[word] ^ received[j * words + word];
+            *value |= ((full >> bit) & 1) << j;
+        }
+    }
+    Ok(values)
+}
+
+#[cfg(test)]
+pub(crate) mod testing {
+    use super::*;
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::sync::Arc;
+
+    use crate::exchange::channel::LocalExchange;
+    use crate::helpers::randomness::CorrelatedRandomness;
+
+    /// Dealer-side sharing of plaintext values into the three nodes' columns
+    pub fn share_values(values: &[u64], width: usize) -> [SharedBitColumn; 3] {
+        let rows = values.len();
+        let mut parts = [0, 1, 2].map(|_| SharedBitColumn::zeros(0, rows, width));
+        for (r, value) in values.iter().enumerate() {
+            let x0: u64 = rand::random();
+            let x1: u64 = rand::random();
+            let x = [x0, x1, value ^ x0 ^ x1];
+            for j in 0..width {
+                let bit = |i: usize| (x[i] >> j) & 1 == 1;
+                for (p, part) in parts.iter_mut().enumerate() {
+                    part.planes[j].set(r, bit(p), bit((p + 2) % 3));
+                }
+            }
+        }
+        for (p, part) in parts.iter_mut().enumerate() {
+            part.party_id = p as u32;
+        }
+        parts
+    }
+
+    /// Reconstruct values from the three nodes' columns
+    pub fn reveal(parts: &[SharedBitColumn]) -> Vec<u64> {
+        (0..parts[0].rows)
+            .map(|r| {
+                (0..parts[0].width()).fold(0u64, |acc, j| {
+                    let bit = parts.iter().fold(false, |b, p| b ^ p.planes[j].get(r).0);
+                    acc | ((bit as u64) << j)
+                })
+            })
+            .collect()
+    }
+
+    pub type PartyFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
+
+    /// Run `f` for all three nodes concurrently over an in-process exchange
+    pub async fn run_parties<T, F>(f: F) -> [T; 3]
+    where
+        F: Fn(ProtocolContext, usize) -> PartyFuture<T>,
+    {
+        let keys: [[u8; 32]; 3] = [[7; 32], [8; 32], [9; 32]];
+        let [ex0, ex1, ex2] = LocalExchange::ring();
+        let context = |exchange: LocalExchange, p: usize| {
+            let randomness = CorrelatedRandomness::from_keys(keys[p], keys[(p + 2) % 3]);
+            ProtocolContext::new(Arc::new(exchange), randomness, 0)
+        };
+        let (r0, r1, r2) = tokio::join!(
+            f(context(ex0, 0), 0),
+            f(context(ex1, 1), 1),
+            f(context(ex2, 2), 2),
+        );
+        [r0, r1, r2]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::*;
+    use super::*;
+
+    #[tokio::test]
+    async fn test_column_gates() {
+        let a = [0b1100u64, 0xFFFF_0000, 7, 0];
+        let b = [0b1010u64, 0x0F0F_0F0F, 0, 5];
+        let sa = share_values(&a, 32);
+        let sb = share_values(&b, 32);
+
+        let results = run_parties(|mut ctx, p| {
+            let (a, b) = (sa[p].clone(), sb[p].clone());
+            Box::pin(async move {
+                let and = a.and(&b, &mut ctx).await.unwrap();
+                let xor = a.xor(&b).unwrap();
+                let not = a.not();
+                let opened = open_column(&mut ctx, &and).await.unwrap();
+                assert_eq!(ctx.stats().rounds, 2);
+                (and, xor, not, opened)
+            })
+        })
+        .await;
+
+        let and: Vec<_> = results.iter().map(|r| r.0.clone()).collect();
+        let xor: Vec<_> = results.iter().map(|r| r.1.clone()).collect();
+        let not: Vec<_> = results.iter().map(|r| r.2.clone()).collect();
+        let expected_and: Vec<u64> = a.iter().zip(&b).map(|(x, y)| x & y).collect();
+        assert_eq!(reveal(&and), expected_and);
+        assert_eq!(reveal(&xor), a.iter().zip(&b).map(|(x, y)| x ^ y).collect::<Vec<_>>());
+        assert_eq!(reveal(&not), a.iter().map(|x| !x & 0xFFFF_FFFF).collect::<Vec<_>>());
+        for r in &results {
+            assert_eq!(r.3, expected_and);
+        }
+    }
+
+    #[test]
+    fn test_row_gates() {
+        // Rows that do not start at word boundaries, with set padding bits
+        let values: Vec<u64> = (0..150).map(|r| (r * 7 % 3 == 0) as u64).collect();
+        let mut planes = share_values(&values, 1).map(|column| column.planes[0].clone());
+        planes[0].own[2] |= !0 << 22;
+
+        let parts: Vec<SharedBitColumn> = planes
+            .iter()
+            .enumerate()
+            .map(|(p, plane)| {
+                let (head, tail) = (plane.slice_rows(0, 70), plane.slice_rows(70, 80));
+                SharedBitColumn {
+                    party_id: p as u32,
+                    rows: 150,
+                    planes: vec![SharedBits::concat_rows(&[(&tail, 80), (&head, 70)])],
+                }
+            })
+            .collect();
+        let rotated: Vec<u64> = values[70..].iter().chain(&values[..70]).copied().collect();
+        assert_eq!(reveal(&parts), rotated);
+        assert_eq!(parts[0].planes[0].own.len(), 3);
+
+        // Splitting rows into strided blocks and interleaving them restores them
+        let parts: Vec<SharedBitColumn> = planes
+            .iter()
+            .enumerate()
+            .map(|(p, plane)| {
+                let low = plane.slice_blocks(0, 25, 50, 3);
+                let high = plane.slice_blocks(25, 25, 50, 3);
+                SharedBitColumn {
+                    party_id: p as u32,
+                    rows: 150,
+                    planes: vec![SharedBits::interleave_rows(&[&low, &high], 75, 25)],
+                }
+            })
+            .collect();
+        assert_eq!(reveal(&parts), values);
+    }
+
+    #[test]
+    fn test_from_party_data() {
+        use crate::receive::server::share_service::BinaryRow;
+
+        // Second column (8 bits at offset 8) of two rows, as written by the data owner
+        let parts = share_values(&[200, 17], 8);
+        for part in &parts {
+            let rows = (0..2)
+                .map(|r| {
+                    let byte = |own: bool| {
+                        (0..8).fold(0u8, |acc, j| {
+                            let (o, p) = part.planes[j].get(r);
+                            acc | (((if own { o } else { p }) as u8) << j)
+                        })
+                    };
+                    BinaryRow {
+                        bitstring_a: vec![0xAA, byte(true)],
+                        bitstring_b: vec![0x55, byte(false)],
+                        column_bit_offsets: vec![0, 8],
+                        column_bit_lengths: vec![8, 8],
+                    }
+                })
+                .collect();
+            let data = BinaryPartyData { party_id: part.party_id, table_id: 1, rows };
+            assert_eq!(&SharedBitColumn::from_party_data(&data, 1).unwrap(), part);
+        }
+    }
+
+    #[test]
+    fn test_load_checks_table_and_schema() {
+        use crate::receive::server::share_service::BinaryRow;
+
+        let rows = (0..3u8)
+            .map(|r| BinaryRow {
+                bitstring_a: vec![r, 0x0F],
+                bitstring_b: vec![!r, 0xF0],
+                column_bit_offsets: vec![0, 8],
+                column_bit_lengths: vec![8, 8],
+            })
+            .collect();
+        let data = BinaryPartyData { party_id: 1, table_id: 7, rows };
+        let path = std::env::temp_dir().join(format!("fesca_load_table_{}.bin", std::process::id()));
+        columnar::write_columnar(&path, &data, 42).unwrap();
+
+        let expected = SharedBitColumn::from_party_data(&data, 1).unwrap();
+        assert_eq!(SharedBitColumn::load(&path, 1, 7, 42, 1).unwrap(), expected);
+        assert!(SharedBitColumn::load(&path, 1, 0, 42, 1).is_err());
+        assert!(SharedBitColumn::load(&path, 0, 7, 42, 1).is_err());
+        // A table shared again with another schema keeps its id, not its hash
+        assert!(SharedBitColumn::load(&path, 1, 7, 43, 1).is_err());
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_constant_column() {
+        let parts = [0, 1, 2].map(|p| SharedBitColumn::constant(p, 70, 16, 5000));
+        assert_eq!(reveal(&parts), vec![5000; 70]);
+    }
+
+    #[test]
+    fn test_not_keeps_padding_clear() {
+        let parts = share_values(&[0; 70], 4).map(|column| column.not());
+        assert_eq!(reveal(&parts), vec![0xF; 70]);
+        for part in &parts {
+            for plane in &part.planes {
+                assert_eq!(plane.own[1] >> 6, 0);
+                assert_eq!(plane.prev[1] >> 6, 0);
+            }
+        }
+    }
+}
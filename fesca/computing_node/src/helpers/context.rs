@@ -0,0 +1,163 @@
+// Protocol Context
+// ================
+// Per-node state needed to run interactive protocols: the exchange with the
+// neighbouring nodes, the correlated randomness stream and the round counter
+// that keeps round ids identical on all three nodes.
+//
+// Replicated sharing convention: a value x = x_0 ^ x_1 ^ x_2 (or x_0 + x_1 + x_2
+// for arithmetic shares) and node i holds the pair (x_i, x_{i-1}), called
+// `own` and `prev`.
+
+use anyhow::{anyhow, Result};
+use std::sync::Arc;
+
+use crate::exchange::channel::{ShareExchange, NUM_NODES};
+use super::randomness::{CorrelatedRandomness, SETUP_ROUND};
+use super::secret_share::SecretShareSend;
+
+/// Bytes put on the wire for one exchanged word (id + share), shared with
+/// the analyst's cost estimates
+pub use ::helpers::circuit::cost::BYTES_PER_WORD;
+
+/// Communication statistics of a node
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CommunicationStats {
+    pub rounds: u64,
+    pub bytes_sent: u64,
+}
+
+/// State of one node while running interactive protocols
+pub struct ProtocolContext {
+    exchange: Arc<dyn ShareExchange>,
+    randomness: CorrelatedRandomness,
+    next_round: u64,
+    stats: CommunicationStats,
+}
+
+impl ProtocolContext {
+    /// Create a context whose rounds are numbered from `first_round`.
+    /// All three nodes must use the same `first_round`.
+    pub fn new(
+        exchange: Arc<dyn ShareExchange>,
+        randomness: CorrelatedRandomness,
+        first_round: u64,
+    ) -> Self {
+        Self {
+            exchange,
+            randomness,
+            next_round: first_round,
+            stats: CommunicationStats::default(),
+        }
+    }
+
+    /// Id of the local node (0, 1 or 2)
+    pub fn party_id(&self) -> u32 {
+        self.exchange.node_id()
+    }
+
+    /// Communication used so far
+    pub fn stats(&self) -> &CommunicationStats {
+        &self.stats
+    }
+
+    /// Correlated randomness stream of this node
+    pub fn randomness(&mut self) -> &mut CorrelatedRandomness {
+        &mut self.randomness
+    }
+
+    /// Give back the randomness stream, e.g. to keep using it in a later context
+    pub fn into_randomness(self) -> CorrelatedRandomness {
+        self.randomness
+    }
+
+    /// One communication round: send `words` to the next node and return the
+    /// words the previous node sent
+    pub async fn exchange_words(&mut self, words: Vec<u64>) -> Result<Vec<u64>> {
+        let party_id = self.party_id();
+        if party_id >= NUM_NODES {
+            return Err(anyhow!("Invalid party id {}", party_id));
+        }
+        // The last round ids belong to the key setup and the handshake
+        let round_id = self.next_round;
+        if round_id >= SETUP_ROUND {
+            return Err(anyhow!("Round id {} is reserved", round_id));
+        }
+        self.next_round += 1;
+
+        let count = words.len();
+        let shares = words
+            .into_iter()
+            .enumerate()
+            .map(|(i, share)| SecretShareSend { id: i as u64, share })
+            .collect();
+
+        let mut received = self.exchange.exchange(round_id, shares).await?;
+        self.stats.rounds += 1;
+        self.stats.bytes_sent += count as u64 * BYTES_PER_WORD;
+
+        if received.len() != count {
+            return Err(anyhow!(
+                "Round {}: expected {} words from previous node, got {}",
+                round_id,
+                count,
+                received.len()
+            ));
+        }
+        received.sort_by_key(|s| s.id);
+        if let Some((index, share)) = received.iter().enumerate().find(|(i, s)| s.id != *i as u64) {
+            return Err(anyhow!(
+                "Round {}: unexpected word id {} at position {} from previous node",
+                round_id,
+                share.id,
+                index
+            ));
+        }
+        Ok(received.into_iter().map(|s| s.share).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Exchange that answers every round with a fixed batch
+    struct FixedExchange {
+        node_id: u32,
+        reply: Vec<SecretShareSend>,
+    }
+
+    #[tonic::async_trait]
+    impl ShareExchange for FixedExchange {
+        fn node_id(&self) -> u32 {
+            self.node_id
+        }
+
+        async fn exchange(&self, _round_id: u64, _shares: Vec<SecretShareSend>) -> Result<Vec<SecretShareSend>> {
+            Ok(self.reply.clone())
+        }
+    }
+
+    fn context(node_id: u32, ids: &[u64], first_round: u64) -> ProtocolContext {
+        let reply = ids.iter().map(|id| SecretShareSend { id: *id, share: *id * 10 }).collect();
+        let randomness = CorrelatedRandomness::from_keys([1; 32], [2; 32]);
+        ProtocolContext::new(Arc::new(FixedExchange { node_id, reply }), randomness, first_round)
+    }
+
+    #[tokio::test]
+    async fn test_exchange_words_orders_by_id() {
+        let mut ctx = context(1, &[1, 0], 0);
+        assert_eq!(ctx.exchange_words(vec![5, 6]).await.unwrap(), vec![0, 10]);
+        assert_eq!(ctx.stats().rounds, 1);
+    }
+
+    #[tokio::test]
+    async fn test_exchange_words_rejects_unknown_ids() {
+        // Word ids out of range or repeated
+        assert!(context(0, &[0, 2], 0).exchange_words(vec![5, 6]).await.is_err());
+        assert!(context(0, &[1, 1], 0).exchange_words(vec![5, 6]).await.is_err());
+        // Party ids past the ring and the reserved rounds fail before sending
+        assert!(context(3, &[0], 0).exchange_words(vec![5]).await.is_err());
+        assert!(context(0, &[0], SETUP_ROUND).exchange_words(vec![5]).await.is_err());
+        assert!(context(0, &[0], u64::MAX).exchange_words(vec![5]).await.is_err());
+    }
+}
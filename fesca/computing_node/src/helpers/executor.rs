@@ -0,0 +1,454 @@
+// Circuit Executor
+// ================
+// Evaluates a `Circuit` received from the data analyst on this node's shares.
+// Gates are scheduled by level (see `Circuit::levels`): the AND and MUL gates
+// of one level only depend on earlier levels, so all of them are evaluated in
+// a single communication round; local gates are evaluated as soon as their
+// level's round is done. A circuit of depth d therefore takes exactly d rounds
+// regardless of how many interactive gates it has.
+//
+// Bit wires are single bit-planes (`SharedBits`) over the wire's rows, word
+// wires are `SharedArithColumn`s. Public constants are shared as x_0 = value,
+// x_1 = x_2 = 0, as assumed by the builder's constant folding. Row gates just
+// move the share pairs of the selected rows.
+
+use anyhow::{anyhow, Result};
+use std::collections::BTreeMap;
+
+use ::helpers::circuit::{Circuit, Gate, OutputWires, Wire, WireShape};
+
+use super::arithmetic::SharedArithColumn;
+use super::bit_column::{words_for, SharedBitColumn, SharedBits};
+use super::context::ProtocolContext;
+
+/// Shares of one result column on this node
+#[derive(Debug, Clone, PartialEq)]
+pub enum OutputShares {
+    Bits(SharedBitColumn),
+    Word(SharedArithColumn),
+}
+
+/// Named result column
+#[derive(Debug, Clone, PartialEq)]
+pub struct CircuitOutput {
+    pub name: String,
+    pub shares: OutputShares,
+}
+
+/// Cost of one circuit evaluation on this node
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExecutionStats {
+    pub rounds: u64,
+    pub bytes_sent: u64,
+    /// Number of gates of each type, e.g. "AND"
+    pub gate_counts: BTreeMap<String, usize>,
+}
+
+/// Outputs and statistics of a circuit evaluation
+#[derive(Debug, Clone, PartialEq)]
+pub struct CircuitResult {
+    pub outputs: Vec<CircuitOutput>,
+    pub stats: ExecutionStats,
+}
+
+/// Value of a wire on this node
+#[derive(Debug, Clone)]
+enum WireValue {
+    Bit(SharedBits),
+    Word(SharedArithColumn),
+}
+
+/// Evaluate `circuit` on this node. `inputs` holds the stored share columns
+/// in the order of `circuit.inputs`; arithmetically shared columns are given
+/// in their stored 64-bit layout.
+pub async fn execute_circuit(
+    ctx: &mut ProtocolContext,
+    circuit: &Circuit,
+    inputs: &[SharedBitColumn],
+) -> Result<CircuitResult> {
+    let shapes = circuit.validate()?;
+    check_inputs(circuit, inputs)?;
+    let start = ctx.stats().clone();
+
+    // Gates per level in index order, which is a topological order
+    let levels = circuit.levels();
+    let depth = levels.iter().copied().max().unwrap_or(0) as usize;
+    let mut schedule: Vec<Vec<usize>> = vec![Vec::new(); depth + 1];
+    for (index, level) in levels.iter().enumerate() {
+        schedule[*level as usize].push(index);
+    }
+
+    let mut executor = Executor {
+        party_id: ctx.party_id(),
+        circuit,
+        shapes: &shapes,
+        inputs,
+        values: vec![None; circuit.gates.len()],
+    };
+    for gates in &schedule {
+        let (interactive, local): (Vec<usize>, Vec<usize>) =
+            gates.iter().partition(|index| circuit.gates[**index].is_interactive());
+        if !interactive.is_empty() {
+            executor.interactive_round(ctx, &interactive).await?;
+        }
+        for index in local {
+            let value = executor.local_gate(index)?;
+            executor.values[index] = Some(value);
+        }
+    }
+
+    let outputs = circuit
+        .outputs
+        .iter()
+        .map(|output| {
+            Ok(CircuitOutput {
+                name: output.name.clone(),
+                shares: executor.output_shares(&output.wires)?,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let end = ctx.stats();
+    let stats = ExecutionStats {
+        rounds: end.rounds - start.rounds,
+        bytes_sent: end.bytes_sent - start.bytes_sent,
+        gate_counts: circuit
+            .gate_counts()
+            .into_iter()
+            .map(|(name, count)| (name.to_string(), count))
+            .collect(),
+    };
+    Ok(CircuitResult { outputs, stats })
+}
+
+fn check_inputs(circuit: &Circuit, inputs: &[SharedBitColumn]) -> Result<()> {
+    if inputs.len() != circuit.inputs.len() {
+        return Err(anyhow!("Circuit reads {} input columns, got {}", circuit.inputs.len(), inputs.len()));
+    }
+    for (expected, column) in circuit.inputs.iter().zip(inputs) {
+        if column.rows != expected.rows || column.width() != expected.width as usize {
+            return Err(anyhow!(
+                "Input {}.{}: expected {} rows of {} bits, got {} rows of {} bits",
+                expected.table,
+                expected.column,
+                expected.rows,
+                expected.width,
+                column.rows,
+                column.width()
+            ));
+        }
+    }
+    Ok(())
+}
+
+struct Executor<'a> {
+    party_id: u32,
+    circuit: &'a Circuit,
+    shapes: &'a [WireShape],
+    inputs: &'a [SharedBitColumn],
+    values: Vec<Option<WireValue>>,
+}
+
+impl Executor<'_> {
+    fn bit(&self, wire: Wire) -> Result<&SharedBits> {
+        match &self.values[wire.index()] {
+            Some(WireValue::Bit(bits)) => Ok(bits),
+            _ => Err(anyhow!("Wire {} is not an evaluated bit wire", wire.0)),
+        }
+    }
+
+    fn value(&self, wire: Wire) -> Result<&WireValue> {
+        self.values[wire.index()]
+            .as_ref()
+            .ok_or_else(|| anyhow!("Wire {} is not evaluated", wire.0))
+    }
+
+    fn word(&self, wire: Wire) -> Result<&SharedArithColumn> {
+        match &self.values[wire.index()] {
+            Some(WireValue::Word(word)) => Ok(word),
+            _ => Err(anyhow!("Wire {} is not an evaluated word wire", wire.0)),
+        }
+    }
+
+    /// All AND and MUL gates of one level in a single round
+    async fn interactive_round(&mut self, ctx: &mut ProtocolContext, gates: &[usize]) -> Result<()> {
+        let (ands, muls): (Vec<usize>, Vec<usize>) =
+            gates.iter().partition(|index| matches!(self.circuit.gates[**index], Gate::And(..)));
+        let operands = |index: usize| match self.circuit.gates[index] {
+            Gate::And(a, b) | Gate::Mul(a, b) => (a, b),
+            _ => unreachable!("only AND and MUL gates are interactive"),
+        };
+
+        let and_words: usize = ands.iter().map(|i| words_for(self.shapes[*i].rows)).sum();
+        let mul_words: usize = muls.iter().map(|i| self.shapes[*i].rows).sum();
+        let and_masks = if and_words > 0 { ctx.randomness().next_masks(and_words) } else { Vec::new() };
+        let mul_masks = if mul_words > 0 { ctx.randomness().next_arith_masks(mul_words) } else { Vec::new() };
+
+        let mut local = Vec::with_capacity(and_words + mul_words);
+        for index in &ands {
+            let (a, b) = operands(*index);
+            let offset = local.len();
+            let words = words_for(self.shapes[*index].rows);
+            local.extend(self.bit(a)?.and_local(self.bit(b)?, &and_masks[offset..offset + words]));
+        }
+        for index in &muls {
+            let (a, b) = operands(*index);
+            let offset = local.len() - and_words;
+            let rows = self.shapes[*index].rows;
+            local.extend(self.word(a)?.mul_local(self.word(b)?, &mul_masks[offset..offset + rows]));
+        }
+
+        let received = ctx.exchange_words(local.clone()).await?;
+
+        let mut offset = 0;
+        for index in ands {
+            let words = words_for(self.shapes[index].rows);
+            let shares = SharedBits {
+                own: local[offset..offset + words].to_vec(),
+                prev: received[offset..offset + words].to_vec(),
+            };
+            self.values[index] = Some(WireValue::Bit(shares));
+            offset += words;
+        }
+        for index in muls {
+            let rows = self.shapes[index].rows;
+            let shares = SharedArithColumn {
+                party_id: self.party_id,
+                own: local[offset..offset + rows].to_vec(),
+                prev: received[offset..offset + rows].to_vec(),
+            };
+            self.values[index] = Some(WireValue::Word(shares));
+            offset += rows;
+        }
+        Ok(())
+    }
+
+    fn local_gate(&self, index: usize) -> Result<WireValue> {
+        let party_id = self.party_id;
+        let rows = self.shapes[index].rows;
+        Ok(match &self.circuit.gates[index] {
+            Gate::Input { input, bit } => WireValue::Bit(self.inputs[*input].planes[*bit as usize].clone()),
+            Gate::InputWord { input } => WireValue::Word(SharedArithColumn::from_stored(&self.inputs[*input])?),
+            Gate::ConstBit { value, rows } => {
+                let zero = SharedBits::zeros(words_for(*rows));
+                WireValue::Bit(if *value { zero.not(party_id, *rows) } else { zero })
+            }
+            Gate::ConstWord { value, rows } => WireValue::Word(SharedArithColumn::constant(party_id, *rows, *value)),
+            Gate::Xor(a, b) => WireValue::Bit(self.bit(*a)?.xor(self.bit(*b)?)),
+            Gate::Not(a) => WireValue::Bit(self.bit(*a)?.not(party_id, rows)),
+            Gate::Add(a, b) => WireValue::Word(self.word(*a)?.add(self.word(*b)?)?),
+            Gate::Sub(a, b) => WireValue::Word(self.word(*a)?.sub(self.word(*b)?)?),
+            Gate::MulConst(a, value) => WireValue::Word(self.word(*a)?.mul_const(*value)),
+            Gate::InjectBit { input, component } => {
+                // Component k is held by node k (own) and node k+1 (prev)
+                let bits = self.bit(*input)?;
+                let mut word = SharedArithColumn::zeros(party_id, rows);
+                for r in 0..rows {
+                    let (own, prev) = bits.get(r);
+                    if party_id == *component {
+                        word.own[r] = own as u64;
+                    }
+                    if party_id == (component + 1) % 3 {
+                        word.prev[r] = prev as u64;
+                    }
+                }
+                WireValue::Word(word)
+            }
+            Gate::InjectWord { input, component, bit } => {
+                let word = self.word(*input)?;
+                let mut bits = SharedBits::zeros(words_for(rows));
+                for r in 0..rows {
+                    let own = party_id == *component && (word.own[r] >> bit) & 1 == 1;
+                    let prev = party_id == (component + 1) % 3 && (word.prev[r] >> bit) & 1 == 1;
+                    bits.set(r, own, prev);
+                }
+                WireValue::Bit(bits)
+            }
+            Gate::Slice { input, start, block, stride, blocks } => match self.value(*input)? {
+                WireValue::Bit(bits) => WireValue::Bit(bits.slice_blocks(*start, *block, *stride, *blocks)),
+                WireValue::Word(word) => WireValue::Word(word.slice_blocks(*start, *block, *stride, *blocks)),
+            },
+            Gate::Concat(inputs) => match self.value(inputs[0])? {
+                WireValue::Bit(_) => {
+                    let parts = inputs
+                        .iter()
+                        .map(|w| Ok((self.bit(*w)?, self.shapes[w.index()].rows)))
+                        .collect::<Result<Vec<_>>>()?;
+                    WireValue::Bit(SharedBits::concat_rows(&parts))
+                }
+                WireValue::Word(_) => {
+                    let parts = inputs.iter().map(|w| self.word(*w)).collect::<Result<Vec<_>>>()?;
+                    WireValue::Word(SharedArithColumn::concat_rows(party_id, &parts))
+                }
+            },
+            Gate::Interleave { inputs, block } => match self.value(inputs[0])? {
+                WireValue::Bit(_) => {
+                    let parts = inputs.iter().map(|w| self.bit(*w)).collect::<Result<Vec<_>>>()?;
+                    let rows = self.shapes[inputs[0].index()].rows;
+                    WireValue::Bit(SharedBits::interleave_rows(&parts, rows, *block))
+                }
+                WireValue::Word(_) => {
+                    let parts = inputs.iter().map(|w| self.word(*w)).collect::<Result<Vec<_>>>()?;
+                    WireValue::Word(SharedArithColumn::interleave_rows(party_id, &parts, *block))
+                }
+            },
+            Gate::And(..) | Gate::Mul(..) => return Err(anyhow!("Gate {} is interactive", index)),
+        })
+    }
+
+    fn output_shares(&self, wires: &OutputWires) -> Result<OutputShares> {
+        Ok(match wires {
+            OutputWires::Bits(bits) => {
+                let rows = bits.first().map_or(0, |w| self.shapes[w.index()].rows);
+                let planes = bits
+                    .iter()
+                    .map(|w| {
+                        // AND masks leave random shares of zero in the padding bits
+                        let mut plane = self.bit(*w)?.clone();
+                        plane.clear_padding(rows);
+                        Ok(plane)
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                OutputShares::Bits(SharedBitColumn {
+                    party_id: self.party_id,
+                    rows,
+                    planes,
+                })
+            }
+            OutputWires::Word(word) => OutputShares::Word(self.word(*word)?.clone()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::arithmetic::testing::reveal_arith;
+    use crate::helpers::bit_column::testing::*;
+    use ::helpers::circuit::adder::AdderKind;
+    use ::helpers::circuit::compare::Comparison;
+    use ::helpers::circuit::plain::evaluate;
+    use ::helpers::circuit::{CircuitBuilder, InputColumn, InputSharing};
+
+    fn column(column: usize, width: u32, sharing: InputSharing, rows: usize) -> InputColumn {
+        InputColumn {
+            table: "partsupp".to_string(),
+            table_id: 4,
+            schema_hash: 0,
+            column,
+            width,
+            sharing,
+            rows,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_execute_matches_plaintext() {
+        let qty = vec![3325u64, 8076, 5000, 9999, 5001, 0, 77];
+        let cost = vec![10u64, 20, 30, 40, 50, 60, 70];
+        let rows = qty.len();
+
+        let mut builder = CircuitBuilder::new();
+        let iq = builder.input(column(2, 32, InputSharing::Boolean, rows));
+        let ic = builder.input(column(3, 64, InputSharing::Arithmetic, rows));
+        let qty_bits = builder.input_bits(iq);
+        let cost_word = builder.input_word(ic);
+        let large = builder.compare_const(&qty_bits, Comparison::Greater, 5000);
+        let small = builder.not(large);
+        builder.output("large", OutputWires::Bits(vec![large]));
+        builder.output("small", OutputWires::Bits(vec![small]));
+        let weight = builder.b2a(&[large]);
+        let selected = builder.mul(weight, cost_word);
+        builder.output("selected cost", OutputWires::Word(selected));
+        let total = builder.add_bits(&qty_bits, &qty_bits, AdderKind::RippleCarry);
+        builder.output("twice", OutputWires::Bits(total));
+        let cost_bits = builder.a2b(cost_word, 16);
+        let cheap = builder.compare(&cost_bits, Comparison::LessEqual, &qty_bits);
+        builder.output("cheap", OutputWires::Bits(vec![cheap]));
+        // Row gates over 7 rows: an odd row is carried at every step
+        let total = builder.sum_rows(selected);
+        builder.output("total selected cost", OutputWires::Word(total));
+        let max = builder.fold_rows(&qty_bits, |builder, low, high| {
+            let greater = builder.less_than(low, high);
+            builder.mux(greater, high, low)
+        });
+        builder.output("max", OutputWires::Bits(max));
+        // Sorting pads the 7 rows to 8 and runs on strided slices
+        let (sorted, flags) = builder.sort_rows(&qty_bits, &[large]);
+        builder.output("sorted", OutputWires::Bits(sorted));
+        builder.output("sorted large", OutputWires::Bits(flags));
+        let circuit = builder.finish();
+        let expected = evaluate(&circuit, &[qty.clone(), cost.clone()]).unwrap();
+
+        // Arithmetic inputs are stored as the 64-bit layout of their additive shares
+        let shared_qty = share_values(&qty, 32);
+        let shared_cost = crate::helpers::arithmetic::testing::share_arith_values(&cost).map(|c| {
+            let mut stored = SharedBitColumn::zeros(c.party_id, rows, 64);
+            for r in 0..rows {
+                for (j, plane) in stored.planes.iter_mut().enumerate() {
+                    plane.set(r, (c.own[r] >> j) & 1 == 1, (c.prev[r] >> j) & 1 == 1);
+                }
+            }
+            stored
+        });
+
+        let depth = circuit.depth() as u64;
+        let results = run_parties(|mut ctx, p| {
+            let circuit = circuit.clone();
+            let inputs = [shared_qty[p].clone(), shared_cost[p].clone()];
+            Box::pin(async move { execute_circuit(&mut ctx, &circuit, &inputs).await.unwrap() })
+        })
+        .await;
+
+        // The analyst's estimate matches the actual communication
+        let cost = circuit.cost().unwrap();
+        for result in &results {
+            assert_eq!(result.stats.rounds, depth);
+            assert_eq!(result.stats.gate_counts["MUL"], 3);
+            assert_eq!(result.stats.rounds, cost.rounds as u64);
+            assert_eq!(result.stats.bytes_sent, cost.bytes_sent);
+        }
+        for (i, expected) in expected.iter().enumerate() {
+            let revealed = match &results[0].outputs[i].shares {
+                OutputShares::Bits(_) => {
+                    let parts: Vec<SharedBitColumn> = results
+                        .iter()
+                        .map(|r| match &r.outputs[i].shares {
+                            OutputShares::Bits(bits) => bits.clone(),
+                            OutputShares::Word(_) => unreachable!(),
+                        })
+                        .collect();
+                    reveal(&parts)
+                }
+                OutputShares::Word(_) => {
+                    let parts: Vec<SharedArithColumn> = results
+                        .iter()
+                        .map(|r| match &r.outputs[i].shares {
+                            OutputShares::Word(word) => word.clone(),
+                            OutputShares::Bits(_) => unreachable!(),
+                        })
+                        .collect();
+                    reveal_arith(&parts)
+                }
+            };
+            assert_eq!(&revealed, expected, "output {}", results[0].outputs[i].name);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rejects_mismatched_inputs() {
+        let mut builder = CircuitBuilder::new();
+        let input = builder.input(column(0, 8, InputSharing::Boolean, 3));
+        let bits = builder.input_bits(input);
+        builder.output("x", OutputWires::Bits(bits));
+        let circuit = builder.finish();
+
+        let results = run_parties(|mut ctx, p| {
+            let circuit = circuit.clone();
+            let input = share_values(&[1, 2], 8)[p].clone();
+            Box::pin(async move { execute_circuit(&mut ctx, &circuit, &[input]).await.is_err() })
+        })
+        .await;
+        assert_eq!(results, [true; 3]);
+    }
+}
@@ -1,3 +1,8 @@
+pub mod arithmetic;
+pub mod bit_column;
+pub mod context;
+pub mod executor;
 pub mod hashing;
 pub mod operation;
+pub mod randomness;
 pub mod secret_share;
@@ -25,7 +25,7 @@ pub fn and_operation(
 
     SecretShare {
         id,
-        share: share,
+        share,
         mask,
     }
 }
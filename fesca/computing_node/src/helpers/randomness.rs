@@ -0,0 +1,196 @@
+// Correlated Randomness
+// =====================
+// Zero-sharings for the AND protocol without a trusted dealer.
+//
+// During setup every node i samples a PRF key k_i and sends it to node i+1,
+// so each pair of neighbouring nodes shares one key and node i holds
+// (k_i, k_{i-1}). For gate g node i derives
+//     alpha_i = F(k_i, g) ^ F(k_{i-1}, g)
+// Each key is used by exactly two nodes, so alpha_0 ^ alpha_1 ^ alpha_2 = 0,
+// while no single node learns the masks of the others. Arithmetic masks use
+// alpha_i = F(k_i, g) - F(k_{i-1}, g) (mod 2^64), which sum to zero.
+
+use anyhow::{anyhow, Result};
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+
+use crate::exchange::channel::ShareExchange;
+use super::secret_share::SecretShareSend;
+
+/// Round id reserved for exchanging the PRF keys
+pub const SETUP_ROUND: u64 = u64::MAX - 1;
+
+/// Top bit of the streams query keys are derived from; the gate counter
+/// never gets there, so query keys are independent of the node's own masks
+const QUERY_STREAM: u64 = 1 << 63;
+
+/// PRF key shared by two neighbouring nodes
+pub type PrfKey = [u8; 32];
+
+/// Per-node stream of correlated randomness
+#[derive(Debug, Clone)]
+pub struct CorrelatedRandomness {
+    own_key: PrfKey,
+    prev_key: PrfKey,
+    gate_counter: u64,
+}
+
+impl CorrelatedRandomness {
+    /// Create a stream from the node's own key and the key received from the
+    /// previous node
+    pub fn from_keys(own_key: PrfKey, prev_key: PrfKey) -> Self {
+        Self {
+            own_key,
+            prev_key,
+            gate_counter: 0,
+        }
+    }
+
+    /// Setup phase: sample a fresh key, send it to the next node and receive
+    /// the previous node's key
+    pub async fn setup<E: ShareExchange + ?Sized>(exchange: &E) -> Result<Self> {
+        let mut own_key = [0u8; 32];
+        rand::rng().fill_bytes(&mut own_key);
+
+        let received = exchange.exchange(SETUP_ROUND, key_to_shares(&own_key)).await?;
+        let prev_key = key_from_shares(&received)?;
+
+        Ok(Self::from_keys(own_key, prev_key))
+    }
+
+    /// Independent stream for one query, derived from both keys with the
+    /// query id. All three nodes derive the same streams for the same id, so
+    /// queries can run concurrently and in any order; an id must not be reused.
+    pub fn for_query(&self, query_id: u32) -> CorrelatedRandomness {
+        let stream = QUERY_STREAM | query_id as u64;
+        Self::from_keys(derive_key(&self.own_key, stream), derive_key(&self.prev_key, stream))
+    }
+
+    /// Number of gates masks have been drawn for
+    pub fn gate_counter(&self) -> u64 {
+        self.gate_counter
+    }
+
+    /// Boolean zero-sharing mask for the next gate
+    pub fn next_mask(&mut self) -> u64 {
+        self.next_masks(1)[0]
+    }
+
+    /// `count` boolean zero-sharing masks for the next gate, e.g. one per
+    /// word of a batched gate
+    pub fn next_masks(&mut self, count: usize) -> Vec<u64> {
+        let gate = self.next_gate();
+        let mut own = prf_stream(&self.own_key, gate);
+        let mut prev = prf_stream(&self.prev_key, gate);
+        (0..count).map(|_| own.next_u64() ^ prev.next_u64()).collect()
+    }
+
+    /// `count` arithmetic zero-sharing masks (mod 2^64) for the next gate
+    pub fn next_arith_masks(&mut self, count: usize) -> Vec<u64> {
+        let gate = self.next_gate();
+        let mut own = prf_stream(&self.own_key, gate);
+        let mut prev = prf_stream(&self.prev_key, gate);
+        (0..count).map(|_| own.next_u64().wrapping_sub(prev.next_u64())).collect()
+    }
+
+    fn next_gate(&mut self) -> u64 {
+        let gate = self.gate_counter;
+        self.gate_counter += 1;
+        gate
+    }
+}
+
+/// Keyed PRG indexed by gate counter: every gate uses its own ChaCha stream
+fn prf_stream(key: &PrfKey, gate: u64) -> ChaCha20Rng {
+    let mut rng = ChaCha20Rng::from_seed(*key);
+    rng.set_stream(gate);
+    rng
+}
+
+/// Fresh key taken from the given stream of `key`
+fn derive_key(key: &PrfKey, stream: u64) -> PrfKey {
+    let mut derived = [0u8; 32];
+    prf_stream(key, stream).fill_bytes(&mut derived);
+    derived
+}
+
+/// Split a key into shares so it can be sent over the share exchange
+fn key_to_shares(key: &PrfKey) -> Vec<SecretShareSend> {
+    key.chunks(8)
+        .enumerate()
+        .map(|(i, chunk)| SecretShareSend {
+            id: i as u64,
+            share: u64::from_le_bytes(chunk.try_into().expect("8-byte chunk")),
+        })
+        .collect()
+}
+
+/// Reassemble a key received over the share exchange
+fn key_from_shares(shares: &[SecretShareSend]) -> Result<PrfKey> {
+    if shares.len() != 4 {
+        return Err(anyhow!("Expected 4 key words, got {}", shares.len()));
+    }
+    let mut key = [0u8; 32];
+    for share in shares {
+        let offset = (share.id as usize)
+            .checked_mul(8)
+            .filter(|o| *o < 32)
+            .ok_or_else(|| anyhow!("Invalid key word index {}", share.id))?;
+        key[offset..offset + 8].copy_from_slice(&share.share.to_le_bytes());
+    }
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exchange::channel::LocalExchange;
+
+    #[tokio::test]
+    async fn test_masks_form_zero_sharing() {
+        let [ex0, ex1, ex2] = LocalExchange::ring();
+        let (r0, r1, r2) = tokio::join!(
+            CorrelatedRandomness::setup(&ex0),
+            CorrelatedRandomness::setup(&ex1),
+            CorrelatedRandomness::setup(&ex2),
+        );
+        let mut streams = [r0.unwrap(), r1.unwrap(), r2.unwrap()];
+
+        for _ in 0..4 {
+            let masks: Vec<Vec<u64>> = streams.iter_mut().map(|s| s.next_masks(3)).collect();
+            for ((m0, m1), m2) in masks[0].iter().zip(&masks[1]).zip(&masks[2]) {
+                assert_eq!(m0 ^ m1 ^ m2, 0);
+            }
+            // Masks are not trivially zero
+            assert_ne!(masks[0], vec![0; 3]);
+
+            let masks: Vec<Vec<u64>> = streams.iter_mut().map(|s| s.next_arith_masks(3)).collect();
+            for ((m0, m1), m2) in masks[0].iter().zip(&masks[1]).zip(&masks[2]) {
+                assert_eq!(m0.wrapping_add(*m1).wrapping_add(*m2), 0);
+            }
+        }
+        assert_eq!(streams[0].gate_counter(), 8);
+    }
+
+    #[test]
+    fn test_query_streams_form_zero_sharing() {
+        let keys: [PrfKey; 3] = [[1; 32], [2; 32], [3; 32]];
+        let base = [0, 1, 2].map(|i| CorrelatedRandomness::from_keys(keys[i], keys[(i + 2) % 3]));
+
+        let mut query = base.each_ref().map(|r| r.for_query(7));
+        let masks: Vec<u64> = query.iter_mut().map(|r| r.next_mask()).collect();
+        assert_eq!(masks[0] ^ masks[1] ^ masks[2], 0);
+
+        // Different queries and the base stream draw different masks
+        let other = base[0].for_query(8).next_mask();
+        let own = base[0].clone().next_mask();
+        assert_ne!(masks[0], other);
+        assert_ne!(masks[0], own);
+    }
+
+    #[test]
+    fn test_key_roundtrip() {
+        let key: PrfKey = std::array::from_fn(|i| i as u8);
+        assert_eq!(key_from_shares(&key_to_shares(&key)).unwrap(), key);
+    }
+}
@@ -1,32 +1,21 @@
 use super::hashing::hash_value;
 use rand::prelude::*;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Default)]
 pub struct SecretShare {
     pub id: u64,
     pub share: u64,
     pub mask: u64,
 }
-impl Default for SecretShare {
-    fn default() -> Self {
-        SecretShare {
-            id: 0,
-            share: 0,
-            mask: 0,
-        }
-    }
-}
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Default)]
 pub struct SecretShareSend {
     pub id: u64,
     pub share: u64, // can be masked or not
 }
 
-impl Default for SecretShareSend {
-    fn default() -> Self {
-        SecretShareSend { id: 0, share: 0 }
-    }
-}
+/// Dealer-generated zero-sharing (three masks XOR-ing to zero) used when a
+/// single party creates all shares of a value. Computing nodes draw their
+/// AND gate masks from `randomness::CorrelatedRandomness` instead.
 pub fn generate_mask() -> Vec<u64> {
     let mut rng = rand::rng();
     let value1: u64 = rng.random::<u64>();
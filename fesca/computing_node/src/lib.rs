@@ -1,20 +1,52 @@
+pub mod config;
 pub mod helpers;
 pub mod node;
 
 // Receive module components
 pub mod receive {
+    pub mod columnar;
     pub mod server;
     pub mod storage;
 }
 
-use anyhow::Result;
+// Node-to-node exchange components
+pub mod exchange {
+    pub mod channel;
+    pub mod client;
+    pub mod mailbox;
+    pub mod server;
+}
+
+// Query execution on behalf of the data analyst
+pub mod query {
+    pub mod server;
+}
+
+use anyhow::{anyhow, Result};
 use std::env;
-use log::info;
+use std::sync::Arc;
+use log::{info, warn};
+
+use crate::config::load_computing_node_config;
+use crate::exchange::channel::previous_node;
+use crate::helpers::secret_share::SecretShareSend;
 
 // Re-export main functionality
 pub use node::Node;
 pub use receive::server::{ShareReceiver, start_server};
 pub use receive::storage::BinaryShareStorage;
+pub use exchange::channel::{LocalExchange, ShareExchange};
+pub use exchange::client::PeerExchange;
+pub use exchange::mailbox::Mailbox;
+pub use exchange::server::{PeerReceiver, serve_exchange, start_exchange_server};
+pub use query::server::{QueryRunner, serve_queries, start_query_server};
+pub use helpers::arithmetic::SharedArithColumn;
+pub use helpers::bit_column::SharedBitColumn;
+pub use helpers::context::ProtocolContext;
+pub use helpers::executor::{execute_circuit, CircuitResult, ExecutionStats};
+
+/// Round id reserved for the start-up handshake between the nodes
+pub const HANDSHAKE_ROUND: u64 = u64::MAX;
 
 /// Main entry point for computing node functionality.
 /// This function is called by the main FESCA entry point.
@@ -39,9 +71,77 @@ async fn run_computing_node_async() -> Result<()> {
     let storage_path = env::var("STORAGE_PATH")
         .unwrap_or_else(|_| format!("{}/fesca_shares", home_dir));
     
+    // Get the node configuration (ring position and neighbour URLs)
+    let config_path = env::var("NODE_CONFIG")
+        .unwrap_or_else(|_| "config_computing_node.json".to_string());
+    let config = match load_computing_node_config(&config_path) {
+        Ok(config) if config.has_peers() => Some(config),
+        Ok(_) => {
+            warn!("No node_id / computation_urls configured, peer exchange disabled");
+            None
+        }
+        Err(e) => {
+            warn!("Could not load {}: {}, peer exchange disabled", config_path, e);
+            None
+        }
+    };
+
     info!("Starting computing node server...");
     info!("Port: {}", port);
     info!("Storage: {}", storage_path);
-    
-    start_server(port, storage_path).await
+
+    let Some(config) = config else {
+        return start_server(port, storage_path).await;
+    };
+
+    let node_id = config.node_id()?;
+    let computation_port = env::var("COMPUTATION_PORT")
+        .unwrap_or_else(|_| "50052".to_string())
+        .parse::<u16>()
+        .unwrap_or(50052);
+
+    let query_port = env::var("QUERY_PORT")
+        .unwrap_or_else(|_| "50053".to_string())
+        .parse::<u16>()
+        .unwrap_or(50053);
+
+    info!("Node id: {}", node_id);
+    info!("Computation port: {}", computation_port);
+    info!("Query port: {}", query_port);
+    info!("Next node: {}", config.computation_urls.url1);
+    info!("Previous node: {}", config.computation_urls.url2);
+
+    let mailbox = Arc::new(Mailbox::new());
+    let exchange = Arc::new(PeerExchange::new(node_id, config.computation_urls.url1.clone(), mailbox.clone()));
+
+    tokio::try_join!(
+        start_server(port, storage_path.clone()),
+        start_exchange_server(computation_port, node_id, mailbox),
+        async {
+            handshake(exchange.as_ref()).await?;
+            start_query_server(query_port, storage_path, exchange).await
+        },
+    )?;
+    Ok(())
+}
+
+/// Verify the ring is wired correctly: every node must receive the id of its
+/// predecessor in the handshake round.
+pub async fn handshake<E: ShareExchange + ?Sized>(exchange: &E) -> Result<()> {
+    let node_id = exchange.node_id();
+    let hello = vec![SecretShareSend { id: node_id as u64, share: 0 }];
+    let received = exchange.exchange(HANDSHAKE_ROUND, hello).await?;
+
+    match received.first() {
+        Some(share) if share.id == previous_node(node_id) as u64 => {
+            info!("Handshake with previous node {} completed", share.id);
+            Ok(())
+        }
+        Some(share) => Err(anyhow!(
+            "Expected handshake from node {}, got node {}",
+            previous_node(node_id),
+            share.id
+        )),
+        None => Err(anyhow!("Empty handshake batch")),
+    }
 }
@@ -1,11 +1,22 @@
+use anyhow::{anyhow, Result};
 use std::collections::HashMap;
+use std::sync::Arc;
 
+use ::helpers::circuit::Circuit;
+
+use crate::exchange::channel::ShareExchange;
+use crate::helpers::bit_column::SharedBitColumn;
+use crate::helpers::context::ProtocolContext;
+use crate::helpers::executor::{execute_circuit, CircuitResult};
+use crate::helpers::randomness::CorrelatedRandomness;
 use crate::helpers::secret_share::{SecretShare, SecretShareSend};
 
+#[derive(Default)]
 pub struct Node {
     pub saved_shares: HashMap<u64, SecretShare>,
     pub received_shares: HashMap<u64, SecretShareSend>,
     pub calculated_shares: HashMap<u64, SecretShare>,
+    pub randomness: Option<CorrelatedRandomness>,
 }
 impl Node {
     pub fn new() -> Self {
@@ -13,9 +24,43 @@ impl Node {
             saved_shares: HashMap::new(),
             received_shares: HashMap::new(),
             calculated_shares: HashMap::new(),
+            randomness: None,
         }
     }
 
+    /// Run the correlated randomness setup with the neighbouring nodes
+    pub async fn setup_randomness<E: ShareExchange>(&mut self, exchange: &E) -> Result<()> {
+        self.randomness = Some(CorrelatedRandomness::setup(exchange).await?);
+        Ok(())
+    }
+
+    /// Mask for the next AND gate from the node's correlated randomness stream
+    pub fn next_mask(&mut self) -> Result<u64> {
+        self.randomness
+            .as_mut()
+            .map(|r| r.next_mask())
+            .ok_or_else(|| anyhow!("Correlated randomness has not been set up"))
+    }
+
+    /// Evaluate a circuit on this node's input columns with the node's
+    /// correlated randomness; rounds are numbered from `first_round`
+    pub async fn evaluate_circuit(
+        &mut self,
+        exchange: Arc<dyn ShareExchange>,
+        circuit: &Circuit,
+        inputs: &[SharedBitColumn],
+        first_round: u64,
+    ) -> Result<CircuitResult> {
+        let randomness = self
+            .randomness
+            .take()
+            .ok_or_else(|| anyhow!("Correlated randomness has not been set up"))?;
+        let mut ctx = ProtocolContext::new(exchange, randomness, first_round);
+        let result = execute_circuit(&mut ctx, circuit, inputs).await;
+        self.randomness = Some(ctx.into_randomness());
+        result
+    }
+
     pub fn add_saved_share(&mut self, share: SecretShare) {
         self.saved_shares.insert(share.id, share);
     }
@@ -29,44 +74,56 @@ impl Node {
     }
 
     pub fn send_masked_share(&self, id: u64) -> Option<SecretShareSend> {
-        match self.calculated_shares.get(&id) {
-            Some(share) => {
-                return Some(SecretShareSend {
-                    id: share.id,
-                    share: share.share ^ share.mask,
-                });
-            }
-            None => {}
+        if let Some(share) = self.calculated_shares.get(&id) {
+            return Some(SecretShareSend {
+                id: share.id,
+                share: share.share ^ share.mask,
+            });
         }
 
-        match self.saved_shares.get(&id) {
-            Some(share) => {
-                return Some(SecretShareSend {
-                    id: share.id,
-                    share: share.share ^ share.mask,
-                });
-            }
-            None => return None,
-        }
+        self.saved_shares.get(&id).map(|share| SecretShareSend {
+            id: share.id,
+            share: share.share ^ share.mask,
+        })
     }
     pub fn send_unmasked_share(&self, id: u64) -> Option<SecretShareSend> {
-        match self.saved_shares.get(&id) {
-            Some(share) => {
-                return Some(SecretShareSend {
-                    id: share.id,
-                    share: share.share,
-                });
-            }
-            None => None,
+        self.saved_shares.get(&id).map(|share| SecretShareSend {
+            id: share.id,
+            share: share.share,
+        })
+    }
+
+    /// Send the unmasked saved shares `ids` to the next node and store the
+    /// previous node's shares of the same values as received shares
+    pub async fn exchange_saved_shares<E: ShareExchange>(
+        &mut self,
+        exchange: &E,
+        round_id: u64,
+        ids: &[u64],
+    ) -> Result<()> {
+        let outgoing = ids
+            .iter()
+            .map(|id| {
+                self.send_unmasked_share(*id)
+                    .ok_or_else(|| anyhow!("Missing saved share for id {}", id))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        for share in exchange.exchange(round_id, outgoing).await? {
+            self.add_received_share(share);
         }
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::helpers::operation::and_operation;
-    use crate::helpers::secret_share::generate_secret_share;
+    use crate::exchange::channel::LocalExchange;
+    use crate::helpers::bit_column::testing::{reveal, share_values};
+    use crate::helpers::executor::OutputShares;
+    use ::helpers::circuit::compare::Comparison;
+    use ::helpers::circuit::{CircuitBuilder, InputColumn, InputSharing, OutputWires, Wire};
     #[test]
     fn test_node_creation() {
         let node = Node::new();
@@ -76,121 +133,67 @@ mod tests {
         assert!(node.calculated_shares.is_empty());
     }
 
-    #[test]
-    fn test_three_nodes_secret_sharing() {
-        // Create 3 nodes
-        let mut node1 = Node::new();
-        let mut node2 = Node::new();
-        let mut node3 = Node::new();
-
-        // Verify initial state
-        assert_eq!(node1.saved_shares.len(), 0);
-        assert_eq!(node1.received_shares.len(), 0);
-        assert_eq!(node1.calculated_shares.len(), 0);
-
-        let secret_share1 = generate_secret_share(0b101010);
-        let secret_share2 = generate_secret_share(0b100010);
-        let id1 = secret_share1[0].id;
-        let id2 = secret_share2[0].id;
-
-        node1.add_saved_share(secret_share1[0].clone());
-        node2.add_saved_share(secret_share1[1].clone());
-        node3.add_saved_share(secret_share1[2].clone());
-
-        node1.add_saved_share(secret_share2[0].clone());
-        node2.add_saved_share(secret_share2[1].clone());
-        node3.add_saved_share(secret_share2[2].clone());
-
-        assert_eq!(node1.saved_shares.len(), 2);
-        assert_eq!(node2.saved_shares.len(), 2);
-        assert_eq!(node3.saved_shares.len(), 2);
-
-        // Now let assume I want to know and of this 2 values
-        node2.add_received_share(node1.send_unmasked_share(id1).unwrap_or_default());
-        node2.add_received_share(node1.send_unmasked_share(id2).unwrap_or_default());
-
-        node3.add_received_share(node2.send_unmasked_share(id1).unwrap_or_default());
-        node3.add_received_share(node2.send_unmasked_share(id2).unwrap_or_default());
-
-        node1.add_received_share(node3.send_unmasked_share(id1).unwrap_or_default());
-        node1.add_received_share(node3.send_unmasked_share(id2).unwrap_or_default());
-
-        node1.add_calculated_share(and_operation(
-            node1
-                .saved_shares
-                .get(&id1)
-                .expect("Missing saved share for id1"),
-            node1
-                .saved_shares
-                .get(&id2)
-                .expect("Missing saved share for id2"),
-            node1
-                .received_shares
-                .get(&id1)
-                .expect("Missing received share for id1"),
-            node1
-                .received_shares
-                .get(&id2)
-                .expect("Missing received share for id2"),
-            node1
-                .saved_shares
-                .get(&id1)
-                .expect("Missing saved share for id1")
-                .mask,
-        ));
-
-        node2.add_calculated_share(and_operation(
-            node2
-                .saved_shares
-                .get(&id1)
-                .expect("Missing saved share for id1"),
-            node2
-                .saved_shares
-                .get(&id2)
-                .expect("Missing saved share for id2"),
-            node2
-                .received_shares
-                .get(&id1)
-                .expect("Missing received share for id1"),
-            node2
-                .received_shares
-                .get(&id2)
-                .expect("Missing received share for id2"),
-            node2
-                .saved_shares
-                .get(&id1)
-                .expect("Missing saved share for id1")
-                .mask,
-        ));
-
-        node3.add_calculated_share(and_operation(
-            node3
-                .saved_shares
-                .get(&id1)
-                .expect("Missing saved share for id1"),
-            node3
-                .saved_shares
-                .get(&id2)
-                .expect("Missing saved share for id2"),
-            node3
-                .received_shares
-                .get(&id1)
-                .expect("Missing received share for id1"),
-            node3
-                .received_shares
-                .get(&id2)
-                .expect("Missing received share for id2"),
-            node3
-                .saved_shares
-                .get(&id1)
-                .expect("Missing saved share for id1")
-                .mask,
-        ));
-
-        let secret1 = node1.send_masked_share(id1 ^ id2).unwrap_or_default();
-        let secret2 = node2.send_masked_share(id1 ^ id2).unwrap_or_default();
-        let secret3 = node3.send_masked_share(id1 ^ id2).unwrap_or_default();
-
-        assert_eq!(secret1.share ^ secret2.share ^ secret3.share, 0b100010)
+    #[tokio::test]
+    async fn test_three_nodes_evaluate_circuit() {
+        // x & y for two rows, plus a comparison that needs several rounds
+        let x = [0b1100u64, 0b101010];
+        let y = [0b1010u64, 0b100010];
+        let mut builder = CircuitBuilder::new();
+        let column = |column: usize| InputColumn {
+            table: "values".to_string(),
+            table_id: 0,
+            schema_hash: 0,
+            column,
+            width: 8,
+            sharing: InputSharing::Boolean,
+            rows: 2,
+        };
+        let (ix, iy) = (builder.input(column(0)), builder.input(column(1)));
+        let (bits_x, bits_y) = (builder.input_bits(ix), builder.input_bits(iy));
+        let and: Vec<Wire> = bits_x.iter().zip(&bits_y).map(|(a, b)| builder.and(*a, *b)).collect();
+        builder.output("x AND y", OutputWires::Bits(and));
+        let greater = builder.compare(&bits_x, Comparison::Greater, &bits_y);
+        builder.output("x > y", OutputWires::Bits(vec![greater]));
+        let circuit = builder.finish();
+
+        let (shared_x, shared_y) = (share_values(&x, 8), share_values(&y, 8));
+        let exchanges = LocalExchange::ring().map(Arc::new);
+        let mut nodes: Vec<Node> = (0..3).map(|_| Node::new()).collect();
+        let [n0, n1, n2] = &mut nodes[..] else { unreachable!() };
+        let (r0, r1, r2) = tokio::join!(
+            n0.setup_randomness(exchanges[0].as_ref()),
+            n1.setup_randomness(exchanges[1].as_ref()),
+            n2.setup_randomness(exchanges[2].as_ref()),
+        );
+        r0.unwrap();
+        r1.unwrap();
+        r2.unwrap();
+
+        let inputs = [0, 1, 2].map(|p| [shared_x[p].clone(), shared_y[p].clone()]);
+        let (r0, r1, r2) = tokio::join!(
+            n0.evaluate_circuit(exchanges[0].clone(), &circuit, &inputs[0], 1),
+            n1.evaluate_circuit(exchanges[1].clone(), &circuit, &inputs[1], 1),
+            n2.evaluate_circuit(exchanges[2].clone(), &circuit, &inputs[2], 1),
+        );
+        let results = [r0.unwrap(), r1.unwrap(), r2.unwrap()];
+
+        let output = |i: usize| -> Vec<SharedBitColumn> {
+            results
+                .iter()
+                .map(|r| match &r.outputs[i].shares {
+                    OutputShares::Bits(bits) => bits.clone(),
+                    OutputShares::Word(_) => panic!("expected bits"),
+                })
+                .collect()
+        };
+        assert_eq!(reveal(&output(0)), vec![0b1000, 0b100010]);
+        assert_eq!(reveal(&output(1)), vec![1, 1]);
+        for result in &results {
+            // The 8 ANDs of x & y share the first round with the comparison leaves
+            assert_eq!(result.stats.rounds, circuit.depth() as u64);
+            assert_eq!(result.stats.rounds, 4);
+            assert_eq!(result.stats.gate_counts["INPUT"], 16);
+        }
+        assert!(nodes.iter().all(|n| n.randomness.is_some()));
     }
 }
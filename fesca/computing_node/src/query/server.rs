@@ -0,0 +1,324 @@
+// Query Server
+// ============
+// gRPC service through which the data analyst runs queries. The analyst sends
+// the same circuit and query id to all three nodes; every node loads the
+// circuit's input columns from its stored shares, evaluates the circuit
+// together with the other two over the share exchange and returns its own
+// shares of the results. The plaintext only exists at the analyst, who
+// reconstructs it from the three responses.
+//
+// Every query gets its own round ids and its own correlated randomness
+// derived from the query id, so all nodes agree on them no matter in which
+// order concurrent queries arrive.
+
+use anyhow::{anyhow, Result};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpListener;
+use tonic::transport::server::TcpIncoming;
+use tonic::{transport::Server, Request, Response, Status};
+use log::{error, info};
+
+use ::helpers::circuit::{Circuit, InputSharing};
+
+// Include the generated protobuf code
+pub mod query_service {
+    tonic::include_proto!("query_service");
+}
+
+use query_service::{
+    query_service_server::{QueryService, QueryServiceServer},
+    QueryStats, ResultColumn, ResultSharing, RunQueryRequest, RunQueryResponse, SharePair,
+};
+
+use crate::exchange::channel::ShareExchange;
+use crate::helpers::bit_column::SharedBitColumn;
+use crate::helpers::context::ProtocolContext;
+use crate::helpers::executor::{execute_circuit, CircuitOutput, CircuitResult, OutputShares};
+use crate::helpers::randomness::CorrelatedRandomness;
+use crate::receive::storage::BinaryShareStorage;
+
+/// First round id of a query; a query can use up to 2^32 rounds before
+/// running into the next query's ids
+pub fn query_first_round(query_id: u32) -> u64 {
+    (query_id as u64) << 32
+}
+
+/// Runs analyst queries on this node's stored shares
+pub struct QueryRunner {
+    storage: BinaryShareStorage,
+    exchange: Arc<dyn ShareExchange>,
+    randomness: CorrelatedRandomness,
+    used_ids: Mutex<HashSet<u32>>,
+}
+
+impl QueryRunner {
+    /// `randomness` is the node's stream set up with its neighbours; queries
+    /// only use streams derived from it
+    pub fn new(
+        storage_base_path: String,
+        exchange: Arc<dyn ShareExchange>,
+        randomness: CorrelatedRandomness,
+    ) -> Self {
+        Self {
+            storage: BinaryShareStorage::new(storage_base_path),
+            exchange,
+            randomness,
+            used_ids: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Id of this node
+    pub fn node_id(&self) -> u32 {
+        self.exchange.node_id()
+    }
+
+    /// Load the input columns of `circuit` from this node's share files
+    pub fn load_inputs(&self, circuit: &Circuit) -> Result<Vec<SharedBitColumn>> {
+        let party_id = self.node_id();
+        let mut tables: HashMap<&str, PathBuf> = HashMap::new();
+        let mut columns = Vec::with_capacity(circuit.inputs.len());
+        for input in &circuit.inputs {
+            let directory = match tables.get(input.table.as_str()) {
+                Some(directory) => directory.clone(),
+                None => {
+                    let directory = self.storage.find_table(&input.table)?;
+                    tables.insert(&input.table, directory.clone());
+                    directory
+                }
+            };
+            let file = directory.join(format!("party{}_data.bin", party_id));
+            let column = SharedBitColumn::load(&file, party_id, input.table_id, input.schema_hash, input.column)?;
+            if input.sharing == InputSharing::Arithmetic && column.width() != 64 {
+                return Err(anyhow!(
+                    "Column {} of table '{}' is not arithmetically shared",
+                    input.column,
+                    input.table
+                ));
+            }
+            columns.push(column);
+        }
+        Ok(columns)
+    }
+
+    /// Evaluate a query circuit. Every query id can only be used once, as a
+    /// second run would reuse the masks of the first.
+    pub async fn run(&self, query_id: u32, circuit: &Circuit) -> Result<CircuitResult> {
+        let is_new = self.used_ids.lock().expect("query id lock poisoned").insert(query_id);
+        if !is_new {
+            return Err(anyhow!("Query id {} has already been used", query_id));
+        }
+
+        let inputs = self.load_inputs(circuit)?;
+        let mut ctx = ProtocolContext::new(
+            self.exchange.clone(),
+            self.randomness.for_query(query_id),
+            query_first_round(query_id),
+        );
+        execute_circuit(&mut ctx, circuit, &inputs).await
+    }
+}
+
+#[tonic::async_trait]
+impl QueryService for QueryRunner {
+    /// Evaluate a circuit sent by the data analyst and return this node's result shares
+    async fn run_query(
+        &self,
+        request: Request<RunQueryRequest>,
+    ) -> Result<Response<RunQueryResponse>, Status> {
+        let req = request.into_inner();
+        let circuit = Circuit::from_json(&req.circuit_json)
+            .map_err(|e| Status::invalid_argument(format!("Invalid circuit: {}", e)))?;
+
+        info!(
+            "Query {}: {} gates {:?}, depth {}",
+            req.query_id,
+            circuit.gates.len(),
+            circuit.gate_counts(),
+            circuit.depth()
+        );
+
+        let result = self.run(req.query_id, &circuit).await.map_err(|e| {
+            error!("Query {} failed: {}", req.query_id, e);
+            Status::internal(format!("Query {} failed: {}", req.query_id, e))
+        })?;
+
+        info!(
+            "Query {} done: {} rounds, {} bytes sent",
+            req.query_id, result.stats.rounds, result.stats.bytes_sent
+        );
+
+        Ok(Response::new(RunQueryResponse {
+            node_id: self.node_id(),
+            query_id: req.query_id,
+            columns: result.outputs.into_iter().map(result_column).collect(),
+            stats: Some(QueryStats {
+                rounds: result.stats.rounds,
+                bytes_sent: result.stats.bytes_sent,
+                gate_counts: result
+                    .stats
+                    .gate_counts
+                    .into_iter()
+                    .map(|(name, count)| (name, count as u64))
+                    .collect(),
+            }),
+        }))
+    }
+}
+
+/// Convert one output of the executor to its protobuf form
+fn result_column(output: CircuitOutput) -> ResultColumn {
+    let (sharing, rows, planes) = match output.shares {
+        OutputShares::Bits(column) => (
+            ResultSharing::Boolean,
+            column.rows,
+            column
+                .planes
+                .into_iter()
+                .map(|plane| SharePair { own: plane.own, prev: plane.prev })
+                .collect(),
+        ),
+        OutputShares::Word(column) => (
+            ResultSharing::Arithmetic,
+            column.rows(),
+            vec![SharePair { own: column.own, prev: column.prev }],
+        ),
+    };
+    ResultColumn {
+        name: output.name,
+        sharing: sharing as i32,
+        rows: rows as u64,
+        planes,
+    }
+}
+
+/// Set up correlated randomness with the neighbouring nodes, then serve
+/// analyst queries. The exchange server must already be running.
+pub async fn start_query_server(
+    port: u16,
+    storage_base_path: String,
+    exchange: Arc<dyn ShareExchange>,
+) -> Result<()> {
+    let listener = TcpListener::bind(("0.0.0.0", port)).await?;
+    serve_queries(listener, storage_base_path, exchange).await
+}
+
+/// Like `start_query_server`, on an already bound listener. Connections that
+/// arrive before the randomness setup is done wait in the listener's backlog.
+pub async fn serve_queries(
+    listener: TcpListener,
+    storage_base_path: String,
+    exchange: Arc<dyn ShareExchange>,
+) -> Result<()> {
+    let randomness = CorrelatedRandomness::setup(exchange.as_ref()).await?;
+
+    info!("Starting computing node query server on {}", listener.local_addr()?);
+
+    Server::builder()
+        .add_service(QueryServiceServer::new(QueryRunner::new(storage_base_path, exchange, randomness)))
+        .serve_with_incoming(TcpIncoming::from_listener(listener, true, None).map_err(|e| anyhow!(e))?)
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::Path;
+
+    use ::helpers::circuit::compare::Comparison;
+    use ::helpers::circuit::{CircuitBuilder, InputColumn, OutputWires};
+
+    use crate::exchange::channel::LocalExchange;
+    use crate::helpers::bit_column::testing::{reveal, share_values};
+    use crate::receive::columnar;
+    use crate::receive::server::share_service::{BinaryPartyData, BinaryRow};
+
+    const SCHEMA_HASH: u64 = 0x5eed;
+
+    /// Store one 8-bit column as node `p`'s `party{p}_data.bin`
+    fn store_column(root: &Path, column: &SharedBitColumn) {
+        let p = column.party_id;
+        let byte = |r: usize, pick: fn((bool, bool)) -> bool| -> u8 {
+            (0..8).fold(0, |acc, j| acc | ((pick(column.planes[j].get(r)) as u8) << j))
+        };
+        let data = BinaryPartyData {
+            party_id: p,
+            table_id: 1,
+            rows: (0..column.rows)
+                .map(|r| BinaryRow {
+                    bitstring_a: vec![byte(r, |(own, _)| own)],
+                    bitstring_b: vec![byte(r, |(_, prev)| prev)],
+                    column_bit_offsets: vec![0],
+                    column_bit_lengths: vec![8],
+                })
+                .collect(),
+        };
+        let directory = root.join(format!("node{}", p)).join("owner_001").join("items");
+        fs::create_dir_all(&directory).unwrap();
+        columnar::write_columnar(&directory.join(format!("party{}_data.bin", p)), &data, SCHEMA_HASH).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_three_runners_evaluate_stored_columns() {
+        let root = std::env::temp_dir().join(format!("fesca_query_test_{}", std::process::id()));
+        let values = [3, 200, 17, 11];
+        let shares = share_values(&values, 8);
+        shares.iter().for_each(|column| store_column(&root, column));
+
+        let mut builder = CircuitBuilder::new();
+        let input = builder.input(InputColumn {
+            table: "ITEMS".to_string(),
+            table_id: 1,
+            schema_hash: SCHEMA_HASH,
+            column: 0,
+            width: 8,
+            sharing: InputSharing::Boolean,
+            rows: values.len(),
+        });
+        let bits = builder.input_bits(input);
+        let big = builder.compare_const(&bits, Comparison::Greater, 10);
+        builder.output("big", OutputWires::Bits(vec![big]));
+        let circuit = builder.finish();
+
+        let keys: [[u8; 32]; 3] = [[1; 32], [2; 32], [3; 32]];
+        let runners: Vec<QueryRunner> = LocalExchange::ring()
+            .into_iter()
+            .enumerate()
+            .map(|(p, exchange)| {
+                QueryRunner::new(
+                    root.join(format!("node{}", p)).to_string_lossy().to_string(),
+                    Arc::new(exchange),
+                    CorrelatedRandomness::from_keys(keys[p], keys[(p + 2) % 3]),
+                )
+            })
+            .collect();
+
+        let (r0, r1, r2) = tokio::join!(
+            runners[0].run(5, &circuit),
+            runners[1].run(5, &circuit),
+            runners[2].run(5, &circuit),
+        );
+        let columns: Vec<SharedBitColumn> = [r0, r1, r2]
+            .into_iter()
+            .map(|result| match result.unwrap().outputs.swap_remove(0).shares {
+                OutputShares::Bits(column) => column,
+                other => panic!("unexpected output {:?}", other),
+            })
+            .collect();
+        assert_eq!(reveal(&columns), vec![0, 1, 1, 1]);
+
+        // A query id is only accepted once
+        assert!(runners[0].run(5, &circuit).await.is_err());
+
+        // Shares stored under another schema are not read
+        let mut stale = circuit.clone();
+        stale.inputs[0].schema_hash += 1;
+        let error = runners[0].load_inputs(&stale).unwrap_err();
+        assert!(error.to_string().contains("expected table 1"), "{}", error);
+        fs::remove_dir_all(&root).ok();
+    }
+}
@@ -0,0 +1,552 @@
+// Columnar Share Format (v2)
+// ==========================
+// On-disk layout for a party's shares of one table. Unlike the row-based v1
+// format ("FESCASHR"), the column layout is stored once in a directory and
+// the body is column-major, so an operator can seek to (or memory-map) the
+// share bits of a single column without scanning the file.
+//
+// Layout (all integers little-endian):
+//   Header, 40 bytes
+//     [8]  magic "FESCASHC"
+//     [2]  format version (2)
+//     [2]  reserved, zero
+//     [4]  table id
+//     [4]  party id
+//     [8]  schema hash (see `schema_hash`)
+//     [8]  row count
+//     [4]  column count
+//   Column directory, 24 bytes per column
+//     [4]  bit width
+//     [4]  CRC32 of the column block
+//     [8]  absolute file offset of the column block (8-byte aligned)
+//     [8]  length of the column block in bytes
+//   [4]  CRC32 over header and directory, then zero padding to 8 bytes
+//   Column blocks, one per column
+//     for each bit-plane j in 0..width:
+//       [8 * W] own share words, then [8 * W] prev share words,
+//       W = ceil(row count / 64), row r is bit r % 64 of word r / 64
+
+use std::fs;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use super::server::share_service;
+use super::storage::{extract_column_bits, ShareFileError};
+use crate::helpers::bit_column::{words_for, SharedBitColumn, SharedBits};
+
+/// Magic number at the start of every v2 party data file
+pub const COLUMNAR_MAGIC: &[u8; 8] = b"FESCASHC";
+
+/// Format version written by `write_columnar`
+pub const FORMAT_VERSION: u16 = 2;
+
+const HEADER_LEN: usize = 40;
+const DIRECTORY_ENTRY_LEN: usize = 24;
+
+/// Location and checksum of one column block
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnEntry {
+    pub bit_width: u32,
+    pub crc32: u32,
+    pub data_offset: u64,
+    pub data_len: u64,
+}
+
+/// Header and column directory of a v2 file
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnarHeader {
+    pub format_version: u16,
+    pub table_id: u32,
+    pub party_id: u32,
+    pub schema_hash: u64,
+    pub row_count: u64,
+    pub columns: Vec<ColumnEntry>,
+}
+
+/// Stable description of a column type, used for hashing and schema files
+pub fn column_type_name(type_hint: &Option<share_service::ColumnType>) -> String {
+    use share_service::charset::Charset;
+    use share_service::column_type::Type;
+
+    match type_hint.as_ref().and_then(|t| t.r#type.as_ref()) {
+        Some(Type::Boolean(_)) => "Boolean".to_string(),
+        Some(Type::UnsignedInt(_)) => "UnsignedInt".to_string(),
+        Some(Type::Float(_)) => "Float".to_string(),
+        Some(Type::String(s)) => {
+            let charset = match s.charset.as_ref().and_then(|c| c.charset.as_ref()) {
+                Some(Charset::Utf8(_)) => "Utf8",
+                _ => "Ascii",
+            };
+            format!("String({},{})", s.max_chars, charset)
+        }
+        None => "Unknown".to_string(),
+    }
+}
+
+/// FNV-1a hash of the table name and the column names, types and sharings.
+/// Computed the same way on every node, so files of all parties of a table
+/// carry the same hash.
+pub fn schema_hash(schema: &share_service::TableSchema) -> u64 {
+    let mut description = schema.table_name.clone();
+    for column in &schema.columns {
+        description.push_str(&format!(";{}:{}", column.name, column_type_name(&column.type_hint)));
+        if column.sharing() == share_service::Sharing::Arithmetic {
+            description.push_str(":arithmetic");
+        }
+        if let Some(decimals) = column.decimals {
+            description.push_str(&format!(":decimals{}", decimals));
+        }
+    }
+    description.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// Whether `file_path` starts with the v2 magic number
+pub fn is_columnar(file_path: &Path) -> Result<bool, ShareFileError> {
+    let mut magic = [0u8; 8];
+    let mut file = fs::File::open(file_path)?;
+    match file.read_exact(&mut magic) {
+        Ok(()) => Ok(&magic == COLUMNAR_MAGIC),
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Write row-based party data in the columnar v2 layout
+pub fn write_columnar(
+    file_path: &Path,
+    party_data: &share_service::BinaryPartyData,
+    schema_hash: u64,
+) -> Result<(), ShareFileError> {
+    let rows = party_data.rows.len();
+    let widths: Vec<u32> = party_data
+        .rows
+        .first()
+        .map(|row| row.column_bit_lengths.clone())
+        .unwrap_or_default();
+
+    // Transpose the rows into one bit-plane block per column
+    let mut blocks = Vec::with_capacity(widths.len());
+    for (column, width) in widths.iter().enumerate() {
+        let mut planes = vec![SharedBits::zeros(words_for(rows)); *width as usize];
+        for (r, row) in party_data.rows.iter().enumerate() {
+            let bits = extract_column_bits(row, column)?;
+            if bits.bits_a.len() != *width as usize {
+                return Err(ShareFileError::Corrupt(format!(
+                    "row {} has width {} for column {}, expected {}",
+                    r,
+                    bits.bits_a.len(),
+                    column,
+                    width
+                )));
+            }
+            for (j, plane) in planes.iter_mut().enumerate() {
+                plane.set(r, bits.bits_a[j], bits.bits_b[j]);
+            }
+        }
+        blocks.push(encode_block(&planes));
+    }
+
+    // Column blocks start after header, directory and header checksum, 8-byte aligned
+    let directory_end = HEADER_LEN + DIRECTORY_ENTRY_LEN * widths.len() + 4;
+    let mut data_offset = directory_end.div_ceil(8) * 8;
+    let mut header = Vec::with_capacity(directory_end);
+    header.extend_from_slice(COLUMNAR_MAGIC);
+    header.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    header.extend_from_slice(&0u16.to_le_bytes());
+    header.extend_from_slice(&party_data.table_id.to_le_bytes());
+    header.extend_from_slice(&party_data.party_id.to_le_bytes());
+    header.extend_from_slice(&schema_hash.to_le_bytes());
+    header.extend_from_slice(&(rows as u64).to_le_bytes());
+    header.extend_from_slice(&(widths.len() as u32).to_le_bytes());
+    for (width, block) in widths.iter().zip(&blocks) {
+        header.extend_from_slice(&width.to_le_bytes());
+        header.extend_from_slice(&crc32fast::hash(block).to_le_bytes());
+        header.extend_from_slice(&(data_offset as u64).to_le_bytes());
+        header.extend_from_slice(&(block.len() as u64).to_le_bytes());
+        data_offset += block.len();
+    }
+    let header_crc = crc32fast::hash(&header);
+    header.extend_from_slice(&header_crc.to_le_bytes());
+    header.resize(header.len().div_ceil(8) * 8, 0);
+
+    // Write next to the target and rename, so readers never see a partial file
+    let temp_path = temp_path(file_path);
+    let mut file = std::io::BufWriter::new(fs::File::create(&temp_path)?);
+    file.write_all(&header)?;
+    for block in &blocks {
+        file.write_all(block)?;
+    }
+    file.flush()?;
+    drop(file);
+    fs::rename(&temp_path, file_path)?;
+    Ok(())
+}
+
+/// `file_path` with `.tmp` appended
+fn temp_path(file_path: &Path) -> PathBuf {
+    let mut path = file_path.as_os_str().to_owned();
+    path.push(".tmp");
+    PathBuf::from(path)
+}
+
+/// A v2 file whose header and column directory have been read and verified
+pub struct ColumnarFile {
+    file: fs::File,
+    pub header: ColumnarHeader,
+}
+
+impl ColumnarFile {
+    pub fn open(file_path: &Path) -> Result<Self, ShareFileError> {
+        let mut file = fs::File::open(file_path)?;
+        let header = read_header(&mut file)?;
+        Ok(Self { file, header })
+    }
+
+    /// Read one column, verifying its block checksum. Only the column's own
+    /// block is read.
+    pub fn read_column(&mut self, column: usize) -> Result<SharedBitColumn, ShareFileError> {
+        read_column_block(&mut self.file, &self.header, column)
+    }
+}
+
+/// Read and verify the header and column directory of a v2 file
+pub fn read_columnar_header(file_path: &Path) -> Result<ColumnarHeader, ShareFileError> {
+    Ok(ColumnarFile::open(file_path)?.header)
+}
+
+/// Read one column, verifying its block checksum. Only the header and the
+/// column's own block are read.
+pub fn read_column(file_path: &Path, column: usize) -> Result<SharedBitColumn, ShareFileError> {
+    ColumnarFile::open(file_path)?.read_column(column)
+}
+
+/// Read a whole v2 file back into the row-based representation
+pub fn read_columnar(file_path: &Path) -> Result<share_service::BinaryPartyData, ShareFileError> {
+    let mut file = fs::File::open(file_path)?;
+    let header = read_header(&mut file)?;
+    let columns = (0..header.columns.len())
+        .map(|c| read_column_block(&mut file, &header, c))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let widths: Vec<u32> = header.columns.iter().map(|c| c.bit_width).collect();
+    let mut offsets = Vec::with_capacity(widths.len());
+    let mut row_bits = 0u32;
+    for width in &widths {
+        offsets.push(row_bits);
+        row_bits = row_bits
+            .checked_add(*width)
+            .ok_or_else(|| ShareFileError::Corrupt("column widths overflow the row size".to_string()))?;
+    }
+    let row_bytes = (row_bits as usize).div_ceil(8);
+
+    let rows = (0..header.row_count as usize)
+        .map(|r| {
+            let mut row = share_service::BinaryRow {
+                bitstring_a: vec![0; row_bytes],
+                bitstring_b: vec![0; row_bytes],
+                column_bit_offsets: offsets.clone(),
+                column_bit_lengths: widths.clone(),
+            };
+            for (column, offset) in columns.iter().zip(&offsets) {
+                for (j, plane) in column.planes.iter().enumerate() {
+                    let (own, prev) = plane.get(r);
+                    let i = *offset as usize + j;
+                    row.bitstring_a[i / 8] |= (own as u8) << (i % 8);
+                    row.bitstring_b[i / 8] |= (prev as u8) << (i % 8);
+                }
+            }
+            row
+        })
+        .collect();
+
+    Ok(share_service::BinaryPartyData {
+        party_id: header.party_id,
+        table_id: header.table_id,
+        rows,
+    })
+}
+
+fn encode_block(planes: &[SharedBits]) -> Vec<u8> {
+    let mut block = Vec::new();
+    for plane in planes {
+        for word in plane.own.iter().chain(&plane.prev) {
+            block.extend_from_slice(&word.to_le_bytes());
+        }
+    }
+    block
+}
+
+fn read_header(file: &mut fs::File) -> Result<ColumnarHeader, ShareFileError> {
+    let mut fixed = [0u8; HEADER_LEN];
+    read_exact_at(file, 0, &mut fixed)?;
+    if &fixed[..8] != COLUMNAR_MAGIC {
+        return Err(ShareFileError::BadMagic { found: fixed[..8].to_vec() });
+    }
+
+    let u16_at = |i: usize| u16::from_le_bytes([fixed[i], fixed[i + 1]]);
+    let u32_at = |i: usize| u32::from_le_bytes(fixed[i..i + 4].try_into().expect("4 bytes"));
+    let u64_at = |i: usize| u64::from_le_bytes(fixed[i..i + 8].try_into().expect("8 bytes"));
+
+    let format_version = u16_at(8);
+    if format_version != FORMAT_VERSION {
+        return Err(ShareFileError::Corrupt(format!(
+            "unsupported format version {}",
+            format_version
+        )));
+    }
+    let column_count = u32_at(36) as usize;
+
+    // Check the directory against the file length before allocating it
+    let entries_len = DIRECTORY_ENTRY_LEN
+        .checked_mul(column_count)
+        .ok_or_else(|| ShareFileError::Corrupt(format!("column count {} overflows", column_count)))?;
+    ensure_within(file.metadata()?.len(), HEADER_LEN as u64, entries_len as u64 + 4)?;
+
+    let mut directory = vec![0u8; entries_len + 4];
+    read_exact_at(file, HEADER_LEN as u64, &mut directory)?;
+    let (entries, crc) = directory.split_at(entries_len);
+
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(&fixed);
+    hasher.update(entries);
+    if hasher.finalize().to_le_bytes() != crc {
+        return Err(ShareFileError::Corrupt("header checksum mismatch".to_string()));
+    }
+
+    let columns: Vec<ColumnEntry> = entries
+        .chunks_exact(DIRECTORY_ENTRY_LEN)
+        .map(|e| ColumnEntry {
+            bit_width: u32::from_le_bytes(e[0..4].try_into().expect("4 bytes")),
+            crc32: u32::from_le_bytes(e[4..8].try_into().expect("4 bytes")),
+            data_offset: u64::from_le_bytes(e[8..16].try_into().expect("8 bytes")),
+            data_len: u64::from_le_bytes(e[16..24].try_into().expect("8 bytes")),
+        })
+        .collect();
+
+    // Blocks are only checked against the file size, which bounds neither the
+    // rows if they carry no share bits nor the widths if there are no rows.
+    // The writer takes the layout from the first row, so neither happens.
+    let row_count = u64_at(28);
+    let has_bits = columns.iter().any(|c| c.bit_width > 0);
+    if (row_count > 0) != has_bits {
+        return Err(ShareFileError::Corrupt(format!(
+            "{} rows with {} share bits per row",
+            row_count,
+            if has_bits { "some" } else { "no" }
+        )));
+    }
+
+    Ok(ColumnarHeader {
+        format_version,
+        table_id: u32_at(12),
+        party_id: u32_at(16),
+        schema_hash: u64_at(20),
+        row_count,
+        columns,
+    })
+}
+
+fn read_column_block(
+    file: &mut fs::File,
+    header: &ColumnarHeader,
+    column: usize,
+) -> Result<SharedBitColumn, ShareFileError> {
+    let entry = header.columns.get(column).ok_or(ShareFileError::NoSuchColumn {
+        column,
+        columns: header.columns.len(),
+    })?;
+
+    let expected_len = (entry.bit_width as u64)
+        .checked_mul(16)
+        .and_then(|len| len.checked_mul(header.row_count.div_ceil(64)))
+        .ok_or_else(|| {
+            ShareFileError::Corrupt(format!(
+                "column {} size overflows ({} bits, {} rows)",
+                column, entry.bit_width, header.row_count
+            ))
+        })?;
+    if entry.data_len != expected_len {
+        return Err(ShareFileError::Corrupt(format!(
+            "column {} block has {} bytes, expected {}",
+            column, entry.data_len, expected_len
+        )));
+    }
+    ensure_within(file.metadata()?.len(), entry.data_offset, entry.data_len)?;
+    let rows = header.row_count as usize;
+    let words = words_for(rows);
+
+    let mut block = vec![0u8; entry.data_len as usize];
+    read_exact_at(file, entry.data_offset, &mut block)?;
+    if crc32fast::hash(&block) != entry.crc32 {
+        return Err(ShareFileError::Corrupt(format!("column {} checksum mismatch", column)));
+    }
+
+    let word_values: Vec<u64> = block
+        .chunks_exact(8)
+        .map(|w| u64::from_le_bytes(w.try_into().expect("8 bytes")))
+        .collect();
+    let planes = word_values
+        .chunks_exact(2 * words)
+        .map(|plane| SharedBits {
+            own: plane[..words].to_vec(),
+            prev: plane[words..].to_vec(),
+        })
+        .collect();
+
+    Ok(SharedBitColumn {
+        party_id: header.party_id,
+        rows,
+        planes,
+    })
+}
+
+/// Read exactly `buf.len()` bytes at `offset`, reporting a short file as truncation
+fn read_exact_at(file: &mut fs::File, offset: u64, buf: &mut [u8]) -> Result<(), ShareFileError> {
+    ensure_within(file.metadata()?.len(), offset, buf.len() as u64)?;
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(buf)?;
+    Ok(())
+}
+
+/// Check that `len` bytes at `offset` lie within a file of `file_len` bytes
+fn ensure_within(file_len: u64, offset: u64, len: u64) -> Result<(), ShareFileError> {
+    let end = offset.saturating_add(len);
+    if end > file_len {
+        return Err(ShareFileError::Truncated {
+            offset: offset.min(file_len) as usize,
+            needed: (end - file_len.max(offset)) as usize,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_party_data() -> share_service::BinaryPartyData {
+        share_service::BinaryPartyData {
+            party_id: 2,
+            table_id: 4,
+            rows: (0..70u32)
+                .map(|r| {
+                    // 1-bit column, then a 32-bit column packed right behind it
+                    let a = ((r as u64) * 0x9E37_79B9) << 1 | (r as u64 & 1);
+                    let b = !a;
+                    share_service::BinaryRow {
+                        bitstring_a: a.to_le_bytes()[..5].to_vec(),
+                        bitstring_b: b.to_le_bytes()[..5].to_vec(),
+                        column_bit_offsets: vec![0, 1],
+                        column_bit_lengths: vec![1, 32],
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    /// Apply `edit` to the header and directory of a v2 file and fix up the
+    /// header checksum, so only the edited values are wrong
+    fn rewrite_header(bytes: &mut [u8], edit: impl FnOnce(&mut [u8])) {
+        let columns = u32::from_le_bytes(bytes[36..40].try_into().unwrap()) as usize;
+        let end = HEADER_LEN + DIRECTORY_ENTRY_LEN * columns;
+        edit(&mut bytes[..end]);
+        let crc = crc32fast::hash(&bytes[..end]);
+        bytes[end..end + 4].copy_from_slice(&crc.to_le_bytes());
+    }
+
+    fn temp_file(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("fesca_columnar_{}_{}.bin", name, std::process::id()))
+    }
+
+    #[test]
+    fn test_columnar_roundtrip() {
+        let path = temp_file("roundtrip");
+        let data = sample_party_data();
+        write_columnar(&path, &data, 42).unwrap();
+
+        let header = read_columnar_header(&path).unwrap();
+        assert_eq!(header.format_version, FORMAT_VERSION);
+        assert_eq!((header.table_id, header.party_id), (4, 2));
+        assert_eq!((header.schema_hash, header.row_count), (42, 70));
+        assert_eq!(header.columns.len(), 2);
+        assert!(header.columns.iter().all(|c| c.data_offset % 8 == 0));
+
+        let column = read_column(&path, 1).unwrap();
+        assert_eq!(column, SharedBitColumn::from_party_data(&data, 1).unwrap());
+
+        let mut restored = read_columnar(&path).unwrap();
+        // The bitstrings are rebuilt without the unused padding bits of the last byte
+        for (row, original) in restored.rows.iter_mut().zip(&data.rows) {
+            assert_eq!(extract_column_bits(row, 1).unwrap(), extract_column_bits(original, 1).unwrap());
+        }
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_detects_corruption() {
+        let path = temp_file("corrupt");
+        write_columnar(&path, &sample_party_data(), 42).unwrap();
+        let header = read_columnar_header(&path).unwrap();
+        let bytes = fs::read(&path).unwrap();
+
+        // Flip a bit inside the second column block
+        let mut flipped = bytes.clone();
+        flipped[header.columns[1].data_offset as usize + 3] ^= 1;
+        fs::write(&path, &flipped).unwrap();
+        assert!(read_column(&path, 0).is_ok());
+        assert!(matches!(read_column(&path, 1), Err(ShareFileError::Corrupt(_))));
+
+        // Change the row count in the header
+        let mut header_changed = bytes.clone();
+        header_changed[28] ^= 1;
+        fs::write(&path, &header_changed).unwrap();
+        assert!(matches!(read_columnar_header(&path), Err(ShareFileError::Corrupt(_))));
+
+        // Cut the file in the middle of the last block
+        fs::write(&path, &bytes[..bytes.len() - 10]).unwrap();
+        assert!(matches!(read_column(&path, 1), Err(ShareFileError::Truncated { .. })));
+
+        // A huge column count is rejected before the directory is allocated
+        let mut many_columns = bytes.clone();
+        many_columns[36..40].copy_from_slice(&u32::MAX.to_le_bytes());
+        fs::write(&path, &many_columns).unwrap();
+        assert!(matches!(read_columnar_header(&path), Err(ShareFileError::Truncated { .. })));
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_rejects_unbounded_sizes() {
+        let path = temp_file("sizes");
+        write_columnar(&path, &sample_party_data(), 42).unwrap();
+        let bytes = fs::read(&path).unwrap();
+
+        // Without rows every block is empty, so the size check passes any widths
+        let mut wide = bytes.clone();
+        rewrite_header(&mut wide, |header| {
+            header[28..36].copy_from_slice(&0u64.to_le_bytes());
+            for column in 0..2 {
+                let entry = HEADER_LEN + DIRECTORY_ENTRY_LEN * column;
+                header[entry..entry + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+                header[entry + 4..entry + 8].copy_from_slice(&crc32fast::hash(&[]).to_le_bytes());
+                header[entry + 16..entry + 24].copy_from_slice(&0u64.to_le_bytes());
+            }
+        });
+        fs::write(&path, &wide).unwrap();
+        assert!(matches!(read_columnar(&path), Err(ShareFileError::Corrupt(_))));
+
+        // A table without columns cannot claim any rows
+        let empty = share_service::BinaryPartyData { party_id: 0, table_id: 4, rows: Vec::new() };
+        write_columnar(&path, &empty, 42).unwrap();
+        let mut bytes = fs::read(&path).unwrap();
+        assert_eq!(read_columnar(&path).unwrap(), empty);
+        rewrite_header(&mut bytes, |header| header[28..36].copy_from_slice(&u64::MAX.to_le_bytes()));
+        fs::write(&path, &bytes).unwrap();
+        assert!(matches!(read_columnar(&path), Err(ShareFileError::Corrupt(_))));
+
+        // The file is written through a temporary file that does not stay behind
+        assert!(!temp_path(&path).exists());
+        fs::remove_file(&path).ok();
+    }
+}
@@ -1,11 +1,15 @@
 // Binary Share Storage
 // ====================
-// Handles storing binary share data received from data owners
+// Handles storing binary share data received from data owners and reading it back.
+// New files use the columnar v2 format (see `columnar`); row-based v1 files
+// written by earlier versions can still be read and migrated in place.
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use std::fs;
 use std::io::Write;
+use std::path::{Path, PathBuf};
 
+use super::columnar;
 use super::server::share_service;
 
 /// Handles storage of binary share data
@@ -27,6 +31,32 @@ impl BinaryShareStorage {
         format!("{}/{}/{}", self.base_path, data_owner.owner_id, schema.table_name)
     }
 
+    /// Directory of a stored table, looked up by name below every data
+    /// owner's directory and matched case-insensitively
+    pub fn find_table(&self, table_name: &str) -> Result<PathBuf> {
+        let mut found = Vec::new();
+        for owner in fs::read_dir(&self.base_path)? {
+            let owner = owner?.path();
+            if !owner.is_dir() {
+                continue;
+            }
+            for table in fs::read_dir(&owner)? {
+                let table = table?.path();
+                let matches = table
+                    .file_name()
+                    .is_some_and(|name| name.to_string_lossy().eq_ignore_ascii_case(table_name));
+                if matches && table.is_dir() {
+                    found.push(table);
+                }
+            }
+        }
+        match found.len() {
+            0 => Err(anyhow!("Table '{}' is not stored on this node", table_name)),
+            1 => Ok(found.remove(0)),
+            _ => Err(anyhow!("Table '{}' is stored by several data owners", table_name)),
+        }
+    }
+
     /// Store binary party data as optimized binary files
     pub async fn store_binary_shares(
         &self,
@@ -41,21 +71,26 @@ impl BinaryShareStorage {
         
         let mut files_created = Vec::new();
 
-        // 1. Store the actual binary data
+        // 1. Store the actual binary data in the columnar format, after checking
+        //    that every received row's column layout fits its bitstrings
+        for (row_index, row) in party_data.rows.iter().enumerate() {
+            validate_row(row, row_index)?;
+        }
         let data_file = format!("{}/party{}_data.bin", storage_path, party_data.party_id);
-        self.write_binary_data(&data_file, party_data).await?;
+        columnar::write_columnar(Path::new(&data_file), party_data, columnar::schema_hash(schema))?;
         files_created.push(data_file);
 
         // 2. Store schema information for reference
         let schema_file = format!("{}/schema.json", storage_path);
-        self.write_schema_json(&schema_file, schema, data_owner).await?;
+        self.write_schema_json(&schema_file, schema, data_owner, party_data).await?;
         files_created.push(schema_file);
 
         Ok(files_created)
     }
 
-    /// Write the actual binary data (bitstrings) with simplified header
-    async fn write_binary_data(
+    /// Write the actual binary data (bitstrings) in the row-based v1 format.
+    /// Only kept to produce v1 files, `store_binary_shares` writes v2.
+    pub async fn write_binary_data(
         &self,
         file_path: &str,
         party_data: &share_service::BinaryPartyData,
@@ -67,8 +102,7 @@ impl BinaryShareStorage {
         // [4 bytes] Number of rows: u32
         // Then the actual row data follows...
 
-        let magic = b"FESCASHR"; // 8 bytes
-        file.write_all(magic)?;
+        file.write_all(SHARE_FILE_MAGIC)?; // 8 bytes
         
         // Binary data format:
         // [4 bytes] Number of rows: u32
@@ -109,25 +143,130 @@ impl BinaryShareStorage {
         Ok(())
     }
 
-    /// Write schema as JSON for human readability with data owner information
+    /// Read a party data file in either format. v1 files do not record party
+    /// and table ids, so the caller supplies them; v2 files must match them.
+    pub fn read_party_data(
+        file_path: &Path,
+        party_id: u32,
+        table_id: u32,
+    ) -> Result<share_service::BinaryPartyData, ShareFileError> {
+        if !columnar::is_columnar(file_path)? {
+            return Self::read_binary_data(file_path, party_id, table_id);
+        }
+        let data = columnar::read_columnar(file_path)?;
+        if (data.party_id, data.table_id) != (party_id, table_id) {
+            return Err(ShareFileError::Corrupt(format!(
+                "file holds table {} of party {}, expected table {} of party {}",
+                data.table_id, data.party_id, table_id, party_id
+            )));
+        }
+        Ok(data)
+    }
+
+    /// Rewrite a v1 party data file in the columnar v2 format.
+    /// Returns false if the file already is v2.
+    pub fn migrate_to_columnar(
+        file_path: &Path,
+        party_id: u32,
+        schema: &share_service::TableSchema,
+    ) -> Result<bool, ShareFileError> {
+        if columnar::is_columnar(file_path)? {
+            return Ok(false);
+        }
+        let data = Self::read_binary_data(file_path, party_id, schema.table_id)?;
+
+        // The file is replaced by a rename, so a failed migration keeps the v1 file
+        columnar::write_columnar(file_path, &data, columnar::schema_hash(schema))?;
+        Ok(true)
+    }
+
+    /// Read a v1 party data file written by `write_binary_data`.
+    /// The file does not record party and table ids, so the caller supplies them.
+    pub fn read_binary_data(
+        file_path: &Path,
+        party_id: u32,
+        table_id: u32,
+    ) -> Result<share_service::BinaryPartyData, ShareFileError> {
+        let data = fs::read(file_path)?;
+        Self::parse_binary_data(&data, party_id, table_id)
+    }
+
+    /// Parse the contents of a v1 party data file, see `write_binary_data` for the layout
+    pub fn parse_binary_data(
+        data: &[u8],
+        party_id: u32,
+        table_id: u32,
+    ) -> Result<share_service::BinaryPartyData, ShareFileError> {
+        let mut cursor = Cursor { data, pos: 0 };
+
+        let magic = cursor.take(SHARE_FILE_MAGIC.len())?;
+        if magic != SHARE_FILE_MAGIC {
+            return Err(ShareFileError::BadMagic { found: magic.to_vec() });
+        }
+
+        let row_count = cursor.read_u32()?;
+        let mut rows = Vec::new();
+        for row_index in 0..row_count as usize {
+            let len = cursor.read_u32()? as usize;
+            let bitstring_a = cursor.take(len)?.to_vec();
+            let len = cursor.read_u32()? as usize;
+            let bitstring_b = cursor.take(len)?.to_vec();
+            let column_bit_offsets = cursor.read_u32_list()?;
+            let column_bit_lengths = cursor.read_u32_list()?;
+
+            let row = share_service::BinaryRow {
+                bitstring_a,
+                bitstring_b,
+                column_bit_offsets,
+                column_bit_lengths,
+            };
+            validate_row(&row, row_index)?;
+            rows.push(row);
+        }
+
+        if cursor.pos != data.len() {
+            return Err(ShareFileError::Corrupt(format!(
+                "{} trailing bytes after {} rows",
+                data.len() - cursor.pos,
+                row_count
+            )));
+        }
+
+        Ok(share_service::BinaryPartyData { party_id, table_id, rows })
+    }
+
+    /// Write schema as JSON for human readability with data owner information.
+    /// Types use the data owner's schema format, and only the columns actually
+    /// stored (rows may have fewer fields than the schema) are listed, so the
+    /// data analyst's catalog can read the file back.
     async fn write_schema_json(
         &self,
         file_path: &str,
         schema: &share_service::TableSchema,
         data_owner: &share_service::DataOwnerInfo,
+        party_data: &share_service::BinaryPartyData,
     ) -> Result<(), Box<dyn std::error::Error>> {
+        let stored_columns = party_data
+            .rows
+            .first()
+            .map_or(schema.columns.len(), |row| row.column_bit_offsets.len());
         let schema_data = serde_json::json!({
             "table_name": schema.table_name,
             "table_id": schema.table_id,
-            "row_count": schema.row_count,
+            "row_count": party_data.rows.len(),
+            "format_version": columnar::FORMAT_VERSION,
+            "schema_hash": format!("{:016x}", columnar::schema_hash(schema)),
             "data_owner": {
                 "owner_id": data_owner.owner_id,
                 "owner_name": data_owner.owner_name
             },
-            "columns": schema.columns.iter().map(|col| {
+            "columns": schema.columns.iter().take(stored_columns).map(|col| {
                 serde_json::json!({
                     "name": col.name,
-                    "type_hint": format!("{:?}", col.type_hint)
+                    "type_hint": type_hint_json(&col.type_hint),
+                    "sharing": format!("{:?}", col.sharing()),
+                    "unique": col.unique,
+                    "decimals": col.decimals
                 })
             }).collect::<Vec<_>>()
         });
@@ -135,4 +274,321 @@ impl BinaryShareStorage {
         fs::write(file_path, serde_json::to_string_pretty(&schema_data)?)?;
         Ok(())
     }
-} 
\ No newline at end of file
+}
+
+/// Column type in the data owner's schema JSON format, e.g. `"UnsignedInt"` or
+/// `{"String": {"max_chars": 8, "charset": "Ascii"}}`
+fn type_hint_json(type_hint: &Option<share_service::ColumnType>) -> serde_json::Value {
+    use share_service::charset::Charset;
+    use share_service::column_type::Type;
+
+    match type_hint.as_ref().and_then(|t| t.r#type.as_ref()) {
+        Some(Type::Boolean(_)) => serde_json::json!("Boolean"),
+        Some(Type::UnsignedInt(_)) => serde_json::json!("UnsignedInt"),
+        Some(Type::Float(_)) => serde_json::json!("Float"),
+        Some(Type::String(s)) => {
+            let charset = match s.charset.as_ref().and_then(|c| c.charset.as_ref()) {
+                Some(Charset::Utf8(_)) => "Utf8",
+                _ => "Ascii",
+            };
+            serde_json::json!({ "String": { "max_chars": s.max_chars, "charset": charset } })
+        }
+        None => serde_json::Value::Null,
+    }
+}
+
+/// Magic number at the start of every v1 party data file
+pub const SHARE_FILE_MAGIC: &[u8; 8] = b"FESCASHR";
+
+/// Errors when reading a stored party data file
+#[derive(Debug)]
+pub enum ShareFileError {
+    /// The file could not be read
+    Io(std::io::Error),
+    /// The file does not start with the expected magic number
+    BadMagic { found: Vec<u8> },
+    /// The file ended while `needed` more bytes were expected at `offset`
+    Truncated { offset: usize, needed: usize },
+    /// The file is complete but its contents are inconsistent
+    Corrupt(String),
+    /// A column index beyond the columns stored in a row
+    NoSuchColumn { column: usize, columns: usize },
+}
+
+impl std::fmt::Display for ShareFileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ShareFileError::Io(e) => write!(f, "I/O error: {}", e),
+            ShareFileError::BadMagic { found } => {
+                write!(f, "Not a FESCA share file (magic {:?})", String::from_utf8_lossy(found))
+            }
+            ShareFileError::Truncated { offset, needed } => {
+                write!(f, "Share file truncated: {} bytes missing at offset {}", needed, offset)
+            }
+            ShareFileError::Corrupt(reason) => write!(f, "Corrupt share file: {}", reason),
+            ShareFileError::NoSuchColumn { column, columns } => {
+                write!(f, "Column {} out of range, row has {} columns", column, columns)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ShareFileError {}
+
+impl From<std::io::Error> for ShareFileError {
+    fn from(e: std::io::Error) -> Self {
+        ShareFileError::Io(e)
+    }
+}
+
+/// The two share bitstrings of one column in one row (LSB first)
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnBits {
+    pub bits_a: Vec<bool>,
+    pub bits_b: Vec<bool>,
+}
+
+/// Extract the bits of a single column from a row using its
+/// `column_bit_offsets` / `column_bit_lengths`
+pub fn extract_column_bits(
+    row: &share_service::BinaryRow,
+    column: usize,
+) -> Result<ColumnBits, ShareFileError> {
+    let columns = row.column_bit_offsets.len();
+    let (offset, length) = match (row.column_bit_offsets.get(column), row.column_bit_lengths.get(column)) {
+        (Some(offset), Some(length)) => (*offset as usize, *length as usize),
+        _ => return Err(ShareFileError::NoSuchColumn { column, columns }),
+    };
+    for bitstring in [&row.bitstring_a, &row.bitstring_b] {
+        let available_bits = bitstring.len() as u64 * 8;
+        if offset as u64 + length as u64 > available_bits {
+            return Err(ShareFileError::Corrupt(format!(
+                "column {} (bits {}..{}) exceeds bitstring of {} bits",
+                column,
+                offset,
+                offset as u64 + length as u64,
+                available_bits
+            )));
+        }
+    }
+
+    let read = |bytes: &[u8]| -> Vec<bool> {
+        (offset..offset + length)
+            .map(|i| (bytes[i / 8] >> (i % 8)) & 1 == 1)
+            .collect()
+    };
+    Ok(ColumnBits {
+        bits_a: read(&row.bitstring_a),
+        bits_b: read(&row.bitstring_b),
+    })
+}
+
+/// Check that a row's column layout fits its bitstrings
+fn validate_row(row: &share_service::BinaryRow, row_index: usize) -> Result<(), ShareFileError> {
+    if row.bitstring_a.len() != row.bitstring_b.len() {
+        return Err(ShareFileError::Corrupt(format!(
+            "row {}: bitstrings have different lengths ({} and {} bytes)",
+            row_index,
+            row.bitstring_a.len(),
+            row.bitstring_b.len()
+        )));
+    }
+    if row.column_bit_offsets.len() != row.column_bit_lengths.len() {
+        return Err(ShareFileError::Corrupt(format!(
+            "row {}: {} column offsets but {} column lengths",
+            row_index,
+            row.column_bit_offsets.len(),
+            row.column_bit_lengths.len()
+        )));
+    }
+    let available_bits = row.bitstring_a.len() as u64 * 8;
+    for (column, (offset, length)) in row.column_bit_offsets.iter().zip(&row.column_bit_lengths).enumerate() {
+        if *offset as u64 + *length as u64 > available_bits {
+            return Err(ShareFileError::Corrupt(format!(
+                "row {}: column {} (bits {}..{}) exceeds bitstring of {} bits",
+                row_index,
+                column,
+                offset,
+                *offset as u64 + *length as u64,
+                available_bits
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Position within a share file being parsed
+struct Cursor<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ShareFileError> {
+        let available = self.data.len() - self.pos;
+        if len > available {
+            return Err(ShareFileError::Truncated { offset: self.pos, needed: len - available });
+        }
+        let bytes = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn read_u32(&mut self) -> Result<u32, ShareFileError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn read_u32_list(&mut self) -> Result<Vec<u32>, ShareFileError> {
+        let count = self.read_u32()? as usize;
+        // Check the whole list is present before allocating for it
+        let needed = count.checked_mul(4).ok_or_else(|| {
+            ShareFileError::Corrupt(format!("list length {} overflows", count))
+        })?;
+        let bytes = self.take(needed)?;
+        Ok(bytes
+            .chunks_exact(4)
+            .map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_party_data() -> share_service::BinaryPartyData {
+        share_service::BinaryPartyData {
+            party_id: 1,
+            table_id: 4,
+            rows: (0..3u8)
+                .map(|r| share_service::BinaryRow {
+                    // 1-bit column at offset 0, 32-bit column at offset 1
+                    bitstring_a: vec![r, 0xF0, 0x0F, 0xFF, 0x01],
+                    bitstring_b: vec![!r, 0x00, 0x11, 0x22, 0x00],
+                    column_bit_offsets: vec![0, 1],
+                    column_bit_lengths: vec![1, 32],
+                })
+                .collect(),
+        }
+    }
+
+    async fn write_sample(dir: &str) -> Vec<u8> {
+        let storage = BinaryShareStorage::new(dir.to_string());
+        let path = format!("{}/sample.bin", dir);
+        fs::create_dir_all(dir).unwrap();
+        storage.write_binary_data(&path, &sample_party_data()).await.unwrap();
+        let bytes = fs::read(&path).unwrap();
+        fs::remove_dir_all(dir).ok();
+        bytes
+    }
+
+    #[tokio::test]
+    async fn test_roundtrip_and_column_extraction() {
+        let dir = std::env::temp_dir().join(format!("fesca_storage_test_{}", std::process::id()));
+        let bytes = write_sample(dir.to_str().unwrap()).await;
+
+        let parsed = BinaryShareStorage::parse_binary_data(&bytes, 1, 4).unwrap();
+        assert_eq!(parsed, sample_party_data());
+
+        let bits = extract_column_bits(&parsed.rows[1], 0).unwrap();
+        assert_eq!(bits.bits_a, vec![true]);
+        assert_eq!(bits.bits_b, vec![false]);
+        let bits = extract_column_bits(&parsed.rows[0], 1).unwrap();
+        assert_eq!(bits.bits_a.len(), 32);
+        // bits 1..33 of [0x00, 0xF0, ...]: bits 12..16 of the bitstring are set
+        assert_eq!(bits.bits_a.iter().position(|b| *b), Some(11));
+        assert!(matches!(
+            extract_column_bits(&parsed.rows[0], 2),
+            Err(ShareFileError::NoSuchColumn { column: 2, columns: 2 })
+        ));
+
+        // A layout pointing past either bitstring is reported, not read
+        let mut short = parsed.rows[0].clone();
+        short.bitstring_b.pop();
+        assert!(matches!(extract_column_bits(&short, 1), Err(ShareFileError::Corrupt(_))));
+        let mut past_end = parsed.rows[0].clone();
+        past_end.column_bit_offsets[1] = u32::MAX;
+        assert!(matches!(extract_column_bits(&past_end, 1), Err(ShareFileError::Corrupt(_))));
+    }
+
+    #[tokio::test]
+    async fn test_rejects_bad_files() {
+        let dir = std::env::temp_dir().join(format!("fesca_storage_bad_{}", std::process::id()));
+        let bytes = write_sample(dir.to_str().unwrap()).await;
+
+        let mut bad_magic = bytes.clone();
+        bad_magic[0] = b'X';
+        assert!(matches!(
+            BinaryShareStorage::parse_binary_data(&bad_magic, 1, 4),
+            Err(ShareFileError::BadMagic { .. })
+        ));
+
+        assert!(matches!(
+            BinaryShareStorage::parse_binary_data(&bytes[..bytes.len() - 3], 1, 4),
+            Err(ShareFileError::Truncated { needed: 3, .. })
+        ));
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(matches!(
+            BinaryShareStorage::parse_binary_data(&trailing, 1, 4),
+            Err(ShareFileError::Corrupt(_))
+        ));
+
+        // Grow the first bitstring length so the column layout no longer matches
+        let mut corrupt = bytes;
+        corrupt[12] = 6;
+        assert!(BinaryShareStorage::parse_binary_data(&corrupt, 1, 4).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rejects_received_rows_with_bad_layout() {
+        let dir = std::env::temp_dir().join(format!("fesca_storage_reject_{}", std::process::id()));
+        let storage = BinaryShareStorage::new(dir.to_string_lossy().to_string());
+        let mut data = sample_party_data();
+        data.rows[2].column_bit_lengths[1] = 40;
+        let schema = share_service::TableSchema {
+            table_name: "sample".to_string(),
+            table_id: 4,
+            ..Default::default()
+        };
+
+        let result = storage.store_binary_shares(&data, &schema, &Default::default()).await;
+        assert!(result.is_err());
+        let data_file = Path::new(&storage.get_storage_path(&Default::default(), &schema)).join("party1_data.bin");
+        assert!(!data_file.exists());
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_migrates_v1_files() {
+        let dir = std::env::temp_dir().join(format!("fesca_storage_migrate_{}", std::process::id()));
+        let path = dir.join("party1_data.bin");
+        fs::create_dir_all(&dir).unwrap();
+        let storage = BinaryShareStorage::new(dir.to_string_lossy().to_string());
+        storage.write_binary_data(path.to_str().unwrap(), &sample_party_data()).await.unwrap();
+        let schema = share_service::TableSchema {
+            table_name: "sample".to_string(),
+            table_id: 4,
+            ..Default::default()
+        };
+
+        // v1 files are readable as they are
+        let v1 = BinaryShareStorage::read_party_data(&path, 1, 4).unwrap();
+        assert!(BinaryShareStorage::migrate_to_columnar(&path, 1, &schema).unwrap());
+        assert!(!BinaryShareStorage::migrate_to_columnar(&path, 1, &schema).unwrap());
+        assert!(columnar::is_columnar(&path).unwrap());
+
+        let v2 = BinaryShareStorage::read_party_data(&path, 1, 4).unwrap();
+        assert_eq!(v2.rows.len(), v1.rows.len());
+        for (new, old) in v2.rows.iter().zip(&v1.rows) {
+            for column in 0..2 {
+                assert_eq!(extract_column_bits(new, column).unwrap(), extract_column_bits(old, column).unwrap());
+            }
+        }
+        assert_eq!(columnar::read_columnar_header(&path).unwrap().schema_hash, columnar::schema_hash(&schema));
+        assert!(BinaryShareStorage::read_party_data(&path, 0, 4).is_err());
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,638 @@
+// Binder
+// ======
+// Resolves the names in a logical plan against the catalog: every table must
+// exist, every column reference is qualified with the table (or alias) it
+// belongs to, wildcards are expanded, and expression types are checked, e.g.
+// AVG over a String column or a WHERE clause that is not a predicate is
+// rejected before anything is sent to the computing nodes.
+
+use std::fmt;
+
+use crate::catalog::{Catalog, Charset, ColumnType};
+use crate::plan::{AggregateExpr, AggregateFunction, BinaryOp, ColumnRef, Literal, LogicalPlan, ProjectItem, ScalarExpr, SortKey};
+
+/// Why a plan does not fit the catalog
+#[derive(Debug, Clone, PartialEq)]
+pub enum BindError {
+    /// No table with this name is stored
+    UnknownTable(String),
+    /// No column with this name is in scope
+    UnknownColumn { column: String, available: Vec<String> },
+    /// The column name matches columns of several tables
+    AmbiguousColumn { column: String, candidates: Vec<String> },
+    /// An operator or aggregate is applied to values of the wrong type
+    Type(String),
+    /// The query is valid but cannot be evaluated on shares
+    Unsupported(String),
+}
+
+impl fmt::Display for BindError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BindError::UnknownTable(table) => write!(f, "Unknown table '{}'", table),
+            BindError::UnknownColumn { column, available } => {
+                write!(f, "Unknown column '{}' (available: {})", column, available.join(", "))
+            }
+            BindError::AmbiguousColumn { column, candidates } => {
+                write!(f, "Column '{}' is ambiguous, could be {}", column, candidates.join(" or "))
+            }
+            BindError::Type(reason) => write!(f, "Type error: {}", reason),
+            BindError::Unsupported(reason) => write!(f, "Unsupported query: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for BindError {}
+
+/// Column of a query result
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputColumn {
+    pub name: String,
+    pub data_type: ColumnType,
+}
+
+/// A plan whose column references are all qualified, with its result columns
+#[derive(Debug, Clone, PartialEq)]
+pub struct BoundQuery {
+    pub plan: LogicalPlan,
+    pub output: Vec<OutputColumn>,
+}
+
+/// Resolve and type-check a plan against the catalog
+pub fn bind(plan: &LogicalPlan, catalog: &Catalog) -> Result<BoundQuery, BindError> {
+    let (plan, scope) = bind_plan(plan, catalog)?;
+    let output = scope
+        .columns
+        .into_iter()
+        .map(|c| OutputColumn {
+            name: c.output_name.unwrap_or(c.name),
+            data_type: c.data_type,
+        })
+        .collect();
+    Ok(BoundQuery { plan, output })
+}
+
+/// Column visible to the expressions of an operator
+#[derive(Debug, Clone)]
+struct ScopeColumn {
+    /// Table name or alias; `None` for computed columns
+    qualifier: Option<String>,
+    /// Name used in the bound plan
+    name: String,
+    /// Name the unbound plan uses for a computed column, if different
+    unbound_name: Option<String>,
+    /// Name in the query result, if different from `name`
+    output_name: Option<String>,
+    data_type: ColumnType,
+    /// Whether the column is an AVG, which is revealed as its sum and count
+    /// and only divided by the analyst
+    average: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Scope {
+    columns: Vec<ScopeColumn>,
+}
+
+impl ScopeColumn {
+    fn column_ref(&self) -> ColumnRef {
+        ColumnRef::new(self.qualifier.as_deref(), &self.name)
+    }
+
+    fn matches(&self, column: &ColumnRef) -> bool {
+        let name_matches = self.name.eq_ignore_ascii_case(&column.name)
+            || self.unbound_name.as_deref().is_some_and(|n| n.eq_ignore_ascii_case(&column.name));
+        let table_matches = match (&column.table, &self.qualifier) {
+            (None, _) => true,
+            (Some(table), Some(qualifier)) => table.eq_ignore_ascii_case(qualifier),
+            (Some(_), None) => false,
+        };
+        name_matches && table_matches
+    }
+}
+
+impl Scope {
+    fn resolve(&self, column: &ColumnRef) -> Result<&ScopeColumn, BindError> {
+        let matches: Vec<&ScopeColumn> = self.columns.iter().filter(|c| c.matches(column)).collect();
+        match matches.as_slice() {
+            [found] => Ok(found),
+            [] => Err(BindError::UnknownColumn {
+                column: column.to_string(),
+                available: self.columns.iter().map(|c| c.column_ref().to_string()).collect(),
+            }),
+            _ => Err(BindError::AmbiguousColumn {
+                column: column.to_string(),
+                candidates: matches.iter().map(|c| c.column_ref().to_string()).collect(),
+            }),
+        }
+    }
+
+    /// Whether a bound column reference is an AVG column
+    fn is_average(&self, column: &ColumnRef) -> bool {
+        self.resolve(column).is_ok_and(|found| found.average)
+    }
+}
+
+fn type_error<T>(reason: String) -> Result<T, BindError> {
+    Err(BindError::Type(reason))
+}
+
+fn bind_plan(plan: &LogicalPlan, catalog: &Catalog) -> Result<(LogicalPlan, Scope), BindError> {
+    match plan {
+        LogicalPlan::Scan { table, alias } => {
+            let info = catalog.table(table).ok_or_else(|| BindError::UnknownTable(table.clone()))?;
+            let qualifier = alias.clone().unwrap_or_else(|| info.table_name.clone());
+            let columns = info
+                .columns
+                .iter()
+                .map(|c| ScopeColumn {
+                    qualifier: Some(qualifier.clone()),
+                    name: c.name.clone(),
+                    unbound_name: None,
+                    output_name: None,
+                    data_type: c.data_type(),
+                    average: false,
+                })
+                .collect();
+            let bound = LogicalPlan::Scan {
+                table: info.table_name.clone(),
+                alias: alias.clone(),
+            };
+            Ok((bound, Scope { columns }))
+        }
+        LogicalPlan::Filter { input, predicate } => {
+            let (input, scope) = bind_plan(input, catalog)?;
+            let predicate = bind_predicate(predicate, &scope, "WHERE")?;
+            let bound = LogicalPlan::Filter {
+                input: Box::new(input),
+                predicate,
+            };
+            Ok((bound, scope))
+        }
+        LogicalPlan::Join { left, right, on } => {
+            let (left, mut scope) = bind_plan(left, catalog)?;
+            let (right, right_scope) = bind_plan(right, catalog)?;
+            scope.columns.extend(right_scope.columns);
+            let on = bind_predicate(on, &scope, "JOIN ... ON")?;
+            let bound = LogicalPlan::Join {
+                left: Box::new(left),
+                right: Box::new(right),
+                on,
+            };
+            Ok((bound, scope))
+        }
+        LogicalPlan::Aggregate { input, group_by, aggregates } => {
+            let (input, scope) = bind_plan(input, catalog)?;
+            let mut output = Scope::default();
+            let mut bound_keys = Vec::with_capacity(group_by.len());
+            for key in group_by {
+                let (bound, data_type) = bind_expr(key, &scope)?;
+                output.columns.push(match key {
+                    ScalarExpr::Column(column) => scope.resolve(column)?.clone(),
+                    _ => ScopeColumn {
+                        qualifier: None,
+                        name: bound.to_string(),
+                        unbound_name: Some(key.to_string()),
+                        output_name: Some(key.to_string()),
+                        data_type,
+                        average: false,
+                    },
+                });
+                bound_keys.push(bound);
+            }
+            let mut bound_aggregates = Vec::with_capacity(aggregates.len());
+            for aggregate in aggregates {
+                let (bound, data_type) = bind_aggregate(aggregate, &scope)?;
+                output.columns.push(ScopeColumn {
+                    qualifier: None,
+                    name: bound.output_name(),
+                    unbound_name: Some(aggregate.output_name()),
+                    output_name: Some(aggregate.output_name()),
+                    data_type,
+                    average: aggregate.func == AggregateFunction::Avg,
+                });
+                bound_aggregates.push(bound);
+            }
+            let bound = LogicalPlan::Aggregate {
+                input: Box::new(input),
+                group_by: bound_keys,
+                aggregates: bound_aggregates,
+            };
+            Ok((bound, output))
+        }
+        LogicalPlan::Sort { input, keys } => {
+            let (input, scope) = bind_plan(input, catalog)?;
+            let keys = keys
+                .iter()
+                .map(|key| {
+                    if key.expr.columns().iter().any(|column| scope.is_average(column)) {
+                        return Err(BindError::Unsupported(format!(
+                            "ORDER BY {}: averages are divided by the analyst after the result is revealed, \
+                             so rows cannot be sorted by them",
+                            key.expr
+                        )));
+                    }
+                    let (expr, data_type) = bind_expr(&key.expr, &scope)?;
+                    if data_type == ColumnType::Float {
+                        return type_error(format!(
+                            "ORDER BY {}: Float values can only be ordered in fixed point",
+                            key.expr
+                        ));
+                    }
+                    Ok(SortKey {
+                        expr,
+                        ascending: key.ascending,
+                    })
+                })
+                .collect::<Result<Vec<_>, BindError>>()?;
+            let bound = LogicalPlan::Sort {
+                input: Box::new(input),
+                keys,
+            };
+            Ok((bound, scope))
+        }
+        LogicalPlan::Project { input, items } => {
+            let (input, scope) = bind_plan(input, catalog)?;
+            let mut output = Scope::default();
+            let mut bound_items = Vec::new();
+            for item in items {
+                match item {
+                    ProjectItem::Wildcard => {
+                        for column in &scope.columns {
+                            let item = ProjectItem::Expr {
+                                expr: ScalarExpr::Column(column.column_ref()),
+                                alias: None,
+                            };
+                            let name = column.output_name.clone().unwrap_or_else(|| column.name.clone());
+                            output.columns.push(ScopeColumn {
+                                average: column.average,
+                                ..projected(&item, name, column.data_type.clone())
+                            });
+                            bound_items.push(item);
+                        }
+                    }
+                    ProjectItem::Expr { expr, alias } => {
+                        let computed = !matches!(expr, ScalarExpr::Column(_));
+                        if computed && expr.columns().iter().any(|c| scope.is_average(c)) {
+                            return Err(BindError::Unsupported(format!(
+                                "{}: averages are divided by the analyst after the result is revealed, \
+                                 so they cannot be used in expressions",
+                                expr
+                            )));
+                        }
+                        let (bound, data_type) = bind_expr(expr, &scope)?;
+                        let average = matches!(&bound, ScalarExpr::Column(column) if scope.is_average(column));
+                        let bound = ProjectItem::Expr {
+                            expr: bound,
+                            alias: alias.clone(),
+                        };
+                        output.columns.push(ScopeColumn {
+                            average,
+                            ..projected(&bound, item.output_name().unwrap_or_default(), data_type)
+                        });
+                        bound_items.push(bound);
+                    }
+                }
+            }
+            let bound = LogicalPlan::Project {
+                input: Box::new(input),
+                items: bound_items,
+            };
+            Ok((bound, output))
+        }
+        LogicalPlan::Distinct { input } => {
+            let (input, scope) = bind_plan(input, catalog)?;
+            if let Some(column) = scope.columns.iter().find(|c| c.average) {
+                return Err(BindError::Unsupported(format!(
+                    "SELECT DISTINCT over {}: averages are divided by the analyst after the result is revealed, \
+                     so equal averages cannot be told apart",
+                    column.output_name.as_ref().unwrap_or(&column.name)
+                )));
+            }
+            let bound = LogicalPlan::Distinct { input: Box::new(input) };
+            Ok((bound, scope))
+        }
+        LogicalPlan::Limit { input, limit } => {
+            let (input, scope) = bind_plan(input, catalog)?;
+            let bound = LogicalPlan::Limit {
+                input: Box::new(input),
+                limit: *limit,
+            };
+            Ok((bound, scope))
+        }
+    }
+}
+
+/// Column produced by a bound projection item. Operators above the projection
+/// refer to it by `name`, the bound item's output name, which the unbound
+/// plan and the result may spell differently (`supplier_key` for
+/// `partsupp.supplier_key`).
+fn projected(item: &ProjectItem, name: String, data_type: ColumnType) -> ScopeColumn {
+    let bound_name = item.output_name().unwrap_or_default();
+    let renamed = (bound_name != name).then_some(name);
+    ScopeColumn {
+        qualifier: None,
+        name: bound_name,
+        unbound_name: renamed.clone(),
+        output_name: renamed,
+        data_type,
+        average: false,
+    }
+}
+
+/// Bind an expression that must be a predicate
+fn bind_predicate(expr: &ScalarExpr, scope: &Scope, clause: &str) -> Result<ScalarExpr, BindError> {
+    let (bound, data_type) = bind_expr(expr, scope)?;
+    if data_type != ColumnType::Boolean {
+        return type_error(format!("{} condition {} is {}, not Boolean", clause, expr, data_type));
+    }
+    Ok(bound)
+}
+
+fn literal_type(literal: &Literal) -> ColumnType {
+    match literal {
+        Literal::Integer(_) => ColumnType::UnsignedInt,
+        Literal::Decimal(_) => match literal.fixed_point() {
+            Some((_, scale)) => ColumnType::Decimal { scale },
+            None => ColumnType::Float,
+        },
+        Literal::String(value) => ColumnType::String {
+            max_chars: value.chars().count(),
+            charset: if value.is_ascii() { Charset::Ascii } else { Charset::Utf8 },
+        },
+        Literal::Boolean(_) => ColumnType::Boolean,
+    }
+}
+
+/// Bind an expression and compute its type
+fn bind_expr(expr: &ScalarExpr, scope: &Scope) -> Result<(ScalarExpr, ColumnType), BindError> {
+    match expr {
+        ScalarExpr::Column(column) => {
+            let found = scope.resolve(column)?;
+            Ok((ScalarExpr::Column(found.column_ref()), found.data_type.clone()))
+        }
+        ScalarExpr::Literal(literal) => Ok((expr.clone(), literal_type(literal))),
+        ScalarExpr::Not(inner) => {
+            let (bound, data_type) = bind_expr(inner, scope)?;
+            if data_type != ColumnType::Boolean {
+                return type_error(format!("NOT needs a Boolean operand, {} is {}", inner, data_type));
+            }
+            Ok((ScalarExpr::Not(Box::new(bound)), ColumnType::Boolean))
+        }
+        ScalarExpr::Binary { op, left, right } => {
+            let (bound_left, left_type) = bind_expr(left, scope)?;
+            let (bound_right, right_type) = bind_expr(right, scope)?;
+            let data_type = binary_type(*op, &left_type, &right_type).ok_or_else(|| {
+                BindError::Type(format!(
+                    "{} cannot be applied to {} ({}) and {} ({})",
+                    op, left, left_type, right, right_type
+                ))
+            })?;
+            Ok((ScalarExpr::binary(*op, bound_left, bound_right), data_type))
+        }
+        ScalarExpr::Like { expr: inner, pattern } => {
+            let (bound, data_type) = bind_expr(inner, scope)?;
+            if !matches!(data_type, ColumnType::String { charset: Charset::Ascii, .. }) {
+                return type_error(format!("LIKE needs an Ascii String operand, {} is {}", inner, data_type));
+            }
+            if !pattern.is_ascii() {
+                return type_error(format!("LIKE pattern '{}' is not Ascii", pattern));
+            }
+            let bound = ScalarExpr::Like {
+                expr: Box::new(bound),
+                pattern: pattern.clone(),
+            };
+            Ok((bound, ColumnType::Boolean))
+        }
+    }
+}
+
+/// Result type of a binary operator, `None` if the operand types do not fit
+pub fn binary_type(op: BinaryOp, left: &ColumnType, right: &ColumnType) -> Option<ColumnType> {
+    let both_strings = matches!((left, right), (ColumnType::String { .. }, ColumnType::String { .. }));
+    let both_numeric = left.is_numeric() && right.is_numeric();
+    let both_boolean = *left == ColumnType::Boolean && *right == ColumnType::Boolean;
+    match op {
+        BinaryOp::And | BinaryOp::Or => both_boolean.then_some(ColumnType::Boolean),
+        BinaryOp::Eq | BinaryOp::NotEq => (both_numeric || both_strings || both_boolean).then_some(ColumnType::Boolean),
+        BinaryOp::Lt | BinaryOp::LtEq | BinaryOp::Gt | BinaryOp::GtEq => {
+            (both_numeric || both_strings).then_some(ColumnType::Boolean)
+        }
+        BinaryOp::Plus | BinaryOp::Minus | BinaryOp::Multiply => both_numeric.then(|| {
+            let is_decimal = |t: &ColumnType| matches!(t, ColumnType::Decimal { .. });
+            if !is_decimal(left) && !is_decimal(right) {
+                ColumnType::UnsignedInt
+            } else if op == BinaryOp::Multiply {
+                // The product of two fixed-point values has the decimals of both
+                ColumnType::Decimal { scale: left.scale() + right.scale() }
+            } else {
+                ColumnType::Decimal { scale: left.scale().max(right.scale()) }
+            }
+        }),
+    }
+}
+
+/// Bind an aggregate and compute its result type
+fn bind_aggregate(aggregate: &AggregateExpr, scope: &Scope) -> Result<(AggregateExpr, ColumnType), BindError> {
+    let Some(arg) = &aggregate.arg else {
+        return Ok((aggregate.clone(), ColumnType::UnsignedInt));
+    };
+    let (bound, arg_type) = bind_expr(arg, scope)?;
+    let data_type = match aggregate.func {
+        AggregateFunction::Count => ColumnType::UnsignedInt,
+        _ if !arg_type.is_numeric() => {
+            return type_error(format!(
+                "{} cannot be applied to {} column {}",
+                aggregate.func, arg_type, arg
+            ));
+        }
+        AggregateFunction::Avg => average_type(&arg_type),
+        AggregateFunction::Sum | AggregateFunction::Min | AggregateFunction::Max => arg_type,
+    };
+    let bound = AggregateExpr {
+        func: aggregate.func,
+        arg: Some(bound),
+    };
+    Ok((bound, data_type))
+}
+
+/// Type of AVG over a column: decimals keep their scale
+pub fn average_type(arg_type: &ColumnType) -> ColumnType {
+    match arg_type {
+        ColumnType::Decimal { scale } => ColumnType::Decimal { scale: *scale },
+        _ => ColumnType::Float,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::{ColumnInfo, OwnerInfo, Sharing, TableInfo};
+    use crate::planner::plan_sql;
+
+    fn table(name: &str, columns: &[(&str, ColumnType)]) -> TableInfo {
+        TableInfo {
+            table_name: name.to_string(),
+            table_id: 1,
+            row_count: 4,
+            schema_hash: 0,
+            data_owner: OwnerInfo::default(),
+            columns: columns
+                .iter()
+                .map(|(name, type_hint)| ColumnInfo {
+                    name: name.to_string(),
+                    type_hint: type_hint.clone(),
+                    sharing: Sharing::Boolean,
+                    unique: false,
+                    decimals: None,
+                })
+                .collect(),
+        }
+    }
+
+    fn catalog() -> Catalog {
+        let comment = ColumnType::String {
+            max_chars: 32,
+            charset: Charset::Ascii,
+        };
+        let mut catalog = Catalog::new();
+        let partsupp = table(
+            "partsupp",
+            &[
+                ("part_key", ColumnType::UnsignedInt),
+                ("supplier_key", ColumnType::UnsignedInt),
+                ("available_qty", ColumnType::UnsignedInt),
+                ("supply_cost", ColumnType::Decimal { scale: 2 }),
+                ("comment", comment.clone()),
+            ],
+        );
+        let supplier = table(
+            "supplier",
+            &[("supplier_key", ColumnType::UnsignedInt), ("name", comment), ("rating", ColumnType::Float)],
+        );
+        catalog.insert(partsupp, None).unwrap();
+        catalog.insert(supplier, None).unwrap();
+        catalog
+    }
+
+    fn bind_sql(sql: &str) -> Result<BoundQuery, BindError> {
+        bind(&plan_sql(sql).unwrap(), &catalog())
+    }
+
+    #[test]
+    fn test_bind_qualifies_columns() {
+        let bound = bind_sql(
+            "SELECT supplier_key, AVG(available_qty) AS avg_qty FROM PartSupp \
+             WHERE available_qty > 5000 GROUP BY supplier_key ORDER BY supplier_key",
+        )
+        .unwrap();
+        let expected = "\
+Project: partsupp.supplier_key, AVG(partsupp.available_qty) AS avg_qty
+  Sort: partsupp.supplier_key ASC
+    Aggregate: AVG(partsupp.available_qty) GROUP BY partsupp.supplier_key
+      Filter: partsupp.available_qty > 5000
+        Scan: partsupp
+";
+        assert_eq!(bound.plan.to_string(), expected);
+        let output: Vec<_> = bound.output.iter().map(|c| (c.name.as_str(), c.data_type.clone())).collect();
+        assert_eq!(
+            output,
+            [("supplier_key", ColumnType::UnsignedInt), ("avg_qty", ColumnType::Float)]
+        );
+
+        let bound = bind_sql("SELECT * FROM partsupp p JOIN supplier s ON p.supplier_key = s.supplier_key").unwrap();
+        let names: Vec<_> = bound.output.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(
+            names,
+            ["part_key", "supplier_key", "available_qty", "supply_cost", "comment", "supplier_key", "name", "rating"]
+        );
+    }
+
+    #[test]
+    fn test_bind_fixed_point_types() {
+        let bound = bind_sql(
+            "SELECT supply_cost + 1.5, supply_cost * 0.125, supply_cost * 2, AVG(supply_cost), SUM(available_qty) \
+             FROM partsupp WHERE supply_cost > 500 GROUP BY supply_cost",
+        )
+        .unwrap();
+        let types: Vec<_> = bound.output.iter().map(|c| c.data_type.clone()).collect();
+        let decimal = |scale| ColumnType::Decimal { scale };
+        assert_eq!(types, [decimal(2), decimal(5), decimal(2), decimal(2), ColumnType::UnsignedInt]);
+        assert_eq!(literal_type(&Literal::Decimal("1e-3".to_string())), ColumnType::Float);
+    }
+
+    #[test]
+    fn test_bind_rejects_float_computations() {
+        // Without `decimals` a Float is stored as its IEEE-754 bits, which no circuit computes on
+        for sql in [
+            "SELECT SUM(rating) FROM supplier",
+            "SELECT AVG(rating) FROM supplier",
+            "SELECT MIN(rating), MAX(rating) FROM supplier",
+            "SELECT rating * 2 FROM supplier",
+            "SELECT name FROM supplier WHERE rating > 4",
+            "SELECT name FROM supplier WHERE rating = rating",
+            // Decimal literals too large for fixed point are Float
+            "SELECT part_key FROM partsupp WHERE supply_cost > 123456789012345678901.5",
+            "SELECT part_key + 99999999999999999999 FROM partsupp",
+            "SELECT name FROM supplier ORDER BY rating DESC",
+        ] {
+            let err = bind_sql(sql).unwrap_err();
+            assert!(matches!(err, BindError::Type(_)), "{}: {}", sql, err);
+        }
+        assert_eq!(
+            bind_sql("SELECT SUM(rating) FROM supplier").unwrap_err().to_string(),
+            "Type error: SUM cannot be applied to Float column rating"
+        );
+        // Float values can still be returned, grouped on and counted
+        assert!(bind_sql("SELECT rating, COUNT(rating) FROM supplier GROUP BY rating").is_ok());
+    }
+
+    #[test]
+    fn test_bind_errors() {
+        assert_eq!(
+            bind_sql("SELECT x FROM lineitem").unwrap_err(),
+            BindError::UnknownTable("lineitem".to_string())
+        );
+        let err = bind_sql("SELECT supply_cost FROM supplier").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unknown column 'supply_cost' (available: supplier.supplier_key, supplier.name, supplier.rating)"
+        );
+        let err = bind_sql("SELECT supplier_key FROM partsupp JOIN supplier ON part_key = name").unwrap_err();
+        assert!(matches!(err, BindError::Type(_)), "{}", err);
+        let err = bind_sql("SELECT name FROM partsupp JOIN supplier ON partsupp.supplier_key = supplier_key").unwrap_err();
+        assert!(matches!(err, BindError::AmbiguousColumn { .. }), "{}", err);
+        assert_eq!(
+            bind_sql("SELECT AVG(comment) FROM partsupp").unwrap_err().to_string(),
+            "Type error: AVG cannot be applied to String(32) column comment"
+        );
+        assert!(bind_sql("SELECT part_key FROM partsupp WHERE available_qty + 1").is_err());
+        assert!(bind_sql("SELECT COUNT(comment) FROM partsupp").is_ok());
+        assert!(bind_sql("SELECT part_key FROM partsupp WHERE comment NOT LIKE '%final%'").is_ok());
+        assert_eq!(
+            bind_sql("SELECT part_key FROM partsupp WHERE part_key LIKE '1%'").unwrap_err().to_string(),
+            "Type error: LIKE needs an Ascii String operand, part_key is UnsignedInt"
+        );
+
+        // An AVG column cannot be a sort key, directly or through its alias
+        let grouped = "SELECT supplier_key, AVG(available_qty) AS avg_qty FROM partsupp GROUP BY supplier_key";
+        for order_by in ["AVG(available_qty)", "avg_qty DESC", "supplier_key, AVG(available_qty) + 1"] {
+            let err = bind_sql(&format!("{} ORDER BY {}", grouped, order_by)).unwrap_err();
+            assert!(matches!(err, BindError::Unsupported(_)), "{}: {}", order_by, err);
+        }
+        assert!(bind_sql(&format!("{} ORDER BY SUM(available_qty), COUNT(*)", grouped)).is_ok());
+
+        // Nor be computed with, as it is only divided after reconstruction
+        for item in ["AVG(available_qty) + 1", "AVG(available_qty) > 10", "SUM(available_qty) * AVG(part_key)"] {
+            let err = bind_sql(&format!("SELECT {} FROM partsupp", item)).unwrap_err();
+            assert!(matches!(err, BindError::Unsupported(_)), "{}: {}", item, err);
+        }
+        assert!(bind_sql("SELECT AVG(available_qty) AS avg_qty, SUM(available_qty) + 1 FROM partsupp").is_ok());
+
+        // Nor be deduplicated: 2 / 1 and 4 / 2 are the same average
+        let distinct = grouped.replacen("SELECT", "SELECT DISTINCT", 1);
+        let err = bind_sql(&distinct).unwrap_err();
+        assert!(err.to_string().starts_with("Unsupported query: SELECT DISTINCT over avg_qty"), "{}", err);
+        assert!(bind_sql("SELECT DISTINCT supplier_key, SUM(available_qty) FROM partsupp GROUP BY supplier_key").is_ok());
+    }
+}
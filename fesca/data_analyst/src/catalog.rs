@@ -0,0 +1,280 @@
+// Schema Catalog
+// ==============
+// Tables the data analyst can query, read from the `schema.json` files the
+// computing nodes write next to the stored shares
+// (`<storage>/<owner_id>/<table_name>/schema.json`). Types use the same JSON
+// format as the data owner's schema files.
+//
+// A Float column with `decimals` set is stored as the value times
+// 10^decimals, so queries see it as `Decimal { scale }` and compute on the
+// scaled integers.
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Character encoding of string columns
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Charset {
+    Ascii,
+    Utf8,
+}
+
+/// Data types of stored columns
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub enum ColumnType {
+    Boolean,
+    UnsignedInt,
+    Float,
+    String { max_chars: usize, charset: Charset },
+    /// Fixed-point number, stored as the value times 10^scale
+    Decimal { scale: u32 },
+}
+
+/// How a column is secret shared
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Sharing {
+    #[default]
+    Boolean,
+    Arithmetic,
+}
+
+/// A stored column
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ColumnInfo {
+    pub name: String,
+    pub type_hint: ColumnType,
+    #[serde(default)]
+    pub sharing: Sharing,
+    /// Every row holds a different value, e.g. a primary key
+    #[serde(default)]
+    pub unique: bool,
+    /// Float only: number of decimals of the fixed-point encoding
+    #[serde(default)]
+    pub decimals: Option<u32>,
+}
+
+/// Owner of a stored table
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+pub struct OwnerInfo {
+    pub owner_id: String,
+    #[serde(default)]
+    pub owner_name: String,
+}
+
+/// A stored table as described by its `schema.json`
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct TableInfo {
+    pub table_name: String,
+    pub table_id: u32,
+    pub row_count: usize,
+    /// Hash of the schema the table was shared with, as recorded in its share
+    /// files; 0 if the file does not give one
+    #[serde(default, with = "hex_hash")]
+    pub schema_hash: u64,
+    #[serde(default)]
+    pub data_owner: OwnerInfo,
+    pub columns: Vec<ColumnInfo>,
+}
+
+impl ColumnType {
+    /// Number of bits of the encoded value
+    pub fn bit_width(&self) -> u32 {
+        match self {
+            ColumnType::Boolean => 1,
+            ColumnType::UnsignedInt => 32,
+            ColumnType::Float | ColumnType::Decimal { .. } => 64,
+            ColumnType::String { max_chars, charset } => {
+                let bits_per_char = match charset {
+                    Charset::Ascii => 7,
+                    Charset::Utf8 => 8,
+                };
+                *max_chars as u32 * bits_per_char
+            }
+        }
+    }
+
+    /// Number of decimals of a fixed-point value, 0 for other types
+    pub fn scale(&self) -> u32 {
+        match self {
+            ColumnType::Decimal { scale } => *scale,
+            _ => 0,
+        }
+    }
+
+    /// Whether SUM, AVG, arithmetic and ordering comparisons apply. Float
+    /// values are stored as IEEE-754 bits, which no circuit computes on.
+    pub fn is_numeric(&self) -> bool {
+        matches!(self, ColumnType::UnsignedInt | ColumnType::Decimal { .. })
+    }
+}
+
+impl std::fmt::Display for ColumnType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ColumnType::String { max_chars, .. } => write!(f, "String({})", max_chars),
+            ColumnType::Decimal { scale } => write!(f, "Decimal({})", scale),
+            other => write!(f, "{:?}", other),
+        }
+    }
+}
+
+impl ColumnInfo {
+    /// Type the column has in queries: fixed-point Float columns are decimals
+    pub fn data_type(&self) -> ColumnType {
+        match (&self.type_hint, self.decimals) {
+            (ColumnType::Float, Some(scale)) => ColumnType::Decimal { scale },
+            (type_hint, _) => type_hint.clone(),
+        }
+    }
+
+    /// Number of bits stored per share: arithmetic shares always take 64
+    pub fn stored_width(&self) -> u32 {
+        match self.sharing {
+            Sharing::Boolean => self.type_hint.bit_width(),
+            Sharing::Arithmetic => 64,
+        }
+    }
+}
+
+impl TableInfo {
+    /// Index and description of a column, matched case-insensitively
+    pub fn column(&self, name: &str) -> Option<(usize, &ColumnInfo)> {
+        self.columns
+            .iter()
+            .enumerate()
+            .find(|(_, c)| c.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Read a `schema.json` file
+    pub fn load(path: &Path) -> Result<TableInfo> {
+        let text = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        serde_json::from_str(&text).with_context(|| format!("Failed to parse {}", path.display()))
+    }
+}
+
+/// `schema_hash` is written as 16 hex digits
+mod hex_hash {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(hash: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("{:016x}", hash))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        u64::from_str_radix(&text, 16).map_err(serde::de::Error::custom)
+    }
+}
+
+/// All tables known to the data analyst
+#[derive(Debug, Clone, Default)]
+pub struct Catalog {
+    tables: BTreeMap<String, (TableInfo, Option<PathBuf>)>,
+}
+
+impl Catalog {
+    /// Empty catalog
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read every `<owner_id>/<table_name>/schema.json` below a node's storage directory
+    pub fn load_dir(root: &Path) -> Result<Catalog> {
+        let mut catalog = Catalog::new();
+        let owners = fs::read_dir(root).with_context(|| format!("Failed to read catalog directory {}", root.display()))?;
+        for owner in owners {
+            let owner = owner?.path();
+            if !owner.is_dir() {
+                continue;
+            }
+            for table in fs::read_dir(&owner)? {
+                let schema_file = table?.path().join("schema.json");
+                if schema_file.is_file() {
+                    let info = TableInfo::load(&schema_file)?;
+                    let directory = schema_file.parent().map(Path::to_path_buf);
+                    catalog.insert(info, directory)?;
+                }
+            }
+        }
+        Ok(catalog)
+    }
+
+    /// Register a table; `directory` is where its share files are stored, if known
+    pub fn insert(&mut self, table: TableInfo, directory: Option<PathBuf>) -> Result<()> {
+        let key = table.table_name.to_ascii_lowercase();
+        if let Some((existing, _)) = self.tables.get(&key) {
+            return Err(anyhow!(
+                "Table '{}' is provided by both '{}' and '{}'",
+                table.table_name,
+                existing.data_owner.owner_id,
+                table.data_owner.owner_id
+            ));
+        }
+        self.tables.insert(key, (table, directory));
+        Ok(())
+    }
+
+    /// Look up a table, case-insensitively
+    pub fn table(&self, name: &str) -> Option<&TableInfo> {
+        self.tables.get(&name.to_ascii_lowercase()).map(|(table, _)| table)
+    }
+
+    /// Directory holding the table's share files, if it was loaded from disk
+    pub fn table_dir(&self, name: &str) -> Option<&Path> {
+        self.tables.get(&name.to_ascii_lowercase()).and_then(|(_, dir)| dir.as_deref())
+    }
+
+    /// All tables, ordered by name
+    pub fn tables(&self) -> impl Iterator<Item = &TableInfo> {
+        self.tables.values().map(|(table, _)| table)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `schema.json` as written by the computing node's `BinaryShareStorage`
+    const PARTSUPP_SCHEMA: &str = r#"{
+      "table_name": "partsupp",
+      "table_id": 4,
+      "row_count": 4,
+      "format_version": 2,
+      "schema_hash": "0123456789abcdef",
+      "data_owner": { "owner_id": "owner_001", "owner_name": "First Data Owner" },
+      "columns": [
+        { "name": "part_key", "type_hint": "UnsignedInt", "sharing": "Boolean" },
+        { "name": "available_qty", "type_hint": "UnsignedInt", "sharing": "Arithmetic" },
+        { "name": "supply_cost", "type_hint": "Float", "sharing": "Arithmetic", "decimals": 2 },
+        { "name": "comment", "type_hint": { "String": { "max_chars": 128, "charset": "Ascii" } }, "sharing": "Boolean" }
+      ]
+    }"#;
+
+    #[test]
+    fn test_load_dir() {
+        let root = std::env::temp_dir().join(format!("fesca_catalog_{}", std::process::id()));
+        let table_dir = root.join("owner_001").join("partsupp");
+        fs::create_dir_all(&table_dir).unwrap();
+        fs::write(table_dir.join("schema.json"), PARTSUPP_SCHEMA).unwrap();
+
+        let catalog = Catalog::load_dir(&root).unwrap();
+        fs::remove_dir_all(&root).ok();
+
+        let table = catalog.table("PartSupp").unwrap();
+        assert_eq!((table.table_id, table.row_count), (4, 4));
+        assert_eq!(table.schema_hash, 0x0123456789abcdef);
+        assert_eq!(table.data_owner.owner_id, "owner_001");
+        assert_eq!(catalog.table_dir("partsupp"), Some(table_dir.as_path()));
+
+        let (index, column) = table.column("COMMENT").unwrap();
+        assert_eq!(index, 3);
+        assert_eq!(column.stored_width(), 896);
+        assert_eq!(table.column("available_qty").unwrap().1.stored_width(), 64);
+        assert_eq!(table.column("available_qty").unwrap().1.data_type(), ColumnType::UnsignedInt);
+        assert_eq!(table.column("supply_cost").unwrap().1.data_type(), ColumnType::Decimal { scale: 2 });
+        assert!(table.column("extra_code").is_none());
+    }
+}
@@ -0,0 +1,88 @@
+// Query Client
+// ============
+// Submits a lowered query to the three computing nodes and collects their
+// result shares. The same request goes to all nodes at once: the nodes
+// evaluate the circuit together, so each of them only answers once the other
+// two have taken part in every round.
+
+use anyhow::{anyhow, Result};
+use tonic::transport::Channel;
+use log::info;
+
+use helpers::circuit::Circuit;
+
+// Include the generated protobuf code
+pub mod query_service {
+    tonic::include_proto!("query_service");
+}
+
+use query_service::{query_service_client::QueryServiceClient, RunQueryRequest, RunQueryResponse};
+
+use crate::config::ComputingNodes;
+use crate::result::{reconstruct, RevealedColumn};
+
+/// gRPC client for the query servers of the three computing nodes
+#[derive(Debug, Clone)]
+pub struct QueryClient {
+    urls: [String; 3],
+}
+
+impl QueryClient {
+    pub fn new(nodes: &ComputingNodes) -> Self {
+        Self { urls: nodes.as_array() }
+    }
+
+    /// Run a circuit under a fresh query id and return every node's response,
+    /// indexed by node id
+    pub async fn submit(&self, circuit: &Circuit) -> Result<[RunQueryResponse; 3]> {
+        let query_id: u32 = rand::random();
+        let circuit_json = circuit.to_json()?;
+        info!("Submitting query {} ({} gates, depth {})", query_id, circuit.gates.len(), circuit.depth());
+
+        let request = || RunQueryRequest { query_id, circuit_json: circuit_json.clone() };
+        let (r0, r1, r2) = tokio::try_join!(
+            submit_to(&self.urls[0], request()),
+            submit_to(&self.urls[1], request()),
+            submit_to(&self.urls[2], request()),
+        )?;
+
+        let responses = [r0, r1, r2];
+        for (node_id, response) in responses.iter().enumerate() {
+            if response.node_id != node_id as u32 || response.query_id != query_id {
+                return Err(anyhow!(
+                    "{} answered as node {} for query {}, expected node {} for query {}",
+                    self.urls[node_id],
+                    response.node_id,
+                    response.query_id,
+                    node_id,
+                    query_id
+                ));
+            }
+        }
+        Ok(responses)
+    }
+
+    /// Run a circuit and reconstruct its output columns
+    pub async fn run(&self, circuit: &Circuit) -> Result<Vec<RevealedColumn>> {
+        let responses = self.submit(circuit).await?;
+        if let Some(stats) = &responses[0].stats {
+            info!("Query took {} rounds, {} bytes sent per node", stats.rounds, stats.bytes_sent);
+        }
+        reconstruct(&responses)
+    }
+}
+
+/// Send a query to one node
+async fn submit_to(url: &str, request: RunQueryRequest) -> Result<RunQueryResponse> {
+    let channel = Channel::from_shared(url.to_string())?
+        .connect()
+        .await
+        .map_err(|e| anyhow!("Could not connect to computing node at {}: {}", url, e))?;
+    // Result shares grow with the table, well beyond tonic's default 4 MiB limit
+    let response = QueryServiceClient::new(channel)
+        .max_decoding_message_size(usize::MAX)
+        .run_query(request)
+        .await
+        .map_err(|status| anyhow!("Computing node at {} rejected the query: {}", url, status.message()))?;
+    Ok(response.into_inner())
+}
@@ -0,0 +1,43 @@
+// Configuration Module
+// ====================
+// Reads the data analyst configuration file (config_data_analyst.json): where
+// the three computing nodes' query servers are reachable and where the table
+// schemas of the catalog are read from.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+
+/// Query server URLs of the three computing nodes
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ComputingNodes {
+    pub node0_url: String,
+    pub node1_url: String,
+    pub node2_url: String,
+}
+
+/// Configuration of the data analyst
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AnalystConfig {
+    pub computing_nodes: ComputingNodes,
+    /// Directory holding the `<owner_id>/<table_name>/schema.json` files
+    pub catalog_path: String,
+}
+
+impl ComputingNodes {
+    /// Node URLs indexed by node id
+    pub fn as_array(&self) -> [String; 3] {
+        [
+            self.node0_url.clone(),
+            self.node1_url.clone(),
+            self.node2_url.clone(),
+        ]
+    }
+}
+
+/// Load the data analyst configuration
+pub fn load_analyst_config(config_path: &str) -> Result<AnalystConfig> {
+    let file = File::open(config_path).with_context(|| format!("Failed to open {}", config_path))?;
+    let config = serde_json::from_reader(file).with_context(|| format!("Failed to parse {}", config_path))?;
+    Ok(config)
+}
@@ -0,0 +1,83 @@
+// Table Rendering
+// ===============
+// Plain-text tables in the style of psql, used for query results and the
+// catalog meta-commands:
+//
+//      part_key | large
+//     ----------+-------
+//             1 | false
+//     (1 row)
+
+/// Horizontal alignment of a column
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Right,
+}
+
+/// Render a table with a header line, a separator, the rows and a row count
+pub fn render_table(header: &[String], align: &[Align], rows: &[Vec<String>]) -> String {
+    let widths: Vec<usize> = header
+        .iter()
+        .enumerate()
+        .map(|(c, name)| {
+            rows.iter()
+                .filter_map(|row| row.get(c))
+                .map(|cell| cell.chars().count())
+                .fold(name.chars().count(), usize::max)
+        })
+        .collect();
+    let line = |cells: &[String], align_of: &dyn Fn(usize) -> Align| -> String {
+        let cells: Vec<String> = widths
+            .iter()
+            .enumerate()
+            .map(|(c, width)| {
+                let cell = cells.get(c).map(String::as_str).unwrap_or("");
+                match align_of(c) {
+                    Align::Left => format!(" {:<width$} ", cell, width = width),
+                    Align::Right => format!(" {:>width$} ", cell, width = width),
+                }
+            })
+            .collect();
+        cells.join("|").trim_end().to_string()
+    };
+
+    let mut out = String::new();
+    out.push_str(&line(header, &|_| Align::Left));
+    out.push('\n');
+    let separator: Vec<String> = widths.iter().map(|width| "-".repeat(width + 2)).collect();
+    out.push_str(&separator.join("+"));
+    out.push('\n');
+    for row in rows {
+        out.push_str(&line(row, &|c| align.get(c).copied().unwrap_or(Align::Left)));
+        out.push('\n');
+    }
+    match rows.len() {
+        1 => out.push_str("(1 row)\n"),
+        count => out.push_str(&format!("({} rows)\n", count)),
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_table() {
+        let header = vec!["part_key".to_string(), "name".to_string()];
+        let rows = vec![
+            vec!["1".to_string(), "bolt".to_string()],
+            vec!["1024".to_string(), "hex nut".to_string()],
+        ];
+        let text = render_table(&header, &[Align::Right, Align::Left], &rows);
+        assert_eq!(
+            text,
+            " part_key | name\n\
+             ----------+---------\n\
+             \x20       1 | bolt\n\
+             \x20    1024 | hex nut\n\
+             (2 rows)\n"
+        );
+    }
+}
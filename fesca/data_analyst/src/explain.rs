@@ -0,0 +1,166 @@
+// Query Explanation
+// =================
+// Output of `EXPLAIN <query>`: the bound logical plan, the stored columns the
+// lowered circuit reads (bit width and row count from the table schemas) and
+// the estimated MPC cost per computing node from `Circuit::cost`. Nothing is
+// sent to the computing nodes.
+//
+// The time estimate only covers the network: every round waits for one
+// message from the previous node, and every byte has to be sent. Local gate
+// evaluation is usually much cheaper and is ignored.
+//
+// With `\reveal_cardinality on`, a query with DISTINCT first runs a circuit
+// counting the distinct rows. Its cost is listed separately; the estimate of
+// the query itself assumes the count stays hidden, since the circuit run
+// after counting depends on the count.
+
+use std::fmt::Write;
+use std::time::Duration;
+
+use anyhow::Result;
+
+use helpers::circuit::{Circuit, InputSharing};
+
+use crate::binder::BoundQuery;
+use crate::catalog::Catalog;
+
+/// Assumed latency of one communication round
+pub const ROUND_LATENCY: Duration = Duration::from_millis(1);
+
+/// Assumed bandwidth between neighbouring nodes, in bytes per second (1 Gbit/s)
+pub const BANDWIDTH: u64 = 125_000_000;
+
+/// Describe how a query would be evaluated and what it would cost
+pub fn explain(query: &BoundQuery, circuit: &Circuit, catalog: &Catalog) -> Result<String> {
+    let cost = circuit.cost()?;
+    let mut out = String::new();
+
+    writeln!(out, "Logical plan:")?;
+    for line in query.plan.to_string().lines() {
+        writeln!(out, "  {}", line)?;
+    }
+
+    writeln!(out, "Inputs:")?;
+    for input in &circuit.inputs {
+        let column = catalog
+            .table(&input.table)
+            .and_then(|table| table.columns.get(input.column))
+            .map_or_else(|| format!("#{}", input.column), |column| column.name.clone());
+        let sharing = match input.sharing {
+            InputSharing::Boolean => "boolean",
+            InputSharing::Arithmetic => "arithmetic",
+        };
+        writeln!(
+            out,
+            "  {}.{}: {} bits x {} rows, {} shares",
+            input.table, column, input.width, input.rows, sharing
+        )?;
+    }
+
+    let counts: Vec<String> = circuit
+        .gate_counts()
+        .iter()
+        .map(|(name, count)| format!("{} {}", name, count))
+        .collect();
+    writeln!(out, "Circuit: {} gates ({})", circuit.gates.len(), counts.join(", "))?;
+
+    let network = ROUND_LATENCY * cost.rounds + Duration::from_secs_f64(cost.bytes_sent as f64 / BANDWIDTH as f64);
+    writeln!(out, "Estimated cost per node:")?;
+    writeln!(out, "  AND gates:    {}", cost.and_gates)?;
+    writeln!(out, "  MUL gates:    {}", cost.mul_gates)?;
+    writeln!(out, "  rounds:       {}", cost.rounds)?;
+    writeln!(out, "  bytes sent:   {} ({} bytes)", format_bytes(cost.bytes_sent), cost.bytes_sent)?;
+    writeln!(out, "  network time: {} (1 ms per round, 1 Gbit/s)", format_duration(network))?;
+    Ok(out)
+}
+
+/// Cost of the circuit counting the rows of DISTINCT before the query runs,
+/// and a note that the query's estimate assumes the count stays hidden
+pub fn explain_counting(counting: &Circuit) -> Result<String> {
+    let cost = counting.cost()?;
+    let mut out = String::new();
+    writeln!(out, "Counting DISTINCT rows first (revealed cardinality):")?;
+    writeln!(out, "  rounds:       {}", cost.rounds)?;
+    writeln!(out, "  bytes sent:   {} ({} bytes)", format_bytes(cost.bytes_sent), cost.bytes_sent)?;
+    writeln!(out, "The estimate above assumes the number of DISTINCT rows stays hidden;")?;
+    writeln!(out, "once revealed, the rest of the query runs on only that many rows.")?;
+    Ok(out)
+}
+
+/// Byte count with a binary unit, e.g. "1.5 MiB"
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// Duration in the largest fitting unit, e.g. "2.5 h"
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs_f64();
+    match seconds {
+        s if s < 1.0 => format!("{:.0} ms", s * 1000.0),
+        s if s < 60.0 => format!("{:.1} s", s),
+        s if s < 3600.0 => format!("{:.1} min", s / 60.0),
+        s if s < 86400.0 => format!("{:.1} h", s / 3600.0),
+        s => format!("{:.1} days", s / 86400.0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::{ColumnInfo, ColumnType, OwnerInfo, Sharing, TableInfo};
+    use crate::{binder, lower, planner};
+
+    #[test]
+    fn test_explain_reports_cost() {
+        let mut catalog = Catalog::new();
+        catalog
+            .insert(
+                TableInfo {
+                    table_name: "partsupp".to_string(),
+                    table_id: 4,
+                    row_count: 1000,
+                    schema_hash: 0,
+                    data_owner: OwnerInfo::default(),
+                    columns: vec![ColumnInfo {
+                        name: "available_qty".to_string(),
+                        type_hint: ColumnType::UnsignedInt,
+                        sharing: Sharing::Boolean,
+                        unique: false,
+                        decimals: None,
+                    }],
+                },
+                None,
+            )
+            .unwrap();
+        let plan = planner::plan_sql("SELECT available_qty > 5000 FROM partsupp").unwrap();
+        let query = binder::bind(&plan, &catalog).unwrap();
+        let circuit = lower::lower(&query, &catalog).unwrap();
+        let text = explain(&query, &circuit, &catalog).unwrap();
+
+        let cost = circuit.cost().unwrap();
+        // Every AND gate works on all 1000 rows, i.e. 16 words per node
+        assert_eq!(cost.and_gates, circuit.gate_counts()["AND"] as u64 * 1000);
+        assert_eq!(cost.bytes_sent, circuit.gate_counts()["AND"] as u64 * 16 * 16);
+        assert!(text.contains("  partsupp.available_qty: 32 bits x 1000 rows, boolean shares\n"), "{}", text);
+        assert!(text.contains(&format!("  rounds:       {}\n", circuit.depth())), "{}", text);
+        assert!(text.starts_with("Logical plan:\n  Project"), "{}", text);
+    }
+
+    #[test]
+    fn test_format_units() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(3 * 1024 * 1024 / 2), "1.5 MiB");
+        assert_eq!(format_duration(Duration::from_secs(5400)), "1.5 h");
+    }
+}
@@ -1,26 +1,106 @@
-mod sql;
+pub mod binder;
+pub mod catalog;
+pub mod client;
+pub mod config;
+pub mod display;
+pub mod explain;
+pub mod lower;
+pub mod plan;
+pub mod planner;
+pub mod result;
+pub mod session;
+pub mod sql;
 
-use log::{error, info};
-use anyhow::Result;
-use std::process;
+use log::{info, warn};
+use anyhow::{anyhow, Context, Result};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
 
-/// Entry point for Data Analyst
-pub fn run() -> Result<()> {
-    info!("Data Analyst: starting query processing");
+use crate::binder::BoundQuery;
+use crate::catalog::Catalog;
+use crate::client::QueryClient;
+use crate::lower::LowerOptions;
+use crate::result::{ResultTable, RevealedValues};
+use crate::session::Session;
 
-    // Example SQL; replace with CLI arg later
-    let sql_text = "SELECT AVG(salary) FROM employees WHERE dept = 'R&D';";
+/// How the data analyst gets its queries
+#[derive(Debug, Clone, Default)]
+pub struct AnalystOptions {
+    /// Run this SQL and exit
+    pub query: Option<String>,
+    /// Run the SQL statements in this file and exit
+    pub file: Option<PathBuf>,
+    /// Reveal the number of rows DISTINCT produces to make the rest of the
+    /// query cheaper
+    pub reveal_cardinality: bool,
+}
 
-    match sql::parse_sql(sql_text) {
-        Ok(ast) => {
-            info!("Parsed AST:\n{:#?}", ast); // to do: the output is repeating itself. paste only either here or in the sql file
-            sql::extract_select(&ast);
-        }
+/// Entry point for Data Analyst: run the query given in `options`, or start
+/// the interactive prompt if there is none
+pub fn run(options: AnalystOptions) -> Result<()> {
+    info!("Data Analyst: starting query processing");
+
+    // Computing nodes to run queries on; without them queries are only checked
+    let config_path = env::var("ANALYST_CONFIG").unwrap_or_else(|_| "config_data_analyst.json".to_string());
+    let config = match config::load_analyst_config(&config_path) {
+        Ok(config) => Some(config),
         Err(e) => {
-            error!("SQL parse error: {}", e);
-            process::exit(1);
+            warn!("Could not load {}: {:#}, queries are only checked", config_path, e);
+            None
+        }
+    };
+
+    // Table schemas as stored by a computing node
+    let catalog_path = env::var("CATALOG_PATH")
+        .ok()
+        .or_else(|| config.as_ref().map(|c| c.catalog_path.clone()))
+        .unwrap_or_else(|| "received_shares".to_string());
+    let catalog = Catalog::load_dir(&PathBuf::from(catalog_path))?;
+
+    let client = config.map(|config| QueryClient::new(&config.computing_nodes));
+    let session = Session::new(catalog, client)?;
+    session.set_reveal_cardinality(options.reveal_cardinality);
+    let mut stdout = std::io::stdout();
+    match (options.query, options.file) {
+        (Some(sql), _) => session.run_sql(&sql, &mut stdout),
+        (None, Some(path)) => {
+            let sql = fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+            session.run_sql(&sql, &mut stdout)
         }
+        (None, None) => session.repl(),
     }
+}
 
-    Ok(())
+/// Plan, bind and lower `sql`, run it on the computing nodes and reconstruct
+/// the result
+pub async fn execute_query(sql: &str, catalog: &Catalog, client: &QueryClient) -> Result<ResultTable> {
+    let plan = planner::plan_sql(sql)?;
+    let query = binder::bind(&plan, catalog)?;
+    run_query(&query, catalog, client, false).await
+}
+
+/// Lower a bound query, run it on the computing nodes and reconstruct the
+/// result. With `reveal_cardinality`, a DISTINCT in the query first has its
+/// number of rows counted and revealed, to the analyst and, through the size
+/// of the query circuit, to the computing nodes.
+pub async fn run_query(
+    query: &BoundQuery,
+    catalog: &Catalog,
+    client: &QueryClient,
+    reveal_cardinality: bool,
+) -> Result<ResultTable> {
+    let mut options = LowerOptions::default();
+    if reveal_cardinality && let Some(circuit) = lower::lower_distinct_rows(query, catalog)? {
+        let revealed = client.run(&circuit).await?;
+        let rows = match revealed.first().map(|column| &column.values) {
+            Some(RevealedValues::Words(words)) if words.len() == 1 => words[0] as usize,
+            _ => return Err(anyhow!("Unexpected result counting the distinct rows")),
+        };
+        info!("DISTINCT produces {} rows", rows);
+        options.distinct_rows = Some(rows);
+    }
+    let circuit = lower::lower_with(query, catalog, options)?;
+    let revealed = client.run(&circuit).await?;
+    ResultTable::decode(&query.output, revealed)
 }
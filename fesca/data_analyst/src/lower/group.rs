@@ -0,0 +1,206 @@
+// Grouping
+// ========
+// GROUP BY does not reveal the groups or their sizes. The rows are sorted by
+// the grouping keys (see `CircuitBuilder::sort_rows`), a secret bit per row
+// tells whether it is in the same group as the row above, and a segmented
+// log-depth scan (`CircuitBuilder::scan_segments`) aggregates every group
+// into its last row. Only the last row of each group is valid; as where those
+// rows are would reveal the group sizes, the valid rows are then sorted to the
+// top (`compact`). The result has as many rows as the input, the others are
+// masked like filtered rows.
+
+use helpers::circuit::Wire;
+
+use super::{LowerError, Lowering, Relation, RelationColumn, Source, Value};
+use crate::plan::{AggregateExpr, AggregateFunction, ColumnRef, ScalarExpr};
+
+impl Lowering<'_> {
+    /// GROUP BY: sort the rows by the grouping keys, then aggregate every run
+    /// of rows with the same key with a segmented scan, which leaves the result
+    /// of a group in its last row. The result keeps the input's rows, with one
+    /// valid row per group, all of them before the invalid rows.
+    pub(super) fn lower_grouped(
+        &mut self,
+        input: Relation,
+        group_by: &[ScalarExpr],
+        aggregates: &[AggregateExpr],
+    ) -> Result<Relation, LowerError> {
+        let rows = input.rows;
+        let mut keys = Vec::with_capacity(group_by.len());
+        for key in group_by {
+            let (value, data_type) = self.lower_expr(key, &input)?;
+            let column = match key {
+                ScalarExpr::Column(column) => column.clone(),
+                other => ColumnRef::new(None, &other.to_string()),
+            };
+            keys.push((column, data_type, self.bits_of(value)));
+        }
+        // Any order that puts equal keys next to each other will do; the
+        // first key is the most significant, invalid rows go last
+        let mut sort_key: Vec<Wire> = keys.iter().rev().flat_map(|(_, _, bits)| bits.iter().copied()).collect();
+        let key_width = sort_key.len();
+        if let Some(valid) = input.valid {
+            sort_key.push(self.builder.not(valid));
+        }
+
+        // Sorted along: a word per SUM and AVG and the bits of MIN and MAX
+        let mut payload = Vec::new();
+        let mut sorted_ranges = Vec::with_capacity(aggregates.len());
+        let mut types = Vec::with_capacity(aggregates.len());
+        for aggregate in aggregates {
+            let (value, data_type) = self.aggregate_argument(aggregate, &input)?;
+            let start = payload.len();
+            match (aggregate.func, value) {
+                (AggregateFunction::Count, _) => {}
+                (AggregateFunction::Sum | AggregateFunction::Avg, Some(value)) => payload.push(self.word_of(value)),
+                (_, Some(value)) => payload.extend(self.bits_of(value)),
+                (_, None) => unreachable!("checked by aggregate_argument"),
+            }
+            sorted_ranges.push(start..payload.len());
+            types.push(data_type);
+        }
+        let (sorted_key, sorted) = self.builder.sort_rows(&sort_key, &payload);
+        let valid = input.valid.map(|_| self.builder.not(sorted_key[key_width]));
+
+        // Scanned: the sorted values, and for COUNT and AVG a count, i.e. a
+        // sum of ones, which need not be sorted
+        let one = self.builder.const_word(1, rows);
+        let mut values = Vec::new();
+        let mut ranges = Vec::with_capacity(aggregates.len());
+        for (aggregate, range) in aggregates.iter().zip(sorted_ranges) {
+            let start = values.len();
+            values.extend(&sorted[range]);
+            if matches!(aggregate.func, AggregateFunction::Count | AggregateFunction::Avg) {
+                values.push(one);
+            }
+            ranges.push(start..values.len());
+        }
+
+        // A row continues the group of the row above if both are valid and
+        // have the same key
+        let mut group = sorted_key[..key_width].to_vec();
+        group.extend(valid);
+        let linked = self.builder.same_as_above(&group);
+        let linked = match valid {
+            Some(valid) => self.builder.and(linked, valid),
+            None => linked,
+        };
+        let funcs: Vec<AggregateFunction> = aggregates.iter().map(|a| a.func).collect();
+        let scanned = self.builder.scan_segments(linked, &values, |builder, above, own| {
+            let mut combined = Vec::with_capacity(own.len());
+            for (func, range) in funcs.iter().zip(&ranges) {
+                let (above, own) = (&above[range.clone()], &own[range.clone()]);
+                match func {
+                    AggregateFunction::Min | AggregateFunction::Max => {
+                        let better = if *func == AggregateFunction::Max {
+                            builder.less_than(own, above)
+                        } else {
+                            builder.less_than(above, own)
+                        };
+                        combined.extend(builder.mux(better, above, own));
+                    }
+                    _ => combined.extend(above.iter().zip(own).map(|(a, b)| builder.add(*a, *b))),
+                }
+            }
+            combined
+        });
+
+        // The grouping keys come out of the sort key, last key first
+        let mut columns = Vec::with_capacity(keys.len() + aggregates.len());
+        let mut offset = 0;
+        for (column, data_type, bits) in keys.iter().rev() {
+            columns.push(RelationColumn {
+                column: column.clone(),
+                data_type: data_type.clone(),
+                source: Source::Computed(Value::Bits(sorted_key[offset..offset + bits.len()].to_vec())),
+            });
+            offset += bits.len();
+        }
+        columns.reverse();
+        for ((aggregate, range), data_type) in aggregates.iter().zip(&ranges).zip(types) {
+            let wires = &scanned[range.clone()];
+            let source = match aggregate.func {
+                AggregateFunction::Avg => Source::Average { sum: wires[0], count: wires[1] },
+                AggregateFunction::Count | AggregateFunction::Sum => Source::Computed(Value::Word(wires[0])),
+                _ => Source::Computed(Value::Bits(wires.to_vec())),
+            };
+            columns.push(RelationColumn {
+                column: ColumnRef::new(None, &aggregate.output_name()),
+                data_type,
+                source,
+            });
+        }
+
+        // The last row of every group holds its result
+        let last = if rows > 1 {
+            let below = self.builder.slice(linked, 1, rows - 1);
+            let end = self.builder.const_bit(false, 1);
+            let below = self.builder.concat(&[below, end]);
+            let last = self.builder.not(below);
+            self.and_valid(valid, Some(last))
+        } else {
+            valid
+        };
+        let grouped = Relation {
+            rows,
+            columns,
+            valid: last,
+        };
+        self.compact(grouped, rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lower::tests::{run, table_values, valid_rows};
+    use crate::lower::VALID_COLUMN;
+
+    #[test]
+    fn test_group_by_aggregates_each_group() {
+        let (circuit, outputs) = run(
+            "SELECT code, COUNT(*), SUM(available_qty), AVG(part_key), MIN(available_qty), MAX(part_key) \
+             FROM partsupp GROUP BY code",
+        );
+        assert_eq!(circuit.outputs.last().unwrap().name, VALID_COLUMN);
+        // One valid row per group, in the input's 4 rows
+        assert_eq!(outputs[0].len(), 4);
+        let code = |r: usize| table_values()[2][r];
+        let mut rows = valid_rows(&outputs);
+        rows.sort();
+        let mut expected = vec![
+            vec![code(0), 2, 3325 + 9999, 5, 2, 3325, 4],
+            vec![code(1), 1, 8076, 2, 1, 8076, 2],
+            vec![code(2), 1, 5000, 3, 1, 5000, 3],
+        ];
+        expected.sort();
+        assert_eq!(rows, expected);
+
+        // Filtered rows stay out of every group
+        let (_, outputs) = run("SELECT code, COUNT(*), SUM(part_key) FROM partsupp WHERE part_key <> 2 GROUP BY code");
+        let mut rows = valid_rows(&outputs);
+        rows.sort();
+        assert_eq!(rows, vec![vec![code(2), 1, 3], vec![code(0), 2, 5]]);
+
+        let (_, outputs) = run("SELECT SUM(part_key) FROM partsupp GROUP BY part_key > 2");
+        let mut rows = valid_rows(&outputs);
+        rows.sort();
+        assert_eq!(rows, vec![vec![3], vec![7]]);
+    }
+
+    #[test]
+    fn test_group_by_hides_group_sizes() {
+        // The valid rows come first whatever the group sizes, so the validity
+        // column is a run of ones followed by zeros
+        for sql in [
+            "SELECT code, COUNT(*) FROM partsupp GROUP BY code",
+            "SELECT COUNT(*) FROM partsupp WHERE part_key <> 2 GROUP BY code",
+            "SELECT MAX(available_qty), SUM(part_key) FROM partsupp GROUP BY part_key > 3",
+            "SELECT AVG(part_key) FROM partsupp GROUP BY available_qty",
+        ] {
+            let (_, outputs) = run(sql);
+            let valid = outputs.last().unwrap();
+            let ones = valid.iter().take_while(|bit| **bit == 1).count();
+            assert!(ones > 0 && valid[ones..].iter().all(|bit| *bit == 0), "{}: {:?}", sql, valid);
+        }
+    }
+}
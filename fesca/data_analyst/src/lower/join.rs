@@ -0,0 +1,293 @@
+// Joins
+// =====
+// Joins are oblivious and only depend on the public row counts. By default
+// they are nested loops: every left row is paired with every right row, and
+// the ON predicate, evaluated on all n * m pairs, becomes their validity.
+//
+// When ON contains an equality between a left and a right column and one of
+// them is declared `unique` in its schema (a primary key), a sort-merge join
+// is used instead: both inputs are stacked into n + m rows and sorted by the
+// key with the unique side's rows first, and a log-depth scan copies the
+// unique side's values down to the rows of the other side that follow them
+// (see `CircuitBuilder::fill_down`). A row of the other side is valid if the
+// row it copied from holds the same key. Only columns the query uses are
+// carried through either join.
+
+use helpers::circuit::{CircuitBuilder, Wire, WireKind};
+
+use super::{LowerError, Lowering, Relation, RelationColumn, Source, Value};
+use crate::catalog::ColumnType;
+use crate::plan::{BinaryOp, ColumnRef, ScalarExpr};
+
+impl Lowering<'_> {
+    /// Pair every row of `left` with every row of `right` and keep the pairs
+    /// satisfying `on`
+    pub(super) fn nested_loop_join(&mut self, left: Relation, right: Relation, on: &ScalarExpr) -> Result<Relation, LowerError> {
+        let (n, m) = (left.rows, right.rows);
+        let mut columns = Vec::new();
+        for column in self.used_columns(&left) {
+            let value = self.load(column)?;
+            let value = self.map_wires(value, |builder, wire| builder.repeat_rows(wire, m));
+            columns.push(RelationColumn { source: Source::Computed(value), ..column.clone() });
+        }
+        for column in self.used_columns(&right) {
+            let value = self.load(column)?;
+            let value = self.map_wires(value, |builder, wire| builder.tile_rows(wire, n));
+            columns.push(RelationColumn { source: Source::Computed(value), ..column.clone() });
+        }
+        let left_valid = left.valid.map(|valid| self.builder.repeat_rows(valid, m));
+        let right_valid = right.valid.map(|valid| self.builder.tile_rows(valid, n));
+        let mut joined = Relation {
+            rows: n * m,
+            columns,
+            valid: self.and_valid(left_valid, right_valid),
+        };
+        let (value, _) = self.lower_expr(on, &joined)?;
+        let matches = self.single_bit(value)?;
+        joined.valid = self.and_valid(joined.valid, Some(matches));
+        Ok(joined)
+    }
+
+    /// The columns of an equality in `on` that allows a sort-merge join, as
+    /// (left column, right column, rest of `on`), if there is one. Keys that
+    /// cannot be compared bit by bit are left to the nested loop, which
+    /// reports them.
+    pub(super) fn merge_key(
+        &self,
+        on: &ScalarExpr,
+        left: &Relation,
+        right: &Relation,
+    ) -> Option<(ColumnRef, ColumnRef, Option<ScalarExpr>)> {
+        let mut conjuncts = Vec::new();
+        split_conjuncts(on, &mut conjuncts);
+        for (i, conjunct) in conjuncts.iter().enumerate() {
+            let ScalarExpr::Binary { op: BinaryOp::Eq, left: a, right: b } = conjunct else {
+                continue;
+            };
+            let (ScalarExpr::Column(a), ScalarExpr::Column(b)) = (a.as_ref(), b.as_ref()) else {
+                continue;
+            };
+            let (left_key, right_key) = match (left.position(a), right.position(b)) {
+                (Ok(_), Ok(_)) => (a, b),
+                _ => (b, a),
+            };
+            let (Ok(l), Ok(r)) = (left.position(left_key), right.position(right_key)) else {
+                continue;
+            };
+            let (l, r) = (&left.columns[l], &right.columns[r]);
+            let comparable = match (&l.data_type, &r.data_type) {
+                (ColumnType::Float, _) | (_, ColumnType::Float) => false,
+                // Equal keys only have equal encodings at the same scale
+                (ColumnType::Decimal { .. }, _) | (_, ColumnType::Decimal { .. }) => l.data_type == r.data_type,
+                (ColumnType::String { charset: a, .. }, ColumnType::String { charset: b, .. }) => a == b,
+                _ => true,
+            };
+            if comparable && (self.is_unique(l) || self.is_unique(r)) {
+                let rest = conjuncts
+                    .iter()
+                    .enumerate()
+                    .filter(|(j, _)| *j != i)
+                    .map(|(_, c)| (*c).clone())
+                    .reduce(|a, b| ScalarExpr::binary(BinaryOp::And, a, b));
+                return Some((left_key.clone(), right_key.clone(), rest));
+            }
+        }
+        None
+    }
+
+    /// Sort-merge join on `left_key = right_key AND rest`, where one of the
+    /// keys is unique
+    pub(super) fn sort_merge_join(
+        &mut self,
+        left: Relation,
+        right: Relation,
+        left_key: &ColumnRef,
+        right_key: &ColumnRef,
+        rest: Option<ScalarExpr>,
+    ) -> Result<Relation, LowerError> {
+        let left_first = self.is_unique(left.find(left_key)?);
+        let (primary, foreign, primary_key, foreign_key) = if left_first {
+            (&left, &right, left_key, right_key)
+        } else {
+            (&right, &left, right_key, left_key)
+        };
+        let (n, m) = (primary.rows, foreign.rows);
+        let rows = n + m;
+
+        // Stack the primary rows on top of the foreign rows, zeros filling in
+        // the columns of the other side
+        let stack = |builder: &mut CircuitBuilder, top: Option<Wire>, bottom: Option<Wire>, kind| {
+            let top = top.unwrap_or_else(|| builder.constant(kind, 0, n));
+            let bottom = bottom.unwrap_or_else(|| builder.constant(kind, 0, m));
+            builder.concat(&[top, bottom])
+        };
+        let primary_bits = self.load(primary.find(primary_key)?)?;
+        let primary_bits = self.bits_of(primary_bits);
+        let foreign_bits = self.load(foreign.find(foreign_key)?)?;
+        let foreign_bits = self.bits_of(foreign_bits);
+        let width = primary_bits.len().max(foreign_bits.len());
+        let key: Vec<Wire> = (0..width)
+            .map(|j| stack(&mut self.builder, primary_bits.get(j).copied(), foreign_bits.get(j).copied(), WireKind::Bit))
+            .collect();
+        let primary_valid = primary.valid.unwrap_or_else(|| self.builder.const_bit(true, n));
+        let foreign_valid = foreign.valid.unwrap_or_else(|| self.builder.const_bit(true, m));
+        let valid = self.builder.concat(&[primary_valid, foreign_valid]);
+        let foreign_rows = self.builder.const_bit(true, m);
+        let is_foreign = stack(&mut self.builder, None, Some(foreign_rows), WireKind::Bit);
+
+        // Sort key LSB first: primary rows before foreign rows with the same
+        // key, invalid rows last
+        let mut sort_key = vec![is_foreign];
+        sort_key.extend(&key);
+        sort_key.push(self.builder.not(valid));
+
+        let mut payload = Vec::new();
+        let mut layouts = Vec::new();
+        for (side, relation) in [primary, foreign].into_iter().enumerate() {
+            for column in self.used_columns(relation) {
+                let value = self.load(column)?;
+                let value = self.map_wires(value, |builder, wire| {
+                    let kind = builder.kind(wire);
+                    match side {
+                        0 => stack(builder, Some(wire), None, kind),
+                        _ => stack(builder, None, Some(wire), kind),
+                    }
+                });
+                match &value {
+                    Value::Bits(bits) => payload.extend(bits),
+                    Value::Word(word) => payload.push(*word),
+                }
+                layouts.push((side, column, value));
+            }
+        }
+        let (sorted_key, sorted) = self.builder.sort_rows(&sort_key, &payload);
+        let is_foreign = sorted_key[0];
+        let key = &sorted_key[1..=width];
+        let valid = self.builder.not(sorted_key[width + 1]);
+
+        // Copy the primary values, key and validity down to the foreign rows
+        let is_primary = self.builder.not(is_foreign);
+        let primary_count = layouts.iter().take_while(|(side, _, _)| *side == 0).count();
+        let primary_wires: usize = layouts[..primary_count].iter().map(|(_, _, v)| value_width(v)).sum();
+        let mut values = sorted[..primary_wires].to_vec();
+        values.extend(key);
+        values.push(self.builder.and(is_primary, valid));
+        let filled = self.builder.fill_down(is_primary, &values);
+        let (filled_key, filled_valid) = (&filled[primary_wires..primary_wires + width], filled[primary_wires + width]);
+
+        let same_key = self.builder.equal(key, filled_key);
+        let matched = self.builder.and(is_foreign, valid);
+        let matched = self.builder.and(matched, filled_valid);
+        let matched = self.builder.and(matched, same_key);
+
+        let mut wires = filled[..primary_wires].iter().chain(&sorted[primary_wires..]).copied();
+        let mut columns: Vec<RelationColumn> = layouts
+            .iter()
+            .map(|(_, column, value)| RelationColumn {
+                source: Source::Computed(match value {
+                    Value::Bits(bits) => Value::Bits(bits.iter().map(|_| wires.next().unwrap()).collect()),
+                    Value::Word(_) => Value::Word(wires.next().unwrap()),
+                }),
+                ..(*column).clone()
+            })
+            .collect();
+        if !left_first {
+            columns.rotate_left(primary_count);
+        }
+        let mut joined = Relation {
+            rows,
+            columns,
+            valid: Some(matched),
+        };
+        if let Some(rest) = rest {
+            let (value, _) = self.lower_expr(&rest, &joined)?;
+            let keep = self.single_bit(value)?;
+            joined.valid = self.and_valid(joined.valid, Some(keep));
+        }
+        Ok(joined)
+    }
+
+    /// Whether a column is declared unique in its stored table's schema
+    fn is_unique(&self, column: &RelationColumn) -> bool {
+        let Source::Stored(input) = &column.source else {
+            return false;
+        };
+        self.catalog
+            .table(&input.table)
+            .and_then(|table| table.columns.get(input.column))
+            .is_some_and(|info| info.unique)
+    }
+
+    /// Columns of a relation the plan refers to anywhere
+    fn used_columns<'r>(&self, relation: &'r Relation) -> Vec<&'r RelationColumn> {
+        let is_used = |column: &RelationColumn| {
+            self.used.iter().any(|used| {
+                used.name.eq_ignore_ascii_case(&column.column.name)
+                    && used
+                        .table
+                        .as_ref()
+                        .is_none_or(|table| column.column.table.as_ref().is_some_and(|t| t.eq_ignore_ascii_case(table)))
+            })
+        };
+        relation.columns.iter().filter(|c| is_used(c)).collect()
+    }
+
+    /// Apply a row gate to every wire of a value
+    fn map_wires(&mut self, value: Value, mut f: impl FnMut(&mut CircuitBuilder, Wire) -> Wire) -> Value {
+        match value {
+            Value::Bits(bits) => Value::Bits(bits.into_iter().map(|bit| f(&mut self.builder, bit)).collect()),
+            Value::Word(word) => Value::Word(f(&mut self.builder, word)),
+        }
+    }
+}
+
+/// Conjuncts of an AND chain, left to right
+fn split_conjuncts<'e>(expr: &'e ScalarExpr, out: &mut Vec<&'e ScalarExpr>) {
+    match expr {
+        ScalarExpr::Binary { op: BinaryOp::And, left, right } => {
+            split_conjuncts(left, out);
+            split_conjuncts(right, out);
+        }
+        other => out.push(other),
+    }
+}
+
+/// Number of wires of a value
+fn value_width(value: &Value) -> usize {
+    match value {
+        Value::Bits(bits) => bits.len(),
+        Value::Word(_) => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lower::tests::{run, table_values, valid_rows};
+
+    #[test]
+    fn test_joins_pair_matching_rows() {
+        // part.part_key is unique: sort-merge over 4 + 3 rows
+        let (circuit, outputs) = run(
+            "SELECT partsupp.part_key, available_qty, size FROM partsupp JOIN part ON partsupp.part_key = part.part_key",
+        );
+        assert_eq!(outputs[0].len(), 7);
+        assert_eq!(valid_rows(&outputs), vec![vec![1, 3325, 10], vec![2, 8076, 20], vec![4, 9999, 40]]);
+        assert_eq!(circuit.inputs.len(), 4);
+
+        // The unique side on the left, the rest of ON and WHERE as filters
+        let (_, outputs) = run(
+            "SELECT size, code FROM part JOIN partsupp ON partsupp.part_key = part.part_key AND available_qty > 5000 \
+             WHERE size < 40",
+        );
+        assert_eq!(outputs[0].len(), 7);
+        assert_eq!(valid_rows(&outputs), vec![vec![20, table_values()[2][1]]]);
+
+        // Without a unique key: nested loop over all 4 * 4 pairs
+        let (_, outputs) = run(
+            "SELECT a.part_key, b.available_qty FROM partsupp a JOIN partsupp b \
+             ON a.part_key = b.part_key + 1 AND b.code = 'AB'",
+        );
+        assert_eq!(outputs[0].len(), 16);
+        assert_eq!(valid_rows(&outputs), vec![vec![2, 3325]]);
+    }
+}
@@ -0,0 +1,117 @@
+// LIKE
+// ====
+// LIKE compares every character position of an Ascii string with the public
+// pattern at once: a segment between two `%` is tried at every offset, and
+// the offsets are combined with log-depth ORs, so neither where a match was
+// found nor the length of the string is revealed. Strings are NUL-padded to
+// their column's width, which tells `%suffix` where the string ends.
+
+use helpers::circuit::compare::Comparison;
+use helpers::circuit::{CircuitBuilder, Wire};
+
+use super::Lowering;
+
+impl Lowering<'_> {
+    /// Whether Ascii strings match a LIKE pattern. The pattern is split at
+    /// every `%` into segments; the first must match at the start, the last
+    /// at the end, and the ones between in order without overlapping, for
+    /// which `reach[s]` tracks whether the segments so far fit into the first
+    /// s characters. A string shorter than its column ends at its first NUL,
+    /// which neither a pattern character nor `_` matches.
+    pub(super) fn lower_like(&mut self, bits: &[Wire], pattern: &str, rows: usize) -> Wire {
+        let chars: Vec<&[Wire]> = bits.chunks(7).collect();
+        let n = chars.len();
+        let yes = self.builder.const_bit(true, rows);
+        let no = self.builder.const_bit(false, rows);
+        let nul: Vec<Wire> = chars.iter().map(|c| self.builder.compare_const(c, Comparison::Equal, 0)).collect();
+        let matches_at = |builder: &mut CircuitBuilder, segment: &str, s: usize| {
+            if s + segment.len() > n {
+                return no;
+            }
+            let same: Vec<Wire> = segment
+                .bytes()
+                .enumerate()
+                .map(|(i, c)| match c {
+                    b'_' => builder.not(nul[s + i]),
+                    _ => builder.compare_const(chars[s + i], Comparison::Equal, c as u64),
+                })
+                .collect();
+            builder.all(&same, rows)
+        };
+        let ends_at = |p: usize| if p == n { yes } else { nul[p] };
+
+        let segments: Vec<&str> = pattern.split('%').collect();
+        let (first, rest) = segments.split_first().expect("split yields a segment");
+        let first_match = matches_at(&mut self.builder, first, 0);
+        let Some((last, middle)) = rest.split_last() else {
+            return if first.len() <= n { self.builder.and(first_match, ends_at(first.len())) } else { no };
+        };
+        let mut reach: Vec<Wire> = (0..=n).map(|s| if s >= first.len() { first_match } else { no }).collect();
+        for segment in middle {
+            // found[s]: the segment follows the earlier ones at s
+            let mut found: Vec<Wire> = (0..=n)
+                .map(|s| {
+                    let here = matches_at(&mut self.builder, segment, s);
+                    self.builder.and(reach[s], here)
+                })
+                .collect();
+            // Prefix OR by doubling, so reach[e] = found[0] | ... | found[e - len]
+            let mut distance = 1;
+            while distance < found.len() {
+                found = (0..found.len())
+                    .map(|i| if i >= distance { self.builder.or(found[i], found[i - distance]) } else { found[i] })
+                    .collect();
+                distance *= 2;
+            }
+            reach = (0..=n).map(|e| if e >= segment.len() { found[e - segment.len()] } else { no }).collect();
+        }
+        if last.is_empty() {
+            return reach[n];
+        }
+        // Any placement of the last segment that ends the string
+        let placed: Vec<Wire> = (0..=n.saturating_sub(last.len()))
+            .map(|s| {
+                let here = matches_at(&mut self.builder, last, s);
+                let here = self.builder.and(reach[s], here);
+                let end = if s + last.len() <= n { ends_at(s + last.len()) } else { no };
+                let missed = self.builder.and(here, end);
+                self.builder.not(missed)
+            })
+            .collect();
+        let none = self.builder.all(&placed, rows);
+        self.builder.not(none)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lower::tests::{run, valid_rows};
+
+    /// Plaintext LIKE: `%` matches any run of characters, `_` any one
+    fn like(value: &[u8], pattern: &[u8]) -> bool {
+        match pattern.split_first() {
+            None => value.is_empty(),
+            Some((b'%', rest)) => (0..=value.len()).any(|i| like(&value[i..], rest)),
+            Some((c, rest)) => {
+                value.first().is_some_and(|v| *c == b'_' || v == c) && like(&value[1..], rest)
+            }
+        }
+    }
+
+    #[test]
+    fn test_like_matches_patterns() {
+        let codes = ["AB", "ABC", "B", "AB"];
+        let patterns = [
+            "A%", "%B", "%BC%", "_B", "___", "A%C", "%", "", "ABCD%", "A_%", "%%B", "%A%B%", "B%B", "%_", "AB",
+        ];
+        for pattern in patterns {
+            let (_, outputs) = run(&format!("SELECT code LIKE '{}', code NOT LIKE '{}' FROM partsupp", pattern, pattern));
+            let expected: Vec<u64> = codes.iter().map(|c| like(c.as_bytes(), pattern.as_bytes()) as u64).collect();
+            assert_eq!(outputs[0], expected, "{}", pattern);
+            assert_eq!(outputs[1], expected.iter().map(|m| 1 - m).collect::<Vec<_>>(), "{}", pattern);
+        }
+
+        let (_, outputs) = run("SELECT part_key FROM partsupp WHERE code LIKE 'A%' AND available_qty > 5000");
+        assert_eq!(valid_rows(&outputs), vec![vec![2], vec![4]]);
+    }
+}
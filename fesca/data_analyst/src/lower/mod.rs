@@ -0,0 +1,951 @@
+// Circuit Lowering
+// ================
+// Turns a bound logical plan into a `Circuit` the computing nodes evaluate on
+// their shares. Every relation keeps its public row count; columns are bit
+// vectors (boolean-shared, LSB first, in the data owner's encoding) or words
+// (arithmetically shared), converted only where an operator needs it:
+// comparisons and logic work on bits, +, - and * on words mod 2^64.
+//
+// Filters are oblivious: a relation carries a secret validity bit per row that
+// WHERE clauses AND their predicate into, instead of dropping rows. Aggregates
+// fold the valid rows into one (AVG as its sum and count, see
+// `AVG_COUNT_SUFFIX`); other results are masked with the bits, which are
+// returned as the output `VALID_COLUMN`. Fixed-point Float columns are
+// `Decimal` integers scaled by 10^scale, brought to a common scale with a
+// public factor before they add, subtract or compare (`align_scales`).
+
+mod group;
+mod join;
+mod like;
+mod sort;
+
+use std::fmt;
+
+use helpers::circuit::compare::Comparison;
+use helpers::circuit::{Circuit, CircuitBuilder, InputColumn, InputSharing, OutputWires, Wire};
+
+use crate::binder::{average_type, binary_type, BoundQuery};
+use crate::catalog::{Catalog, Charset, ColumnType, Sharing};
+use crate::plan::{
+    AggregateExpr, AggregateFunction, BinaryOp, ColumnRef, Literal, LogicalPlan, ProjectItem, ScalarExpr,
+};
+
+/// Why a plan cannot be turned into a circuit
+#[derive(Debug, Clone, PartialEq)]
+pub enum LowerError {
+    /// The plan uses an operator or type the circuits do not cover
+    Unsupported(String),
+    /// The plan does not fit the catalog; binding should have caught this
+    Invalid(String),
+}
+
+impl fmt::Display for LowerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LowerError::Unsupported(what) => write!(f, "Unsupported: {} not supported in circuits", what),
+            LowerError::Invalid(reason) => write!(f, "Invalid plan: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for LowerError {}
+
+fn unsupported<T>(what: impl Into<String>) -> Result<T, LowerError> {
+    Err(LowerError::Unsupported(what.into()))
+}
+
+/// Name of the output column holding the validity bit of every result row;
+/// it follows the query's own output columns
+pub const VALID_COLUMN: &str = "__valid";
+
+/// Appended to the name of an AVG output column to name the output holding
+/// its count, which directly follows the one holding its sum
+pub const AVG_COUNT_SUFFIX: &str = "__count";
+
+/// Name of the only output of the circuit built by `lower_distinct_rows`
+pub const DISTINCT_ROWS_COLUMN: &str = "__distinct_rows";
+
+/// Choices the analyst makes about what a query may reveal
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LowerOptions {
+    /// Number of rows DISTINCT produces, as revealed by running the circuit
+    /// of `lower_distinct_rows`. If set, the distinct rows are moved to the
+    /// top and cut to this many, so the operators above and the result only
+    /// work on them. None keeps every row and hides the number.
+    pub distinct_rows: Option<usize>,
+}
+
+/// Build the circuit computing a bound query
+pub fn lower(query: &BoundQuery, catalog: &Catalog) -> Result<Circuit, LowerError> {
+    lower_with(query, catalog, LowerOptions::default())
+}
+
+/// Build the circuit computing a bound query with the given options
+pub fn lower_with(query: &BoundQuery, catalog: &Catalog, options: LowerOptions) -> Result<Circuit, LowerError> {
+    let mut lowering = Lowering::new(query, catalog, options);
+    let relation = lowering.lower_plan(&query.plan)?;
+    if relation.columns.len() != query.output.len() {
+        return Err(LowerError::Invalid(format!(
+            "plan produces {} columns, query has {}",
+            relation.columns.len(),
+            query.output.len()
+        )));
+    }
+    for (column, output) in relation.columns.iter().zip(&query.output) {
+        let values = match column.source {
+            Source::Average { sum, count } => vec![
+                (output.name.clone(), Value::Word(sum)),
+                (format!("{}{}", output.name, AVG_COUNT_SUFFIX), Value::Word(count)),
+            ],
+            _ => vec![(output.name.clone(), lowering.load(column)?)],
+        };
+        for (name, mut value) in values {
+            if let Some(valid) = relation.valid {
+                value = lowering.mask(value, valid);
+            }
+            let wires = match value {
+                Value::Bits(bits) => OutputWires::Bits(bits),
+                Value::Word(word) => OutputWires::Word(word),
+            };
+            lowering.builder.output(&name, wires);
+        }
+    }
+    if let Some(valid) = relation.valid {
+        lowering.builder.output(VALID_COLUMN, OutputWires::Bits(vec![valid]));
+    }
+    Ok(lowering.builder.finish())
+}
+
+/// Build the circuit counting the rows the DISTINCT of a bound query
+/// produces, or None if the query has no DISTINCT. Revealing the count is up
+/// to the analyst (see `LowerOptions::distinct_rows`).
+pub fn lower_distinct_rows(query: &BoundQuery, catalog: &Catalog) -> Result<Option<Circuit>, LowerError> {
+    let mut plan = &query.plan;
+    while !matches!(plan, LogicalPlan::Distinct { .. }) {
+        match plan.inputs().as_slice() {
+            [input] => plan = input,
+            _ => return Ok(None),
+        }
+    }
+    let mut lowering = Lowering::new(query, catalog, LowerOptions::default());
+    let relation = lowering.lower_plan(plan)?;
+    let count = match relation.rows {
+        0 => lowering.builder.const_word(0, 1),
+        _ => lowering.count_valid(&relation),
+    };
+    lowering.builder.output(DISTINCT_ROWS_COLUMN, OutputWires::Word(count));
+    Ok(Some(lowering.builder.finish()))
+}
+
+/// Lowered value of a column or expression
+#[derive(Debug, Clone)]
+enum Value {
+    Bits(Vec<Wire>),
+    Word(Wire),
+}
+
+/// Where the values of a relation column come from
+#[derive(Debug, Clone)]
+enum Source {
+    /// Stored share column, read only if the column is used
+    Stored(InputColumn),
+    Computed(Value),
+    /// Result of AVG, divided by the analyst
+    Average { sum: Wire, count: Wire },
+}
+
+#[derive(Debug, Clone)]
+struct RelationColumn {
+    column: ColumnRef,
+    data_type: ColumnType,
+    source: Source,
+}
+
+/// Intermediate result: public row count, columns and validity
+#[derive(Debug, Clone)]
+struct Relation {
+    rows: usize,
+    columns: Vec<RelationColumn>,
+    /// Secret bit per row telling whether the row belongs to the relation;
+    /// None if every row does
+    valid: Option<Wire>,
+}
+
+impl Relation {
+    fn find(&self, column: &ColumnRef) -> Result<&RelationColumn, LowerError> {
+        Ok(&self.columns[self.position(column)?])
+    }
+
+    fn position(&self, column: &ColumnRef) -> Result<usize, LowerError> {
+        let same = |a: &Option<String>, b: &Option<String>| match (a, b) {
+            (Some(a), Some(b)) => a.eq_ignore_ascii_case(b),
+            (None, None) => true,
+            _ => false,
+        };
+        self.columns
+            .iter()
+            .position(|c| c.column.name.eq_ignore_ascii_case(&column.name) && same(&c.column.table, &column.table))
+            .ok_or_else(|| LowerError::Invalid(format!("column {} is not in scope", column)))
+    }
+}
+
+struct Lowering<'a> {
+    builder: CircuitBuilder,
+    catalog: &'a Catalog,
+    /// Every column reference in the plan, to leave unused columns out of joins
+    used: Vec<&'a ColumnRef>,
+    options: LowerOptions,
+}
+
+impl<'a> Lowering<'a> {
+    fn new(query: &'a BoundQuery, catalog: &'a Catalog, options: LowerOptions) -> Self {
+        Self {
+            builder: CircuitBuilder::new(),
+            catalog,
+            used: query.plan.columns(),
+            options,
+        }
+    }
+
+    fn lower_plan(&mut self, plan: &LogicalPlan) -> Result<Relation, LowerError> {
+        match plan {
+            LogicalPlan::Scan { table, alias } => {
+                let info = self
+                    .catalog
+                    .table(table)
+                    .ok_or_else(|| LowerError::Invalid(format!("unknown table {}", table)))?;
+                let qualifier = alias.as_deref().unwrap_or(&info.table_name);
+                let columns = info
+                    .columns
+                    .iter()
+                    .enumerate()
+                    .map(|(index, column)| RelationColumn {
+                        column: ColumnRef::new(Some(qualifier), &column.name),
+                        data_type: column.data_type(),
+                        source: Source::Stored(InputColumn {
+                            table: info.table_name.clone(),
+                            table_id: info.table_id,
+                            schema_hash: info.schema_hash,
+                            column: index,
+                            width: column.stored_width(),
+                            sharing: match column.sharing {
+                                Sharing::Boolean => InputSharing::Boolean,
+                                Sharing::Arithmetic => InputSharing::Arithmetic,
+                            },
+                            rows: info.row_count,
+                        }),
+                    })
+                    .collect();
+                Ok(Relation {
+                    rows: info.row_count,
+                    columns,
+                    valid: None,
+                })
+            }
+            LogicalPlan::Project { input, items } => {
+                let input = match input.as_ref() {
+                    // Only the projected columns are worth carrying through the sort
+                    LogicalPlan::Sort { input, keys } => {
+                        let used: Vec<&ColumnRef> = items
+                            .iter()
+                            .filter_map(|item| match item {
+                                ProjectItem::Expr { expr, .. } => Some(expr.columns()),
+                                _ => None,
+                            })
+                            .flatten()
+                            .collect();
+                        self.lower_sort(input, keys, Some(&used))?
+                    }
+                    _ => self.lower_plan(input)?,
+                };
+                let mut columns = Vec::with_capacity(items.len());
+                for item in items {
+                    let ProjectItem::Expr { expr, .. } = item else {
+                        return Err(LowerError::Invalid("wildcard left in a bound plan".to_string()));
+                    };
+                    // Columns are passed on as they are, so unused stored
+                    // columns are never read
+                    let (source, data_type) = match expr {
+                        ScalarExpr::Column(column) => {
+                            let column = input.find(column)?;
+                            (column.source.clone(), column.data_type.clone())
+                        }
+                        _ => {
+                            let (value, data_type) = self.lower_expr(expr, &input)?;
+                            (Source::Computed(value), data_type)
+                        }
+                    };
+                    columns.push(RelationColumn {
+                        column: ColumnRef::new(None, &item.output_name().unwrap_or_default()),
+                        data_type,
+                        source,
+                    });
+                }
+                Ok(Relation {
+                    rows: input.rows,
+                    columns,
+                    valid: input.valid,
+                })
+            }
+            LogicalPlan::Filter { input, predicate } => {
+                let input = self.lower_plan(input)?;
+                let (value, _) = self.lower_expr(predicate, &input)?;
+                let keep = self.single_bit(value)?;
+                let valid = match input.valid {
+                    Some(valid) => self.builder.and(valid, keep),
+                    None => keep,
+                };
+                Ok(Relation {
+                    valid: Some(valid),
+                    ..input
+                })
+            }
+            LogicalPlan::Aggregate { input, group_by, aggregates } => {
+                let input = self.lower_plan(input)?;
+                if !group_by.is_empty() {
+                    return self.lower_grouped(input, group_by, aggregates);
+                }
+                let mut columns = Vec::with_capacity(aggregates.len());
+                for aggregate in aggregates {
+                    let (source, data_type) = self.lower_aggregate(aggregate, &input)?;
+                    columns.push(RelationColumn {
+                        column: ColumnRef::new(None, &aggregate.output_name()),
+                        data_type,
+                        source,
+                    });
+                }
+                Ok(Relation {
+                    rows: 1,
+                    columns,
+                    valid: None,
+                })
+            }
+            LogicalPlan::Join { left, right, on } => {
+                let left = self.lower_plan(left)?;
+                let right = self.lower_plan(right)?;
+                match self.merge_key(on, &left, &right) {
+                    Some((left_key, right_key, rest)) => self.sort_merge_join(left, right, &left_key, &right_key, rest),
+                    None => self.nested_loop_join(left, right, on),
+                }
+            }
+            LogicalPlan::Distinct { input } => {
+                let input = self.lower_plan(input)?;
+                self.lower_distinct(input)
+            }
+            LogicalPlan::Sort { input, keys } => self.lower_sort(input, keys, None),
+            LogicalPlan::Limit { input, limit } => {
+                let sorted = sort::is_sorted(input);
+                let input = self.lower_plan(input)?;
+                let rows = usize::try_from(*limit).unwrap_or(usize::MAX).min(input.rows);
+                // A sort already put the invalid rows last
+                if sorted {
+                    self.slice_rows(input, rows)
+                } else {
+                    self.compact(input, rows)
+                }
+            }
+        }
+    }
+
+    /// Wires of a relation column, reading the stored shares if needed
+    fn load(&mut self, column: &RelationColumn) -> Result<Value, LowerError> {
+        match &column.source {
+            Source::Computed(value) => Ok(value.clone()),
+            Source::Stored(input) => {
+                let sharing = input.sharing;
+                let index = self.builder.input(input.clone());
+                Ok(match sharing {
+                    InputSharing::Boolean => Value::Bits(self.builder.input_bits(index)),
+                    InputSharing::Arithmetic => Value::Word(self.builder.input_word(index)),
+                })
+            }
+            Source::Average { .. } => unsupported(format!("using {} in an expression is", column.column)),
+        }
+    }
+
+    /// The lowered argument of an aggregate (None for COUNT) and the type of
+    /// its result
+    fn aggregate_argument(
+        &mut self,
+        aggregate: &AggregateExpr,
+        input: &Relation,
+    ) -> Result<(Option<Value>, ColumnType), LowerError> {
+        let func = aggregate.func;
+        let (value, data_type) = match &aggregate.arg {
+            Some(arg) => {
+                let (value, data_type) = self.lower_expr(arg, input)?;
+                (Some(value), data_type)
+            }
+            None => (None, ColumnType::UnsignedInt),
+        };
+        if func != AggregateFunction::Count && data_type == ColumnType::Float {
+            return unsupported(format!("{} over Float values is", func));
+        }
+        match (func, value) {
+            (AggregateFunction::Count, _) => Ok((None, ColumnType::UnsignedInt)),
+            (AggregateFunction::Avg, Some(value)) => Ok((Some(value), average_type(&data_type))),
+            (_, Some(value)) => Ok((Some(value), data_type)),
+            (_, None) => Err(LowerError::Invalid(format!("{} without an argument", func))),
+        }
+    }
+
+    /// One aggregate over the valid rows of a relation, as a single row
+    fn lower_aggregate(
+        &mut self,
+        aggregate: &AggregateExpr,
+        input: &Relation,
+    ) -> Result<(Source, ColumnType), LowerError> {
+        let func = aggregate.func;
+        let (value, data_type) = self.aggregate_argument(aggregate, input)?;
+        if input.rows == 0 {
+            let zero = self.builder.const_word(0, 1);
+            return Ok(match func {
+                AggregateFunction::Avg => (Source::Average { sum: zero, count: zero }, data_type),
+                _ => (Source::Computed(Value::Word(zero)), data_type),
+            });
+        }
+
+        let source = match (func, value) {
+            (AggregateFunction::Count, _) => Source::Computed(Value::Word(self.count_valid(input))),
+            (AggregateFunction::Sum, Some(value)) => Source::Computed(Value::Word(self.sum_valid(value, input))),
+            (AggregateFunction::Avg, Some(value)) => Source::Average {
+                sum: self.sum_valid(value, input),
+                count: self.count_valid(input),
+            },
+            (_, Some(value)) => {
+                let max = func == AggregateFunction::Max;
+                Source::Computed(Value::Bits(self.extreme_valid(value, input, max)))
+            }
+            (_, None) => unreachable!("checked above"),
+        };
+        Ok((source, data_type))
+    }
+
+    /// Number of valid rows
+    fn count_valid(&mut self, input: &Relation) -> Wire {
+        let ones = match input.valid {
+            Some(valid) => self.builder.b2a(&[valid]),
+            None => self.builder.const_word(1, input.rows),
+        };
+        self.builder.sum_rows(ones)
+    }
+
+    /// Sum of a value over the valid rows; bits are masked before B2A, which
+    /// is cheaper than a multiplication per row afterwards
+    fn sum_valid(&mut self, value: Value, input: &Relation) -> Wire {
+        let value = match input.valid {
+            Some(valid) => self.mask(value, valid),
+            None => value,
+        };
+        let word = self.word_of(value);
+        self.builder.sum_rows(word)
+    }
+
+    /// Largest (or smallest) value among the valid rows, 0 if there is none
+    fn extreme_valid(&mut self, value: Value, input: &Relation, max: bool) -> Vec<Wire> {
+        let mut tuple = self.bits_of(value);
+        let width = tuple.len();
+        tuple.extend(input.valid);
+        let folded = self.builder.fold_rows(&tuple, |builder, low, high| {
+            let (low_bits, high_bits) = (&low[..width], &high[..width]);
+            let better = if max {
+                builder.less_than(low_bits, high_bits)
+            } else {
+                builder.less_than(high_bits, low_bits)
+            };
+            match (low.get(width), high.get(width)) {
+                (Some(low_valid), Some(high_valid)) => {
+                    // A valid row beats an invalid one, two valid rows compare
+                    let low_invalid = builder.not(*low_valid);
+                    let wins = builder.or(low_invalid, better);
+                    let take_high = builder.and(*high_valid, wins);
+                    let mut result = builder.mux(take_high, high_bits, low_bits);
+                    result.push(builder.or(*low_valid, *high_valid));
+                    result
+                }
+                _ => builder.mux(better, high_bits, low_bits),
+            }
+        });
+        match folded.get(width) {
+            Some(valid) => folded[..width].iter().map(|bit| self.builder.and(*bit, *valid)).collect(),
+            None => folded,
+        }
+    }
+
+    fn lower_expr(&mut self, expr: &ScalarExpr, relation: &Relation) -> Result<(Value, ColumnType), LowerError> {
+        match expr {
+            ScalarExpr::Column(column) => {
+                let column = relation.find(column)?.clone();
+                Ok((self.load(&column)?, column.data_type))
+            }
+            ScalarExpr::Literal(literal) => self.lower_literal(literal, None, relation.rows),
+            ScalarExpr::Not(inner) => {
+                let (value, _) = self.lower_expr(inner, relation)?;
+                let bit = self.single_bit(value)?;
+                Ok((Value::Bits(vec![self.builder.not(bit)]), ColumnType::Boolean))
+            }
+            ScalarExpr::Like { expr, pattern } => {
+                let (value, data_type) = self.lower_expr(expr, relation)?;
+                if !matches!(data_type, ColumnType::String { charset: Charset::Ascii, .. }) {
+                    return unsupported(format!("LIKE over {} values is", data_type));
+                }
+                let bits = self.bits_of(value);
+                let bit = self.lower_like(&bits, pattern, relation.rows);
+                Ok((Value::Bits(vec![bit]), ColumnType::Boolean))
+            }
+            ScalarExpr::Binary { op, left, right } => {
+                let (left, left_type, right, right_type) = self.lower_operands(left, right, relation)?;
+                match op {
+                    BinaryOp::And | BinaryOp::Or => {
+                        let (a, b) = (self.single_bit(left)?, self.single_bit(right)?);
+                        let bit = if *op == BinaryOp::And { self.builder.and(a, b) } else { self.builder.or(a, b) };
+                        Ok((Value::Bits(vec![bit]), ColumnType::Boolean))
+                    }
+                    BinaryOp::Plus | BinaryOp::Minus | BinaryOp::Multiply => {
+                        if left_type == ColumnType::Float || right_type == ColumnType::Float {
+                            return unsupported("arithmetic on Float values is");
+                        }
+                        let data_type = binary_type(*op, &left_type, &right_type).ok_or_else(|| {
+                            LowerError::Invalid(format!("{} of {} and {}", op, left_type, right_type))
+                        })?;
+                        let (left, right) = match op {
+                            BinaryOp::Multiply => (left, right),
+                            _ => self.align_scales(left, &left_type, right, &right_type)?,
+                        };
+                        let (a, b) = (self.word_of(left), self.word_of(right));
+                        let word = match op {
+                            BinaryOp::Plus => self.builder.add(a, b),
+                            BinaryOp::Minus => self.builder.sub(a, b),
+                            _ => self.builder.mul(a, b),
+                        };
+                        Ok((Value::Word(word), data_type))
+                    }
+                    comparison => {
+                        let comparison = match comparison {
+                            BinaryOp::Eq => Comparison::Equal,
+                            BinaryOp::NotEq => Comparison::NotEqual,
+                            BinaryOp::Lt => Comparison::Less,
+                            BinaryOp::LtEq => Comparison::LessEqual,
+                            BinaryOp::Gt => Comparison::Greater,
+                            _ => Comparison::GreaterEqual,
+                        };
+                        let (left, right) = self.align_scales(left, &left_type, right, &right_type)?;
+                        let bit =
+                            self.lower_comparison(left, &left_type, comparison, right, &right_type, relation.rows)?;
+                        Ok((Value::Bits(vec![bit]), ColumnType::Boolean))
+                    }
+                }
+            }
+        }
+    }
+
+    /// Lower both operands of a binary operator; a string literal takes the
+    /// charset of the other operand so both use the same encoding
+    fn lower_operands(
+        &mut self,
+        left: &ScalarExpr,
+        right: &ScalarExpr,
+        relation: &Relation,
+    ) -> Result<(Value, ColumnType, Value, ColumnType), LowerError> {
+        let (left, left_type, right, right_type) = match (left, right) {
+            (ScalarExpr::Literal(literal), other) => {
+                let (right, right_type) = self.lower_expr(other, relation)?;
+                let (left, left_type) = self.lower_literal(literal, Some(&right_type), relation.rows)?;
+                (left, left_type, right, right_type)
+            }
+            (other, right) => {
+                let (left, left_type) = self.lower_expr(other, relation)?;
+                let (right, right_type) = match right {
+                    ScalarExpr::Literal(literal) => self.lower_literal(literal, Some(&left_type), relation.rows)?,
+                    _ => self.lower_expr(right, relation)?,
+                };
+                (left, left_type, right, right_type)
+            }
+        };
+        Ok((left, left_type, right, right_type))
+    }
+
+    /// Public constant in every row, encoded like the column it is compared with
+    fn lower_literal(
+        &mut self,
+        literal: &Literal,
+        other: Option<&ColumnType>,
+        rows: usize,
+    ) -> Result<(Value, ColumnType), LowerError> {
+        match literal {
+            Literal::Integer(value) => {
+                let width = (64 - value.leading_zeros() as usize).max(1);
+                Ok((Value::Bits(self.builder.const_bits(*value, width, rows)), ColumnType::UnsignedInt))
+            }
+            Literal::Boolean(value) => Ok((Value::Bits(vec![self.builder.const_bit(*value, rows)]), ColumnType::Boolean)),
+            Literal::String(value) => {
+                let charset = match other {
+                    Some(ColumnType::String { charset, .. }) => *charset,
+                    _ => Charset::Ascii,
+                };
+                let bits = encode_string(value, charset)
+                    .into_iter()
+                    .map(|bit| self.builder.const_bit(bit, rows))
+                    .collect();
+                let data_type = ColumnType::String {
+                    max_chars: value.chars().count(),
+                    charset,
+                };
+                Ok((Value::Bits(bits), data_type))
+            }
+            Literal::Decimal(value) => match literal.fixed_point() {
+                Some((number, scale)) => {
+                    let width = (64 - number.leading_zeros() as usize).max(1);
+                    Ok((Value::Bits(self.builder.const_bits(number, width, rows)), ColumnType::Decimal { scale }))
+                }
+                None => unsupported(format!("decimal literal {} is", value)),
+            },
+        }
+    }
+
+    /// Bring two values to the same number of decimals, so fixed-point values
+    /// add, subtract and compare as integers; other values are unchanged
+    fn align_scales(
+        &mut self,
+        left: Value,
+        left_type: &ColumnType,
+        right: Value,
+        right_type: &ColumnType,
+    ) -> Result<(Value, Value), LowerError> {
+        let scale = left_type.scale().max(right_type.scale());
+        Ok((self.rescale(left, left_type, scale)?, self.rescale(right, right_type, scale)?))
+    }
+
+    /// Multiply a value by the public factor that takes it to `scale` decimals
+    fn rescale(&mut self, value: Value, data_type: &ColumnType, scale: u32) -> Result<Value, LowerError> {
+        let Some(factor) = 10u64.checked_pow(scale - data_type.scale()) else {
+            return unsupported(format!("scaling {} values to {} decimals is", data_type, scale));
+        };
+        if factor == 1 {
+            return Ok(value);
+        }
+        let word = self.word_of(value);
+        Ok(Value::Word(self.builder.mul_const(word, factor)))
+    }
+
+    fn lower_comparison(
+        &mut self,
+        left: Value,
+        left_type: &ColumnType,
+        op: Comparison,
+        right: Value,
+        right_type: &ColumnType,
+        rows: usize,
+    ) -> Result<Wire, LowerError> {
+        let (a, b) = (self.bits_of(left), self.bits_of(right));
+        match (left_type, right_type) {
+            (ColumnType::Float, _) | (_, ColumnType::Float) => unsupported("comparing Float values is"),
+            (ColumnType::String { charset: a_charset, .. }, ColumnType::String { charset: b_charset, .. }) => {
+                if a_charset != b_charset {
+                    return unsupported("comparing Ascii and Utf8 strings is");
+                }
+                // Pad with NUL characters, then order the first character
+                // highest. An empty literal has no bits and is all padding; at
+                // least one padding bit keeps the row count when both are.
+                let width = a.len().max(b.len()).max(1);
+                let zero = self.builder.const_bit(false, rows);
+                let significance = |mut bits: Vec<Wire>| {
+                    bits.resize(width, zero);
+                    by_significance(bits, *a_charset)
+                };
+                Ok(self.builder.compare(&significance(a), op, &significance(b)))
+            }
+            _ => Ok(self.builder.compare(&a, op, &b)),
+        }
+    }
+
+    /// Combined validity of two row sets with the same rows
+    fn and_valid(&mut self, a: Option<Wire>, b: Option<Wire>) -> Option<Wire> {
+        match (a, b) {
+            (Some(a), Some(b)) => Some(self.builder.and(a, b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    /// Zero a value in the rows whose validity bit is not set
+    fn mask(&mut self, value: Value, valid: Wire) -> Value {
+        match value {
+            Value::Bits(bits) => {
+                Value::Bits(bits.into_iter().map(|bit| self.builder.and(bit, valid)).collect())
+            }
+            Value::Word(word) => {
+                let weight = self.builder.b2a(&[valid]);
+                Value::Word(self.builder.mul(word, weight))
+            }
+        }
+    }
+
+    fn single_bit(&mut self, value: Value) -> Result<Wire, LowerError> {
+        match self.bits_of(value).as_slice() {
+            [bit] => Ok(*bit),
+            bits => Err(LowerError::Invalid(format!("expected a Boolean value, got {} bits", bits.len()))),
+        }
+    }
+
+    fn bits_of(&mut self, value: Value) -> Vec<Wire> {
+        match value {
+            Value::Bits(bits) => bits,
+            Value::Word(word) => self.builder.a2b(word, 64),
+        }
+    }
+
+    fn word_of(&mut self, value: Value) -> Wire {
+        match value {
+            Value::Bits(bits) => self.builder.b2a(&bits),
+            Value::Word(word) => word,
+        }
+    }
+}
+
+/// Reorder the bits of a string so that its first character is the most
+/// significant, as for an unsigned number. Character i occupies bits
+/// 7i..7i+7 (8 for Utf8).
+fn by_significance(bits: Vec<Wire>, charset: Charset) -> Vec<Wire> {
+    let bits_per_char = match charset {
+        Charset::Ascii => 7,
+        Charset::Utf8 => 8,
+    };
+    bits.chunks(bits_per_char).rev().flatten().copied().collect()
+}
+
+/// Bits of a string in the data owner's encoding, without padding
+fn encode_string(value: &str, charset: Charset) -> Vec<bool> {
+    let (bits_per_char, mask) = match charset {
+        Charset::Ascii => (7, 0x7F),
+        Charset::Utf8 => (8, 0xFF),
+    };
+    value
+        .chars()
+        .flat_map(|c| {
+            let code = c as u32 & mask;
+            (0..bits_per_char).map(move |j| (code >> j) & 1 == 1)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binder::bind;
+    use crate::catalog::{ColumnInfo, OwnerInfo, TableInfo};
+    use crate::planner::plan_sql;
+    use helpers::circuit::plain::evaluate;
+
+    fn catalog() -> Catalog {
+        let column = |name: &str, type_hint: ColumnType, sharing: Sharing| ColumnInfo {
+            name: name.to_string(),
+            type_hint,
+            sharing,
+            unique: false,
+            decimals: None,
+        };
+        let mut catalog = Catalog::new();
+        let partsupp = TableInfo {
+            table_name: "partsupp".to_string(),
+            table_id: 4,
+            row_count: 4,
+            schema_hash: 0,
+            data_owner: OwnerInfo::default(),
+            columns: vec![
+                column("part_key", ColumnType::UnsignedInt, Sharing::Boolean),
+                column("available_qty", ColumnType::UnsignedInt, Sharing::Arithmetic),
+                column(
+                    "code",
+                    ColumnType::String {
+                        max_chars: 3,
+                        charset: Charset::Ascii,
+                    },
+                    Sharing::Boolean,
+                ),
+                ColumnInfo {
+                    decimals: Some(2),
+                    ..column("cost", ColumnType::Float, Sharing::Boolean)
+                },
+            ],
+        };
+        catalog.insert(partsupp, None).unwrap();
+        let part = TableInfo {
+            table_name: "part".to_string(),
+            table_id: 1,
+            row_count: 3,
+            schema_hash: 0,
+            data_owner: OwnerInfo::default(),
+            columns: vec![
+                ColumnInfo {
+                    unique: true,
+                    ..column("part_key", ColumnType::UnsignedInt, Sharing::Boolean)
+                },
+                column("size", ColumnType::UnsignedInt, Sharing::Arithmetic),
+            ],
+        };
+        catalog.insert(part, None).unwrap();
+        catalog
+    }
+
+    /// Plaintext columns of partsupp; strings in the stored encoding
+    pub(super) fn table_values() -> Vec<Vec<u64>> {
+        let encode = |s: &str| {
+            encode_string(s, Charset::Ascii)
+                .iter()
+                .enumerate()
+                .fold(0u64, |acc, (j, bit)| acc | ((*bit as u64) << j))
+        };
+        vec![
+            vec![1, 2, 3, 4],
+            vec![3325, 8076, 5000, 9999],
+            vec![encode("AB"), encode("ABC"), encode("B"), encode("AB")],
+            vec![77164, 99349, 33709, 35784],
+        ]
+    }
+
+    pub(super) fn run(sql: &str) -> (Circuit, Vec<Vec<u64>>) {
+        let catalog = catalog();
+        let query = bind(&plan_sql(sql).unwrap(), &catalog).unwrap();
+        evaluate_json(lower(&query, &catalog).unwrap())
+    }
+
+    /// Evaluate a circuit on the test tables after a round trip through JSON
+    fn evaluate_json(circuit: Circuit) -> (Circuit, Vec<Vec<u64>>) {
+        let circuit = Circuit::from_json(&circuit.to_json().unwrap()).unwrap();
+        let part = [vec![4, 1, 2], vec![40, 10, 20]];
+        let inputs: Vec<Vec<u64>> = circuit
+            .inputs
+            .iter()
+            .map(|c| if c.table == "part" { part[c.column].clone() } else { table_values()[c.column].clone() })
+            .collect();
+        let outputs = evaluate(&circuit, &inputs).unwrap();
+        (circuit, outputs)
+    }
+
+    /// Rows whose validity bit (the last output) is set
+    pub(super) fn valid_rows(outputs: &[Vec<u64>]) -> Vec<Vec<u64>> {
+        let (valid, columns) = outputs.split_last().unwrap();
+        (0..valid.len())
+            .filter(|r| valid[*r] == 1)
+            .map(|r| columns.iter().map(|column| column[r]).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_lower_projection() {
+        let (circuit, outputs) = run(
+            "SELECT part_key, available_qty > 5000 AS large, part_key * available_qty + 1, \
+             NOT (code = 'AB') OR part_key >= 4 FROM partsupp",
+        );
+        let names: Vec<&str> = circuit.outputs.iter().map(|o| o.name.as_str()).collect();
+        assert_eq!(
+            names,
+            ["part_key", "large", "part_key * available_qty + 1", "NOT (code = 'AB') OR part_key >= 4"]
+        );
+        assert_eq!(
+            outputs,
+            vec![vec![1, 2, 3, 4], vec![0, 1, 0, 1], vec![3326, 16153, 15001, 39997], vec![0, 1, 1, 1]]
+        );
+    }
+
+    #[test]
+    fn test_fixed_point_arithmetic_and_comparisons() {
+        // cost has 2 decimals; operands with fewer are scaled up to match
+        let (_, outputs) = run("SELECT cost > 500.5, cost <= 357.84, cost + 1, cost - 0.005, cost * 2.5 FROM partsupp");
+        assert_eq!(
+            outputs,
+            vec![
+                vec![1, 1, 0, 0],
+                vec![0, 0, 1, 1],
+                vec![77264, 99449, 33809, 35884],
+                vec![771635, 993485, 337085, 357835],
+                vec![1929100, 2483725, 842725, 894600],
+            ]
+        );
+
+        let (_, outputs) = run("SELECT SUM(cost), AVG(cost), MAX(cost) FROM partsupp WHERE cost < 800");
+        assert_eq!(outputs, vec![vec![146657], vec![146657], vec![3], vec![77164]]);
+
+        let (_, outputs) = run("SELECT part_key FROM partsupp ORDER BY cost DESC");
+        assert_eq!(outputs, vec![vec![2, 1, 4, 3]]);
+    }
+
+    #[test]
+    fn test_empty_string_comparisons() {
+        let (_, outputs) = run("SELECT '' = code, code > '', '' < code, '' = '' FROM partsupp");
+        assert_eq!(outputs, vec![vec![0, 0, 0, 0], vec![1, 1, 1, 1], vec![1, 1, 1, 1], vec![1, 1, 1, 1]]);
+        let (_, outputs) = run("SELECT part_key FROM partsupp WHERE '' = code");
+        assert_eq!(outputs[1], vec![0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_string_ordering_and_unused_columns() {
+        let (circuit, outputs) = run("SELECT code < 'AC', code >= 'AB' FROM partsupp");
+        assert_eq!(outputs, vec![vec![1, 1, 0, 1], vec![1, 1, 1, 1]]);
+        assert_eq!(circuit.inputs.len(), 1);
+        assert_eq!(circuit.inputs[0].width, 21);
+    }
+
+    #[test]
+    fn test_filter_masks_invalid_rows() {
+        let (circuit, outputs) = run(
+            "SELECT part_key, available_qty, code FROM partsupp \
+             WHERE available_qty > 4000 AND code = 'AB' OR part_key = 3",
+        );
+        assert_eq!(circuit.outputs.last().unwrap().name, VALID_COLUMN);
+        let ab = table_values()[2][0];
+        assert_eq!(
+            outputs,
+            vec![vec![0, 0, 3, 4], vec![0, 0, 5000, 9999], vec![0, 0, table_values()[2][2], ab], vec![0, 0, 1, 1]]
+        );
+
+        // A constant predicate folds to constant validity bits
+        let (circuit, outputs) = run("SELECT part_key FROM partsupp WHERE 1 < 2");
+        assert_eq!(circuit.outputs.len(), 2);
+        assert_eq!(outputs, vec![vec![1, 2, 3, 4], vec![1, 1, 1, 1]]);
+    }
+
+    #[test]
+    fn test_aggregates_skip_invalid_rows() {
+        let (circuit, outputs) = run(
+            "SELECT COUNT(*), SUM(available_qty), AVG(part_key), MIN(available_qty), MAX(part_key) \
+             FROM partsupp WHERE code = 'AB' OR part_key = 2",
+        );
+        let names: Vec<&str> = circuit.outputs.iter().map(|o| o.name.as_str()).collect();
+        assert_eq!(
+            names,
+            ["COUNT(*)", "SUM(available_qty)", "AVG(part_key)", "AVG(part_key)__count", "MIN(available_qty)", "MAX(part_key)"]
+        );
+        assert_eq!(outputs, vec![vec![3], vec![3325 + 8076 + 9999], vec![7], vec![3], vec![3325], vec![4]]);
+
+        // Without a filter the count is public; no valid row gives 0
+        let (circuit, outputs) = run("SELECT COUNT(*), MIN(part_key) FROM partsupp");
+        assert_eq!(outputs, vec![vec![4], vec![1]]);
+        assert!(matches!(circuit.gates[0], helpers::circuit::Gate::ConstWord { value: 4, rows: 1 }));
+        let (_, outputs) = run("SELECT MAX(available_qty), SUM(part_key) + 1 FROM partsupp WHERE part_key > 10");
+        assert_eq!(outputs, vec![vec![0], vec![1]]);
+    }
+
+    #[test]
+    fn test_distinct_with_revealed_cardinality() {
+        let catalog = catalog();
+        let sql = "SELECT DISTINCT code AS c, part_key > 1 FROM partsupp WHERE part_key <> 2 ORDER BY c";
+        let query = bind(&plan_sql(sql).unwrap(), &catalog).unwrap();
+        let count = lower_distinct_rows(&query, &catalog).unwrap().unwrap();
+        let (count, outputs) = evaluate_json(count);
+        assert_eq!(count.outputs[0].name, DISTINCT_ROWS_COLUMN);
+        assert_eq!(outputs, vec![vec![3]]);
+
+        // Only the 3 distinct rows are sorted and returned, without validity
+        let options = LowerOptions { distinct_rows: Some(3) };
+        let (circuit, outputs) = evaluate_json(lower_with(&query, &catalog, options).unwrap());
+        assert!(circuit.outputs.iter().all(|o| o.name != VALID_COLUMN));
+        let code = |r: usize| table_values()[2][r];
+        assert_eq!(outputs, vec![vec![code(0), code(0), code(2)], vec![0, 1, 1]]);
+
+        let options = LowerOptions { distinct_rows: Some(5) };
+        assert!(matches!(lower_with(&query, &catalog, options), Err(LowerError::Invalid(_))));
+        let query = bind(&plan_sql("SELECT code FROM partsupp").unwrap(), &catalog).unwrap();
+        assert!(lower_distinct_rows(&query, &catalog).unwrap().is_none());
+    }
+}
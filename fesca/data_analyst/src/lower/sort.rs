@@ -0,0 +1,352 @@
+// Sorting, DISTINCT and LIMIT
+// ===========================
+// ORDER BY sorts the rows with a bitonic sorting network (see
+// `CircuitBuilder::sort_rows`) keyed by the sort keys, most significant first,
+// below a top bit that puts invalid rows last. Descending keys are inverted,
+// strings are ordered as in comparisons, and every column the query still
+// uses is carried along as payload, so which rows moved stays secret.
+//
+// DISTINCT sorts the rows by all of their columns, invalidates every row equal
+// to the row above it and sorts the valid rows to the top, so neither the
+// number of distinct rows nor how often each occurs is revealed. The analyst
+// can choose to reveal that number instead (`LowerOptions::distinct_rows`,
+// counted by a circuit of its own) to have the result cut down to it.
+//
+// LIMIT keeps the first rows. Without validity bits that is a public slice.
+// Otherwise the valid rows are first sorted to the top on their negated
+// validity bit (`compact`), unless ORDER BY has already put invalid rows last,
+// so the result keeps up to that many valid rows and hides how many there are.
+
+use helpers::circuit::Wire;
+
+use super::{by_significance, unsupported, LowerError, Lowering, Relation, RelationColumn, Source, Value};
+use crate::catalog::ColumnType;
+use crate::plan::{ColumnRef, LogicalPlan, SortKey};
+
+impl Lowering<'_> {
+    /// Sort the rows of `input` by `keys`, keeping only the columns in `used`
+    /// (all if None)
+    pub(super) fn lower_sort(
+        &mut self,
+        input: &LogicalPlan,
+        keys: &[SortKey],
+        used: Option<&[&ColumnRef]>,
+    ) -> Result<Relation, LowerError> {
+        let input = self.lower_plan(input)?;
+        let mut columns = input.columns.clone();
+        if let Some(used) = used {
+            let positions = used.iter().map(|c| input.position(c)).collect::<Result<Vec<_>, _>>()?;
+            columns = (0..columns.len())
+                .filter(|i| positions.contains(i))
+                .map(|i| columns[i].clone())
+                .collect();
+        }
+        if input.rows <= 1 {
+            return Ok(Relation { columns, ..input });
+        }
+
+        // Sort key LSB first: the last key is the least significant
+        let mut key = Vec::new();
+        for sort_key in keys.iter().rev() {
+            let (value, data_type) = self.lower_expr(&sort_key.expr, &input)?;
+            let bits = self.order_bits(value, &data_type)?;
+            if sort_key.ascending {
+                key.extend(bits);
+            } else {
+                key.extend(bits.into_iter().map(|bit| self.builder.not(bit)));
+            }
+        }
+        if let Some(valid) = input.valid {
+            key.push(self.builder.not(valid));
+        }
+
+        let (mut payload, layout) = self.flatten(&columns)?;
+        payload.extend(input.valid);
+        let (_, sorted) = self.builder.sort_rows(&key, &payload);
+
+        let mut sorted = sorted.into_iter();
+        unflatten(&mut columns, layout, &mut sorted);
+        Ok(Relation {
+            rows: input.rows,
+            columns,
+            valid: input.valid.and(sorted.next()),
+        })
+    }
+
+    /// DISTINCT: sort the rows by all of their columns, so equal rows end up
+    /// next to each other, and invalidate every row equal to the row above.
+    /// The valid rows are then sorted to the top; with
+    /// `LowerOptions::distinct_rows` the rest is cut off.
+    pub(super) fn lower_distinct(&mut self, input: Relation) -> Result<Relation, LowerError> {
+        let rows = input.rows;
+        if rows <= 1 {
+            return Ok(input);
+        }
+
+        // The binder rejects averages: equal averages can have different sums
+        // and counts
+        let mut values = Vec::with_capacity(input.columns.len());
+        for column in &input.columns {
+            if let Source::Average { .. } = column.source {
+                return unsupported("DISTINCT over averages is");
+            }
+            values.push(self.load(column)?);
+        }
+
+        // Sort key LSB first, the first column most significant and invalid
+        // rows last; words are compared by their bits and sorted along as
+        // payload
+        let mut key = Vec::new();
+        let mut words = Vec::new();
+        for value in values.iter().rev() {
+            match value {
+                Value::Bits(bits) => key.extend(bits),
+                Value::Word(word) => {
+                    key.extend(self.builder.a2b(*word, 64));
+                    words.push(*word);
+                }
+            }
+        }
+        let width = key.len();
+        if let Some(valid) = input.valid {
+            key.push(self.builder.not(valid));
+        }
+        let (sorted_key, sorted_words) = self.builder.sort_rows(&key, &words);
+
+        // A valid row equal to the row above is a duplicate; as invalid rows
+        // come last, the row above a valid row is valid as well
+        let duplicate = self.builder.same_as_above(&sorted_key[..width]);
+        let first = self.builder.not(duplicate);
+        let sorted_valid = input.valid.map(|_| self.builder.not(sorted_key[width]));
+        let valid = self.and_valid(sorted_valid, Some(first));
+
+        let (mut offset, mut words) = (0, sorted_words.into_iter());
+        let mut sorted = Vec::with_capacity(values.len());
+        for value in values.iter().rev() {
+            sorted.push(match value {
+                Value::Bits(bits) => {
+                    offset += bits.len();
+                    Value::Bits(sorted_key[offset - bits.len()..offset].to_vec())
+                }
+                Value::Word(_) => {
+                    offset += 64;
+                    Value::Word(words.next().expect("sorted payload has a word per word"))
+                }
+            });
+        }
+        sorted.reverse();
+
+        let columns = input
+            .columns
+            .iter()
+            .zip(sorted)
+            .map(|(column, value)| RelationColumn {
+                source: Source::Computed(value),
+                ..column.clone()
+            })
+            .collect();
+        let distinct = Relation { rows, columns, valid };
+        match self.options.distinct_rows {
+            Some(distinct_rows) if distinct_rows > rows => Err(LowerError::Invalid(format!(
+                "DISTINCT cannot produce {} of {} rows",
+                distinct_rows, rows
+            ))),
+            // With no distinct rows the masked result is just as small
+            Some(0) => Ok(distinct),
+            Some(distinct_rows) => {
+                let compacted = self.compact(distinct, distinct_rows)?;
+                Ok(Relation { valid: None, ..compacted })
+            }
+            // Where the valid rows are would reveal how often each row occurs
+            None => self.compact(distinct, rows),
+        }
+    }
+
+    /// Keep the first `rows` rows of a relation
+    pub(super) fn slice_rows(&mut self, relation: Relation, rows: usize) -> Result<Relation, LowerError> {
+        if rows >= relation.rows {
+            return Ok(relation);
+        }
+        let mut columns = relation.columns;
+        let (mut wires, layout) = self.flatten(&columns)?;
+        wires.extend(relation.valid);
+        let sliced: Vec<Wire> = wires.into_iter().map(|wire| self.builder.slice(wire, 0, rows)).collect();
+
+        let mut sliced = sliced.into_iter();
+        unflatten(&mut columns, layout, &mut sliced);
+        Ok(Relation {
+            rows,
+            columns,
+            valid: relation.valid.and(sliced.next()),
+        })
+    }
+
+    /// Sort the valid rows of a relation to the top, in no particular order,
+    /// and keep its first `rows` rows. Which rows were valid, and so how many
+    /// there are, stays secret: the validity is sorted along.
+    pub(super) fn compact(&mut self, relation: Relation, rows: usize) -> Result<Relation, LowerError> {
+        let Some(valid) = relation.valid else {
+            return self.slice_rows(relation, rows);
+        };
+        let mut columns = relation.columns;
+        let (payload, layout) = self.flatten(&columns)?;
+        let flag = self.builder.not(valid);
+        let (sorted_flag, sorted) = self.builder.sort_rows(&[flag], &payload);
+
+        unflatten(&mut columns, layout, &mut sorted.into_iter());
+        let compacted = Relation {
+            rows: relation.rows,
+            columns,
+            valid: Some(self.builder.not(sorted_flag[0])),
+        };
+        self.slice_rows(compacted, rows)
+    }
+
+    /// Wires of all columns one after the other, averages as their sum and
+    /// count, and the layout `unflatten` rebuilds the columns with
+    fn flatten(&mut self, columns: &[RelationColumn]) -> Result<(Vec<Wire>, Vec<Option<Value>>), LowerError> {
+        let mut wires = Vec::new();
+        let mut layout = Vec::with_capacity(columns.len());
+        for column in columns {
+            let value = match column.source {
+                Source::Average { sum, count } => {
+                    wires.extend([sum, count]);
+                    None
+                }
+                _ => {
+                    let value = self.load(column)?;
+                    wires.extend(value_wires(&value));
+                    Some(value)
+                }
+            };
+            layout.push(value);
+        }
+        Ok((wires, layout))
+    }
+
+    /// Bits of a value, LSB first, in the order ORDER BY sorts it
+    fn order_bits(&mut self, value: Value, data_type: &ColumnType) -> Result<Vec<Wire>, LowerError> {
+        let bits = self.bits_of(value);
+        match data_type {
+            ColumnType::Float => unsupported("ordering by Float values is"),
+            ColumnType::String { charset, .. } => Ok(by_significance(bits, *charset)),
+            _ => Ok(bits),
+        }
+    }
+}
+
+/// Whether a plan's rows come out of ORDER BY, which sorts invalid rows last
+pub(super) fn is_sorted(plan: &LogicalPlan) -> bool {
+    match plan {
+        LogicalPlan::Sort { .. } => true,
+        LogicalPlan::Project { input, .. } => is_sorted(input),
+        _ => false,
+    }
+}
+
+/// Wires of a value, in payload order
+fn value_wires(value: &Value) -> Vec<Wire> {
+    match value {
+        Value::Bits(bits) => bits.clone(),
+        Value::Word(word) => vec![*word],
+    }
+}
+
+/// Rebuild columns from wires in the layout `Lowering::flatten` returned
+fn unflatten(columns: &mut [RelationColumn], layout: Vec<Option<Value>>, wires: &mut impl Iterator<Item = Wire>) {
+    for (column, value) in columns.iter_mut().zip(layout) {
+        let mut next = || wires.next().expect("wires follow the flattened layout");
+        column.source = match value {
+            None => Source::Average { sum: next(), count: next() },
+            Some(Value::Bits(bits)) => Source::Computed(Value::Bits(bits.iter().map(|_| next()).collect())),
+            Some(Value::Word(_)) => Source::Computed(Value::Word(next())),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lower::tests::{run, table_values, valid_rows};
+    use crate::lower::VALID_COLUMN;
+
+    #[test]
+    fn test_sort_orders_rows() {
+        // "AB" sorts before "ABC"; ties on code are broken by the quantity
+        let (_, outputs) = run("SELECT part_key FROM partsupp ORDER BY code DESC, available_qty");
+        assert_eq!(outputs, vec![vec![3, 2, 1, 4]]);
+
+        // Invalid rows sort last, and columns the query no longer uses are dropped
+        let (circuit, outputs) = run(
+            "SELECT part_key, code FROM partsupp WHERE part_key <> 2 ORDER BY available_qty DESC",
+        );
+        let codes = table_values()[2].clone();
+        assert_eq!(
+            outputs,
+            vec![vec![4, 3, 1, 0], vec![codes[3], codes[2], codes[0], 0], vec![1, 1, 1, 0]]
+        );
+        assert_eq!(circuit.inputs.len(), 3);
+
+        let (_, outputs) = run("SELECT COUNT(*) FROM partsupp ORDER BY COUNT(*)");
+        assert_eq!(outputs, vec![vec![4]]);
+    }
+
+    #[test]
+    fn test_limit_keeps_first_valid_rows() {
+        // Without validity LIMIT is a public slice
+        let (circuit, outputs) = run("SELECT part_key FROM partsupp LIMIT 2");
+        assert_eq!(outputs, vec![vec![1, 2]]);
+        assert!(circuit.gates.iter().all(|gate| !gate.is_interactive()));
+        let (_, outputs) = run("SELECT part_key FROM partsupp ORDER BY available_qty DESC LIMIT 3");
+        assert_eq!(outputs, vec![vec![4, 2, 3]]);
+        let (_, outputs) = run("SELECT part_key FROM partsupp LIMIT 10");
+        assert_eq!(outputs, vec![vec![1, 2, 3, 4]]);
+
+        // Filtered rows are sorted below the valid ones first
+        let (_, outputs) = run("SELECT part_key FROM partsupp WHERE part_key > 3 LIMIT 2");
+        assert_eq!(outputs, vec![vec![4, 0], vec![1, 0]]);
+        let (_, outputs) = run("SELECT part_key FROM partsupp WHERE part_key <> 2 LIMIT 2");
+        assert_eq!(outputs[1], vec![1, 1]);
+        assert!(outputs[0].iter().all(|key| [1, 3, 4].contains(key)));
+        let (_, outputs) = run("SELECT part_key FROM partsupp WHERE part_key <> 2 ORDER BY available_qty DESC LIMIT 2");
+        assert_eq!(outputs, vec![vec![4, 3], vec![1, 1]]);
+    }
+
+    #[test]
+    fn test_distinct_invalidates_duplicates() {
+        let code = |r: usize| table_values()[2][r];
+        let (circuit, outputs) = run("SELECT DISTINCT code FROM partsupp");
+        assert_eq!(circuit.outputs.last().unwrap().name, VALID_COLUMN);
+        assert_eq!(outputs[0].len(), 4);
+        let mut rows = valid_rows(&outputs);
+        rows.sort();
+        let mut expected = vec![vec![code(0)], vec![code(1)], vec![code(2)]];
+        expected.sort();
+        assert_eq!(rows, expected);
+
+        // Words and filtered rows
+        let (_, outputs) = run("SELECT DISTINCT available_qty > 5000, part_key * 0 FROM partsupp");
+        assert_eq!(valid_rows(&outputs), vec![vec![0, 0], vec![1, 0]]);
+        let (_, outputs) = run("SELECT DISTINCT part_key > 1 FROM partsupp WHERE code <> 'ABC'");
+        assert_eq!(valid_rows(&outputs), vec![vec![0], vec![1]]);
+
+        // The distinct rows come first, however many duplicates they had
+        let (_, outputs) = run("SELECT DISTINCT part_key > 3 FROM partsupp");
+        assert_eq!(outputs[1], vec![1, 1, 0, 0]);
+        let (_, outputs) = run("SELECT DISTINCT code FROM partsupp WHERE part_key > 1");
+        assert_eq!(outputs[1], vec![1, 1, 1, 0]);
+
+        // ORDER BY sorts the distinct rows
+        let (_, outputs) = run("SELECT DISTINCT code AS c FROM partsupp ORDER BY c DESC");
+        assert_eq!(valid_rows(&outputs), vec![vec![code(2)], vec![code(1)], vec![code(0)]]);
+    }
+
+    #[test]
+    fn test_equal_averages_differ_in_sum_and_count() {
+        // Both groups average 2, as (2, 1) and (4, 2): sorting by sum and count
+        // would not bring them together, so the binder rejects DISTINCT over them
+        let (_, outputs) = run("SELECT AVG(part_key) FROM partsupp WHERE part_key < 4 GROUP BY part_key = 2");
+        let mut rows = valid_rows(&outputs);
+        rows.sort();
+        assert_eq!(rows, vec![vec![2, 1], vec![4, 2]]);
+    }
+}
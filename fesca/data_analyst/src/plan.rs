@@ -0,0 +1,402 @@
+// Logical Plan
+// ============
+// Relational operator tree built from an analyst's SQL query (see `planner`).
+// The plan only says *what* to compute; how each operator is evaluated on the
+// secret-shared tables is decided when the plan is lowered for the computing
+// nodes.
+
+use std::fmt;
+
+/// Relational operator tree
+#[derive(Debug, Clone, PartialEq)]
+pub enum LogicalPlan {
+    /// Read all rows of a stored table
+    Scan { table: String, alias: Option<String> },
+    /// Keep the rows satisfying `predicate`
+    Filter { input: Box<LogicalPlan>, predicate: ScalarExpr },
+    /// Compute the output columns
+    Project { input: Box<LogicalPlan>, items: Vec<ProjectItem> },
+    /// Group rows by `group_by` (one group if empty) and aggregate each group.
+    /// Outputs the grouping columns followed by the aggregates.
+    Aggregate {
+        input: Box<LogicalPlan>,
+        group_by: Vec<ScalarExpr>,
+        aggregates: Vec<AggregateExpr>,
+    },
+    /// Inner join of two inputs on `on`
+    Join {
+        left: Box<LogicalPlan>,
+        right: Box<LogicalPlan>,
+        on: ScalarExpr,
+    },
+    /// Keep one row of every set of equal rows
+    Distinct { input: Box<LogicalPlan> },
+    /// Order rows by `keys`, most significant key first
+    Sort { input: Box<LogicalPlan>, keys: Vec<SortKey> },
+    /// Keep the first `limit` rows
+    Limit { input: Box<LogicalPlan>, limit: u64 },
+}
+
+/// Output column of a projection
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProjectItem {
+    /// `*`: all columns of the input
+    Wildcard,
+    /// An expression, optionally renamed with `AS`
+    Expr { expr: ScalarExpr, alias: Option<String> },
+}
+
+/// Reference to a column, optionally qualified with a table name or alias
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ColumnRef {
+    pub table: Option<String>,
+    pub name: String,
+}
+
+/// Constant appearing in a query
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal {
+    Integer(u64),
+    /// Number with a fractional part, kept as written so it can be scaled exactly
+    Decimal(String),
+    String(String),
+    Boolean(bool),
+}
+
+/// Binary operators on scalar values
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Eq,
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+    And,
+    Or,
+    Plus,
+    Minus,
+    Multiply,
+}
+
+/// Row-level expression
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScalarExpr {
+    Column(ColumnRef),
+    Literal(Literal),
+    Binary {
+        op: BinaryOp,
+        left: Box<ScalarExpr>,
+        right: Box<ScalarExpr>,
+    },
+    Not(Box<ScalarExpr>),
+    /// `expr LIKE pattern`, where `%` matches any run of characters and `_`
+    /// any single character
+    Like { expr: Box<ScalarExpr>, pattern: String },
+}
+
+/// Aggregate functions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AggregateFunction {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+/// Aggregate over the rows of a group; `arg` is `None` for `COUNT(*)`
+#[derive(Debug, Clone, PartialEq)]
+pub struct AggregateExpr {
+    pub func: AggregateFunction,
+    pub arg: Option<ScalarExpr>,
+}
+
+/// Sort key with direction
+#[derive(Debug, Clone, PartialEq)]
+pub struct SortKey {
+    pub expr: ScalarExpr,
+    pub ascending: bool,
+}
+
+impl ColumnRef {
+    pub fn new(table: Option<&str>, name: &str) -> Self {
+        Self {
+            table: table.map(str::to_string),
+            name: name.to_string(),
+        }
+    }
+}
+
+impl Literal {
+    /// A decimal literal as an integer and its number of decimals, e.g.
+    /// `771.64` as (77164, 2); None if it is not written as digits with a point
+    pub fn fixed_point(&self) -> Option<(u64, u32)> {
+        let Literal::Decimal(text) = self else {
+            return None;
+        };
+        let (whole, fraction) = text.split_once('.')?;
+        let digits = format!("{}{}", whole, fraction);
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        Some((digits.parse().ok()?, fraction.len() as u32))
+    }
+}
+
+impl ScalarExpr {
+    /// Unqualified column reference
+    pub fn column(name: &str) -> Self {
+        ScalarExpr::Column(ColumnRef::new(None, name))
+    }
+
+    /// Binary expression
+    pub fn binary(op: BinaryOp, left: ScalarExpr, right: ScalarExpr) -> Self {
+        ScalarExpr::Binary {
+            op,
+            left: Box::new(left),
+            right: Box::new(right),
+        }
+    }
+
+    /// All column references in the expression, left to right
+    pub fn columns(&self) -> Vec<&ColumnRef> {
+        match self {
+            ScalarExpr::Column(column) => vec![column],
+            ScalarExpr::Literal(_) => Vec::new(),
+            ScalarExpr::Binary { left, right, .. } => {
+                let mut columns = left.columns();
+                columns.extend(right.columns());
+                columns
+            }
+            ScalarExpr::Not(expr) | ScalarExpr::Like { expr, .. } => expr.columns(),
+        }
+    }
+}
+
+impl AggregateExpr {
+    /// Name of the aggregate's output column, e.g. `AVG(salary)`
+    pub fn output_name(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl ProjectItem {
+    /// Name of the output column; `None` for the wildcard
+    pub fn output_name(&self) -> Option<String> {
+        match self {
+            ProjectItem::Wildcard => None,
+            ProjectItem::Expr { expr, alias } => Some(alias.clone().unwrap_or_else(|| expr.to_string())),
+        }
+    }
+}
+
+impl LogicalPlan {
+    /// Direct inputs of this operator
+    pub fn inputs(&self) -> Vec<&LogicalPlan> {
+        match self {
+            LogicalPlan::Scan { .. } => Vec::new(),
+            LogicalPlan::Filter { input, .. }
+            | LogicalPlan::Project { input, .. }
+            | LogicalPlan::Aggregate { input, .. }
+            | LogicalPlan::Distinct { input }
+            | LogicalPlan::Sort { input, .. }
+            | LogicalPlan::Limit { input, .. } => vec![input],
+            LogicalPlan::Join { left, right, .. } => vec![left, right],
+        }
+    }
+
+    /// Column references in the expressions of this operator and all of its
+    /// inputs
+    pub fn columns(&self) -> Vec<&ColumnRef> {
+        let mut columns: Vec<&ColumnRef> = match self {
+            LogicalPlan::Scan { .. } | LogicalPlan::Distinct { .. } | LogicalPlan::Limit { .. } => Vec::new(),
+            LogicalPlan::Filter { predicate, .. } => predicate.columns(),
+            LogicalPlan::Project { items, .. } => items
+                .iter()
+                .flat_map(|item| match item {
+                    ProjectItem::Wildcard => Vec::new(),
+                    ProjectItem::Expr { expr, .. } => expr.columns(),
+                })
+                .collect(),
+            LogicalPlan::Aggregate { group_by, aggregates, .. } => group_by
+                .iter()
+                .chain(aggregates.iter().filter_map(|a| a.arg.as_ref()))
+                .flat_map(ScalarExpr::columns)
+                .collect(),
+            LogicalPlan::Join { on, .. } => on.columns(),
+            LogicalPlan::Sort { keys, .. } => keys.iter().flat_map(|k| k.expr.columns()).collect(),
+        };
+        for input in self.inputs() {
+            columns.extend(input.columns());
+        }
+        columns
+    }
+
+    /// One-line description of this operator without its inputs
+    pub fn describe(&self) -> String {
+        match self {
+            LogicalPlan::Scan { table, alias: Some(alias) } => format!("Scan: {} AS {}", table, alias),
+            LogicalPlan::Scan { table, alias: None } => format!("Scan: {}", table),
+            LogicalPlan::Filter { predicate, .. } => format!("Filter: {}", predicate),
+            LogicalPlan::Project { items, .. } => {
+                let items: Vec<String> = items.iter().map(|i| i.to_string()).collect();
+                format!("Project: {}", items.join(", "))
+            }
+            LogicalPlan::Aggregate { group_by, aggregates, .. } => {
+                let aggregates: Vec<String> = aggregates.iter().map(|a| a.to_string()).collect();
+                if group_by.is_empty() {
+                    format!("Aggregate: {}", aggregates.join(", "))
+                } else {
+                    let keys: Vec<String> = group_by.iter().map(|k| k.to_string()).collect();
+                    format!("Aggregate: {} GROUP BY {}", aggregates.join(", "), keys.join(", "))
+                }
+            }
+            LogicalPlan::Join { on, .. } => format!("Join: ON {}", on),
+            LogicalPlan::Distinct { .. } => "Distinct".to_string(),
+            LogicalPlan::Sort { keys, .. } => {
+                let keys: Vec<String> = keys.iter().map(|k| k.to_string()).collect();
+                format!("Sort: {}", keys.join(", "))
+            }
+            LogicalPlan::Limit { limit, .. } => format!("Limit: {}", limit),
+        }
+    }
+
+    fn fmt_indented(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+        writeln!(f, "{:indent$}{}", "", self.describe(), indent = depth * 2)?;
+        for input in self.inputs() {
+            input.fmt_indented(f, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+/// Indented operator tree, root first
+impl fmt::Display for LogicalPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_indented(f, 0)
+    }
+}
+
+impl fmt::Display for ColumnRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.table {
+            Some(table) => write!(f, "{}.{}", table, self.name),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Literal::Integer(value) => write!(f, "{}", value),
+            Literal::Decimal(value) => write!(f, "{}", value),
+            Literal::String(value) => write!(f, "'{}'", value.replace('\'', "''")),
+            Literal::Boolean(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+impl BinaryOp {
+    /// Binding strength, higher binds tighter
+    fn precedence(self) -> u8 {
+        match self {
+            BinaryOp::Or => 1,
+            BinaryOp::And => 2,
+            BinaryOp::Eq | BinaryOp::NotEq | BinaryOp::Lt | BinaryOp::LtEq | BinaryOp::Gt | BinaryOp::GtEq => 3,
+            BinaryOp::Plus | BinaryOp::Minus => 4,
+            BinaryOp::Multiply => 5,
+        }
+    }
+}
+
+impl fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let symbol = match self {
+            BinaryOp::Eq => "=",
+            BinaryOp::NotEq => "<>",
+            BinaryOp::Lt => "<",
+            BinaryOp::LtEq => "<=",
+            BinaryOp::Gt => ">",
+            BinaryOp::GtEq => ">=",
+            BinaryOp::And => "AND",
+            BinaryOp::Or => "OR",
+            BinaryOp::Plus => "+",
+            BinaryOp::Minus => "-",
+            BinaryOp::Multiply => "*",
+        };
+        write!(f, "{}", symbol)
+    }
+}
+
+impl fmt::Display for ScalarExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Parenthesize operands that bind weaker than their parent operator
+        let operand = |expr: &ScalarExpr, parent: BinaryOp, right: bool| match expr {
+            ScalarExpr::Binary { op, .. }
+                if op.precedence() < parent.precedence()
+                    || (right && op.precedence() == parent.precedence() && parent == BinaryOp::Minus) =>
+            {
+                format!("({})", expr)
+            }
+            _ => expr.to_string(),
+        };
+        match self {
+            ScalarExpr::Column(column) => write!(f, "{}", column),
+            ScalarExpr::Literal(literal) => write!(f, "{}", literal),
+            ScalarExpr::Binary { op, left, right } => {
+                write!(f, "{} {} {}", operand(left, *op, false), op, operand(right, *op, true))
+            }
+            ScalarExpr::Not(expr) => match expr.as_ref() {
+                ScalarExpr::Binary { .. } => write!(f, "NOT ({})", expr),
+                _ => write!(f, "NOT {}", expr),
+            },
+            ScalarExpr::Like { expr, pattern } => {
+                let pattern = Literal::String(pattern.clone());
+                match expr.as_ref() {
+                    ScalarExpr::Binary { .. } => write!(f, "({}) LIKE {}", expr, pattern),
+                    _ => write!(f, "{} LIKE {}", expr, pattern),
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for AggregateFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            AggregateFunction::Count => "COUNT",
+            AggregateFunction::Sum => "SUM",
+            AggregateFunction::Avg => "AVG",
+            AggregateFunction::Min => "MIN",
+            AggregateFunction::Max => "MAX",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl fmt::Display for AggregateExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.arg {
+            Some(arg) => write!(f, "{}({})", self.func, arg),
+            None => write!(f, "{}(*)", self.func),
+        }
+    }
+}
+
+impl fmt::Display for ProjectItem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProjectItem::Wildcard => write!(f, "*"),
+            ProjectItem::Expr { expr, alias: Some(alias) } => write!(f, "{} AS {}", expr, alias),
+            ProjectItem::Expr { expr, alias: None } => write!(f, "{}", expr),
+        }
+    }
+}
+
+impl fmt::Display for SortKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.expr, if self.ascending { "ASC" } else { "DESC" })
+    }
+}
@@ -0,0 +1,589 @@
+// Query Planner
+// =============
+// Turns a parsed `sqlparser::ast::Query` into a `LogicalPlan`. Operators are
+// stacked in SQL evaluation order:
+//
+//     Scan -> Join* -> Filter (WHERE) -> Aggregate -> Sort -> Project -> Limit
+//
+// With SELECT DISTINCT, a Distinct operator follows the Project, and the
+// Sort moves above it, ordering by the output columns:
+//
+//     ... -> Aggregate -> Project -> Distinct -> Sort -> Limit
+//
+// Aggregate calls in the select list and ORDER BY are pulled into the
+// Aggregate operator and referenced by their output name (e.g. `AVG(salary)`)
+// above it. Anything the computing nodes cannot evaluate is rejected with a
+// `PlanError::Unsupported` naming the construct.
+
+use std::fmt;
+
+use sqlparser::ast::{
+    BinaryOperator, Expr, FunctionArg, FunctionArgExpr, JoinConstraint, JoinOperator, Query, Select,
+    SelectItem, SetExpr, Statement, TableFactor, UnaryOperator, Value,
+};
+
+use crate::plan::{
+    AggregateExpr, AggregateFunction, BinaryOp, ColumnRef, Literal, LogicalPlan, ProjectItem, ScalarExpr,
+    SortKey,
+};
+use crate::sql::parse_sql;
+
+/// Reasons a query cannot be planned
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlanError {
+    /// The SQL text does not parse
+    Parse(String),
+    /// Valid SQL using a construct the planner does not support
+    Unsupported(String),
+    /// The query is malformed, e.g. an ungrouped column next to an aggregate
+    Invalid(String),
+}
+
+impl fmt::Display for PlanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlanError::Parse(reason) => write!(f, "SQL parse error: {}", reason),
+            PlanError::Unsupported(what) => write!(f, "Unsupported: {} not supported", what),
+            PlanError::Invalid(reason) => write!(f, "Invalid query: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for PlanError {}
+
+fn unsupported<T>(what: impl Into<String>) -> Result<T, PlanError> {
+    Err(PlanError::Unsupported(what.into()))
+}
+
+/// Parse and plan a single SQL query
+pub fn plan_sql(sql: &str) -> Result<LogicalPlan, PlanError> {
+    let statements = parse_sql(sql).map_err(|e| PlanError::Parse(e.to_string()))?;
+    match statements.as_slice() {
+        [Statement::Query(query)] => plan_query(query),
+        [_] => unsupported("statements other than SELECT are"),
+        _ => Err(PlanError::Invalid(format!("expected one statement, got {}", statements.len()))),
+    }
+}
+
+/// Build the logical plan of a query
+pub fn plan_query(query: &Query) -> Result<LogicalPlan, PlanError> {
+    if query.with.is_some() {
+        return unsupported("WITH clauses are");
+    }
+    if query.offset.is_some() {
+        return unsupported("OFFSET is");
+    }
+    if query.fetch.is_some() {
+        return unsupported("FETCH is");
+    }
+    if query.lock.is_some() {
+        return unsupported("FOR UPDATE/SHARE is");
+    }
+    let select = match query.body.as_ref() {
+        SetExpr::Select(select) => select,
+        SetExpr::Query(_) => return unsupported("nested queries are"),
+        SetExpr::SetOperation { op, .. } => return unsupported(format!("{} is", op)),
+        SetExpr::Values(_) => return unsupported("VALUES is"),
+        SetExpr::Insert(_) => return unsupported("INSERT is"),
+    };
+    check_select_clauses(select)?;
+
+    let mut plan = plan_from(select)?;
+    if let Some(selection) = &select.selection {
+        plan = LogicalPlan::Filter {
+            input: Box::new(plan),
+            predicate: convert_expr(selection, None, "WHERE")?,
+        };
+    }
+
+    // Pull aggregate calls out of the select list and ORDER BY
+    let mut aggregates = Vec::new();
+    let mut items = Vec::new();
+    for item in &select.projection {
+        items.push(match item {
+            SelectItem::Wildcard => ProjectItem::Wildcard,
+            SelectItem::QualifiedWildcard(name) => return unsupported(format!("qualified wildcard {}.* is", name)),
+            SelectItem::UnnamedExpr(expr) => ProjectItem::Expr {
+                expr: convert_expr(expr, Some(&mut aggregates), "SELECT")?,
+                alias: None,
+            },
+            SelectItem::ExprWithAlias { expr, alias } => ProjectItem::Expr {
+                expr: convert_expr(expr, Some(&mut aggregates), "SELECT")?,
+                alias: Some(alias.value.clone()),
+            },
+        });
+    }
+    let mut sort_keys = Vec::new();
+    for order in &query.order_by {
+        let expr = resolve_alias(&order.expr, &select.projection);
+        sort_keys.push(SortKey {
+            expr: convert_expr(expr, Some(&mut aggregates), "ORDER BY")?,
+            ascending: order.asc.unwrap_or(true),
+        });
+    }
+
+    let group_by = select
+        .group_by
+        .iter()
+        .map(|expr| convert_expr(expr, None, "GROUP BY"))
+        .collect::<Result<Vec<_>, _>>()?;
+    if !aggregates.is_empty() || !group_by.is_empty() {
+        check_grouped(&items, &sort_keys, &group_by, &aggregates)?;
+        plan = LogicalPlan::Aggregate {
+            input: Box::new(plan),
+            group_by,
+            aggregates,
+        };
+    }
+
+    if select.distinct {
+        let keys = sort_keys
+            .into_iter()
+            .map(|key| {
+                Ok(SortKey {
+                    expr: distinct_sort_key(key.expr, &items)?,
+                    ascending: key.ascending,
+                })
+            })
+            .collect::<Result<Vec<_>, PlanError>>()?;
+        plan = LogicalPlan::Distinct {
+            input: Box::new(LogicalPlan::Project {
+                input: Box::new(plan),
+                items,
+            }),
+        };
+        if !keys.is_empty() {
+            plan = LogicalPlan::Sort {
+                input: Box::new(plan),
+                keys,
+            };
+        }
+    } else {
+        if !sort_keys.is_empty() {
+            plan = LogicalPlan::Sort {
+                input: Box::new(plan),
+                keys: sort_keys,
+            };
+        }
+        plan = LogicalPlan::Project {
+            input: Box::new(plan),
+            items,
+        };
+    }
+    if let Some(limit) = &query.limit {
+        plan = LogicalPlan::Limit {
+            input: Box::new(plan),
+            limit: convert_limit(limit)?,
+        };
+    }
+    Ok(plan)
+}
+
+/// Reject SELECT clauses the planner has no operator for
+fn check_select_clauses(select: &Select) -> Result<(), PlanError> {
+    if select.top.is_some() {
+        return unsupported("TOP is");
+    }
+    if select.into.is_some() {
+        return unsupported("SELECT INTO is");
+    }
+    if !select.lateral_views.is_empty() {
+        return unsupported("LATERAL VIEW is");
+    }
+    if !select.cluster_by.is_empty() || !select.distribute_by.is_empty() || !select.sort_by.is_empty() {
+        return unsupported("CLUSTER/DISTRIBUTE/SORT BY is");
+    }
+    if select.having.is_some() {
+        return unsupported("HAVING is");
+    }
+    if select.qualify.is_some() {
+        return unsupported("QUALIFY is");
+    }
+    Ok(())
+}
+
+/// Scan of the FROM table, joined with every table in its JOIN clauses
+fn plan_from(select: &Select) -> Result<LogicalPlan, PlanError> {
+    let from = match select.from.as_slice() {
+        [] => return unsupported("queries without FROM are"),
+        [from] => from,
+        _ => return unsupported("comma-separated FROM lists are (use JOIN ... ON)"),
+    };
+
+    let mut plan = plan_table(&from.relation)?;
+    for join in &from.joins {
+        let right = plan_table(&join.relation)?;
+        let on = match &join.join_operator {
+            JoinOperator::Inner(JoinConstraint::On(expr)) => convert_expr(expr, None, "JOIN ON")?,
+            JoinOperator::Inner(JoinConstraint::Using(_)) => return unsupported("JOIN USING is (use JOIN ... ON)"),
+            JoinOperator::Inner(JoinConstraint::Natural) => return unsupported("NATURAL JOIN is"),
+            JoinOperator::Inner(JoinConstraint::None) => return unsupported("JOIN without ON is"),
+            JoinOperator::LeftOuter(_) | JoinOperator::RightOuter(_) | JoinOperator::FullOuter(_) => {
+                return unsupported("outer joins are");
+            }
+            JoinOperator::CrossJoin | JoinOperator::CrossApply | JoinOperator::OuterApply => {
+                return unsupported("CROSS JOIN/APPLY is");
+            }
+        };
+        plan = LogicalPlan::Join {
+            left: Box::new(plan),
+            right: Box::new(right),
+            on,
+        };
+    }
+    Ok(plan)
+}
+
+fn plan_table(relation: &TableFactor) -> Result<LogicalPlan, PlanError> {
+    match relation {
+        TableFactor::Table { name, alias, args, with_hints } => {
+            if args.is_some() {
+                return unsupported("table-valued functions are");
+            }
+            if !with_hints.is_empty() {
+                return unsupported("table hints are");
+            }
+            let table = match name.0.as_slice() {
+                [table] => table.value.clone(),
+                _ => return unsupported(format!("qualified table name {} is", name)),
+            };
+            if alias.as_ref().is_some_and(|a| !a.columns.is_empty()) {
+                return unsupported("column aliases in FROM are");
+            }
+            Ok(LogicalPlan::Scan {
+                table,
+                alias: alias.as_ref().map(|a| a.name.value.clone()),
+            })
+        }
+        TableFactor::Derived { .. } => unsupported("subqueries in FROM are"),
+        TableFactor::NestedJoin { .. } => unsupported("parenthesized joins are"),
+        TableFactor::TableFunction { .. } | TableFactor::UNNEST { .. } => unsupported("table functions are"),
+    }
+}
+
+/// ORDER BY may name an output column by its alias
+fn resolve_alias<'a>(expr: &'a Expr, projection: &'a [SelectItem]) -> &'a Expr {
+    if let Expr::Identifier(ident) = expr {
+        for item in projection {
+            if let SelectItem::ExprWithAlias { expr: aliased, alias } = item
+                && alias.value == ident.value
+            {
+                return aliased;
+            }
+        }
+    }
+    expr
+}
+
+/// Above a Distinct only the output columns are left, so an ORDER BY key
+/// must be one of them, or a column under `SELECT DISTINCT *`
+fn distinct_sort_key(expr: ScalarExpr, items: &[ProjectItem]) -> Result<ScalarExpr, PlanError> {
+    for item in items {
+        if let ProjectItem::Expr { expr: selected, .. } = item
+            && *selected == expr
+        {
+            return Ok(ScalarExpr::column(&item.output_name().unwrap_or_default()));
+        }
+    }
+    match expr {
+        ScalarExpr::Column(column) if items.contains(&ProjectItem::Wildcard) => Ok(ScalarExpr::column(&column.name)),
+        _ => Err(PlanError::Invalid(format!(
+            "ORDER BY {} must appear in the select list of SELECT DISTINCT",
+            expr
+        ))),
+    }
+}
+
+/// In an aggregating query, every column outside an aggregate must be grouped
+fn check_grouped(
+    items: &[ProjectItem],
+    sort_keys: &[SortKey],
+    group_by: &[ScalarExpr],
+    aggregates: &[AggregateExpr],
+) -> Result<(), PlanError> {
+    let aggregate_names: Vec<String> = aggregates.iter().map(|a| a.output_name()).collect();
+    let grouped = |column: &ColumnRef| {
+        (column.table.is_none() && aggregate_names.contains(&column.name))
+            || group_by.iter().any(|key| match key {
+                ScalarExpr::Column(key) => {
+                    key.name == column.name && (key.table.is_none() || column.table.is_none() || key.table == column.table)
+                }
+                _ => false,
+            })
+    };
+
+    let mut exprs = Vec::new();
+    for item in items {
+        match item {
+            ProjectItem::Wildcard => {
+                return Err(PlanError::Invalid("SELECT * cannot be combined with aggregates or GROUP BY".to_string()));
+            }
+            ProjectItem::Expr { expr, .. } => exprs.push(expr),
+        }
+    }
+    exprs.extend(sort_keys.iter().map(|k| &k.expr));
+    for expr in exprs {
+        if let Some(column) = expr.columns().into_iter().find(|c| !grouped(c)) {
+            return Err(PlanError::Invalid(format!(
+                "column {} must appear in GROUP BY or be used in an aggregate",
+                column
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn convert_limit(limit: &Expr) -> Result<u64, PlanError> {
+    match limit {
+        Expr::Value(Value::Number(value, _)) => value
+            .parse()
+            .map_err(|_| PlanError::Invalid(format!("LIMIT must be a non-negative integer, got {}", value))),
+        other => unsupported(format!("LIMIT {} is", other)),
+    }
+}
+
+/// Convert an expression. Aggregate calls are only allowed if `aggregates` is
+/// given; they are appended to it (once) and replaced by their output column.
+fn convert_expr(
+    expr: &Expr,
+    mut aggregates: Option<&mut Vec<AggregateExpr>>,
+    clause: &str,
+) -> Result<ScalarExpr, PlanError> {
+    match expr {
+        Expr::Identifier(ident) => Ok(ScalarExpr::Column(ColumnRef::new(None, &ident.value))),
+        Expr::CompoundIdentifier(parts) => match parts.as_slice() {
+            [table, column] => Ok(ScalarExpr::Column(ColumnRef::new(Some(&table.value), &column.value))),
+            _ => unsupported(format!("column reference {} is", expr)),
+        },
+        Expr::Value(value) => Ok(ScalarExpr::Literal(convert_value(value)?)),
+        Expr::Nested(inner) => convert_expr(inner, aggregates, clause),
+        Expr::UnaryOp { op: UnaryOperator::Not, expr: inner } => {
+            Ok(ScalarExpr::Not(Box::new(convert_expr(inner, aggregates, clause)?)))
+        }
+        Expr::UnaryOp { op, .. } => unsupported(format!("unary operator {} is", op)),
+        Expr::BinaryOp { left, op, right } => {
+            let op = convert_operator(op)?;
+            let left = convert_expr(left, aggregates.as_deref_mut(), clause)?;
+            let right = convert_expr(right, aggregates, clause)?;
+            Ok(ScalarExpr::binary(op, left, right))
+        }
+        Expr::Between { expr: inner, negated, low, high } => {
+            // a BETWEEN l AND h  =>  a >= l AND a <= h
+            let value = convert_expr(inner, aggregates.as_deref_mut(), clause)?;
+            let low = convert_expr(low, aggregates.as_deref_mut(), clause)?;
+            let high = convert_expr(high, aggregates, clause)?;
+            let between = ScalarExpr::binary(
+                BinaryOp::And,
+                ScalarExpr::binary(BinaryOp::GtEq, value.clone(), low),
+                ScalarExpr::binary(BinaryOp::LtEq, value, high),
+            );
+            Ok(if *negated { ScalarExpr::Not(Box::new(between)) } else { between })
+        }
+        Expr::InList { expr: inner, list, negated } => {
+            // a IN (x, y)  =>  a = x OR a = y
+            let value = convert_expr(inner, aggregates.as_deref_mut(), clause)?;
+            let mut alternatives = Vec::new();
+            for item in list {
+                let item = convert_expr(item, aggregates.as_deref_mut(), clause)?;
+                alternatives.push(ScalarExpr::binary(BinaryOp::Eq, value.clone(), item));
+            }
+            let any = alternatives
+                .into_iter()
+                .reduce(|a, b| ScalarExpr::binary(BinaryOp::Or, a, b))
+                .ok_or_else(|| PlanError::Invalid("empty IN list".to_string()))?;
+            Ok(if *negated { ScalarExpr::Not(Box::new(any)) } else { any })
+        }
+        Expr::Function(function) => {
+            let Some(aggregates) = aggregates else {
+                return Err(PlanError::Invalid(format!("function {} is not allowed in {}", function.name, clause)));
+            };
+            let aggregate = convert_aggregate(expr)?;
+            let name = aggregate.output_name();
+            if !aggregates.contains(&aggregate) {
+                aggregates.push(aggregate);
+            }
+            Ok(ScalarExpr::column(&name))
+        }
+        Expr::Like { negated, expr: inner, pattern, escape_char } => {
+            if escape_char.is_some() {
+                return unsupported("LIKE ... ESCAPE is");
+            }
+            let Expr::Value(Value::SingleQuotedString(pattern)) = pattern.as_ref() else {
+                return unsupported(format!("LIKE with the pattern {} is (use a string constant)", pattern));
+            };
+            let like = ScalarExpr::Like {
+                expr: Box::new(convert_expr(inner, aggregates, clause)?),
+                pattern: pattern.clone(),
+            };
+            Ok(if *negated { ScalarExpr::Not(Box::new(like)) } else { like })
+        }
+        Expr::ILike { .. } => unsupported("ILIKE is"),
+        Expr::SimilarTo { .. } => unsupported("SIMILAR TO is"),
+        Expr::IsNull(_) | Expr::IsNotNull(_) => unsupported("IS NULL is (shared tables have no NULLs)"),
+        Expr::Subquery(_) | Expr::InSubquery { .. } | Expr::Exists { .. } => unsupported("subqueries are"),
+        Expr::Case { .. } => unsupported("CASE is"),
+        Expr::Cast { .. } | Expr::TryCast { .. } | Expr::SafeCast { .. } => unsupported("CAST is"),
+        other => unsupported(format!("expression {} is", other)),
+    }
+}
+
+fn convert_aggregate(expr: &Expr) -> Result<AggregateExpr, PlanError> {
+    let Expr::Function(function) = expr else {
+        return Err(PlanError::Invalid(format!("{} is not an aggregate", expr)));
+    };
+    let name = function.name.to_string().to_uppercase();
+    let func = match name.as_str() {
+        "COUNT" => AggregateFunction::Count,
+        "SUM" => AggregateFunction::Sum,
+        "AVG" => AggregateFunction::Avg,
+        "MIN" => AggregateFunction::Min,
+        "MAX" => AggregateFunction::Max,
+        _ => return unsupported(format!("function {} is", name)),
+    };
+    if function.distinct {
+        return unsupported(format!("{}(DISTINCT ...) is", name));
+    }
+    if function.over.is_some() {
+        return unsupported("window functions are");
+    }
+
+    let arg = match function.args.as_slice() {
+        [FunctionArg::Unnamed(FunctionArgExpr::Wildcard)] if func == AggregateFunction::Count => None,
+        [FunctionArg::Unnamed(FunctionArgExpr::Expr(arg))] => Some(convert_expr(arg, None, &name)?),
+        _ => return Err(PlanError::Invalid(format!("{} takes exactly one column argument", name))),
+    };
+    Ok(AggregateExpr { func, arg })
+}
+
+fn convert_operator(op: &BinaryOperator) -> Result<BinaryOp, PlanError> {
+    Ok(match op {
+        BinaryOperator::Eq => BinaryOp::Eq,
+        BinaryOperator::NotEq => BinaryOp::NotEq,
+        BinaryOperator::Lt => BinaryOp::Lt,
+        BinaryOperator::LtEq => BinaryOp::LtEq,
+        BinaryOperator::Gt => BinaryOp::Gt,
+        BinaryOperator::GtEq => BinaryOp::GtEq,
+        BinaryOperator::And => BinaryOp::And,
+        BinaryOperator::Or => BinaryOp::Or,
+        BinaryOperator::Plus => BinaryOp::Plus,
+        BinaryOperator::Minus => BinaryOp::Minus,
+        BinaryOperator::Multiply => BinaryOp::Multiply,
+        other => return unsupported(format!("operator {} is", other)),
+    })
+}
+
+fn convert_value(value: &Value) -> Result<Literal, PlanError> {
+    match value {
+        Value::Number(number, _) => {
+            if let Ok(integer) = number.parse::<u64>() {
+                Ok(Literal::Integer(integer))
+            } else if number.parse::<f64>().is_ok() {
+                Ok(Literal::Decimal(number.clone()))
+            } else {
+                Err(PlanError::Invalid(format!("number {} is out of range", number)))
+            }
+        }
+        Value::SingleQuotedString(text) => Ok(Literal::String(text.clone())),
+        Value::Boolean(flag) => Ok(Literal::Boolean(*flag)),
+        Value::Null => unsupported("NULL is"),
+        other => unsupported(format!("literal {} is", other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_and_projection() {
+        let plan = plan_sql("SELECT part_key, available_qty FROM partsupp WHERE available_qty > 5000").unwrap();
+        let expected = "\
+Project: part_key, available_qty
+  Filter: available_qty > 5000
+    Scan: partsupp
+";
+        assert_eq!(plan.to_string(), expected);
+    }
+
+    #[test]
+    fn test_aggregate_sort_limit() {
+        let plan = plan_sql(
+            "SELECT supplier_key, COUNT(*) AS parts, AVG(available_qty) FROM partsupp \
+             WHERE available_qty BETWEEN 100 AND 9000 GROUP BY supplier_key ORDER BY parts DESC LIMIT 3",
+        )
+        .unwrap();
+        let expected = "\
+Limit: 3
+  Project: supplier_key, COUNT(*) AS parts, AVG(available_qty)
+    Sort: COUNT(*) DESC
+      Aggregate: COUNT(*), AVG(available_qty) GROUP BY supplier_key
+        Filter: available_qty >= 100 AND available_qty <= 9000
+          Scan: partsupp
+";
+        assert_eq!(plan.to_string(), expected);
+    }
+
+    #[test]
+    fn test_distinct_sorts_output_columns() {
+        let plan = plan_sql("SELECT DISTINCT supplier_key AS s, part_key FROM partsupp ORDER BY s DESC, part_key").unwrap();
+        let expected = "\
+Sort: s DESC, part_key ASC
+  Distinct
+    Project: supplier_key AS s, part_key
+      Scan: partsupp
+";
+        assert_eq!(plan.to_string(), expected);
+    }
+
+    #[test]
+    fn test_join() {
+        let plan = plan_sql(
+            "SELECT p.part_key, s.supplier_key FROM partsupp AS p JOIN supplier s ON p.supplier_key = s.supplier_key",
+        )
+        .unwrap();
+        let LogicalPlan::Project { input, .. } = plan else { panic!("expected Project") };
+        let LogicalPlan::Join { left, right, on } = *input else { panic!("expected Join") };
+        assert_eq!(*left, LogicalPlan::Scan { table: "partsupp".to_string(), alias: Some("p".to_string()) });
+        assert_eq!(*right, LogicalPlan::Scan { table: "supplier".to_string(), alias: Some("s".to_string()) });
+        assert_eq!(on.to_string(), "p.supplier_key = s.supplier_key");
+    }
+
+    #[test]
+    fn test_rejects_unsupported_constructs() {
+        let cases = [
+            "SELECT a FROM t UNION SELECT a FROM u",
+            "SELECT a FROM t LEFT JOIN u ON t.a = u.a",
+            "SELECT a FROM (SELECT a FROM t) AS s",
+            "SELECT a FROM t WHERE a IN (SELECT a FROM u)",
+            "SELECT a FROM t, u",
+            "SELECT a, COUNT(*) FROM t GROUP BY a HAVING COUNT(*) > 1",
+            "SELECT a / 2 FROM t",
+            "SELECT a FROM t WHERE a LIKE b",
+            "SELECT a FROM t WHERE a ILIKE 'x%'",
+            "SELECT a FROM t LIMIT 5 OFFSET 2",
+            "SELECT UPPER(a) FROM t",
+            "INSERT INTO t VALUES (1)",
+        ];
+        for sql in cases {
+            match plan_sql(sql) {
+                Err(PlanError::Unsupported(_)) => {}
+                other => panic!("{}: expected Unsupported, got {:?}", sql, other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_rejects_invalid_queries() {
+        let cases = [
+            "SELECT part_key, COUNT(*) FROM partsupp",
+            "SELECT * FROM partsupp GROUP BY part_key",
+            "SELECT a FROM t WHERE SUM(a) > 3",
+            "SELECT SUM(a, b) FROM t",
+            "SELECT DISTINCT a FROM t ORDER BY b",
+        ];
+        for sql in cases {
+            assert!(matches!(plan_sql(sql), Err(PlanError::Invalid(_))), "{}", sql);
+        }
+        assert!(matches!(plan_sql("SELEC a FROM t"), Err(PlanError::Parse(_))));
+    }
+}
@@ -0,0 +1,403 @@
+// Query Results
+// =============
+// Reconstruction of a query result from the shares returned by the three
+// computing nodes, and decoding of the reconstructed bits into values of the
+// result columns' types. If the query filters rows, the last revealed column
+// holds the validity bit of every row, and only the valid rows are kept. An
+// AVG column is revealed as its sum and count and divided here. Decimal
+// values arrive as integers scaled by 10^scale and are divided back.
+//
+// Every share word is reconstructed with `helpers::sharing::reconstruct_secret`,
+// which also checks that the two copies of each share component held by
+// neighbouring nodes agree, so a single misbehaving node cannot silently
+// change the result.
+
+use anyhow::{anyhow, Result};
+use std::fmt;
+
+use helpers::circuit::InputSharing;
+use helpers::sharing::{reconstruct_secret, ReplicatedShare};
+
+use crate::binder::OutputColumn;
+use crate::catalog::{Charset, ColumnType};
+use crate::client::query_service::{ResultColumn, ResultSharing, RunQueryResponse};
+use crate::display::{render_table, Align};
+use crate::lower::{AVG_COUNT_SUFFIX, VALID_COLUMN};
+
+/// Reconstructed values of one result column
+#[derive(Debug, Clone, PartialEq)]
+pub enum RevealedValues {
+    /// Bits of every row, least significant first
+    Bits(Vec<Vec<bool>>),
+    /// One word per row
+    Words(Vec<u64>),
+}
+
+/// Reconstructed result column
+#[derive(Debug, Clone, PartialEq)]
+pub struct RevealedColumn {
+    pub name: String,
+    pub values: RevealedValues,
+}
+
+/// A decoded result value
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    UInt(u64),
+    Float(f64),
+    Text(String),
+}
+
+/// Decoded query result
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResultTable {
+    pub columns: Vec<OutputColumn>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+impl RevealedValues {
+    /// Number of rows
+    pub fn len(&self) -> usize {
+        match self {
+            RevealedValues::Bits(rows) => rows.len(),
+            RevealedValues::Words(words) => words.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::UInt(value) => write!(f, "{}", value),
+            Value::Float(value) => write!(f, "{}", value),
+            Value::Text(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+impl fmt::Display for ResultTable {
+    /// psql-style table, numbers right-aligned
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let header: Vec<String> = self.columns.iter().map(|c| c.name.clone()).collect();
+        let align: Vec<Align> = self
+            .columns
+            .iter()
+            .map(|c| match c.data_type {
+                ColumnType::Float => Align::Right,
+                ref data_type if data_type.is_numeric() => Align::Right,
+                _ => Align::Left,
+            })
+            .collect();
+        let rows: Vec<Vec<String>> = self
+            .rows
+            .iter()
+            .map(|row| row.iter().map(Value::to_string).collect())
+            .collect();
+        write!(f, "{}", render_table(&header, &align, &rows))
+    }
+}
+
+/// Reconstruct every result column from the responses of nodes 0, 1 and 2
+pub fn reconstruct(responses: &[RunQueryResponse]) -> Result<Vec<RevealedColumn>> {
+    if responses.len() != 3 {
+        return Err(anyhow!("Expected responses of 3 nodes, got {}", responses.len()));
+    }
+    let columns = responses[0].columns.len();
+    if responses.iter().any(|response| response.columns.len() != columns) {
+        return Err(anyhow!("Nodes returned different numbers of result columns"));
+    }
+
+    (0..columns)
+        .map(|c| {
+            let parts = [0, 1, 2].map(|node| &responses[node].columns[c]);
+            reconstruct_column(parts)
+        })
+        .collect()
+}
+
+fn reconstruct_column(parts: [&ResultColumn; 3]) -> Result<RevealedColumn> {
+    let [first, ..] = parts;
+    let same_layout = parts.iter().all(|part| {
+        part.name == first.name
+            && part.sharing == first.sharing
+            && part.rows == first.rows
+            && part.planes.len() == first.planes.len()
+    });
+    if !same_layout {
+        return Err(anyhow!("Nodes returned different layouts for result column '{}'", first.name));
+    }
+
+    // Word-wise reconstruction of every plane
+    let sharing = match first.sharing() {
+        ResultSharing::Boolean => InputSharing::Boolean,
+        ResultSharing::Arithmetic => InputSharing::Arithmetic,
+    };
+    let mut planes = Vec::with_capacity(first.planes.len());
+    for p in 0..first.planes.len() {
+        let words = first.planes[p].own.len();
+        let consistent = parts
+            .iter()
+            .all(|part| part.planes[p].own.len() == words && part.planes[p].prev.len() == words);
+        if !consistent {
+            return Err(anyhow!("Nodes returned different share lengths for result column '{}'", first.name));
+        }
+        let plane = (0..words)
+            .map(|w| {
+                let shares = parts.map(|part| ReplicatedShare {
+                    own: part.planes[p].own[w],
+                    prev: part.planes[p].prev[w],
+                });
+                reconstruct_secret(&shares, sharing)
+                    .map_err(|e| anyhow!("Result column '{}': {}", first.name, e))
+            })
+            .collect::<Result<Vec<u64>>>()?;
+        planes.push(plane);
+    }
+
+    let rows = first.rows as usize;
+    let values = match sharing {
+        InputSharing::Arithmetic => {
+            let words = planes.pop().filter(|words| words.len() == rows && planes.is_empty());
+            RevealedValues::Words(
+                words.ok_or_else(|| anyhow!("Malformed arithmetic result column '{}'", first.name))?,
+            )
+        }
+        InputSharing::Boolean => {
+            if planes.iter().any(|plane| plane.len() * 64 < rows) {
+                return Err(anyhow!("Result column '{}' has fewer than {} rows", first.name, rows));
+            }
+            RevealedValues::Bits(
+                (0..rows)
+                    .map(|r| planes.iter().map(|plane| (plane[r / 64] >> (r % 64)) & 1 == 1).collect())
+                    .collect(),
+            )
+        }
+    };
+    Ok(RevealedColumn { name: first.name.clone(), values })
+}
+
+impl ResultTable {
+    /// Decode reconstructed columns according to the types of the query's
+    /// output columns, dropping the rows marked invalid
+    pub fn decode(columns: &[OutputColumn], mut revealed: Vec<RevealedColumn>) -> Result<ResultTable> {
+        // The validity column is found by name: AVG columns add a count column
+        // each, so the number of revealed columns does not identify it
+        let valid = match revealed.last() {
+            Some(column) if column.name == VALID_COLUMN && columns.iter().all(|c| c.name != VALID_COLUMN) => {
+                match revealed.pop().map(|column| column.values) {
+                    Some(RevealedValues::Bits(bits)) => Some(bits.iter().map(|bits| bits == &[true]).collect::<Vec<bool>>()),
+                    _ => return Err(anyhow!("Malformed validity column")),
+                }
+            }
+            _ => None,
+        };
+        let rows = revealed.first().map_or(0, |column| column.values.len());
+        if revealed.iter().any(|column| column.values.len() != rows) {
+            return Err(anyhow!("Result columns have different row counts"));
+        }
+
+        let returned = revealed.len();
+        let mut revealed = revealed.into_iter().peekable();
+        let mut decoded: Vec<Vec<Value>> = Vec::with_capacity(columns.len());
+        for column in columns {
+            let count_name = format!("{}{}", column.name, AVG_COUNT_SUFFIX);
+            let values = match (revealed.next(), revealed.next_if(|next| next.name == count_name)) {
+                (Some(sum), Some(count)) => decode_average(&sum.values, &count.values, &column.data_type)?,
+                (Some(revealed), None) => match &revealed.values {
+                    RevealedValues::Bits(rows) => rows.iter().map(|bits| decode_bits(bits, &column.data_type)).collect(),
+                    RevealedValues::Words(words) => words.iter().map(|word| decode_word(*word, &column.data_type)).collect(),
+                },
+                (None, _) => break,
+            };
+            decoded.push(values);
+        }
+        if decoded.len() != columns.len() || revealed.next().is_some() {
+            return Err(anyhow!("Query has {} columns, the nodes returned {}", columns.len(), returned));
+        }
+        if valid.as_ref().is_some_and(|valid| valid.len() != rows) {
+            return Err(anyhow!("Validity column has a different row count"));
+        }
+        let rows = (0..rows)
+            .filter(|r| valid.as_ref().is_none_or(|valid| valid[*r]))
+            .map(|r| decoded.iter().map(|column| column[r].clone()).collect())
+            .collect();
+        Ok(ResultTable { columns: columns.to_vec(), rows })
+    }
+}
+
+/// Decode an LSB-first bit string, mirroring the data owner's encoding
+fn decode_bits(bits: &[bool], data_type: &ColumnType) -> Value {
+    let read = |start: usize, width: usize| -> u64 {
+        (0..width.min(64)).fold(0u64, |acc, j| {
+            let bit = bits.get(start + j).copied().unwrap_or(false);
+            acc | ((bit as u64) << j)
+        })
+    };
+    match data_type {
+        ColumnType::Boolean => Value::Bool(read(0, 1) == 1),
+        ColumnType::UnsignedInt => Value::UInt(read(0, bits.len())),
+        ColumnType::Float => Value::Float(f64::from_bits(read(0, 64))),
+        ColumnType::Decimal { scale } => Value::Float(read(0, bits.len()) as f64 / 10f64.powi(*scale as i32)),
+        ColumnType::String { max_chars, charset } => {
+            let bits_per_char = match charset {
+                Charset::Ascii => 7,
+                Charset::Utf8 => 8,
+            };
+            Value::Text(
+                (0..*max_chars)
+                    .map(|i| read(i * bits_per_char, bits_per_char) as u8 as char)
+                    .take_while(|c| *c != '\0')
+                    .collect(),
+            )
+        }
+    }
+}
+
+/// Divide the revealed sums of an AVG column by the counts; NaN for no rows
+fn decode_average(sums: &RevealedValues, counts: &RevealedValues, data_type: &ColumnType) -> Result<Vec<Value>> {
+    let unit = 10f64.powi(data_type.scale() as i32);
+    match (sums, counts) {
+        (RevealedValues::Words(sums), RevealedValues::Words(counts)) => Ok(sums
+            .iter()
+            .zip(counts)
+            .map(|(sum, count)| Value::Float(*sum as f64 / *count as f64 / unit))
+            .collect()),
+        _ => Err(anyhow!("Malformed AVG result column")),
+    }
+}
+
+/// Decode a reconstructed word of an arithmetic result column
+fn decode_word(word: u64, data_type: &ColumnType) -> Value {
+    match data_type {
+        ColumnType::Boolean => Value::Bool(word != 0),
+        ColumnType::Float => Value::Float(f64::from_bits(word)),
+        ColumnType::Decimal { scale } => Value::Float(word as f64 / 10f64.powi(*scale as i32)),
+        _ => Value::UInt(word),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::query_service::SharePair;
+
+    /// Responses of the three nodes for one column with the given share components
+    fn responses(sharing: ResultSharing, rows: u64, planes: &[[Vec<u64>; 3]]) -> Vec<RunQueryResponse> {
+        (0..3)
+            .map(|node| RunQueryResponse {
+                node_id: node as u32,
+                query_id: 1,
+                columns: vec![ResultColumn {
+                    name: "c".to_string(),
+                    sharing: sharing as i32,
+                    rows,
+                    planes: planes
+                        .iter()
+                        .map(|components| SharePair {
+                            own: components[node].clone(),
+                            prev: components[(node + 2) % 3].clone(),
+                        })
+                        .collect(),
+                }],
+                stats: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_reconstruct_and_decode() {
+        // Two rows of a 7-bit ASCII string: 'A' (0b1000001) and 'b' (0b1100010)
+        let planes: Vec<[Vec<u64>; 3]> = (0..7)
+            .map(|j| {
+                let bits = (((b'A' >> j) & 1) as u64) | ((((b'b' >> j) & 1) as u64) << 1);
+                [vec![0b11], vec![0b10], vec![bits ^ 0b01]]
+            })
+            .collect();
+        let columns = reconstruct(&responses(ResultSharing::Boolean, 2, &planes)).unwrap();
+        let output = OutputColumn {
+            name: "c".to_string(),
+            data_type: ColumnType::String { max_chars: 1, charset: Charset::Ascii },
+        };
+        let table = ResultTable::decode(std::slice::from_ref(&output), columns).unwrap();
+        assert_eq!(table.rows, vec![vec![Value::Text("A".into())], vec![Value::Text("b".into())]]);
+
+        let words = [[vec![10, u64::MAX], vec![5, 2], vec![1, 0]]];
+        let columns = reconstruct(&responses(ResultSharing::Arithmetic, 2, &words)).unwrap();
+        assert_eq!(columns[0].values, RevealedValues::Words(vec![16, 1]));
+    }
+
+    #[test]
+    fn test_decode_drops_invalid_rows() {
+        let output = OutputColumn { name: "n".to_string(), data_type: ColumnType::UnsignedInt };
+        let revealed = vec![
+            RevealedColumn { name: "n".to_string(), values: RevealedValues::Words(vec![7, 0, 9]) },
+            RevealedColumn {
+                name: VALID_COLUMN.to_string(),
+                values: RevealedValues::Bits(vec![vec![true], vec![false], vec![true]]),
+            },
+        ];
+        let table = ResultTable::decode(std::slice::from_ref(&output), revealed).unwrap();
+        assert_eq!(table.rows, vec![vec![Value::UInt(7)], vec![Value::UInt(9)]]);
+    }
+
+    #[test]
+    fn test_decode_divides_averages() {
+        let column = |name: &str, data_type| OutputColumn { name: name.to_string(), data_type };
+        let words = |name: &str, words: Vec<u64>| RevealedColumn {
+            name: name.to_string(),
+            values: RevealedValues::Words(words),
+        };
+        let columns = [column("AVG(x)", ColumnType::Float), column("n", ColumnType::UnsignedInt)];
+        let revealed = vec![words("AVG(x)", vec![7]), words("AVG(x)__count", vec![2]), words("n", vec![2])];
+        let table = ResultTable::decode(&columns, revealed).unwrap();
+        assert_eq!(table.rows, vec![vec![Value::Float(3.5), Value::UInt(2)]]);
+
+        let revealed = vec![words("AVG(x)", vec![7]), words("n", vec![2]), words("m", vec![2])];
+        assert!(ResultTable::decode(&columns, revealed).is_err());
+
+        // Fixed-point sums are scaled by 10^scale
+        let decimal = ColumnType::Decimal { scale: 2 };
+        let columns = [column("AVG(c)", decimal.clone()), column("SUM(c)", decimal)];
+        let revealed = vec![words("AVG(c)", vec![1001]), words("AVG(c)__count", vec![2]), words("SUM(c)", vec![1001])];
+        let table = ResultTable::decode(&columns, revealed).unwrap();
+        assert_eq!(table.rows, vec![vec![Value::Float(5.005), Value::Float(10.01)]]);
+    }
+
+    #[test]
+    fn test_decode_grouped_averages_with_validity() {
+        // SELECT supplier_key, AVG(available_qty) FROM partsupp GROUP BY supplier_key
+        let columns = [
+            OutputColumn { name: "supplier_key".to_string(), data_type: ColumnType::UnsignedInt },
+            OutputColumn { name: "AVG(available_qty)".to_string(), data_type: ColumnType::Float },
+        ];
+        let bits = |values: &[u64], width: usize| -> RevealedValues {
+            RevealedValues::Bits(values.iter().map(|v| (0..width).map(|j| (v >> j) & 1 == 1).collect()).collect())
+        };
+        let revealed = vec![
+            RevealedColumn { name: "supplier_key".to_string(), values: bits(&[1, 1, 2], 8) },
+            RevealedColumn { name: "AVG(available_qty)".to_string(), values: RevealedValues::Words(vec![0, 30, 5]) },
+            RevealedColumn { name: "AVG(available_qty)__count".to_string(), values: RevealedValues::Words(vec![0, 4, 1]) },
+            RevealedColumn { name: VALID_COLUMN.to_string(), values: bits(&[0, 1, 1], 1) },
+        ];
+        let table = ResultTable::decode(&columns, revealed).unwrap();
+        assert_eq!(
+            table.rows,
+            vec![vec![Value::UInt(1), Value::Float(7.5)], vec![Value::UInt(2), Value::Float(5.0)]]
+        );
+    }
+
+    #[test]
+    fn test_rejects_inconsistent_copies() {
+        let words = [[vec![10], vec![5], vec![1]]];
+        let mut responses = responses(ResultSharing::Arithmetic, 1, &words);
+        responses[1].columns[0].planes[0].prev[0] += 1;
+        let error = reconstruct(&responses).unwrap_err();
+        assert!(error.to_string().contains("Inconsistent shares"), "{}", error);
+    }
+}
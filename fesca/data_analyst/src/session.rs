@@ -0,0 +1,321 @@
+// Analyst Session
+// ===============
+// Front end of the data analyst: runs SQL given on the command line, read from
+// a file or typed at the interactive prompt. `EXPLAIN <query>` prints the plan
+// and estimated cost instead of running the query, including the circuit that
+// counts the rows of DISTINCT when they are revealed. Meta-commands answer from
+// the catalog alone:
+//
+//     \tables              list the tables of the catalog
+//     \describe <table>    columns, types and sharing of a table
+//     \reveal_cardinality on|off
+//                          whether DISTINCT reveals its number of rows
+//     \help                list the meta-commands
+//     \q                   leave the prompt
+//
+// At the prompt a statement may span several lines and runs once a line ends
+// with `;`. Errors are printed and the prompt continues; in the one-shot modes
+// the first error ends the run.
+
+use anyhow::{anyhow, Result};
+use std::cell::Cell;
+use std::io::{BufRead, Write};
+use tokio::runtime::Runtime;
+
+use sqlparser::ast::Statement;
+
+use helpers::circuit::Circuit;
+
+use crate::binder::{self, BoundQuery};
+use crate::catalog::{Catalog, Sharing};
+use crate::client::QueryClient;
+use crate::display::{render_table, Align};
+use crate::explain::{explain, explain_counting};
+use crate::lower;
+use crate::planner::{plan_query, PlanError};
+use crate::result::ResultTable;
+use crate::run_query;
+use crate::sql::parse_sql;
+
+const PROMPT: &str = "fesca=> ";
+const CONTINUATION_PROMPT: &str = "fesca-> ";
+
+const HELP: &str = "\
+\\tables              list the tables of the catalog
+\\describe <table>    columns, types and sharing of a table
+\\reveal_cardinality on|off
+                     reveal the number of rows of DISTINCT to speed up the query
+\\help                show this help
+\\q                   quit
+SQL statements end with ';', EXPLAIN <query> shows the estimated cost
+";
+
+/// State of an analyst session
+pub struct Session {
+    catalog: Catalog,
+    client: Option<QueryClient>,
+    runtime: Runtime,
+    /// Whether queries reveal the number of rows DISTINCT produces
+    reveal_cardinality: Cell<bool>,
+}
+
+impl Session {
+    /// Without a client queries are planned and checked, but not run
+    pub fn new(catalog: Catalog, client: Option<QueryClient>) -> Result<Self> {
+        Ok(Self {
+            catalog,
+            client,
+            runtime: Runtime::new()?,
+            reveal_cardinality: Cell::new(false),
+        })
+    }
+
+    /// Reveal the number of rows DISTINCT produces, so the rows can be
+    /// compacted before the rest of the query runs; off by default
+    pub fn set_reveal_cardinality(&self, reveal: bool) {
+        self.reveal_cardinality.set(reveal);
+    }
+
+    /// Run every statement of `sql` in order and print the results
+    pub fn run_sql(&self, sql: &str, out: &mut dyn Write) -> Result<()> {
+        let statements = parse_sql(sql).map_err(|e| PlanError::Parse(e.to_string()))?;
+        for statement in &statements {
+            match statement {
+                Statement::Explain { analyze: true, .. } => {
+                    return Err(PlanError::Unsupported("EXPLAIN ANALYZE is".to_string()).into());
+                }
+                Statement::Explain { statement, .. } => {
+                    let (bound, circuit) = self.prepare(statement)?;
+                    write!(out, "{}", explain(&bound, &circuit, &self.catalog)?)?;
+                    if self.reveal_cardinality.get()
+                        && let Some(counting) = lower::lower_distinct_rows(&bound, &self.catalog)?
+                    {
+                        write!(out, "{}", explain_counting(&counting)?)?;
+                    }
+                }
+                _ => {
+                    let table = self.run_statement(statement)?;
+                    write!(out, "{}", table)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Plan, check and lower one statement
+    fn prepare(&self, statement: &Statement) -> Result<(BoundQuery, Circuit)> {
+        let query = match statement {
+            Statement::Query(query) => query,
+            _ => return Err(PlanError::Unsupported("statements other than SELECT are".to_string()).into()),
+        };
+        let plan = plan_query(query)?;
+        let bound = binder::bind(&plan, &self.catalog)?;
+        let circuit = lower::lower(&bound, &self.catalog)?;
+        Ok((bound, circuit))
+    }
+
+    /// Plan, check, run and reconstruct one statement
+    fn run_statement(&self, statement: &Statement) -> Result<ResultTable> {
+        let (bound, _) = self.prepare(statement)?;
+        let client = self
+            .client
+            .as_ref()
+            .ok_or_else(|| anyhow!("No computing nodes configured, the query was checked but not run"))?;
+        self.runtime
+            .block_on(run_query(&bound, &self.catalog, client, self.reveal_cardinality.get()))
+    }
+
+    /// Run a meta-command line such as `\tables`. Returns false for `\q`.
+    pub fn run_command(&self, line: &str, out: &mut dyn Write) -> Result<bool> {
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or_default();
+        let argument = words.next();
+        match (command, argument) {
+            ("\\q" | "\\quit", _) => return Ok(false),
+            ("\\tables" | "\\dt", None) => write!(out, "{}", self.describe_tables())?,
+            ("\\describe" | "\\d", Some(table)) => write!(out, "{}", self.describe_table(table)?)?,
+            ("\\describe" | "\\d", None) => return Err(anyhow!("Usage: \\describe <table>")),
+            ("\\reveal_cardinality", Some(setting @ ("on" | "off"))) => {
+                self.set_reveal_cardinality(setting == "on");
+                writeln!(out, "Revealing the number of rows of DISTINCT is {}", setting)?;
+            }
+            ("\\reveal_cardinality", _) => return Err(anyhow!("Usage: \\reveal_cardinality on|off")),
+            ("\\help" | "\\?", _) => write!(out, "{}", HELP)?,
+            _ => return Err(anyhow!("Unknown command '{}', try \\help", line.trim())),
+        }
+        Ok(true)
+    }
+
+    fn describe_tables(&self) -> String {
+        let header = ["table", "owner", "rows", "columns"].map(String::from);
+        let rows: Vec<Vec<String>> = self
+            .catalog
+            .tables()
+            .map(|table| {
+                vec![
+                    table.table_name.clone(),
+                    table.data_owner.owner_id.clone(),
+                    table.row_count.to_string(),
+                    table.columns.len().to_string(),
+                ]
+            })
+            .collect();
+        render_table(&header, &[Align::Left, Align::Left, Align::Right, Align::Right], &rows)
+    }
+
+    fn describe_table(&self, name: &str) -> Result<String> {
+        let table = self
+            .catalog
+            .table(name)
+            .ok_or_else(|| binder::BindError::UnknownTable(name.to_string()))?;
+        let header = ["column", "type", "sharing", "bits"].map(String::from);
+        let rows: Vec<Vec<String>> = table
+            .columns
+            .iter()
+            .map(|column| {
+                let sharing = match column.sharing {
+                    Sharing::Boolean => "boolean",
+                    Sharing::Arithmetic => "arithmetic",
+                };
+                vec![
+                    column.name.clone(),
+                    column.data_type().to_string(),
+                    sharing.to_string(),
+                    column.stored_width().to_string(),
+                ]
+            })
+            .collect();
+        Ok(format!(
+            "Table \"{}\" ({} rows)\n{}",
+            table.table_name,
+            table.row_count,
+            render_table(&header, &[Align::Left, Align::Left, Align::Left, Align::Right], &rows)
+        ))
+    }
+
+    /// Interactive prompt reading from stdin until `\q` or end of input
+    pub fn repl(&self) -> Result<()> {
+        let stdin = std::io::stdin();
+        self.repl_from(stdin.lock(), &mut std::io::stdout())
+    }
+
+    /// Prompt loop over any input, e.g. a script in tests
+    pub fn repl_from(&self, input: impl BufRead, out: &mut dyn Write) -> Result<()> {
+        let mut statement = String::new();
+        write!(out, "{}", PROMPT)?;
+        out.flush()?;
+        for line in input.lines() {
+            let line = line?;
+            let trimmed = line.trim();
+
+            if statement.is_empty() && trimmed.starts_with('\\') {
+                match self.run_command(trimmed, out) {
+                    Ok(true) => {}
+                    Ok(false) => return Ok(()),
+                    Err(e) => writeln!(out, "ERROR: {}", e)?,
+                }
+            } else if !trimmed.is_empty() {
+                statement.push_str(&line);
+                statement.push('\n');
+                if trimmed.ends_with(';') {
+                    self.report(&statement, out)?;
+                    statement.clear();
+                }
+            }
+
+            write!(out, "{}", if statement.is_empty() { PROMPT } else { CONTINUATION_PROMPT })?;
+            out.flush()?;
+        }
+        writeln!(out)?;
+        // An unterminated statement at the end of the input still runs
+        if !statement.trim().is_empty() {
+            self.report(&statement, out)?;
+        }
+        Ok(())
+    }
+
+    /// Run a statement, printing an error instead of returning it
+    fn report(&self, sql: &str, out: &mut dyn Write) -> Result<()> {
+        if let Err(e) = self.run_sql(sql, out) {
+            writeln!(out, "ERROR: {}", e)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::{ColumnInfo, ColumnType, OwnerInfo, TableInfo};
+
+    fn session() -> Session {
+        let column = |name: &str, type_hint: ColumnType, sharing: Sharing| ColumnInfo {
+            name: name.to_string(),
+            type_hint,
+            sharing,
+            unique: false,
+            decimals: None,
+        };
+        let mut catalog = Catalog::new();
+        let table = TableInfo {
+            table_name: "partsupp".to_string(),
+            table_id: 4,
+            row_count: 4,
+            schema_hash: 0,
+            data_owner: OwnerInfo { owner_id: "owner_001".to_string(), owner_name: String::new() },
+            columns: vec![
+                column("part_key", ColumnType::UnsignedInt, Sharing::Boolean),
+                column("available_qty", ColumnType::UnsignedInt, Sharing::Arithmetic),
+            ],
+        };
+        catalog.insert(table, None).unwrap();
+        Session::new(catalog, None).unwrap()
+    }
+
+    fn run_script(script: &str) -> String {
+        let mut out = Vec::new();
+        session().repl_from(script.as_bytes(), &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_meta_commands() {
+        let out = run_script(
+            "\\tables\n\\describe PARTSUPP\n\\describe lineitem\n\\reveal_cardinality on\n\\reveal_cardinality\n\\q\n\\tables\n",
+        );
+        assert!(out.contains(" partsupp | owner_001 |    4 |       2\n"), "{}", out);
+        assert!(out.contains(" available_qty | UnsignedInt | arithmetic |   64\n"), "{}", out);
+        assert!(out.contains("ERROR: Unknown table 'lineitem'"), "{}", out);
+        assert!(out.contains("Revealing the number of rows of DISTINCT is on\n"), "{}", out);
+        assert!(out.contains("ERROR: Usage: \\reveal_cardinality on|off"), "{}", out);
+        // Nothing runs after \q
+        assert_eq!(out.matches("(1 row)").count(), 1, "{}", out);
+    }
+
+    #[test]
+    fn test_statements_span_lines_and_report_errors() {
+        let out = run_script("SELECT part_key\nFROM partsupp;\nSELECT nope FROM partsupp;\nSELECT part_key FROM");
+        assert!(out.contains("fesca-> "), "{}", out);
+        assert_eq!(out.matches("the query was checked but not run").count(), 1, "{}", out);
+        assert!(out.contains("ERROR: Unknown column 'nope'"), "{}", out);
+        assert!(out.contains("ERROR: SQL parse error"), "{}", out);
+    }
+
+    #[test]
+    fn test_explain_does_not_need_nodes() {
+        let out = run_script("EXPLAIN SELECT part_key, available_qty * 2 FROM partsupp;\n\\q\n");
+        assert!(out.contains("Logical plan:"), "{}", out);
+        assert!(out.contains("  MUL gates:    0\n"), "{}", out);
+        assert!(!out.contains("ERROR"), "{}", out);
+
+        // Revealing the rows of DISTINCT adds the counting circuit, only to
+        // queries with DISTINCT
+        let sql = "EXPLAIN SELECT DISTINCT part_key FROM partsupp;\n";
+        let out = run_script(sql);
+        assert!(!out.contains("Counting DISTINCT rows"), "{}", out);
+        let script = format!("\\reveal_cardinality on\n{}EXPLAIN SELECT part_key FROM partsupp;\n{}", sql, sql);
+        let out = run_script(&script);
+        assert_eq!(out.matches("Counting DISTINCT rows first").count(), 2, "{}", out);
+        assert!(out.contains("assumes the number of DISTINCT rows stays hidden"), "{}", out);
+    }
+}
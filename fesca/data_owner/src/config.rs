@@ -27,6 +27,12 @@ pub struct DataOwnerInfo {
     pub owner_name: String,
 }
 
+/// Records, schema and configuration loaded by `load_data_and_config`
+pub type LoadedData = (Vec<Vec<String>>, TableSchema, DataOwnerConfig);
+
+/// Records and schema loaded by `load_table_data`
+pub type TableData = (Vec<Vec<String>>, TableSchema);
+
 /// Unified configuration structure for data owner
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct DataOwnerConfig {
@@ -67,12 +73,24 @@ pub fn load_data_owner_config(config_path: &str) -> Result<DataOwnerConfig> {
 /// # File Structure Expected
 /// - TBL file: Contains the actual data rows with pipe-separated values
 /// - JSON file: Contains schema with same name as TBL but .json extension
-pub fn load_data_and_config(config_path: &str) -> Result<(Vec<Vec<String>>, TableSchema, DataOwnerConfig), Box<dyn std::error::Error>> {
+pub fn load_data_and_config(config_path: &str) -> Result<LoadedData, Box<dyn std::error::Error>> {
     // Step 1: Load the unified configuration
     let config = load_data_owner_config(config_path)?;
 
-    // Step 2: Load TBL data from the configured path
-    let mut file = File::open(&config.data_path)?;
+    // Step 2: Load TBL data and schema from the configured path
+    let (records, schema) = load_table_data(&config.data_path)?;
+
+    // Step 3: Return data, schema, and config
+    Ok((records, schema, config))
+}
+
+/// Loads TBL data and the JSON schema stored next to it.
+///
+/// # Arguments
+/// * `data_path` - Path to the TBL file; the schema is read from the same path with .json extension
+pub fn load_table_data(data_path: &str) -> Result<TableData, Box<dyn std::error::Error>> {
+    // Step 1: Load TBL data from the given path
+    let mut file = File::open(data_path)?;
     let mut contents = String::new();
     file.read_to_string(&mut contents)?;
     
@@ -85,25 +103,24 @@ pub fn load_data_and_config(config_path: &str) -> Result<(Vec<Vec<String>>, Tabl
             // Split by pipe and remove trailing empty field if present
             let mut fields: Vec<String> = line.split('|').map(|s| s.to_string()).collect();
             // Remove the last empty field if it exists (common in TBL format)
-            if fields.last().map_or(false, |s| s.is_empty()) {
+            if fields.last().is_some_and(|s| s.is_empty()) {
                 fields.pop();
             }
             records.push(fields);
         }
     }
 
-    // Step 3: Construct the schema file path
+    // Step 2: Construct the schema file path
     // Schema file should have the same name as TBL but with .json extension
-    let tbl_path = Path::new(&config.data_path);
+    let tbl_path = Path::new(data_path);
     let schema_path = tbl_path.with_extension("json");
 
-    // Step 4: Load and parse the JSON schema file
+    // Step 3: Load and parse the JSON schema file
     let schema_file = File::open(&schema_path)
         .map_err(|e| format!("Failed to open schema file '{}': {}", schema_path.display(), e))?;
     
     let schema: TableSchema = serde_json::from_reader(schema_file)
         .map_err(|e| format!("Failed to parse schema file '{}': {}", schema_path.display(), e))?;
 
-    // Step 5: Return data, schema, and config
-    Ok((records, schema, config))
-} 
\ No newline at end of file
+    Ok((records, schema))
+}
@@ -10,10 +10,13 @@
 // - Floating point (f64): IEEE 754 double precision encoding
 // - Strings: Character-by-character encoding with configurable charsets
 //
+// Columns marked `Sharing::Arithmetic` are encoded as integers in Z_2^64
+// instead (see `encode_arithmetic`).
+//
 // Memory Optimization:
 // - Uses BitVector for efficient bit storage (1 bit per bit instead of 8 bits per bool)
 
-use crate::types::{ColumnDescriptor, ColumnType, Charset, BitVector};
+use crate::types::{ColumnDescriptor, ColumnType, Charset, BitVector, Sharing};
 
 /// Main encoding function that dispatches to type-specific encoders.
 /// 
@@ -35,11 +38,88 @@ pub fn encode_value(value: &str, column: &ColumnDescriptor) -> BitVector {
             bv
         },
         ColumnType::UnsignedInt => encode_unsigned(value),
-        ColumnType::Float => encode_float(value),
+        ColumnType::Float => match column.decimals {
+            Some(decimals) => encode_word(encode_fixed_point(value, decimals)),
+            None => encode_float(value),
+        },
         ColumnType::String { max_chars, charset } => encode_string(value, *max_chars, charset),
     }
 }
 
+/// Encodes a value of an arithmetically shared column as an integer in Z_2^64.
+///
+/// Only `UnsignedInt`, `Boolean` and fixed-point `Float` columns can be shared
+/// arithmetically; `check_arithmetic_columns` rejects other types before any
+/// row is encoded.
+///
+/// # Arguments
+/// * `value` - String representation of the value to encode
+/// * `column` - Column descriptor containing type information
+///
+/// # Returns
+/// * `u64` - The value as an element of Z_2^64
+///
+/// # Panics
+/// * If the column type cannot be shared arithmetically or the value does not parse
+pub fn encode_arithmetic(value: &str, column: &ColumnDescriptor) -> u64 {
+    match (&column.type_hint, column.decimals) {
+        (ColumnType::Boolean, _) => encode_bool(value) as u64,
+        (ColumnType::UnsignedInt, _) => value.parse::<u32>().expect("Invalid u32 value") as u64,
+        (ColumnType::Float, Some(decimals)) => encode_fixed_point(value, decimals),
+        (other, _) => panic!("Column type {:?} cannot be shared arithmetically", other),
+    }
+}
+
+/// Checks that every arithmetically shared column has an integer type;
+/// fixed-point Float columns count as integers.
+///
+/// # Returns
+/// * `Err` naming the first column whose type cannot be shared arithmetically
+pub fn check_arithmetic_columns(columns: &[ColumnDescriptor]) -> Result<(), String> {
+    for column in columns {
+        let integer = match column.type_hint {
+            ColumnType::Boolean | ColumnType::UnsignedInt => true,
+            ColumnType::Float => column.decimals.is_some(),
+            ColumnType::String { .. } => false,
+        };
+        if column.sharing == Sharing::Arithmetic && !integer {
+            return Err(format!(
+                "Column '{}' of type {:?} cannot be shared arithmetically",
+                column.name, column.type_hint
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Checks that only Float columns are fixed point, with a scale that fits
+/// a 64-bit integer.
+///
+/// # Returns
+/// * `Err` naming the first column with invalid `decimals`
+pub fn check_fixed_point_columns(columns: &[ColumnDescriptor]) -> Result<(), String> {
+    for column in columns {
+        match (column.decimals, &column.type_hint) {
+            (None, _) => {}
+            (Some(decimals), ColumnType::Float) if decimals <= MAX_DECIMALS => {}
+            (Some(decimals), ColumnType::Float) => {
+                return Err(format!(
+                    "Column '{}' has {} decimals, at most {} are supported",
+                    column.name, decimals, MAX_DECIMALS
+                ));
+            }
+            (Some(_), other) => {
+                return Err(format!("Column '{}' of type {:?} cannot have decimals", column.name, other));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Largest number of decimals of a fixed-point column; 10^19 is the largest
+/// power of ten below 2^64
+pub const MAX_DECIMALS: u32 = 19;
+
 /// Encodes a boolean value into a single bit.
 /// 
 /// Accepts various string representations of boolean values:
@@ -78,17 +158,21 @@ fn encode_bool(value: &str) -> bool {
 /// 
 /// # Example
 /// ```
+/// use data_owner::encode::encode_unsigned;
+///
 /// let bits = encode_unsigned("5");
 /// // bits[0] = true  (bit 0: 1)
 /// // bits[1] = false (bit 1: 0)
 /// // bits[2] = true  (bit 2: 1)
 /// // bits[3..31] = false (remaining bits: 0)
 /// // This represents: 00000000000000000000000000000101 (binary for 5)
+/// assert!(bits[0] && !bits[1] && bits[2]);
+/// assert!(bits[3..].not_any());
 /// ```
 /// 
 /// # Panics
 /// * If the value cannot be parsed as a u32
-fn encode_unsigned(value: &str) -> BitVector {
+pub fn encode_unsigned(value: &str) -> BitVector {
     let n: u32 = value.parse().expect("Invalid u32 value");
     let mut bv = BitVector::new();
     // Extract bits in little-endian order (LSB first)
@@ -116,13 +200,16 @@ fn encode_unsigned(value: &str) -> BitVector {
 /// 
 /// # Example
 /// ```
+/// use data_owner::encode::encode_float;
+///
 /// let bits = encode_float("3.14");
 /// // bits will contain 64 bits representing 3.14 in IEEE 754 format
+/// assert_eq!(bits.len(), 64);
 /// ```
 /// 
 /// # Panics
 /// * If the value cannot be parsed as an f64
-fn encode_float(value: &str) -> BitVector {
+pub fn encode_float(value: &str) -> BitVector {
     let f: f64 = value.parse().expect("Invalid f64 value");
     let bits_u64 = f.to_bits();
     let mut bv = BitVector::new();
@@ -133,6 +220,45 @@ fn encode_float(value: &str) -> BitVector {
     bv
 }
 
+/// Scales a non-negative decimal number by 10^decimals into an integer.
+///
+/// The digits are read exactly rather than through an f64, so e.g. "0.29"
+/// with 2 decimals is 29. Further digits round half up.
+///
+/// # Arguments
+/// * `value` - String representation of the number, e.g. "771.64"
+/// * `decimals` - Number of decimal places kept
+///
+/// # Returns
+/// * `u64` - The value times 10^decimals
+///
+/// # Panics
+/// * If the value is not a non-negative decimal number or does not fit 64 bits
+fn encode_fixed_point(value: &str, decimals: u32) -> u64 {
+    let value = value.trim();
+    let (integer, fraction) = value.split_once('.').unwrap_or((value, ""));
+    let digits_only = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+    if !digits_only(integer) || !digits_only(fraction) || (integer.is_empty() && fraction.is_empty()) {
+        panic!("Invalid fixed-point value: {}", value);
+    }
+    let kept: String = fraction.chars().chain(std::iter::repeat('0')).take(decimals as usize).collect();
+    let round_up = fraction.as_bytes().get(decimals as usize).is_some_and(|digit| *digit >= b'5');
+    let scaled = format!("0{}{}", integer, kept)
+        .parse::<u64>()
+        .ok()
+        .and_then(|scaled| scaled.checked_add(round_up as u64));
+    scaled.unwrap_or_else(|| panic!("Fixed-point value out of range: {}", value))
+}
+
+/// Encodes a 64-bit integer LSB first.
+fn encode_word(value: u64) -> BitVector {
+    let mut bv = BitVector::new();
+    for i in 0..64 {
+        bv.push((value >> i) & 1 == 1);
+    }
+    bv
+}
+
 /// Encodes a string using the specified character set and maximum length.
 /// 
 /// This function converts each character in the string to its binary representation
@@ -153,11 +279,15 @@ fn encode_float(value: &str) -> BitVector {
 /// 
 /// # Example
 /// ```
+/// use data_owner::encode::encode_string;
+/// use data_owner::types::Charset;
+///
 /// let bits = encode_string("hello", 10, &Charset::Ascii);
 /// // Encodes "hello" + 5 null characters using 7 bits per character
 /// // Total: 10 characters × 7 bits = 70 bits
+/// assert_eq!(bits.len(), 70);
 /// ```
-fn encode_string(value: &str, max_chars: usize, charset: &Charset) -> BitVector {
+pub fn encode_string(value: &str, max_chars: usize, charset: &Charset) -> BitVector {
     let mut bv = BitVector::new();
     let chars: Vec<char> = value.chars().collect();
     
@@ -186,4 +316,49 @@ fn encode_string(value: &str, max_chars: usize, charset: &Charset) -> BitVector
     }
     
     bv
-} 
\ No newline at end of file
+} 
+/// Decodes a bit vector produced by `encode_value` back into its string representation.
+/// 
+/// This is the inverse of `encode_value` and is used after reconstruction to turn
+/// the recovered bits into human-readable values. Strings are cut at the first
+/// null padding character.
+/// 
+/// # Arguments
+/// * `bits` - Reconstructed bits in the layout written by `encode_value`
+/// * `column` - Column descriptor containing type information
+/// 
+/// # Returns
+/// * `String` - Human-readable value
+pub fn decode_value(bits: &BitVector, column: &ColumnDescriptor) -> String {
+    // Read `width` bits starting at `start` as an LSB-first unsigned integer
+    let read = |start: usize, width: usize| -> u64 {
+        (0..width).fold(0u64, |acc, j| {
+            let bit = bits.get(start + j).map(|b| *b).unwrap_or(false);
+            acc | ((bit as u64) << j)
+        })
+    };
+
+    match &column.type_hint {
+        ColumnType::Boolean => read(0, 1).eq(&1).to_string(),
+        ColumnType::UnsignedInt => (read(0, 32) as u32).to_string(),
+        ColumnType::Float => match column.decimals {
+            Some(0) => read(0, 64).to_string(),
+            Some(decimals) => {
+                let scale = 10u64.pow(decimals);
+                let value = read(0, 64);
+                format!("{}.{:0width$}", value / scale, value % scale, width = decimals as usize)
+            }
+            None => f64::from_bits(read(0, 64)).to_string(),
+        },
+        ColumnType::String { max_chars, charset } => {
+            let bits_per_char = match charset {
+                Charset::Ascii => 7,
+                Charset::Utf8 => 8,
+            };
+            (0..*max_chars)
+                .map(|i| read(i * bits_per_char, bits_per_char) as u8 as char)
+                .take_while(|c| *c != '\0')
+                .collect()
+        }
+    }
+}
@@ -20,6 +20,7 @@ use share_service::{
     DataOwnerInfo as ProtoDataOwnerInfo, TableSchema as ProtoTableSchema,
     ColumnDescriptor as ProtoColumnDescriptor,
     ColumnType as ProtoColumnType,
+    Sharing as ProtoSharing,
     // Updated imports for binary format
     BinaryPartyData as ProtoBinaryPartyData,
     BinaryRow as ProtoBinaryRow,
@@ -29,7 +30,7 @@ use share_service::{
 };
 
 use crate::types::{
-    TableSchema, ColumnDescriptor, ColumnType, Charset,
+    TableSchema, ColumnDescriptor, ColumnType, Charset, Sharing,
     BinaryPartyData, BinaryRow,
 };
 use crate::config::DataOwnerInfo;
@@ -71,6 +72,12 @@ impl ShareClient {
         ProtoColumnDescriptor {
             name: col.name.clone(),
             type_hint: Some(self.convert_column_type(&col.type_hint)),
+            sharing: match col.sharing {
+                Sharing::Boolean => ProtoSharing::Boolean,
+                Sharing::Arithmetic => ProtoSharing::Arithmetic,
+            } as i32,
+            unique: col.unique,
+            decimals: col.decimals,
         }
     }
 
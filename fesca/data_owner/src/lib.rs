@@ -9,13 +9,14 @@ pub mod grpc_client;
 #[cfg(test)]
 mod tests;
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use log::{info, error};
+use rand::Rng;
 
-use crate::config::load_data_and_config;
-use crate::encode::encode_value;
-use crate::types::{ColumnType, BinaryPartyData, BinaryRow, Charset};
-use crate::sharing::share_bit_vector;
+use crate::config::{load_data_owner_config, load_table_data, DataOwnerConfig};
+use crate::encode::{check_arithmetic_columns, check_fixed_point_columns, encode_arithmetic, encode_value};
+use crate::types::{ColumnType, BinaryPartyData, BinaryRow, Charset, Sharing, TableSchema};
+use crate::sharing::{share_row, RowField};
 use crate::grpc_client::ShareClient;
 
 /// Loads data, creates 3-party secret shares, and distributes to computing nodes.
@@ -28,29 +29,84 @@ pub fn run_data_owner() -> Result<()> {
 
 /// Internal async implementation of data owner functionality
 async fn run_data_owner_async() -> Result<()> {
-
-    // Step 1: Load TBL data, schema, and configuration from unified config file
     let config_path = "config_data_owner.json";
-    
-    let (records, schema, config) = match load_data_and_config(config_path) {
-        Ok((records, schema, config)) => {
-            info!("Loaded {} records and schema for table '{}'.", records.len(), schema.table_name);
+
+    let config = match load_data_owner_config(config_path) {
+        Ok(config) => {
             info!("Loaded data owner configuration");
-            (records, schema, config)
+            config
         },
         Err(e) => {
-            error!("Error loading data, schema, or configuration: {e}");
+            error!("Error loading configuration: {e}");
             std::process::exit(1);
         }
     };
 
-    // Step 2: Initialize random number generator for secret sharing
+    run_data_owner_with_config(config).await
+}
+
+/// Loads the configured table, creates 3-party secret shares, and sends them to
+/// the computing nodes listed in `config`. Usable from an existing tokio runtime.
+pub async fn run_data_owner_with_config(config: DataOwnerConfig) -> Result<()> {
+
+    // Step 1: Load TBL data and schema from the configured data path
+    let (records, schema) = load_table_data(&config.data_path)
+        .map_err(|e| anyhow!("Error loading data or schema: {e}"))?;
+    info!("Loaded {} records and schema for table '{}'.", records.len(), schema.table_name);
+    check_arithmetic_columns(&schema.columns).map_err(|e| anyhow!(e))?;
+    check_fixed_point_columns(&schema.columns).map_err(|e| anyhow!(e))?;
+
+    // Step 2: Encode and share every record, one row per party
     let mut rng = rand::thread_rng();
+    let [binary_party0, binary_party1, binary_party2] = share_table(&records, &schema, &mut rng);
+
+    info!("Binary data ready for transmission. Party 0 has {} rows with {} bytes per row", 
+             binary_party0.rows.len(),
+             binary_party0.rows.first().map(|r| r.bitstring_a.len() + r.bitstring_b.len()).unwrap_or(0));
+    
+    // Step 3: Send individual party data to computing nodes via gRPC
+    info!("Sending shares to computing nodes...");
+    
+    let client = ShareClient::new(config.data_owner);
+    let node_urls = config.computing_nodes.as_array();
+    
+    // Send binary data to each computing node using the new binary format
+    let responses = client.send_binary_table_shares(
+        &schema,
+        &[binary_party0.clone(), binary_party1.clone(), binary_party2.clone()],
+        &node_urls,
+    ).await.map_err(|e| anyhow!("Error sending data to computing nodes: {e}"))?;
+
+    for (i, response) in responses.iter().enumerate() {
+        info!("Node {} response: success={}, message={}, path={}", 
+                 i, response.success, response.message, response.storage_path);
+        if !response.success {
+            return Err(anyhow!("Node {} failed to store shares: {}", i, response.message));
+        }
+    }
+    
+    info!("Data sharing completed successfully!");
+    info!("Party data sizes - Binary format:");
+    info!("  Party 0: {} rows, {} bytes per row", binary_party0.rows.len(), 
+             binary_party0.rows.first().map(|r| r.bitstring_a.len() + r.bitstring_b.len()).unwrap_or(0));
+    info!("  Party 1: {} rows, {} bytes per row", binary_party1.rows.len(),
+             binary_party1.rows.first().map(|r| r.bitstring_a.len() + r.bitstring_b.len()).unwrap_or(0));
+    info!("  Party 2: {} rows, {} bytes per row", binary_party2.rows.len(),
+             binary_party2.rows.first().map(|r| r.bitstring_a.len() + r.bitstring_b.len()).unwrap_or(0));
     
-    // Step 3: Calculate column bit sizes for binary format
+    Ok(())
+}
+
+/// Encode every record and split it into 3-party shares. Returns the rows of
+/// parties 0, 1 and 2, each row holding the party's two bitstrings and the
+/// bit offset and length of every column in them.
+fn share_table(records: &[Vec<String>], schema: &TableSchema, rng: &mut impl Rng) -> [BinaryPartyData; 3] {
+    // Calculate column bit sizes for binary format
     let mut column_bit_sizes = Vec::new();
     for col_desc in &schema.columns {
         let bit_size = match &col_desc.type_hint {
+            // Arithmetic shares are elements of Z_2^64
+            _ if col_desc.sharing == Sharing::Arithmetic => 64,
             ColumnType::Boolean => 1,
             ColumnType::UnsignedInt => 32,
             ColumnType::Float => 64,
@@ -65,7 +121,7 @@ async fn run_data_owner_async() -> Result<()> {
         column_bit_sizes.push(bit_size);
     }
     
-    // Step 4: Initialize binary party data structures directly
+    // Initialize binary party data structures directly
     let mut binary_party0 = BinaryPartyData {
         party_id: 0,
         table_id: schema.table_id,
@@ -85,55 +141,52 @@ async fn run_data_owner_async() -> Result<()> {
     info!("Encoding, sharing, and converting to binary format...");
     let mut processed_rows = 0;
     
-    // Step 5: Process each record in the TBL data, generating binary data directly
+    // Process each record in the TBL data, generating binary data directly
     for (row_idx, record) in records.iter().enumerate() {
-        // Initialize binary data containers for each party
-        let mut bitstring_a0 = Vec::new();
-        let mut bitstring_b0 = Vec::new();
-        let mut bitstring_a1 = Vec::new();
-        let mut bitstring_b1 = Vec::new();
-        let mut bitstring_a2 = Vec::new();
-        let mut bitstring_b2 = Vec::new();
+        // Collect the encoded fields of all columns contiguously, so the recorded
+        // bit offsets point at the right bits for every column width
+        let mut row_fields = Vec::new();
         let mut column_bit_offsets = Vec::new();
         let mut current_offset = 0u32;
         
-        // Step 6: Process each field in the record
+        // Process each field in the record
         for (col_idx, (field, col_desc)) in record.iter().zip(&schema.columns).enumerate() {
             column_bit_offsets.push(current_offset);
             
-            // Encode all types uniformly using encode_value, then share the bits
-            let bits = encode_value(field, col_desc);
-            let ((a0_bytes, b0_bytes), (a1_bytes, b1_bytes), (a2_bytes, b2_bytes)) = share_bit_vector(&bits, &mut rng);
-            
-            // Append the byte shares directly to each party's bitstrings
-            bitstring_a0.extend_from_slice(&a0_bytes);
-            bitstring_b0.extend_from_slice(&b0_bytes);
-            bitstring_a1.extend_from_slice(&a1_bytes);
-            bitstring_b1.extend_from_slice(&b1_bytes);
-            bitstring_a2.extend_from_slice(&a2_bytes);
-            bitstring_b2.extend_from_slice(&b2_bytes);
+            // Encode all types uniformly using encode_value, unless shared arithmetically
+            row_fields.push(match col_desc.sharing {
+                Sharing::Boolean => RowField::Bits(encode_value(field, col_desc)),
+                Sharing::Arithmetic => RowField::Arithmetic(encode_arithmetic(field, col_desc)),
+            });
             
             current_offset += column_bit_sizes[col_idx];
         }
+
+        // Share the whole row at once; each party gets its two byte strings
+        let ((bitstring_a0, bitstring_b0), (bitstring_a1, bitstring_b1), (bitstring_a2, bitstring_b2)) =
+            share_row(&row_fields, rng);
+
+        // Rows with fewer fields than the schema only describe the stored columns
+        let column_bit_lengths = column_bit_sizes[..column_bit_offsets.len()].to_vec();
         
-        // Step 7: Create BinaryRow objects and add to each party's data
+        // Create BinaryRow objects and add to each party's data
         let binary_row0 = BinaryRow {
             bitstring_a: bitstring_a0,
             bitstring_b: bitstring_b0,
             column_bit_offsets: column_bit_offsets.clone(),
-            column_bit_lengths: column_bit_sizes.clone(),
+            column_bit_lengths: column_bit_lengths.clone(),
         };
         let binary_row1 = BinaryRow {
             bitstring_a: bitstring_a1,
             bitstring_b: bitstring_b1,
             column_bit_offsets: column_bit_offsets.clone(),
-            column_bit_lengths: column_bit_sizes.clone(),
+            column_bit_lengths: column_bit_lengths.clone(),
         };
         let binary_row2 = BinaryRow {
             bitstring_a: bitstring_a2,
             bitstring_b: bitstring_b2,
             column_bit_offsets: column_bit_offsets.clone(),
-            column_bit_lengths: column_bit_sizes.clone(),
+            column_bit_lengths: column_bit_lengths.clone(),
         };
         
         binary_party0.rows.push(binary_row0);
@@ -148,48 +201,11 @@ async fn run_data_owner_async() -> Result<()> {
         
         // Debug output for first few rows
         if row_idx < 2 {
-            info!("Row {row_idx} shared: first field = {:?}", record.get(0));
+            info!("Row {row_idx} shared: first field = {:?}", record.first());
         }
     }
     
     info!("All records encoded, shared, and converted to binary. Total rows processed: {}", processed_rows);
-    
-    info!("Binary data ready for transmission. Party 0 has {} rows with {} bytes per row", 
-             binary_party0.rows.len(),
-             binary_party0.rows.get(0).map(|r| r.bitstring_a.len() + r.bitstring_b.len()).unwrap_or(0));
-    
-    // Step 8: Send individual party data to computing nodes via gRPC
-    info!("Sending shares to computing nodes...");
-    
-    let client = ShareClient::new(config.data_owner);
-    let node_urls = config.computing_nodes.as_array();
-    
-    // Send binary data to each computing node using the new binary format
-    match client.send_binary_table_shares(
-        &schema,
-        &[binary_party0.clone(), binary_party1.clone(), binary_party2.clone()],
-        &node_urls,
-    ).await {
-        Ok(responses) => {
-            for (i, response) in responses.iter().enumerate() {
-                info!("Node {} response: success={}, message={}, path={}", 
-                         i, response.success, response.message, response.storage_path);
-            }
-        },
-        Err(e) => {
-            error!("Error sending data to computing nodes: {}", e);
-            // Continue execution for now, but log the error
-        }
-    }
-    
-    info!("Data sharing completed successfully!");
-    info!("Party data sizes - Binary format:");
-    info!("  Party 0: {} rows, {} bytes per row", binary_party0.rows.len(), 
-             binary_party0.rows.get(0).map(|r| r.bitstring_a.len() + r.bitstring_b.len()).unwrap_or(0));
-    info!("  Party 1: {} rows, {} bytes per row", binary_party1.rows.len(),
-             binary_party1.rows.get(0).map(|r| r.bitstring_a.len() + r.bitstring_b.len()).unwrap_or(0));
-    info!("  Party 2: {} rows, {} bytes per row", binary_party2.rows.len(),
-             binary_party2.rows.get(0).map(|r| r.bitstring_a.len() + r.bitstring_b.len()).unwrap_or(0));
-    
-    Ok(())
-}
\ No newline at end of file
+
+    [binary_party0, binary_party1, binary_party2]
+}
@@ -1,63 +1,89 @@
 use rand::Rng;
 use crate::types::BitVector;
 
+/// Byte shares (share_a, share_b) held by a single party.
+pub type PartyShareBytes = (Vec<u8>, Vec<u8>);
+
+/// One encoded field of a row, together with how it is shared.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RowField {
+    /// Encoded bits, shared with XOR
+    Bits(BitVector),
+    /// Integer in Z_2^64, shared additively; each share takes 64 bits (LSB first)
+    Arithmetic(u64),
+}
+
 /// Share a BitVector using 3-party replicated secret sharing and convert to bytes.
 /// Returns three tuples, each containing (share_a_bytes, share_b_bytes) for each party.
-pub fn share_bit_vector(bits: &BitVector, rng: &mut impl Rng) -> ((Vec<u8>, Vec<u8>), (Vec<u8>, Vec<u8>), (Vec<u8>, Vec<u8>)) {
+/// With bits = x_0 ⊕ x_1 ⊕ x_2, party i receives (x_i, x_{i-1}): its own share first,
+/// then the share it has in common with the previous party. This matches the AND
+/// protocol on the computing nodes, where node i sends to node i+1.
+pub fn share_bit_vector(bits: &BitVector, rng: &mut impl Rng) -> (PartyShareBytes, PartyShareBytes, PartyShareBytes) {
+    share_row(&[RowField::Bits(bits.clone())], rng)
+}
+
+/// Split a value into additive shares x_0 + x_1 + x_2 = value (mod 2^64).
+pub fn share_arithmetic(value: u64, rng: &mut impl Rng) -> [u64; 3] {
+    let x0: u64 = rng.gen();
+    let x1: u64 = rng.gen();
+    [x0, x1, value.wrapping_sub(x0).wrapping_sub(x1)]
+}
+
+/// Share a row whose fields may use different sharings, concatenating the
+/// fields' shares in order. Party i receives (x_i, x_{i-1}) as in `share_bit_vector`.
+pub fn share_row(fields: &[RowField], rng: &mut impl Rng) -> (PartyShareBytes, PartyShareBytes, PartyShareBytes) {
     let mut a_bits = BitVector::new();
     let mut b_bits = BitVector::new();
     let mut c_bits = BitVector::new();
-    
-    // Generate random shares for each bit
-    for bit in bits.iter() {
-        let a = rng.gen_bool(0.5);
-        let b = rng.gen_bool(0.5);
-        let c = *bit ^ a ^ b;  // Ensure XOR reconstruction works
-        
-        a_bits.push(a);
-        b_bits.push(b);
-        c_bits.push(c);
-    }
-    
-    // Convert bit vectors to bytes directly
-    let mut a_bytes = Vec::new();
-    for chunk in a_bits.chunks(8) {
-        let mut byte = 0u8;
-        for (i, bit) in chunk.iter().enumerate() {
-            if *bit {
-                byte |= 1 << i;
+
+    for field in fields {
+        match field {
+            RowField::Bits(bits) => {
+                // Generate random shares for each bit
+                for bit in bits.iter() {
+                    let a = rng.gen_bool(0.5);
+                    let b = rng.gen_bool(0.5);
+                    let c = *bit ^ a ^ b;  // Ensure XOR reconstruction works
+
+                    a_bits.push(a);
+                    b_bits.push(b);
+                    c_bits.push(c);
+                }
             }
-        }
-        a_bytes.push(byte);
-    }
-    
-    let mut b_bytes = Vec::new();
-    for chunk in b_bits.chunks(8) {
-        let mut byte = 0u8;
-        for (i, bit) in chunk.iter().enumerate() {
-            if *bit {
-                byte |= 1 << i;
+            RowField::Arithmetic(value) => {
+                let [a, b, c] = share_arithmetic(*value, rng);
+                for i in 0..64 {
+                    a_bits.push((a >> i) & 1 == 1);
+                    b_bits.push((b >> i) & 1 == 1);
+                    c_bits.push((c >> i) & 1 == 1);
+                }
             }
         }
-        b_bytes.push(byte);
     }
-    
-    let mut c_bytes = Vec::new();
-    for chunk in c_bits.chunks(8) {
+
+    let a_bytes = to_bytes(&a_bits);
+    let b_bytes = to_bytes(&b_bits);
+    let c_bytes = to_bytes(&c_bits);
+
+    // Return bytes for each party: (share_a, share_b) with x_0 = a, x_1 = b, x_2 = c
+    (
+        (a_bytes.clone(), c_bytes.clone()),    // Party 0: shares a and c
+        (b_bytes.clone(), a_bytes),            // Party 1: shares b and a
+        (c_bytes, b_bytes),                    // Party 2: shares c and b
+    )
+}
+
+/// Convert a bit vector to bytes, LSB first within each byte.
+fn to_bytes(bits: &BitVector) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for chunk in bits.chunks(8) {
         let mut byte = 0u8;
         for (i, bit) in chunk.iter().enumerate() {
             if *bit {
                 byte |= 1 << i;
             }
         }
-        c_bytes.push(byte);
+        bytes.push(byte);
     }
-    
-    // Return bytes for each party: (share_a, share_b)
-    (
-        (a_bytes.clone(), b_bytes.clone()),    // Party 0: shares a and b
-        (b_bytes.clone(), c_bytes.clone()),    // Party 1: shares b and c  
-        (a_bytes, c_bytes),                    // Party 2: shares a and c
-    )
+    bytes
 }
-
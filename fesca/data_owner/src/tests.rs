@@ -0,0 +1,169 @@
+// Data Owner Tests
+// ================
+// Unit tests for encoding and secret sharing of table values.
+
+use crate::encode::{check_arithmetic_columns, check_fixed_point_columns, decode_value, encode_arithmetic, encode_value};
+use crate::sharing::{share_bit_vector, share_row, RowField};
+use crate::share_table;
+use crate::types::{BitVector, Charset, ColumnDescriptor, ColumnType, Sharing, TableSchema};
+
+fn column(type_hint: ColumnType) -> ColumnDescriptor {
+    ColumnDescriptor {
+        name: "test".to_string(),
+        type_hint,
+        sharing: Sharing::Boolean,
+        unique: false,
+        decimals: None,
+    }
+}
+
+/// Reconstruct the first `len` bits from the three distinct byte shares.
+fn reconstruct_bits(a: &[u8], b: &[u8], c: &[u8], len: usize) -> Vec<bool> {
+    (0..len)
+        .map(|i| {
+            let byte = a[i / 8] ^ b[i / 8] ^ c[i / 8];
+            (byte >> (i % 8)) & 1 == 1
+        })
+        .collect()
+}
+
+#[test]
+fn test_encode_unsigned_is_lsb_first() {
+    let bits = encode_value("5", &column(ColumnType::UnsignedInt));
+    assert_eq!(bits.len(), 32);
+    assert!(bits[0]);
+    assert!(!bits[1]);
+    assert!(bits[2]);
+    assert!(bits[3..].iter().all(|b| !*b));
+}
+
+#[test]
+fn test_encode_string_pads_to_max_chars() {
+    let charset = Charset::Ascii;
+    let bits = encode_value("ab", &column(ColumnType::String { max_chars: 4, charset }));
+    assert_eq!(bits.len(), 28);
+    // 'a' = 0x61 in the first 7 bits, padding characters are zero
+    let first: u32 = (0..7).map(|j| (bits[j] as u32) << j).sum();
+    assert_eq!(first, 'a' as u32);
+    assert!(bits[14..].iter().all(|b| !*b));
+}
+
+#[test]
+fn test_share_bit_vector_reconstructs() {
+    let mut rng = rand::thread_rng();
+    let bits: BitVector = encode_value("3325", &column(ColumnType::UnsignedInt));
+    let ((a0, c0), (b1, a1), (c2, b2)) = share_bit_vector(&bits, &mut rng);
+
+    // Party i holds (x_i, x_{i-1}), so replicated copies must agree between neighbours
+    assert_eq!(a0, a1);
+    assert_eq!(b1, b2);
+    assert_eq!(c2, c0);
+
+    let reconstructed = reconstruct_bits(&a0, &b1, &c2, bits.len());
+    assert_eq!(reconstructed, bits.iter().map(|b| *b).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_decode_value_roundtrip() {
+    let charset = Charset::Ascii;
+    let cases = [
+        (ColumnType::Boolean, "true"),
+        (ColumnType::UnsignedInt, "8076"),
+        (ColumnType::Float, "993.49"),
+        (ColumnType::String { max_chars: 8, charset }, "ven ide"),
+    ];
+    for (type_hint, value) in cases {
+        let col = column(type_hint);
+        assert_eq!(decode_value(&encode_value(value, &col), &col), value);
+    }
+}
+
+#[test]
+fn test_share_row_mixes_sharings() {
+    let mut rng = rand::thread_rng();
+    let flag = encode_value("1", &column(ColumnType::Boolean));
+    let fields = [RowField::Bits(flag), RowField::Arithmetic(8076)];
+    let ((a0, c0), (b1, a1), (c2, b2)) = share_row(&fields, &mut rng);
+    assert_eq!((a0.len(), a0.clone()), (9, a1));
+    assert_eq!((b1, c2.clone()), (b2.clone(), c0));
+
+    // Bit 0 is XOR shared, bits 1..65 hold the additive shares
+    assert_eq!(reconstruct_bits(&a0, &b2, &c2, 1), vec![true]);
+    let read = |bytes: &[u8]| (0..64).fold(0u64, |acc, j| acc | ((((bytes[(j + 1) / 8] >> ((j + 1) % 8)) & 1) as u64) << j));
+    assert_eq!(read(&a0).wrapping_add(read(&b2)).wrapping_add(read(&c2)), 8076);
+}
+
+#[test]
+fn test_arithmetic_sharing_only_for_integers() {
+    let mut columns = vec![column(ColumnType::UnsignedInt), column(ColumnType::Float)];
+    columns[0].sharing = Sharing::Arithmetic;
+    assert!(check_arithmetic_columns(&columns).is_ok());
+    columns[1].sharing = Sharing::Arithmetic;
+    assert!(check_arithmetic_columns(&columns).is_err());
+    assert_eq!(encode_arithmetic("5000", &columns[0]), 5000);
+}
+
+#[test]
+fn test_share_table_packs_columns_contiguously() {
+    let mut rng = rand::thread_rng();
+    let charset = Charset::Ascii;
+    // 1 + 21 + 32 + 64 bits: every column after the first starts off a byte boundary
+    let mut columns = vec![
+        column(ColumnType::Boolean),
+        column(ColumnType::String { max_chars: 3, charset }),
+        column(ColumnType::UnsignedInt),
+        column(ColumnType::UnsignedInt),
+    ];
+    columns[3].sharing = Sharing::Arithmetic;
+    let schema = TableSchema { table_name: "test".to_string(), table_id: 1, columns, row_count: 2 };
+    let records: Vec<Vec<String>> = vec![
+        ["true", "abc", "3325", "8076"].map(String::from).to_vec(),
+        ["false", "xy", "4294967295", "1"].map(String::from).to_vec(),
+    ];
+
+    let [party0, party1, party2] = share_table(&records, &schema, &mut rng);
+    for (row_idx, record) in records.iter().enumerate() {
+        let [row0, row1, row2] = [&party0, &party1, &party2].map(|party| &party.rows[row_idx]);
+        assert_eq!(row0.column_bit_offsets, vec![0, 1, 22, 54]);
+        assert_eq!(row0.column_bit_lengths, vec![1, 21, 32, 64]);
+        assert_eq!(row0.bitstring_a.len(), 15);
+
+        // Party i holds (x_i, x_{i-1})
+        let all = reconstruct_bits(&row0.bitstring_a, &row1.bitstring_a, &row2.bitstring_a, 118);
+        for (col_idx, col) in schema.columns.iter().enumerate().take(3) {
+            let offset = row0.column_bit_offsets[col_idx] as usize;
+            let length = row0.column_bit_lengths[col_idx] as usize;
+            let bits: BitVector = all[offset..offset + length].iter().collect();
+            assert_eq!(bits, encode_value(&record[col_idx], col));
+        }
+        // The arithmetic column's additive shares sit at bits 54..118
+        let read = |bytes: &[u8]| {
+            (54..118).fold(0u64, |acc, i| acc | ((((bytes[i / 8] >> (i % 8)) & 1) as u64) << (i - 54)))
+        };
+        let shares = [&row0.bitstring_a, &row1.bitstring_a, &row2.bitstring_a].map(|bytes| read(bytes));
+        assert_eq!(shares.iter().fold(0u64, |acc, x| acc.wrapping_add(*x)), record[3].parse::<u64>().unwrap());
+    }
+}
+
+#[test]
+fn test_fixed_point_floats_are_scaled_integers() {
+    let cost = ColumnDescriptor { decimals: Some(2), ..column(ColumnType::Float) };
+    let bits = encode_value("771.64", &cost);
+    assert_eq!(bits.len(), 64);
+    let value: u64 = (0..64).map(|j| (bits[j] as u64) << j).sum();
+    assert_eq!(value, 77164);
+    assert_eq!(decode_value(&bits, &cost), "771.64");
+    assert_eq!(decode_value(&encode_value("5", &cost), &cost), "5.00");
+    // Exact digits, rounding half up beyond the kept ones
+    assert_eq!(encode_arithmetic("0.29", &cost), 29);
+    assert_eq!(encode_arithmetic("1.005", &cost), 101);
+    assert_eq!(encode_arithmetic(".5", &cost), 50);
+
+    let arithmetic = ColumnDescriptor { sharing: Sharing::Arithmetic, ..cost.clone() };
+    assert!(check_arithmetic_columns(std::slice::from_ref(&arithmetic)).is_ok());
+    assert!(check_fixed_point_columns(&[cost, arithmetic]).is_ok());
+    let count = ColumnDescriptor { decimals: Some(2), ..column(ColumnType::UnsignedInt) };
+    assert!(check_fixed_point_columns(&[count]).is_err());
+    let too_fine = ColumnDescriptor { decimals: Some(20), ..column(ColumnType::Float) };
+    assert!(check_fixed_point_columns(&[too_fine]).is_err());
+}
@@ -16,15 +16,29 @@ pub enum Charset {
 pub enum ColumnType {
     Boolean,
     UnsignedInt,       // Only u32 is supported (32 bits)
-    Float,             // f64 (64 bits)
+    Float,             // f64 (64 bits), or fixed point with `decimals`
     String { max_chars: usize, charset: Charset }, // Fixed-length string encoding
 }
 
+/// How the values of a column are secret shared.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub enum Sharing {
+    #[default]
+    Boolean,     // XOR shares of the encoded bits
+    Arithmetic,  // Additive shares over Z_2^64 (UnsignedInt and Boolean columns only)
+}
+
 /// Column metadata for table schema.
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct ColumnDescriptor {
     pub name: String,
     pub type_hint: ColumnType,
+    #[serde(default)]
+    pub sharing: Sharing,
+    #[serde(default)]
+    pub unique: bool,  // Every row holds a different value (e.g. a primary key)
+    #[serde(default)]
+    pub decimals: Option<u32>,  // Float only: stored as the value times 10^decimals (fixed point)
 }
 
 /// Table schema with column definitions and metadata.
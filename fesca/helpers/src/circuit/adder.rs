@@ -0,0 +1,174 @@
+// Adder Circuits
+// ==============
+// Addition and subtraction of boolean-shared unsigned integers modulo
+// 2^width. Two carry computations are offered, selectable per call:
+//
+// * Ripple-carry: c_{i+1} = c_i ^ ((a_i ^ c_i) & (b_i ^ c_i)), one AND per bit
+//   but one round per bit (width - 1 rounds).
+// * Kogge-Stone: generate g_i = a_i & b_i and propagate p_i = a_i ^ b_i, then
+//   a parallel prefix over (G, P) with
+//       (G, P) = (G_hi ^ (P_hi & G_lo), P_hi & P_lo)
+//   costing 1 + ceil(log2(width - 1)) rounds and about width * log2(width) ANDs.
+//
+// Subtraction is a + !b + 1; the carry-in is folded into bit 0 locally.
+
+use super::builder::CircuitBuilder;
+use super::Wire;
+
+/// Carry computation of `add_bits` and `sub_bits`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AdderKind {
+    /// c_{i+1} = c_i ^ ((a_i ^ c_i) & (b_i ^ c_i)): one AND per bit, the
+    /// fewest bytes sent, but one round per bit
+    RippleCarry,
+    /// Kogge-Stone parallel prefix over (generate, propagate): about
+    /// width * log2(width) ANDs in at most 1 + ceil(log2(width - 1)) rounds
+    #[default]
+    KoggeStone,
+}
+
+impl CircuitBuilder {
+    /// Row-wise a + b modulo 2^max(width)
+    pub fn add_bits(&mut self, a: &[Wire], b: &[Wire], kind: AdderKind) -> Vec<Wire> {
+        self.add_with_carry(a, b, false, kind)
+    }
+
+    /// Row-wise a - b modulo 2^max(width), computed as a + !b + 1
+    pub fn sub_bits(&mut self, a: &[Wire], b: &[Wire], kind: AdderKind) -> Vec<Wire> {
+        let (a, b) = self.pad(a, b);
+        let not_b: Vec<Wire> = b.iter().map(|w| self.not(*w)).collect();
+        self.add_with_carry(&a, &not_b, true, kind)
+    }
+
+    fn add_with_carry(&mut self, a: &[Wire], b: &[Wire], carry_in: bool, kind: AdderKind) -> Vec<Wire> {
+        let rows = self.rows_of(a, b);
+        let (a, b) = self.pad(a, b);
+        let width = a.len();
+        let propagate: Vec<Wire> = a.iter().zip(&b).map(|(x, y)| self.xor(*x, *y)).collect();
+
+        // Carry into bit i; only bits 0..width-1 produce a carry that is used
+        let needed = width.saturating_sub(1);
+        let mut carries = vec![self.const_bit(carry_in, rows)];
+        match kind {
+            AdderKind::RippleCarry => {
+                for i in 0..needed {
+                    let carry = carries[i];
+                    let (x, y) = (self.xor(a[i], carry), self.xor(b[i], carry));
+                    let both = self.and(x, y);
+                    carries.push(self.xor(carry, both));
+                }
+            }
+            AdderKind::KoggeStone => carries.extend(self.prefix_carries(&a, &b, &propagate, carry_in, needed)),
+        }
+        (0..width).map(|i| self.xor(propagate[i], carries[i])).collect()
+    }
+
+    /// Carries out of bits 0..needed with a Kogge-Stone prefix over
+    /// (generate, propagate)
+    fn prefix_carries(
+        &mut self,
+        a: &[Wire],
+        b: &[Wire],
+        propagate: &[Wire],
+        carry_in: bool,
+        needed: usize,
+    ) -> Vec<Wire> {
+        let mut generate: Vec<Wire> = (0..needed).map(|i| self.and(a[i], b[i])).collect();
+        let mut spans = propagate[..needed].to_vec();
+        if carry_in && needed > 0 {
+            // g_0 | p_0 = g_0 ^ p_0 since they are never both set
+            generate[0] = self.xor(generate[0], propagate[0]);
+        }
+        let mut distance = 1;
+        while distance < needed {
+            let (previous_generate, previous_spans) = (generate.clone(), spans.clone());
+            for i in distance..needed {
+                let carried = self.and(previous_spans[i], previous_generate[i - distance]);
+                generate[i] = self.xor(previous_generate[i], carried);
+                if i >= 2 * distance {
+                    spans[i] = self.and(previous_spans[i], previous_spans[i - distance]);
+                }
+            }
+            distance *= 2;
+        }
+        generate
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::plain::evaluate;
+    use crate::circuit::{InputColumn, InputSharing, OutputWires};
+
+    fn column(column: usize, width: u32, rows: usize) -> InputColumn {
+        InputColumn {
+            table: "t".to_string(),
+            table_id: 0,
+            schema_hash: 0,
+            column,
+            width,
+            sharing: InputSharing::Boolean,
+            rows,
+        }
+    }
+
+    /// Sums and differences of a and b for both adder kinds, in that order
+    fn sums_and_differences(a: &[u64], a_width: u32, b: &[u64], b_width: u32) -> Vec<Vec<u64>> {
+        let mut builder = CircuitBuilder::new();
+        let ia = builder.input(column(0, a_width, a.len()));
+        let ib = builder.input(column(1, b_width, b.len()));
+        let (bits_a, bits_b) = (builder.input_bits(ia), builder.input_bits(ib));
+        for kind in [AdderKind::RippleCarry, AdderKind::KoggeStone] {
+            let sum = builder.add_bits(&bits_a, &bits_b, kind);
+            builder.output(&format!("sum {:?}", kind), OutputWires::Bits(sum));
+            let difference = builder.sub_bits(&bits_a, &bits_b, kind);
+            builder.output(&format!("difference {:?}", kind), OutputWires::Bits(difference));
+        }
+        evaluate(&builder.finish(), &[a.to_vec(), b.to_vec()]).unwrap()
+    }
+
+    #[test]
+    fn test_adders_match_plaintext() {
+        let a = vec![0u64, 1, 5000, 4999, 0xFFFF, 77];
+        let b = vec![0u64, 2, 5000, 5001, 1, 77];
+        let mask = (1 << 20) - 1;
+        let sums: Vec<u64> = a.iter().zip(&b).map(|(x, y)| (x + y) & mask).collect();
+        let differences: Vec<u64> = a.iter().zip(&b).map(|(x, y)| x.wrapping_sub(*y) & mask).collect();
+        let outputs = sums_and_differences(&a, 16, &b, 20);
+        assert_eq!(outputs, vec![sums.clone(), differences.clone(), sums, differences]);
+    }
+
+    #[test]
+    fn test_narrow_widths() {
+        for width in [1u32, 2, 3, 5] {
+            let values: Vec<u64> = (0..1u64 << width).collect();
+            let others: Vec<u64> = values.iter().rev().copied().collect();
+            let mask = (1u64 << width) - 1;
+            let sums: Vec<u64> = values.iter().zip(&others).map(|(x, y)| (x + y) & mask).collect();
+            let differences: Vec<u64> = values.iter().zip(&others).map(|(x, y)| x.wrapping_sub(*y) & mask).collect();
+            let outputs = sums_and_differences(&values, width, &others, width);
+            assert_eq!(outputs, vec![sums.clone(), differences.clone(), sums, differences], "width {}", width);
+        }
+    }
+
+    #[test]
+    fn test_adder_kinds_trade_rounds_for_ands() {
+        let shape = |kind: AdderKind| {
+            let mut builder = CircuitBuilder::new();
+            let ia = builder.input(column(0, 20, 3));
+            let ib = builder.input(column(1, 21, 3));
+            let (a, b) = (builder.input_bits(ia), builder.input_bits(ib));
+            let sum = builder.add_bits(&a, &b, kind);
+            builder.output("sum", OutputWires::Bits(sum));
+            let circuit = builder.finish();
+            (circuit.gate_counts()["AND"], circuit.depth())
+        };
+        // 21 bits: 20 carries, one round each, or ceil(log2(20)) rounds with
+        // more ANDs
+        assert_eq!(shape(AdderKind::RippleCarry), (20, 20));
+        let (ands, depth) = shape(AdderKind::KoggeStone);
+        assert_eq!(depth, 5);
+        assert!(ands > 20);
+    }
+}
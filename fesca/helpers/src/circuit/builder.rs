@@ -0,0 +1,788 @@
+// Circuit Builder
+// ===============
+// Incremental construction of a `Circuit` plus the gadgets queries are made
+// of (multiplexers, folds over rows, sorting networks); comparisons, adders
+// and share conversions are in `compare`, `adder` and `convert`.
+// Bit vectors are `Vec<Wire>` with the least significant bit first, like the
+// stored bit planes.
+//
+// The builder folds public constants (an AND with a constant 0 is a constant,
+// an AND with a constant 1 is its other input, ...) and reuses identical gates,
+// so gadgets can be written generically and comparisons against literals still
+// come out with local leaves. `finish` drops gates and inputs no output
+// depends on.
+
+use std::collections::HashMap;
+
+use super::{Circuit, Gate, InputColumn, InputSharing, Output, OutputWires, Wire, WireKind, WireShape};
+
+/// Builds a circuit gate by gate
+#[derive(Debug, Default)]
+pub struct CircuitBuilder {
+    circuit: Circuit,
+    shapes: Vec<WireShape>,
+    /// Public value of each wire, if it is a constant
+    known: Vec<Option<u64>>,
+    existing: HashMap<Gate, Wire>,
+}
+
+impl CircuitBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a stored column; registering the same column twice returns the same index
+    pub fn input(&mut self, column: InputColumn) -> usize {
+        if let Some(index) = self.circuit.inputs.iter().position(|c| *c == column) {
+            return index;
+        }
+        self.circuit.inputs.push(column);
+        self.circuit.inputs.len() - 1
+    }
+
+    /// All bit planes of a boolean-shared input
+    pub fn input_bits(&mut self, input: usize) -> Vec<Wire> {
+        let width = self.circuit.inputs[input].width;
+        (0..width).map(|bit| self.push(Gate::Input { input, bit })).collect()
+    }
+
+    /// Words of an arithmetically shared input
+    pub fn input_word(&mut self, input: usize) -> Wire {
+        self.push(Gate::InputWord { input })
+    }
+
+    /// Number of rows of a wire
+    pub fn rows(&self, wire: Wire) -> usize {
+        self.shapes[wire.index()].rows
+    }
+
+    /// Kind of a wire
+    pub fn kind(&self, wire: Wire) -> WireKind {
+        self.shapes[wire.index()].kind
+    }
+
+    /// Public value of a wire, if it is a constant
+    pub fn known(&self, wire: Wire) -> Option<u64> {
+        self.known[wire.index()]
+    }
+
+    pub fn const_bit(&mut self, value: bool, rows: usize) -> Wire {
+        self.push(Gate::ConstBit { value, rows })
+    }
+
+    /// The low `width` bits of a public value
+    pub fn const_bits(&mut self, value: u64, width: usize, rows: usize) -> Vec<Wire> {
+        (0..width)
+            .map(|j| self.const_bit(j < 64 && (value >> j) & 1 == 1, rows))
+            .collect()
+    }
+
+    pub fn const_word(&mut self, value: u64, rows: usize) -> Wire {
+        self.push(Gate::ConstWord { value, rows })
+    }
+
+    pub fn xor(&mut self, a: Wire, b: Wire) -> Wire {
+        self.push(Gate::Xor(a, b))
+    }
+
+    pub fn and(&mut self, a: Wire, b: Wire) -> Wire {
+        self.push(Gate::And(a, b))
+    }
+
+    pub fn not(&mut self, a: Wire) -> Wire {
+        self.push(Gate::Not(a))
+    }
+
+    /// a | b = a ^ b ^ (a & b)
+    pub fn or(&mut self, a: Wire, b: Wire) -> Wire {
+        let both = self.and(a, b);
+        let either = self.xor(a, b);
+        self.xor(either, both)
+    }
+
+    pub fn add(&mut self, a: Wire, b: Wire) -> Wire {
+        self.push(Gate::Add(a, b))
+    }
+
+    pub fn sub(&mut self, a: Wire, b: Wire) -> Wire {
+        self.push(Gate::Sub(a, b))
+    }
+
+    pub fn mul(&mut self, a: Wire, b: Wire) -> Wire {
+        self.push(Gate::Mul(a, b))
+    }
+
+    pub fn mul_const(&mut self, a: Wire, value: u64) -> Wire {
+        self.push(Gate::MulConst(a, value))
+    }
+
+    /// Rows `start..start + count` of a wire
+    pub fn slice(&mut self, a: Wire, start: usize, count: usize) -> Wire {
+        self.slice_blocks(a, start, count, count, 1)
+    }
+
+    /// `blocks` blocks of `block` rows, block q starting at row `start + q * stride`
+    pub fn slice_blocks(&mut self, a: Wire, start: usize, block: usize, stride: usize, blocks: usize) -> Wire {
+        // A single block has no stride
+        let stride = if blocks == 1 { block } else { stride };
+        self.push(Gate::Slice { input: a, start, block, stride, blocks })
+    }
+
+    /// Rows of all wires one after the other
+    pub fn concat(&mut self, wires: &[Wire]) -> Wire {
+        self.push(Gate::Concat(wires.to_vec()))
+    }
+
+    /// Blocks of `block` rows taken from each wire in turn
+    pub fn interleave(&mut self, wires: &[Wire], block: usize) -> Wire {
+        self.push(Gate::Interleave { inputs: wires.to_vec(), block })
+    }
+
+    /// Declare a result column
+    pub fn output(&mut self, name: &str, wires: OutputWires) {
+        self.circuit.outputs.push(Output {
+            name: name.to_string(),
+            wires,
+        });
+    }
+
+    /// Conjunction of all bits as a log-depth tree; true for no bits
+    pub fn all(&mut self, bits: &[Wire], rows: usize) -> Wire {
+        let mut level = bits.to_vec();
+        if level.is_empty() {
+            return self.const_bit(true, rows);
+        }
+        while level.len() > 1 {
+            level = level
+                .chunks(2)
+                .map(|pair| match pair {
+                    [a, b] => self.and(*a, *b),
+                    [a] => *a,
+                    _ => unreachable!(),
+                })
+                .collect();
+        }
+        level[0]
+    }
+
+    /// Row-wise `if condition { a } else { b }` as b ^ (condition & (a ^ b)),
+    /// one AND per bit
+    pub fn mux(&mut self, condition: Wire, a: &[Wire], b: &[Wire]) -> Vec<Wire> {
+        let (a, b) = self.pad(a, b);
+        a.iter()
+            .zip(&b)
+            .map(|(x, y)| {
+                let diff = self.xor(*x, *y);
+                let picked = self.and(condition, diff);
+                self.xor(*y, picked)
+            })
+            .collect()
+    }
+
+    /// Fold all rows of a tuple of wires into a single row with a log-depth
+    /// tree: every step combines the first half of the rows with the second
+    /// half, and an odd row left over moves on unchanged. `combine` gets two
+    /// tuples with the same number of rows and returns their combination.
+    pub fn fold_rows(
+        &mut self,
+        values: &[Wire],
+        mut combine: impl FnMut(&mut Self, &[Wire], &[Wire]) -> Vec<Wire>,
+    ) -> Vec<Wire> {
+        let mut values = values.to_vec();
+        let mut rows = values.first().map_or(0, |w| self.rows(*w));
+        while rows > 1 {
+            let half = rows / 2;
+            let low: Vec<Wire> = values.iter().map(|w| self.slice(*w, 0, half)).collect();
+            let high: Vec<Wire> = values.iter().map(|w| self.slice(*w, half, half)).collect();
+            let mut combined = combine(self, &low, &high);
+            if rows % 2 == 1 {
+                for (wire, value) in combined.iter_mut().zip(&values) {
+                    let rest = self.slice(*value, 2 * half, 1);
+                    *wire = self.concat(&[*wire, rest]);
+                }
+            }
+            values = combined;
+            rows = half + rows % 2;
+        }
+        values
+    }
+
+    /// Sum of all rows of a word wire as a single row; local
+    pub fn sum_rows(&mut self, word: Wire) -> Wire {
+        if let Some(value) = self.known(word) {
+            let total = value.wrapping_mul(self.rows(word) as u64);
+            return self.const_word(total, 1);
+        }
+        self.fold_rows(&[word], |builder, low, high| vec![builder.add(low[0], high[0])])[0]
+    }
+
+    /// Sort the rows of a tuple of wires by `key` (unsigned, ascending) with a
+    /// bitonic sorting network and return the sorted key and payload. Bit and
+    /// word wires can be mixed in the payload. The rows are padded to a power
+    /// of two with rows that sort last, and every compare-exchange stage pairs
+    /// the rows i and i + d of each block of 2d rows with strided slices, so a
+    /// stage takes a constant number of gates per wire.
+    pub fn sort_rows(&mut self, key: &[Wire], payload: &[Wire]) -> (Vec<Wire>, Vec<Wire>) {
+        let rows = key.first().or(payload.first()).map_or(0, |w| self.rows(*w));
+        if rows <= 1 || key.is_empty() {
+            return (key.to_vec(), payload.to_vec());
+        }
+        let size = rows.next_power_of_two();
+        let mut tuple: Vec<Wire> = key.iter().chain(payload).copied().collect();
+        let mut width = key.len();
+        if size > rows {
+            for wire in tuple.iter_mut() {
+                let padding = self.constant(self.kind(*wire), 0, size - rows);
+                *wire = self.concat(&[*wire, padding]);
+            }
+            // A most significant key bit set only in the padding rows
+            let (real, padding) = (self.const_bit(false, rows), self.const_bit(true, size - rows));
+            tuple.insert(width, self.concat(&[real, padding]));
+            width += 1;
+        }
+
+        let mut phase = 2;
+        while phase <= size {
+            // The blocks of `phase` rows are sorted alternately ascending and
+            // descending so that pairs of them form bitonic sequences; the
+            // direction of the lower row of each compared pair is public
+            let descending = (phase < size).then(|| {
+                let up = self.const_bit(false, size / 4);
+                let down = self.const_bit(true, size / 4);
+                self.interleave(&[up, down], phase / 2)
+            });
+            let mut distance = phase / 2;
+            while distance >= 1 {
+                tuple = self.compare_exchange(&tuple, width, distance, descending);
+                distance /= 2;
+            }
+            phase *= 2;
+        }
+
+        let sorted: Vec<Wire> = tuple.iter().map(|w| self.slice(*w, 0, rows)).collect();
+        (sorted[..key.len()].to_vec(), sorted[width..].to_vec())
+    }
+
+    /// One stage of a sorting network: rows i and i + d of every block of 2d
+    /// rows are swapped if the key (the first `width` wires) of row i is
+    /// greater, or, where `descending` is set, not greater
+    fn compare_exchange(&mut self, tuple: &[Wire], width: usize, distance: usize, descending: Option<Wire>) -> Vec<Wire> {
+        let rows = self.rows(tuple[0]);
+        let blocks = rows / (2 * distance);
+        let low: Vec<Wire> = tuple
+            .iter()
+            .map(|w| self.slice_blocks(*w, 0, distance, 2 * distance, blocks))
+            .collect();
+        let high: Vec<Wire> = tuple
+            .iter()
+            .map(|w| self.slice_blocks(*w, distance, distance, 2 * distance, blocks))
+            .collect();
+        let greater = self.less_than(&high[..width], &low[..width]);
+        let swap = match descending {
+            Some(descending) => self.xor(greater, descending),
+            None => greater,
+        };
+
+        // Swap by adding the masked difference: one AND per bit wire, one MUL
+        // per word wire
+        let mut swap_word = None;
+        low.iter()
+            .zip(&high)
+            .map(|(a, b)| {
+                let (a, b) = match self.kind(*a) {
+                    WireKind::Bit => {
+                        let diff = self.xor(*a, *b);
+                        let diff = self.and(swap, diff);
+                        (self.xor(*a, diff), self.xor(*b, diff))
+                    }
+                    WireKind::Word => {
+                        let weight = *swap_word.get_or_insert_with(|| self.b2a(&[swap]));
+                        let diff = self.sub(*b, *a);
+                        let diff = self.mul(weight, diff);
+                        (self.add(*a, diff), self.sub(*b, diff))
+                    }
+                };
+                self.interleave(&[a, b], distance)
+            })
+            .collect()
+    }
+
+    /// Every row `times` times in a row, e.g. for the outer loop of a
+    /// nested-loop join; local
+    pub fn repeat_rows(&mut self, a: Wire, times: usize) -> Wire {
+        self.interleave(&vec![a; times], 1)
+    }
+
+    /// All rows `times` times over, e.g. for the inner loop of a nested-loop
+    /// join; local
+    pub fn tile_rows(&mut self, a: Wire, times: usize) -> Wire {
+        self.concat(&vec![a; times])
+    }
+
+    /// Rows whose `filled` bit is not set take the values of the nearest row
+    /// above whose bit is set, or zeros if there is no such row.
+    /// A log-depth scan: after the step for distance d every row holds the
+    /// values of the nearest filled row less than 2d rows above it.
+    pub fn fill_down(&mut self, filled: Wire, values: &[Wire]) -> Vec<Wire> {
+        let rows = self.rows(filled);
+        let (mut filled, mut values) = (filled, values.to_vec());
+        let mut distance = 1;
+        while distance < rows {
+            let above: Vec<Wire> = values.iter().map(|v| self.shift_down(*v, distance)).collect();
+            values = self.select(filled, &values, &above);
+            let filled_above = self.shift_down(filled, distance);
+            filled = self.or(filled, filled_above);
+            distance *= 2;
+        }
+        values
+    }
+
+    /// Segmented inclusive scan: every row combines its values with those of
+    /// the rows above it in the same segment, where `linked` says whether a
+    /// row continues the segment of the row above. `combine` gets the tuples
+    /// of the rows above and of the rows themselves, as in `fold_rows`. Takes
+    /// log(rows) steps; the last row of a segment ends up with the
+    /// combination of the whole segment.
+    pub fn scan_segments(
+        &mut self,
+        linked: Wire,
+        values: &[Wire],
+        mut combine: impl FnMut(&mut Self, &[Wire], &[Wire]) -> Vec<Wire>,
+    ) -> Vec<Wire> {
+        let rows = self.rows(linked);
+        let (mut linked, mut values) = (linked, values.to_vec());
+        let mut distance = 1;
+        // `linked` says whether the row `distance` rows above is in the same
+        // segment, and every row holds the combination of the (up to)
+        // `distance` rows ending at it
+        while distance < rows {
+            let above: Vec<Wire> = values.iter().map(|v| self.shift_down(*v, distance)).collect();
+            let combined = combine(self, &above, &values);
+            values = self.select(linked, &combined, &values);
+            let linked_above = self.shift_down(linked, distance);
+            linked = self.and(linked, linked_above);
+            distance *= 2;
+        }
+        values
+    }
+
+    /// Row-wise whether the values equal those of the row above; never in the
+    /// first row
+    pub fn same_as_above(&mut self, values: &[Wire]) -> Wire {
+        let rows = values.first().map_or(0, |w| self.rows(*w));
+        if rows <= 1 {
+            return self.const_bit(false, rows);
+        }
+        let above: Vec<Wire> = values.iter().map(|v| self.shift_down(*v, 1)).collect();
+        let same = self.equal(values, &above);
+        let first = self.const_bit(false, 1);
+        let rest = self.const_bit(true, rows - 1);
+        let not_first = self.concat(&[first, rest]);
+        self.and(same, not_first)
+    }
+
+    /// Row-wise `if condition { a } else { b }` wire by wire, for bit and
+    /// word wires: one AND per bit wire, one MUL per word wire
+    fn select(&mut self, condition: Wire, a: &[Wire], b: &[Wire]) -> Vec<Wire> {
+        let mut weight = None;
+        a.iter()
+            .zip(b)
+            .map(|(x, y)| match self.kind(*x) {
+                WireKind::Bit => self.mux(condition, &[*x], &[*y])[0],
+                WireKind::Word => {
+                    let weight = *weight.get_or_insert_with(|| self.b2a(&[condition]));
+                    let diff = self.sub(*x, *y);
+                    let diff = self.mul(weight, diff);
+                    self.add(*y, diff)
+                }
+            })
+            .collect()
+    }
+
+    /// Rows moved `distance` (less than the row count) rows down, with zeros
+    /// in the first rows
+    fn shift_down(&mut self, a: Wire, distance: usize) -> Wire {
+        let rows = self.rows(a);
+        let zeros = self.constant(self.kind(a), 0, distance);
+        let rest = self.slice(a, 0, rows - distance);
+        self.concat(&[zeros, rest])
+    }
+
+    /// Rows shared by two bit vectors
+    pub(super) fn rows_of(&self, a: &[Wire], b: &[Wire]) -> usize {
+        a.first().or(b.first()).map_or(0, |w| self.rows(*w))
+    }
+
+    /// Zero-extend two bit vectors to the same width
+    pub(super) fn pad(&mut self, a: &[Wire], b: &[Wire]) -> (Vec<Wire>, Vec<Wire>) {
+        let rows = self.rows_of(a, b);
+        let width = a.len().max(b.len());
+        let zero = self.const_bit(false, rows);
+        let extend = |bits: &[Wire]| {
+            let mut bits = bits.to_vec();
+            bits.resize(width, zero);
+            bits
+        };
+        (extend(a), extend(b))
+    }
+
+    /// Add a gate after constant folding, or reuse an identical one
+    pub(super) fn push(&mut self, gate: Gate) -> Wire {
+        let gate = match self.fold(gate) {
+            Ok(wire) => return wire,
+            Err(gate) => gate,
+        };
+        if let Some(wire) = self.existing.get(&gate) {
+            return *wire;
+        }
+
+        let (shape, known) = self.describe(&gate);
+        let wire = Wire(self.circuit.gates.len() as u32);
+        self.circuit.gates.push(gate.clone());
+        self.shapes.push(shape);
+        self.known.push(known);
+        self.existing.insert(gate, wire);
+        wire
+    }
+
+    /// Shape and public value of a gate that survived folding
+    fn describe(&self, gate: &Gate) -> (WireShape, Option<u64>) {
+        let bit = |rows| WireShape { kind: WireKind::Bit, rows };
+        let word = |rows| WireShape { kind: WireKind::Word, rows };
+        match gate {
+            Gate::Input { input, .. } => (bit(self.circuit.inputs[*input].rows), None),
+            Gate::InputWord { input } => {
+                debug_assert_eq!(self.circuit.inputs[*input].sharing, InputSharing::Arithmetic);
+                (word(self.circuit.inputs[*input].rows), None)
+            }
+            Gate::ConstBit { value, rows } => (bit(*rows), Some(*value as u64)),
+            Gate::ConstWord { value, rows } => (word(*rows), Some(*value)),
+            Gate::InjectBit { input, .. } => (word(self.rows(*input)), None),
+            Gate::InjectWord { input, .. } => (bit(self.rows(*input)), None),
+            Gate::Slice { input, block, blocks, .. } => {
+                (WireShape { rows: block * blocks, ..self.shapes[input.index()] }, None)
+            }
+            Gate::Concat(inputs) => {
+                let rows = inputs.iter().map(|w| self.rows(*w)).sum();
+                (WireShape { kind: self.kind(inputs[0]), rows }, None)
+            }
+            Gate::Interleave { inputs, .. } => {
+                let rows = self.rows(inputs[0]) * inputs.len();
+                (WireShape { kind: self.kind(inputs[0]), rows }, None)
+            }
+            other => (self.shapes[other.operands()[0].index()], None),
+        }
+    }
+
+    /// Replace a gate by an existing wire or a cheaper gate where constants allow
+    fn fold(&mut self, gate: Gate) -> Result<Wire, Gate> {
+        let known = |builder: &Self, w: Wire| builder.known[w.index()];
+        match gate {
+            Gate::Xor(a, b) => match (known(self, a), known(self, b)) {
+                (Some(x), Some(y)) => Ok(self.const_bit(x != y, self.rows(a))),
+                (Some(0), _) => Ok(b),
+                (_, Some(0)) => Ok(a),
+                (Some(_), _) => Ok(self.not(b)),
+                (_, Some(_)) => Ok(self.not(a)),
+                _ if a == b => Ok(self.const_bit(false, self.rows(a))),
+                _ => Err(Gate::Xor(a.min(b), a.max(b))),
+            },
+            Gate::And(a, b) => match (known(self, a), known(self, b)) {
+                (Some(0), _) => Ok(a),
+                (_, Some(0)) => Ok(b),
+                (Some(_), _) => Ok(b),
+                (_, Some(_)) => Ok(a),
+                _ if a == b => Ok(a),
+                _ => Err(Gate::And(a.min(b), a.max(b))),
+            },
+            Gate::Not(a) => match (known(self, a), self.circuit.gates[a.index()].clone()) {
+                (Some(x), _) => Ok(self.const_bit(x == 0, self.rows(a))),
+                (None, Gate::Not(inner)) => Ok(inner),
+                _ => Err(gate),
+            },
+            Gate::Add(a, b) => match (known(self, a), known(self, b)) {
+                (Some(x), Some(y)) => Ok(self.const_word(x.wrapping_add(y), self.rows(a))),
+                (Some(0), _) => Ok(b),
+                (_, Some(0)) => Ok(a),
+                _ => Err(Gate::Add(a.min(b), a.max(b))),
+            },
+            Gate::Sub(a, b) => match (known(self, a), known(self, b)) {
+                (Some(x), Some(y)) => Ok(self.const_word(x.wrapping_sub(y), self.rows(a))),
+                (_, Some(0)) => Ok(a),
+                _ if a == b => Ok(self.const_word(0, self.rows(a))),
+                _ => Err(gate),
+            },
+            Gate::Mul(a, b) => match (known(self, a), known(self, b)) {
+                (Some(x), _) => Ok(self.mul_const(b, x)),
+                (_, Some(y)) => Ok(self.mul_const(a, y)),
+                _ => Err(Gate::Mul(a.min(b), a.max(b))),
+            },
+            Gate::MulConst(a, value) => match (known(self, a), value) {
+                (Some(x), _) => Ok(self.const_word(x.wrapping_mul(value), self.rows(a))),
+                (_, 0) => Ok(self.const_word(0, self.rows(a))),
+                (_, 1) => Ok(a),
+                _ => Err(gate),
+            },
+            // Constants are shared as x_0 = value, x_1 = x_2 = 0
+            Gate::InjectBit { input, component } => match known(self, input) {
+                Some(x) => Ok(self.const_word(if component == 0 { x } else { 0 }, self.rows(input))),
+                None => Err(gate),
+            },
+            Gate::InjectWord { input, component, bit } => match known(self, input) {
+                Some(x) => Ok(self.const_bit(component == 0 && (x >> bit) & 1 == 1, self.rows(input))),
+                None => Err(gate),
+            },
+            Gate::Slice { input, start, block, stride, blocks } => match known(self, input) {
+                Some(x) => Ok(self.constant(self.kind(input), x, block * blocks)),
+                None if start == 0 && (blocks == 1 || stride == block) && block * blocks == self.rows(input) => Ok(input),
+                None => Err(gate),
+            },
+            Gate::Concat(inputs) | Gate::Interleave { inputs, .. } if inputs.len() == 1 => Ok(inputs[0]),
+            Gate::Concat(inputs) => self.fold_uniform(&inputs).ok_or(Gate::Concat(inputs)),
+            Gate::Interleave { inputs, block } => match self.fold_uniform(&inputs) {
+                Some(wire) => Ok(wire),
+                None if block >= self.rows(inputs[0]) => Ok(self.concat(&inputs)),
+                None => Err(Gate::Interleave { inputs, block }),
+            },
+            other => Err(other),
+        }
+    }
+
+    /// A constant with the rows of all wires if they all hold the same constant
+    fn fold_uniform(&mut self, wires: &[Wire]) -> Option<Wire> {
+        let value = self.known(wires[0])?;
+        if wires.iter().any(|w| self.known(*w) != Some(value)) {
+            return None;
+        }
+        let rows = wires.iter().map(|w| self.rows(*w)).sum();
+        Some(self.constant(self.kind(wires[0]), value, rows))
+    }
+
+    /// Public value in every row of a bit or word wire
+    pub fn constant(&mut self, kind: WireKind, value: u64, rows: usize) -> Wire {
+        match kind {
+            WireKind::Bit => self.const_bit(value == 1, rows),
+            WireKind::Word => self.const_word(value, rows),
+        }
+    }
+
+    /// The circuit without gates and inputs that no output depends on
+    pub fn finish(self) -> Circuit {
+        let circuit = self.circuit;
+        let mut live = vec![false; circuit.gates.len()];
+        for output in &circuit.outputs {
+            for wire in output.wires.wires() {
+                live[wire.index()] = true;
+            }
+        }
+        for index in (0..circuit.gates.len()).rev() {
+            if live[index] {
+                for operand in circuit.gates[index].operands() {
+                    live[operand.index()] = true;
+                }
+            }
+        }
+
+        let mut input_map = vec![None; circuit.inputs.len()];
+        let mut inputs = Vec::new();
+        let mut wire_map = vec![Wire(0); circuit.gates.len()];
+        let mut gates = Vec::new();
+        for (index, gate) in circuit.gates.into_iter().enumerate() {
+            if !live[index] {
+                continue;
+            }
+            let mut map_input = |input: usize| {
+                *input_map[input].get_or_insert_with(|| {
+                    inputs.push(circuit.inputs[input].clone());
+                    inputs.len() - 1
+                })
+            };
+            let w = |wire: Wire| wire_map[wire.index()];
+            let gate = match gate {
+                Gate::Input { input, bit } => Gate::Input { input: map_input(input), bit },
+                Gate::InputWord { input } => Gate::InputWord { input: map_input(input) },
+                Gate::ConstBit { .. } | Gate::ConstWord { .. } => gate,
+                Gate::Xor(a, b) => Gate::Xor(w(a), w(b)),
+                Gate::And(a, b) => Gate::And(w(a), w(b)),
+                Gate::Not(a) => Gate::Not(w(a)),
+                Gate::Add(a, b) => Gate::Add(w(a), w(b)),
+                Gate::Sub(a, b) => Gate::Sub(w(a), w(b)),
+                Gate::Mul(a, b) => Gate::Mul(w(a), w(b)),
+                Gate::MulConst(a, value) => Gate::MulConst(w(a), value),
+                Gate::InjectBit { input, component } => Gate::InjectBit { input: w(input), component },
+                Gate::InjectWord { input, component, bit } => Gate::InjectWord { input: w(input), component, bit },
+                Gate::Slice { input, start, block, stride, blocks } => {
+                    Gate::Slice { input: w(input), start, block, stride, blocks }
+                }
+                Gate::Concat(inputs) => Gate::Concat(inputs.into_iter().map(w).collect()),
+                Gate::Interleave { inputs, block } => Gate::Interleave { inputs: inputs.into_iter().map(w).collect(), block },
+            };
+            wire_map[index] = Wire(gates.len() as u32);
+            gates.push(gate);
+        }
+
+        let outputs = circuit
+            .outputs
+            .into_iter()
+            .map(|output| Output {
+                name: output.name,
+                wires: match output.wires {
+                    OutputWires::Bits(bits) => OutputWires::Bits(bits.iter().map(|w| wire_map[w.index()]).collect()),
+                    OutputWires::Word(word) => OutputWires::Word(wire_map[word.index()]),
+                },
+            })
+            .collect();
+        Circuit { inputs, gates, outputs }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::compare::Comparison;
+    use crate::circuit::plain::evaluate;
+
+    fn column(width: u32, sharing: InputSharing, rows: usize) -> InputColumn {
+        InputColumn {
+            table: "t".to_string(),
+            table_id: 0,
+            schema_hash: 0,
+            column: width as usize,
+            width,
+            sharing,
+            rows,
+        }
+    }
+
+    #[test]
+    fn test_row_folds_match_plaintext() {
+        let a = vec![7u64, 3, 12, 3, 9];
+        let mut builder = CircuitBuilder::new();
+        let ia = builder.input(column(4, InputSharing::Boolean, a.len()));
+        let bits = builder.input_bits(ia);
+
+        let word = builder.b2a(&bits);
+        let sum = builder.sum_rows(word);
+        builder.output("sum", OutputWires::Word(sum));
+        let min = builder.fold_rows(&bits, |builder, low, high| {
+            let less = builder.less_than(high, low);
+            builder.mux(less, high, low)
+        });
+        builder.output("min", OutputWires::Bits(min));
+        let tail = builder.slice(word, 3, 2);
+        let rotated = builder.slice(word, 0, 3);
+        let rotated = builder.concat(&[tail, rotated]);
+        builder.output("rotated", OutputWires::Word(rotated));
+        let circuit = builder.finish();
+
+        let outputs = evaluate(&circuit, std::slice::from_ref(&a)).unwrap();
+        assert_eq!(outputs, vec![vec![34], vec![3], vec![3, 9, 7, 3, 12]]);
+        assert_eq!(circuit.gate_counts()["MUL"], 2 * 4);
+
+        // Folding a public column gives a public value
+        let mut builder = CircuitBuilder::new();
+        let one = builder.const_word(1, 1001);
+        let count = builder.sum_rows(one);
+        assert_eq!((builder.known(count), builder.rows(count)), (Some(1001), 1));
+    }
+
+    #[test]
+    fn test_sort_rows_carries_payload() {
+        let keys = vec![5u64, 1, 7, 1, 0, 6];
+        let payload: Vec<u64> = (0..keys.len() as u64).map(|r| 100 + r).collect();
+        let mut builder = CircuitBuilder::new();
+        let ik = builder.input(column(3, InputSharing::Boolean, keys.len()));
+        let ip = builder.input(column(64, InputSharing::Arithmetic, keys.len()));
+        let key = builder.input_bits(ik);
+        let word = builder.input_word(ip);
+        let (sorted_key, sorted_payload) = builder.sort_rows(&key, &[word, key[0]]);
+        builder.output("key", OutputWires::Bits(sorted_key));
+        builder.output("payload", OutputWires::Word(sorted_payload[0]));
+        builder.output("parity", OutputWires::Bits(vec![sorted_payload[1]]));
+        let circuit = builder.finish();
+
+        let outputs = evaluate(&circuit, &[keys.clone(), payload.clone()]).unwrap();
+        assert_eq!(outputs[0], vec![0, 1, 1, 5, 6, 7]);
+        // Equal keys may come in any order
+        let order = |r: usize| payload[keys.iter().position(|k| *k == outputs[0][r]).unwrap()];
+        assert_eq!(outputs[1][0], order(0));
+        assert_eq!(outputs[1][3..], [order(3), order(4), order(5)]);
+        assert_eq!(outputs[1][1] + outputs[1][2], 101 + 103);
+        assert_eq!(outputs[2], vec![0, 1, 1, 1, 0, 1]);
+        // 8 padded rows take 6 stages; each converts its swap bits once (two
+        // MULs) and swaps the word column with one MUL
+        assert_eq!(circuit.gate_counts()["MUL"], 6 * 3);
+    }
+
+    #[test]
+    fn test_join_gadgets_match_plaintext() {
+        let mut builder = CircuitBuilder::new();
+        let ia = builder.input(column(8, InputSharing::Boolean, 3));
+        let ib = builder.input(column(64, InputSharing::Arithmetic, 3));
+        let bits = builder.input_bits(ia);
+        let word = builder.input_word(ib);
+        let repeated = builder.repeat_rows(word, 2);
+        builder.output("repeated", OutputWires::Word(repeated));
+        let tiled = builder.tile_rows(word, 2);
+        builder.output("tiled", OutputWires::Word(tiled));
+        // Rows with an odd value are filled, the others take the value above
+        let filled = builder.fill_down(bits[0], &[word, bits[1]]);
+        builder.output("filled", OutputWires::Word(filled[0]));
+        builder.output("filled bit", OutputWires::Bits(vec![filled[1]]));
+        let circuit = builder.finish();
+
+        let outputs = evaluate(&circuit, &[vec![2, 3, 4], vec![10, 20, 30]]).unwrap();
+        assert_eq!(outputs[0], vec![10, 10, 20, 20, 30, 30]);
+        assert_eq!(outputs[1], vec![10, 20, 30, 10, 20, 30]);
+        assert_eq!(outputs[2], vec![0, 20, 20]);
+        assert_eq!(outputs[3], vec![0, 1, 1]);
+    }
+
+    #[test]
+    fn test_segmented_scan_matches_plaintext() {
+        let keys = vec![1u64, 1, 2, 3, 3, 3, 1];
+        let values = vec![5u64, 7, 1, 4, 9, 2, 6];
+        let mut builder = CircuitBuilder::new();
+        let ik = builder.input(column(2, InputSharing::Boolean, keys.len()));
+        let iv = builder.input(column(64, InputSharing::Arithmetic, keys.len()));
+        let key = builder.input_bits(ik);
+        let word = builder.input_word(iv);
+        let same = builder.same_as_above(&key);
+        builder.output("same", OutputWires::Bits(vec![same]));
+        let sums = builder.scan_segments(same, &[word], |builder, above, own| vec![builder.add(above[0], own[0])]);
+        builder.output("sums", OutputWires::Word(sums[0]));
+        let bits = builder.a2b(word, 4);
+        let max = builder.scan_segments(same, &bits, |builder, above, own| {
+            let greater = builder.less_than(own, above);
+            builder.mux(greater, above, own)
+        });
+        builder.output("max", OutputWires::Bits(max));
+        let circuit = builder.finish();
+
+        let outputs = evaluate(&circuit, &[keys, values]).unwrap();
+        assert_eq!(outputs[0], vec![0, 1, 0, 0, 1, 1, 0]);
+        assert_eq!(outputs[1], vec![5, 12, 1, 4, 13, 15, 6]);
+        assert_eq!(outputs[2], vec![5, 7, 1, 4, 9, 9, 6]);
+    }
+
+    #[test]
+    fn test_constant_folding_and_pruning() {
+        let mut builder = CircuitBuilder::new();
+        let input = builder.input(column(8, InputSharing::Boolean, 3));
+        let unused = builder.input(column(4, InputSharing::Boolean, 3));
+        let bits = builder.input_bits(input);
+        builder.input_bits(unused);
+
+        // Against a constant the leaves are local: only the tree needs ANDs
+        let equal = builder.compare_const(&bits, Comparison::Equal, 7);
+        assert_eq!(builder.compare_const(&bits, Comparison::Equal, 7), equal);
+        let always = builder.compare_const(&bits, Comparison::Less, 256);
+        assert_eq!(builder.known(always), Some(1));
+        builder.output("equal", OutputWires::Bits(vec![equal]));
+
+        let circuit = builder.finish();
+        assert_eq!(circuit.inputs.len(), 1);
+        assert_eq!(circuit.gate_counts()["AND"], 7);
+        assert!(circuit.gates.iter().all(|g| !matches!(g, Gate::ConstBit { .. })));
+    }
+}
@@ -0,0 +1,204 @@
+// Comparison Circuits
+// ===================
+// Oblivious comparisons of boolean-shared unsigned integers. Every comparison
+// yields a single bit wire, so a predicate such as `available_qty > 5000` is
+// evaluated for all rows without revealing either the column or the outcome.
+//
+// Less-than uses a log-depth prefix tree over the bits (LSB first). Leaf j
+// holds (l_j, e_j) with l_j = !x_j & y_j ("x < y decided at bit j") and
+// e_j = !(x_j ^ y_j). Two neighbouring groups combine as
+//     l = l_hi ^ (e_hi & l_lo),   e = e_hi & e_lo
+// (XOR suffices since l_hi and e_hi are never both set). A width-w comparison
+// of two shared values takes 1 + ceil(log2 w) rounds; against a public
+// constant the builder folds the leaves to local gates and it takes
+// ceil(log2 w) rounds.
+
+use super::builder::CircuitBuilder;
+use super::Wire;
+
+/// Comparison operators on unsigned integers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Equal,
+    NotEqual,
+}
+
+impl CircuitBuilder {
+    /// Row-wise a == b
+    pub fn equal(&mut self, a: &[Wire], b: &[Wire]) -> Wire {
+        let rows = self.rows_of(a, b);
+        let (a, b) = self.pad(a, b);
+        let same: Vec<Wire> = a
+            .iter()
+            .zip(&b)
+            .map(|(x, y)| {
+                let diff = self.xor(*x, *y);
+                self.not(diff)
+            })
+            .collect();
+        self.all(&same, rows)
+    }
+
+    /// Row-wise a < b with the prefix tree described above
+    pub fn less_than(&mut self, a: &[Wire], b: &[Wire]) -> Wire {
+        let rows = self.rows_of(a, b);
+        let (a, b) = self.pad(a, b);
+        let mut level: Vec<(Wire, Wire)> = a
+            .iter()
+            .zip(&b)
+            .map(|(x, y)| {
+                let not_x = self.not(*x);
+                let less = self.and(not_x, *y);
+                let diff = self.xor(*x, *y);
+                (less, self.not(diff))
+            })
+            .collect();
+        if level.is_empty() {
+            return self.const_bit(false, rows);
+        }
+        while level.len() > 1 {
+            level = level
+                .chunks(2)
+                .map(|pair| match pair {
+                    [(l_lo, e_lo), (l_hi, e_hi)] => {
+                        let carried = self.and(*e_hi, *l_lo);
+                        (self.xor(*l_hi, carried), self.and(*e_hi, *e_lo))
+                    }
+                    [single] => *single,
+                    _ => unreachable!(),
+                })
+                .collect();
+        }
+        level[0].0
+    }
+
+    /// Row-wise comparison of unsigned integers of any widths
+    pub fn compare(&mut self, a: &[Wire], op: Comparison, b: &[Wire]) -> Wire {
+        match op {
+            Comparison::Less => self.less_than(a, b),
+            Comparison::Greater => self.less_than(b, a),
+            Comparison::LessEqual => {
+                let greater = self.less_than(b, a);
+                self.not(greater)
+            }
+            Comparison::GreaterEqual => {
+                let less = self.less_than(a, b);
+                self.not(less)
+            }
+            Comparison::Equal => self.equal(a, b),
+            Comparison::NotEqual => {
+                let equal = self.equal(a, b);
+                self.not(equal)
+            }
+        }
+    }
+
+    /// Row-wise comparison against a public value
+    pub fn compare_const(&mut self, a: &[Wire], op: Comparison, value: u64) -> Wire {
+        let rows = a.first().map_or(0, |w| self.rows(*w));
+        let width = (64 - value.leading_zeros() as usize).max(a.len());
+        let constant = self.const_bits(value, width, rows);
+        self.compare(a, op, &constant)
+    }
+
+    /// Row-wise range check `low <= a <= high` with public bounds. Both bound
+    /// comparisons run side by side, so this costs one round more than a
+    /// single comparison.
+    pub fn in_range(&mut self, a: &[Wire], low: u64, high: u64) -> Wire {
+        let not_below = self.compare_const(a, Comparison::GreaterEqual, low);
+        let not_above = self.compare_const(a, Comparison::LessEqual, high);
+        self.and(not_below, not_above)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::plain::evaluate;
+    use crate::circuit::{InputColumn, InputSharing, OutputWires};
+
+    const OPS: [Comparison; 6] = [
+        Comparison::Less,
+        Comparison::LessEqual,
+        Comparison::Greater,
+        Comparison::GreaterEqual,
+        Comparison::Equal,
+        Comparison::NotEqual,
+    ];
+
+    fn column(width: u32, rows: usize) -> InputColumn {
+        InputColumn {
+            table: "t".to_string(),
+            table_id: 0,
+            schema_hash: 0,
+            column: width as usize,
+            width,
+            sharing: InputSharing::Boolean,
+            rows,
+        }
+    }
+
+    fn expected(x: u64, op: Comparison, y: u64) -> u64 {
+        (match op {
+            Comparison::Less => x < y,
+            Comparison::LessEqual => x <= y,
+            Comparison::Greater => x > y,
+            Comparison::GreaterEqual => x >= y,
+            Comparison::Equal => x == y,
+            Comparison::NotEqual => x != y,
+        }) as u64
+    }
+
+    #[test]
+    fn test_comparisons_match_plaintext() {
+        let a = vec![0u64, 1, 5000, 4999, 0xFFFF, 77];
+        let b = vec![0u64, 2, 5000, 5001, 1, 77];
+        let mut builder = CircuitBuilder::new();
+        let ia = builder.input(column(16, a.len()));
+        let ib = builder.input(column(20, b.len()));
+        let (bits_a, bits_b) = (builder.input_bits(ia), builder.input_bits(ib));
+        for op in OPS {
+            let result = builder.compare(&bits_a, op, &bits_b);
+            builder.output(&format!("{:?}", op), OutputWires::Bits(vec![result]));
+            let result = builder.compare_const(&bits_a, op, 5000);
+            builder.output(&format!("{:?} 5000", op), OutputWires::Bits(vec![result]));
+        }
+        let result = builder.in_range(&bits_a, 1, 5000);
+        builder.output("in range", OutputWires::Bits(vec![result]));
+        let circuit = builder.finish();
+
+        let outputs = evaluate(&circuit, &[a.clone(), b.clone()]).unwrap();
+        let mut outputs = outputs.into_iter();
+        for op in OPS {
+            let want: Vec<u64> = a.iter().zip(&b).map(|(x, y)| expected(*x, op, *y)).collect();
+            assert_eq!(outputs.next().unwrap(), want, "{:?}", op);
+            let want: Vec<u64> = a.iter().map(|x| expected(*x, op, 5000)).collect();
+            assert_eq!(outputs.next().unwrap(), want, "{:?} 5000", op);
+        }
+        let want: Vec<u64> = a.iter().map(|x| (1..=5000).contains(x) as u64).collect();
+        assert_eq!(outputs.next().unwrap(), want);
+    }
+
+    #[test]
+    fn test_comparison_rounds() {
+        type Gadget = dyn Fn(&mut CircuitBuilder, &[Wire], &[Wire]) -> Wire;
+        let depth = |build: &Gadget| {
+            let mut builder = CircuitBuilder::new();
+            let ia = builder.input(column(16, 4));
+            let ib = builder.input(column(15, 4));
+            let (a, b) = (builder.input_bits(ia), builder.input_bits(ib));
+            let result = build(&mut builder, &a, &b);
+            builder.output("result", OutputWires::Bits(vec![result]));
+            builder.finish().depth()
+        };
+        // 16 bits: one round for the leaves and four for the tree; the leaves
+        // are local against a constant, and the two range bounds share rounds
+        assert_eq!(depth(&|builder, a, b| builder.less_than(a, b)), 5);
+        assert_eq!(depth(&|builder, a, _| builder.compare_const(a, Comparison::Less, 5000)), 4);
+        assert_eq!(depth(&|builder, a, _| builder.in_range(a, 1, 5000)), 5);
+    }
+}
@@ -0,0 +1,147 @@
+// Share Conversion Circuits
+// =========================
+// Conversion between boolean shares (bit planes) and arithmetic shares over
+// Z_2^64, so predicates computed by boolean circuits can be combined with
+// arithmetic aggregation, e.g. SUM(qty) over the rows where a comparison holds.
+//
+// Both directions start from the observation that node i knows the share
+// components x_i and x_{i-1}, so it can form a trivial sharing of either
+// component in the other domain locally (component k is held by nodes k and
+// k+1, and is zero for the third node); the circuit expresses this with the
+// InjectBit and InjectWord gates.
+//
+// * B2A: for every bit plane, the three components are injected as 0/1
+//   arithmetic values and XORed with a ^ b = a + b - 2ab, taking two rounds
+//   for all planes together. The value is the weighted sum of its bits.
+// * A2B: the three additive components are injected as bit vectors, reduced
+//   with a carry-save step (one round) and added with a boolean adder.
+
+use super::adder::AdderKind;
+use super::builder::CircuitBuilder;
+use super::{Gate, Wire};
+
+impl CircuitBuilder {
+    /// Boolean to arithmetic conversion of an unsigned value (up to 64 bits).
+    /// Every plane's three share components are injected as words and XORed
+    /// with a ^ b = a + b - 2ab; the value is the weighted sum of the bits.
+    pub fn b2a(&mut self, bits: &[Wire]) -> Wire {
+        let rows = bits.first().map_or(0, |w| self.rows(*w));
+        let mut value = self.const_word(0, rows);
+        for (j, plane) in bits.iter().take(64).enumerate() {
+            let [c0, c1, c2] = [0, 1, 2].map(|component| self.push(Gate::InjectBit { input: *plane, component }));
+            let partial = self.xor_words(c0, c1);
+            let bit = self.xor_words(partial, c2);
+            let weighted = self.mul_const(bit, 1 << j);
+            value = self.add(value, weighted);
+        }
+        value
+    }
+
+    /// Arithmetic to boolean conversion of the low `width` bits of a word.
+    /// The three additive components are injected as bit vectors, reduced with
+    /// one carry-save step and added.
+    pub fn a2b(&mut self, word: Wire, width: usize) -> Vec<Wire> {
+        let rows = self.rows(word);
+        let [x0, x1, x2] = [0, 1, 2].map(|component| {
+            (0..width.min(64) as u32)
+                .map(|bit| self.push(Gate::InjectWord { input: word, component, bit }))
+                .collect::<Vec<Wire>>()
+        });
+        let mut sum = Vec::with_capacity(x0.len());
+        let mut carry = vec![self.const_bit(false, rows)];
+        for j in 0..x0.len() {
+            // Majority of the three bits: a ^ ((a ^ b) & (a ^ c))
+            let ab = self.xor(x0[j], x1[j]);
+            let ac = self.xor(x0[j], x2[j]);
+            sum.push(self.xor(ab, x2[j]));
+            let product = self.and(ab, ac);
+            carry.push(self.xor(x0[j], product));
+        }
+        carry.truncate(sum.len());
+        self.add_bits(&sum, &carry, AdderKind::KoggeStone)
+    }
+
+    /// a ^ b = a + b - 2ab for words holding 0 or 1
+    fn xor_words(&mut self, a: Wire, b: Wire) -> Wire {
+        let sum = self.add(a, b);
+        let product = self.mul(a, b);
+        let twice = self.mul_const(product, 2);
+        self.sub(sum, twice)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::compare::Comparison;
+    use crate::circuit::plain::evaluate;
+    use crate::circuit::{InputColumn, InputSharing, OutputWires};
+
+    fn column(width: u32, rows: usize) -> InputColumn {
+        InputColumn {
+            table: "t".to_string(),
+            table_id: 0,
+            schema_hash: 0,
+            column: 0,
+            width,
+            sharing: InputSharing::Boolean,
+            rows,
+        }
+    }
+
+    #[test]
+    fn test_conversions_match_plaintext() {
+        let a = vec![0u64, 1, 5000, 4999, 0xFFFF, 77];
+        let mut builder = CircuitBuilder::new();
+        let ia = builder.input(column(16, a.len()));
+        let bits = builder.input_bits(ia);
+
+        let word = builder.b2a(&bits);
+        let product = builder.mul(word, word);
+        builder.output("square", OutputWires::Word(product));
+        let back = builder.a2b(product, 40);
+        builder.output("square bits", OutputWires::Bits(back));
+        let circuit = builder.finish();
+        circuit.validate().unwrap();
+
+        let outputs = evaluate(&circuit, std::slice::from_ref(&a)).unwrap();
+        let squares: Vec<u64> = a.iter().map(|x| x * x).collect();
+        assert_eq!(outputs, vec![squares.clone(), squares]);
+    }
+
+    #[test]
+    fn test_predicate_selects_arithmetic_values() {
+        let qty = vec![4000u64, 6000, 5001, 12];
+        let cost = vec![10u64, 20, 30, 40];
+        let mut builder = CircuitBuilder::new();
+        let iq = builder.input(column(32, qty.len()));
+        let ic = builder.input(InputColumn {
+            column: 1,
+            width: 64,
+            sharing: InputSharing::Arithmetic,
+            ..column(64, cost.len())
+        });
+        let bits = builder.input_bits(iq);
+        let big = builder.compare_const(&bits, Comparison::Greater, 5000);
+        let weight = builder.b2a(&[big]);
+        let word = builder.input_word(ic);
+        let selected = builder.mul(weight, word);
+        builder.output("selected", OutputWires::Word(selected));
+
+        let outputs = evaluate(&builder.finish(), &[qty, cost]).unwrap();
+        assert_eq!(outputs, vec![vec![0, 20, 30, 0]]);
+    }
+
+    #[test]
+    fn test_b2a_takes_two_rounds() {
+        let mut builder = CircuitBuilder::new();
+        let ia = builder.input(column(32, 3));
+        let bits = builder.input_bits(ia);
+        let word = builder.b2a(&bits);
+        builder.output("value", OutputWires::Word(word));
+        let circuit = builder.finish();
+        // Two multiplications per plane, all planes side by side
+        assert_eq!(circuit.depth(), 2);
+        assert_eq!(circuit.gate_counts()["MUL"], 2 * 32);
+    }
+}
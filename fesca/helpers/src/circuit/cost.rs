@@ -0,0 +1,56 @@
+// Cost Model
+// ==========
+// Estimated cost of evaluating a circuit on the computing nodes, as shown by
+// EXPLAIN. The executor puts all AND and MUL gates of one level into a single
+// round, so the number of rounds is the circuit depth. Each node sends one
+// word per 64 rows of every AND gate (bit-sliced shares) and one word per row
+// of every MUL gate; local gates cost nothing.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use super::{Circuit, Gate};
+
+/// Bytes one exchanged word takes on the wire (id + share)
+pub const BYTES_PER_WORD: u64 = 16;
+
+/// Rows packed into one word of a bit-sliced share
+const ROWS_PER_WORD: usize = 64;
+
+/// Estimated cost of one circuit evaluation, per computing node
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CircuitCost {
+    /// Single-bit ANDs over all rows
+    pub and_gates: u64,
+    /// Z_2^64 multiplications over all rows
+    pub mul_gates: u64,
+    pub rounds: u32,
+    pub words_sent: u64,
+    pub bytes_sent: u64,
+}
+
+impl Circuit {
+    /// Estimate the cost of evaluating the circuit
+    pub fn cost(&self) -> Result<CircuitCost> {
+        let shapes = self.validate()?;
+        let mut cost = CircuitCost {
+            rounds: self.depth(),
+            ..CircuitCost::default()
+        };
+        for (gate, shape) in self.gates.iter().zip(&shapes) {
+            match gate {
+                Gate::And(..) => {
+                    cost.and_gates += shape.rows as u64;
+                    cost.words_sent += shape.rows.div_ceil(ROWS_PER_WORD) as u64;
+                }
+                Gate::Mul(..) => {
+                    cost.mul_gates += shape.rows as u64;
+                    cost.words_sent += shape.rows as u64;
+                }
+                _ => {}
+            }
+        }
+        cost.bytes_sent = cost.words_sent * BYTES_PER_WORD;
+        Ok(cost)
+    }
+}
@@ -0,0 +1,435 @@
+// Circuits
+// ========
+// Gate-level description of a query, built by the data analyst from its
+// logical plan and evaluated by all three computing nodes on their shares.
+//
+// Every wire carries one value per row of a (public-size) relation, so a gate
+// is applied to all rows at once, matching the bit-sliced share columns of the
+// computing nodes. A wire is either
+// * a bit wire: one bit plane, boolean (XOR) shared, or
+// * a word wire: one Z_2^64 value per row, arithmetically shared.
+//
+// Gates are stored in topological order and gate i defines wire i. XOR, NOT,
+// ADD, SUB, constants and the share injections are local; AND and MUL need one
+// communication round each, which all interactive gates of the same level
+// share. The row gates SLICE, CONCAT and INTERLEAVE move whole rows between
+// wires, e.g. to fold a column into a single aggregate or to pair the rows a
+// sorting network compares; the rows they pick are public, so they are local
+// too.
+//
+// The circuit is shipped to the nodes as JSON; `from_json` re-validates it so a
+// malformed circuit is rejected before any share is touched.
+
+pub mod adder;
+pub mod builder;
+pub mod compare;
+pub mod convert;
+pub mod cost;
+pub mod plain;
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+pub use builder::CircuitBuilder;
+pub use cost::CircuitCost;
+
+/// Reference to the value defined by a gate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Wire(pub u32);
+
+impl Wire {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// How a stored column is shared
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum InputSharing {
+    Boolean,
+    Arithmetic,
+}
+
+/// A stored share column read by the circuit
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InputColumn {
+    pub table: String,
+    /// Table id recorded in the stored share files
+    pub table_id: u32,
+    /// Schema hash recorded in the stored share files
+    pub schema_hash: u64,
+    /// Index of the column in the stored table
+    pub column: usize,
+    /// Stored bits per row (64 for arithmetic columns)
+    pub width: u32,
+    pub sharing: InputSharing,
+    pub rows: usize,
+}
+
+/// Most rows a wire may have per row of the largest input, so a circuit
+/// cannot make the nodes allocate arbitrarily large columns
+pub const MAX_ROW_FAN_OUT: usize = 1 << 16;
+
+/// Kind of value carried by a wire
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WireKind {
+    Bit,
+    Word,
+}
+
+/// Kind and row count of a wire
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WireShape {
+    pub kind: WireKind,
+    pub rows: usize,
+}
+
+/// Circuit gates; gate i defines wire i
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Gate {
+    /// Bit plane `bit` of a boolean-shared input column
+    Input { input: usize, bit: u32 },
+    /// Words of an arithmetically shared input column
+    InputWord { input: usize },
+    /// Public bit in every row
+    ConstBit { value: bool, rows: usize },
+    /// Public word in every row
+    ConstWord { value: u64, rows: usize },
+    Xor(Wire, Wire),
+    And(Wire, Wire),
+    Not(Wire),
+    Add(Wire, Wire),
+    Sub(Wire, Wire),
+    Mul(Wire, Wire),
+    /// Multiplication by a public constant
+    MulConst(Wire, u64),
+    /// Share component `component` of a bit wire as a word (0 or 1), shared trivially
+    InjectBit { input: Wire, component: u32 },
+    /// Bit `bit` of share component `component` of a word wire, shared trivially
+    InjectWord { input: Wire, component: u32, bit: u32 },
+    /// `blocks` blocks of `block` rows of a wire, block q starting at row
+    /// `start + q * stride`
+    Slice { input: Wire, start: usize, block: usize, stride: usize, blocks: usize },
+    /// Rows of all wires one after the other; the wires are of the same kind
+    Concat(Vec<Wire>),
+    /// Blocks of `block` rows taken from each wire in turn: block 0 of every
+    /// wire, then block 1, ...; the wires are of the same kind and row count
+    Interleave { inputs: Vec<Wire>, block: usize },
+}
+
+/// Values exposed by an output column
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OutputWires {
+    /// Bit planes, least significant first
+    Bits(Vec<Wire>),
+    Word(Wire),
+}
+
+/// Named result column
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Output {
+    pub name: String,
+    pub wires: OutputWires,
+}
+
+/// A complete circuit
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Circuit {
+    pub inputs: Vec<InputColumn>,
+    pub gates: Vec<Gate>,
+    pub outputs: Vec<Output>,
+}
+
+impl Gate {
+    /// Short name of the gate type, e.g. for statistics
+    pub fn name(&self) -> &'static str {
+        match self {
+            Gate::Input { .. } => "INPUT",
+            Gate::InputWord { .. } => "INPUT_WORD",
+            Gate::ConstBit { .. } => "CONST_BIT",
+            Gate::ConstWord { .. } => "CONST_WORD",
+            Gate::Xor(..) => "XOR",
+            Gate::And(..) => "AND",
+            Gate::Not(_) => "NOT",
+            Gate::Add(..) => "ADD",
+            Gate::Sub(..) => "SUB",
+            Gate::Mul(..) => "MUL",
+            Gate::MulConst(..) => "MUL_CONST",
+            Gate::InjectBit { .. } => "INJECT_BIT",
+            Gate::InjectWord { .. } => "INJECT_WORD",
+            Gate::Slice { .. } => "SLICE",
+            Gate::Concat(_) => "CONCAT",
+            Gate::Interleave { .. } => "INTERLEAVE",
+        }
+    }
+
+    /// Wires read by the gate
+    pub fn operands(&self) -> Vec<Wire> {
+        match self {
+            Gate::Input { .. } | Gate::InputWord { .. } | Gate::ConstBit { .. } | Gate::ConstWord { .. } => Vec::new(),
+            Gate::Xor(a, b) | Gate::And(a, b) | Gate::Add(a, b) | Gate::Sub(a, b) | Gate::Mul(a, b) => vec![*a, *b],
+            Gate::Not(a) | Gate::MulConst(a, _) => vec![*a],
+            Gate::InjectBit { input, .. } | Gate::InjectWord { input, .. } | Gate::Slice { input, .. } => {
+                vec![*input]
+            }
+            Gate::Concat(inputs) | Gate::Interleave { inputs, .. } => inputs.clone(),
+        }
+    }
+
+    /// Whether evaluating the gate takes a communication round
+    pub fn is_interactive(&self) -> bool {
+        matches!(self, Gate::And(..) | Gate::Mul(..))
+    }
+}
+
+impl OutputWires {
+    pub fn wires(&self) -> Vec<Wire> {
+        match self {
+            OutputWires::Bits(wires) => wires.clone(),
+            OutputWires::Word(wire) => vec![*wire],
+        }
+    }
+}
+
+impl Circuit {
+    /// Check that every gate only reads earlier wires of the right kind and
+    /// row count, and return the shape of every wire. No wire may have more
+    /// than `MAX_ROW_FAN_OUT` rows per row of the largest input.
+    pub fn validate(&self) -> Result<Vec<WireShape>> {
+        let largest_input = self.inputs.iter().map(|input| input.rows).max().unwrap_or(0).max(1);
+        let max_rows = largest_input.saturating_mul(MAX_ROW_FAN_OUT);
+        let mut shapes: Vec<WireShape> = Vec::with_capacity(self.gates.len());
+        for (index, gate) in self.gates.iter().enumerate() {
+            let defined = |wire: Wire| -> Result<WireShape> {
+                shapes
+                    .get(wire.index())
+                    .copied()
+                    .ok_or_else(|| anyhow!("Gate {} reads undefined wire {}", index, wire.0))
+            };
+            let operand = |wire: Wire, kind: WireKind| -> Result<WireShape> {
+                let shape = defined(wire)?;
+                if shape.kind != kind {
+                    return Err(anyhow!("Gate {} ({}) expects a {:?} wire, wire {} is {:?}", index, gate.name(), kind, wire.0, shape.kind));
+                }
+                Ok(shape)
+            };
+            let same_rows = |a: WireShape, b: WireShape| -> Result<WireShape> {
+                if a.rows != b.rows {
+                    return Err(anyhow!("Gate {} ({}) mixes {} and {} rows", index, gate.name(), a.rows, b.rows));
+                }
+                Ok(a)
+            };
+            let input = |input: usize, sharing: InputSharing| -> Result<&InputColumn> {
+                let column = self
+                    .inputs
+                    .get(input)
+                    .ok_or_else(|| anyhow!("Gate {} reads undefined input {}", index, input))?;
+                if column.sharing != sharing {
+                    return Err(anyhow!("Gate {} ({}) cannot read {:?}-shared input {}", index, gate.name(), column.sharing, input));
+                }
+                Ok(column)
+            };
+
+            let shape = match gate {
+                Gate::Input { input: i, bit } => {
+                    let column = input(*i, InputSharing::Boolean)?;
+                    if *bit >= column.width {
+                        return Err(anyhow!("Gate {} reads bit {} of {}-bit input {}", index, bit, column.width, i));
+                    }
+                    WireShape { kind: WireKind::Bit, rows: column.rows }
+                }
+                Gate::InputWord { input: i } => {
+                    let column = input(*i, InputSharing::Arithmetic)?;
+                    WireShape { kind: WireKind::Word, rows: column.rows }
+                }
+                Gate::ConstBit { rows, .. } => WireShape { kind: WireKind::Bit, rows: *rows },
+                Gate::ConstWord { rows, .. } => WireShape { kind: WireKind::Word, rows: *rows },
+                Gate::Xor(a, b) | Gate::And(a, b) => {
+                    same_rows(operand(*a, WireKind::Bit)?, operand(*b, WireKind::Bit)?)?
+                }
+                Gate::Not(a) => operand(*a, WireKind::Bit)?,
+                Gate::Add(a, b) | Gate::Sub(a, b) | Gate::Mul(a, b) => {
+                    same_rows(operand(*a, WireKind::Word)?, operand(*b, WireKind::Word)?)?
+                }
+                Gate::MulConst(a, _) => operand(*a, WireKind::Word)?,
+                Gate::InjectBit { input, component } => {
+                    check_component(index, *component)?;
+                    WireShape { kind: WireKind::Word, ..operand(*input, WireKind::Bit)? }
+                }
+                Gate::InjectWord { input, component, bit } => {
+                    check_component(index, *component)?;
+                    if *bit >= 64 {
+                        return Err(anyhow!("Gate {} reads bit {} of a word", index, bit));
+                    }
+                    WireShape { kind: WireKind::Bit, ..operand(*input, WireKind::Word)? }
+                }
+                Gate::Slice { input, start, block, stride, blocks } => {
+                    let shape = defined(*input)?;
+                    let end = match blocks {
+                        0 => Some(0),
+                        _ => (blocks - 1)
+                            .checked_mul(*stride)
+                            .and_then(|offset| start.checked_add(*block).and_then(|tail| offset.checked_add(tail))),
+                    };
+                    let rows = block.checked_mul(*blocks);
+                    if end.is_none_or(|end| end > shape.rows) || rows.is_none() {
+                        return Err(anyhow!("Gate {} slices rows past the {} rows of wire {}", index, shape.rows, input.0));
+                    }
+                    WireShape { rows: block * blocks, ..shape }
+                }
+                Gate::Concat(inputs) => {
+                    let first = inputs
+                        .first()
+                        .ok_or_else(|| anyhow!("Gate {} concatenates no wires", index))?;
+                    let kind = defined(*first)?.kind;
+                    let mut rows: usize = 0;
+                    for input in inputs {
+                        rows = rows
+                            .checked_add(operand(*input, kind)?.rows)
+                            .ok_or_else(|| anyhow!("Gate {} concatenates too many rows", index))?;
+                    }
+                    WireShape { kind, rows }
+                }
+                Gate::Interleave { inputs, block } => {
+                    let first = inputs
+                        .first()
+                        .ok_or_else(|| anyhow!("Gate {} interleaves no wires", index))?;
+                    let shape = defined(*first)?;
+                    for input in inputs {
+                        same_rows(shape, operand(*input, shape.kind)?)?;
+                    }
+                    if *block == 0 || shape.rows % block != 0 {
+                        return Err(anyhow!("Gate {} interleaves {} rows in blocks of {}", index, shape.rows, block));
+                    }
+                    WireShape { rows: shape.rows * inputs.len(), ..shape }
+                }
+            };
+            if shape.rows > max_rows {
+                return Err(anyhow!(
+                    "Gate {} ({}) has {} rows, more than {} per row of the largest input",
+                    index,
+                    gate.name(),
+                    shape.rows,
+                    MAX_ROW_FAN_OUT
+                ));
+            }
+            shapes.push(shape);
+        }
+
+        for output in &self.outputs {
+            let kind = match output.wires {
+                OutputWires::Bits(_) => WireKind::Bit,
+                OutputWires::Word(_) => WireKind::Word,
+            };
+            for wire in output.wires.wires() {
+                match shapes.get(wire.index()) {
+                    Some(shape) if shape.kind == kind => {}
+                    _ => return Err(anyhow!("Output '{}' refers to invalid wire {}", output.name, wire.0)),
+                }
+            }
+        }
+        Ok(shapes)
+    }
+
+    /// Communication rounds needed before each wire is available: an
+    /// interactive gate is one round after its latest operand, a local gate
+    /// is available with its latest operand
+    pub fn levels(&self) -> Vec<u32> {
+        let mut levels: Vec<u32> = Vec::with_capacity(self.gates.len());
+        for gate in &self.gates {
+            let ready = gate.operands().iter().map(|w| levels[w.index()]).max().unwrap_or(0);
+            levels.push(ready + gate.is_interactive() as u32);
+        }
+        levels
+    }
+
+    /// Number of communication rounds to evaluate the circuit when all
+    /// interactive gates of a level share one round
+    pub fn depth(&self) -> u32 {
+        self.levels().into_iter().max().unwrap_or(0)
+    }
+
+    /// Serialized form shipped to the computing nodes
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// Parse and validate a serialized circuit
+    pub fn from_json(text: &str) -> Result<Circuit> {
+        let circuit: Circuit = serde_json::from_str(text)?;
+        circuit.validate()?;
+        Ok(circuit)
+    }
+
+    /// Number of gates of each type, ordered by name
+    pub fn gate_counts(&self) -> std::collections::BTreeMap<&'static str, usize> {
+        let mut counts = std::collections::BTreeMap::new();
+        for gate in &self.gates {
+            *counts.entry(gate.name()).or_insert(0) += 1;
+        }
+        counts
+    }
+}
+
+fn check_component(index: usize, component: u32) -> Result<()> {
+    if component > 2 {
+        return Err(anyhow!("Gate {} injects share component {}", index, component));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_roundtrip_and_validation() {
+        let mut builder = CircuitBuilder::new();
+        let input = builder.input(InputColumn {
+            table: "partsupp".to_string(),
+            table_id: 4,
+            schema_hash: 0,
+            column: 2,
+            width: 32,
+            sharing: InputSharing::Boolean,
+            rows: 4,
+        });
+        let bits = builder.input_bits(input);
+        let big = builder.compare_const(&bits, compare::Comparison::Greater, 5000);
+        builder.output("big", OutputWires::Bits(vec![big]));
+        let circuit = builder.finish();
+        assert_eq!(circuit.validate().unwrap().len(), circuit.gates.len());
+        let parsed = Circuit::from_json(&circuit.to_json().unwrap()).unwrap();
+        assert_eq!(parsed, circuit);
+
+        // A gate reading a later wire, mixing row counts, or a word gate on
+        // bit wires is rejected
+        let mut broken = circuit.clone();
+        broken.gates.push(Gate::Not(Wire(broken.gates.len() as u32 + 1)));
+        assert!(broken.validate().is_err());
+        let mut broken = circuit.clone();
+        broken.gates.push(Gate::ConstBit { value: true, rows: 5 });
+        broken.gates.push(Gate::Xor(Wire(0), Wire(broken.gates.len() as u32 - 1)));
+        assert!(broken.validate().is_err());
+        let mut broken = circuit.clone();
+        broken.gates.push(Gate::Add(Wire(0), Wire(1)));
+        assert!(Circuit::from_json(&broken.to_json().unwrap()).is_err());
+
+        // Constants and row gates cannot blow up the row count
+        let mut broken = circuit.clone();
+        broken.gates.push(Gate::ConstBit { value: false, rows: 4 * MAX_ROW_FAN_OUT + 1 });
+        assert!(broken.validate().is_err());
+        let mut broken = circuit.clone();
+        broken.gates.push(Gate::Concat(vec![Wire(0); MAX_ROW_FAN_OUT + 1]));
+        assert!(broken.validate().is_err());
+        let mut broken = circuit.clone();
+        broken.gates.push(Gate::ConstWord { value: 0, rows: usize::MAX });
+        assert!(broken.validate().is_err());
+
+        // Slice bounds that overflow are rejected, not wrapped
+        let mut broken = circuit.clone();
+        broken.gates.push(Gate::Slice { input: Wire(0), start: usize::MAX, block: 1, stride: 0, blocks: 1 });
+        assert!(broken.validate().is_err());
+    }
+}
@@ -0,0 +1,79 @@
+// Plaintext Evaluation
+// ====================
+// Reference evaluation of a circuit on plaintext inputs, used to test circuit
+// construction independently of the secret-sharing protocols. Input values
+// are given per row (up to 64 bits); share injections treat the whole value
+// as share component 0, the same convention as public constants.
+
+use anyhow::{anyhow, Result};
+
+use super::{Circuit, Gate, OutputWires};
+
+/// Evaluate a circuit on one vector of row values per input column and
+/// return one vector of row values per output (bit outputs reassembled, up
+/// to 64 bits)
+pub fn evaluate(circuit: &Circuit, inputs: &[Vec<u64>]) -> Result<Vec<Vec<u64>>> {
+    circuit.validate()?;
+    if inputs.len() != circuit.inputs.len() {
+        return Err(anyhow!("Circuit has {} inputs, got {}", circuit.inputs.len(), inputs.len()));
+    }
+    for (column, values) in circuit.inputs.iter().zip(inputs) {
+        if values.len() != column.rows {
+            return Err(anyhow!("Input {}.{} has {} rows, got {}", column.table, column.column, column.rows, values.len()));
+        }
+    }
+
+    let mut wires: Vec<Vec<u64>> = Vec::with_capacity(circuit.gates.len());
+    for gate in &circuit.gates {
+        let unary = |a: &Vec<u64>, f: &dyn Fn(u64) -> u64| -> Vec<u64> { a.iter().map(|x| f(*x)).collect() };
+        let binary = |a: &Vec<u64>, b: &Vec<u64>, f: fn(u64, u64) -> u64| -> Vec<u64> {
+            a.iter().zip(b).map(|(x, y)| f(*x, *y)).collect()
+        };
+        let w = |wire: super::Wire| &wires[wire.index()];
+        let values = match gate {
+            Gate::Input { input, bit } => unary(&inputs[*input], &|x| if *bit < 64 { (x >> bit) & 1 } else { 0 }),
+            Gate::InputWord { input } => inputs[*input].clone(),
+            Gate::ConstBit { value, rows } => vec![*value as u64; *rows],
+            Gate::ConstWord { value, rows } => vec![*value; *rows],
+            Gate::Xor(a, b) => binary(w(*a), w(*b), |x, y| x ^ y),
+            Gate::And(a, b) => binary(w(*a), w(*b), |x, y| x & y),
+            Gate::Not(a) => unary(w(*a), &|x| x ^ 1),
+            Gate::Add(a, b) => binary(w(*a), w(*b), u64::wrapping_add),
+            Gate::Sub(a, b) => binary(w(*a), w(*b), u64::wrapping_sub),
+            Gate::Mul(a, b) => binary(w(*a), w(*b), u64::wrapping_mul),
+            Gate::MulConst(a, value) => unary(w(*a), &|x| x.wrapping_mul(*value)),
+            Gate::InjectBit { input, component } => unary(w(*input), &|x| if *component == 0 { x } else { 0 }),
+            Gate::InjectWord { input, component, bit } => {
+                unary(w(*input), &|x| if *component == 0 { (x >> bit) & 1 } else { 0 })
+            }
+            Gate::Slice { input, start, block, stride, blocks } => (0..*blocks)
+                .flat_map(|q| w(*input)[start + q * stride..start + q * stride + block].iter().copied())
+                .collect(),
+            Gate::Concat(inputs) => inputs.iter().flat_map(|input| w(*input).iter().copied()).collect(),
+            Gate::Interleave { inputs, block } => {
+                let rows = w(inputs[0]).len();
+                (0..rows / block)
+                    .flat_map(|q| inputs.iter().flat_map(move |input| w(*input)[q * block..(q + 1) * block].iter().copied()))
+                    .collect()
+            }
+        };
+        wires.push(values);
+    }
+
+    circuit
+        .outputs
+        .iter()
+        .map(|output| match &output.wires {
+            OutputWires::Word(wire) => Ok(wires[wire.index()].clone()),
+            OutputWires::Bits(bits) if bits.len() > 64 => {
+                Err(anyhow!("Output '{}' has {} bits, at most 64 can be reassembled", output.name, bits.len()))
+            }
+            OutputWires::Bits(bits) => {
+                let rows = bits.first().map_or(0, |w| wires[w.index()].len());
+                Ok((0..rows)
+                    .map(|r| bits.iter().enumerate().fold(0, |acc, (j, w)| acc | (wires[w.index()][r] << j)))
+                    .collect())
+            }
+        })
+        .collect()
+}
@@ -1 +1,3 @@
+pub mod circuit;
 pub mod read_config;
+pub mod sharing;
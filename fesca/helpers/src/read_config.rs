@@ -7,16 +7,14 @@ pub fn read_config(path: &str, name: &str) -> Option<String> {
     let reader = BufReader::new(file);
 
     // Read line by line
-    for line in reader.lines() {
-        if let Ok(line) = line {
-            let trimmed_line = line.trim();
+    for line in reader.lines().map_while(Result::ok) {
+        let trimmed_line = line.trim();
 
-            // Split once at the first colon
-            if let Some((key, value)) = trimmed_line.split_once(':') {
-                if key.trim() == name {
-                    return Some(value.trim().to_string());
-                }
-            }
+        // Split once at the first colon
+        if let Some((key, value)) = trimmed_line.split_once(':')
+            && key.trim() == name
+        {
+            return Some(value.trim().to_string());
         }
     }
 
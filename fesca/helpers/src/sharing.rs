@@ -0,0 +1,80 @@
+// Share Reconstruction
+// ====================
+// Reconstruction of values shared with the three-party replicated scheme used
+// by the computing nodes: x = x_0 ^ x_1 ^ x_2 for boolean shares, or
+// x = x_0 + x_1 + x_2 (mod 2^64) for arithmetic shares, where node i holds
+// the pair (x_i, x_{i-1}).
+//
+// Every component is held by two nodes, so the party reconstructing a value
+// gets each component twice and can check that the copies agree. A mismatch
+// means a node deviated from the protocol or the shares belong to different
+// computations; the value is then rejected instead of returned.
+
+use anyhow::{anyhow, Result};
+
+use crate::circuit::InputSharing;
+
+/// Number of computing nodes holding shares
+pub const NUM_PARTIES: usize = 3;
+
+/// Share components (x_i, x_{i-1}) held by node i
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReplicatedShare {
+    pub own: u64,
+    pub prev: u64,
+}
+
+/// Reconstruct a value from the shares of nodes 0, 1 and 2, in that order,
+/// after checking that both copies of every component agree. Boolean shares
+/// are combined word-wise, so one call reconstructs 64 packed bits.
+pub fn reconstruct_secret(shares: &[ReplicatedShare], sharing: InputSharing) -> Result<u64> {
+    if shares.len() != NUM_PARTIES {
+        return Err(anyhow!("Expected shares of {} nodes, got {}", NUM_PARTIES, shares.len()));
+    }
+    for (i, share) in shares.iter().enumerate() {
+        let previous = (i + NUM_PARTIES - 1) % NUM_PARTIES;
+        if share.prev != shares[previous].own {
+            return Err(anyhow!(
+                "Inconsistent shares: node {} and node {} disagree on share component {}",
+                i,
+                previous,
+                previous
+            ));
+        }
+    }
+
+    let components = shares.iter().map(|share| share.own);
+    Ok(match sharing {
+        InputSharing::Boolean => components.fold(0, |acc, x| acc ^ x),
+        InputSharing::Arithmetic => components.fold(0, |acc: u64, x| acc.wrapping_add(x)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Replicated shares of `components` (x_0, x_1, x_2)
+    fn replicate(components: [u64; 3]) -> Vec<ReplicatedShare> {
+        (0..3)
+            .map(|i| ReplicatedShare { own: components[i], prev: components[(i + 2) % 3] })
+            .collect()
+    }
+
+    #[test]
+    fn test_reconstruct_and_detect_inconsistency() {
+        let shares = replicate([0x0F, 0xF0, 0x33]);
+        assert_eq!(reconstruct_secret(&shares, InputSharing::Boolean).unwrap(), 0x0F ^ 0xF0 ^ 0x33);
+
+        let shares = replicate([u64::MAX, 5, 7]);
+        assert_eq!(reconstruct_secret(&shares, InputSharing::Arithmetic).unwrap(), 11);
+
+        // A node reporting a different copy of x_1 is caught
+        let mut tampered = shares.clone();
+        tampered[2].prev ^= 1;
+        let error = reconstruct_secret(&tampered, InputSharing::Arithmetic).unwrap_err();
+        assert!(error.to_string().contains("node 2 and node 1"));
+
+        assert!(reconstruct_secret(&shares[..2], InputSharing::Boolean).is_err());
+    }
+}
@@ -0,0 +1,3 @@
+// FESCA library: building blocks shared by the command-line entry point and tests.
+
+pub mod local;
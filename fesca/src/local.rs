@@ -0,0 +1,633 @@
+// Local Three-Party Simulation
+// ============================
+// Runs the whole FESCA pipeline in one process: three computing node servers
+// on loopback ports, the data owner sharing a table with them over gRPC, and
+// the analyst running a query on the shared table.
+//
+// The computing nodes are started with the regular `start_server`,
+// `serve_exchange` and `serve_queries`, the data owner uses the regular
+// `ShareClient`, shares end up on disk through the regular
+// `BinaryShareStorage` and queries are submitted with the analyst's regular
+// `QueryClient`, so tests built on this module exercise the same code paths
+// as a deployment on three machines.
+
+use anyhow::{anyhow, Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use log::info;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+use computing_node::{serve_exchange, serve_queries, start_server, Mailbox, PeerExchange};
+use data_analyst::catalog::Catalog;
+use data_analyst::client::QueryClient;
+use data_analyst::config::ComputingNodes as QueryNodes;
+use data_analyst::execute_query;
+use data_owner::config::{load_data_owner_config, load_table_data, ComputingNodes, DataOwnerConfig};
+use data_owner::run_data_owner_with_config;
+
+/// Number of attempts to wait for a freshly started server to accept connections
+const STARTUP_ATTEMPTS: u32 = 50;
+
+/// Options for a local simulation run
+#[derive(Debug, Clone)]
+pub struct LocalOptions {
+    /// Data owner configuration providing the table and owner information.
+    /// The computing node URLs in it are replaced by the local servers.
+    pub data_owner_config: String,
+    /// Share port of computing node 0. Nodes 1 and 2 use the following
+    /// ports, the exchange and query servers the six after those. With 0
+    /// every server gets a free port picked by the system.
+    pub base_port: u16,
+    /// Directory under which each node gets its own storage directory
+    pub storage_root: PathBuf,
+    /// SQL query to run once the table is shared, `SELECT * FROM <table>`
+    /// if not given
+    pub query: Option<String>,
+}
+
+impl Default for LocalOptions {
+    fn default() -> Self {
+        Self {
+            data_owner_config: "data_owner/config_data_owner.json".to_string(),
+            base_port: 0,
+            storage_root: std::env::temp_dir().join(format!("fesca_local_{}", std::process::id())),
+            query: None,
+        }
+    }
+}
+
+/// Three computing nodes running on loopback ports
+pub struct LocalCluster {
+    ports: [u16; 3],
+    query_ports: [u16; 3],
+    storage_paths: [PathBuf; 3],
+    servers: Vec<JoinHandle<Result<()>>>,
+}
+
+impl LocalCluster {
+    /// Start three computing nodes with share, exchange and query servers.
+    /// Ports `base_port..base_port + 9` are used, or free ports picked by the
+    /// system if `base_port` is 0. Returns once all servers accept connections.
+    pub async fn start(base_port: u16, storage_root: &Path) -> Result<Self> {
+        // `start_server` binds its own port, so the share ports are only
+        // reserved here and released right before the servers start
+        let share_listeners = bind_loopback(base_port).await?;
+        let exchange_listeners = bind_loopback(if base_port == 0 { 0 } else { base_port + 3 }).await?;
+        let query_listeners = bind_loopback(if base_port == 0 { 0 } else { base_port + 6 }).await?;
+        let ports = local_ports(&share_listeners)?;
+        let exchange_ports = local_ports(&exchange_listeners)?;
+        let query_ports = local_ports(&query_listeners)?;
+        let storage_paths = [0, 1, 2].map(|i| storage_root.join(format!("node{}", i)));
+        drop(share_listeners);
+
+        let mut servers = Vec::new();
+        let listeners = exchange_listeners.into_iter().zip(query_listeners);
+        for (node_id, (exchange_listener, query_listener)) in listeners.enumerate() {
+            let storage = storage_paths[node_id].to_string_lossy().to_string();
+            let mailbox = Arc::new(Mailbox::new());
+            let next_url = format!("http://127.0.0.1:{}", exchange_ports[(node_id + 1) % 3]);
+            let exchange = Arc::new(PeerExchange::new(node_id as u32, next_url, mailbox.clone()));
+
+            servers.push(tokio::spawn(start_server(ports[node_id], storage.clone())));
+            servers.push(tokio::spawn(serve_exchange(exchange_listener, node_id as u32, mailbox)));
+            servers.push(tokio::spawn(serve_queries(query_listener, storage, exchange)));
+        }
+
+        let mut cluster = Self { ports, query_ports, storage_paths, servers };
+        for port in ports {
+            if let Err(e) = wait_for_port(port, &mut cluster.servers).await {
+                cluster.shutdown();
+                return Err(e);
+            }
+        }
+        info!("Local computing nodes listening on ports {:?}, queries on {:?}", ports, query_ports);
+
+        Ok(cluster)
+    }
+
+    /// URLs the data owner uses to reach the local nodes
+    pub fn node_urls(&self) -> ComputingNodes {
+        let [url0, url1, url2] = self.ports.map(|port| format!("http://127.0.0.1:{}", port));
+        ComputingNodes {
+            node0_url: url0,
+            node1_url: url1,
+            node2_url: url2,
+        }
+    }
+
+    /// Client submitting analyst queries to the local nodes
+    pub fn query_client(&self) -> QueryClient {
+        let [url0, url1, url2] = self.query_ports.map(|port| format!("http://127.0.0.1:{}", port));
+        QueryClient::new(&QueryNodes {
+            node0_url: url0,
+            node1_url: url1,
+            node2_url: url2,
+        })
+    }
+
+    /// Storage directory of the given node
+    pub fn storage_path(&self, node: usize) -> &Path {
+        &self.storage_paths[node]
+    }
+
+    /// Run the data owner against the local nodes
+    pub async fn load_table(&self, mut config: DataOwnerConfig) -> Result<()> {
+        config.computing_nodes = self.node_urls();
+        run_data_owner_with_config(config).await
+    }
+
+    /// Stop the servers
+    pub fn shutdown(self) {
+        for server in self.servers {
+            server.abort();
+        }
+    }
+}
+
+/// Entry point of the `local` role: share the configured table with three
+/// in-process computing nodes, run the query on them and print the result.
+pub fn run_local(options: LocalOptions) -> Result<()> {
+    let rt = tokio::runtime::Runtime::new()?;
+    rt.block_on(run_local_async(options))
+}
+
+async fn run_local_async(options: LocalOptions) -> Result<()> {
+    let config = load_data_owner_config(&options.data_owner_config)
+        .with_context(|| format!("Failed to load {}", options.data_owner_config))?;
+    let (_, schema) = load_table_data(&config.data_path)
+        .map_err(|e| anyhow!("Failed to load table data: {e}"))?;
+
+    let sql = options.query.unwrap_or_else(|| format!("SELECT * FROM {}", schema.table_name));
+
+    let cluster = LocalCluster::start(options.base_port, &options.storage_root).await?;
+    let result = async {
+        cluster.load_table(config).await?;
+        let catalog = Catalog::load_dir(cluster.storage_path(0))?;
+        execute_query(&sql, &catalog, &cluster.query_client()).await
+    }
+    .await;
+    cluster.shutdown();
+    fs::remove_dir_all(&options.storage_root).ok();
+
+    let table = result?;
+    info!("{} returned {} rows", sql, table.rows.len());
+    print!("{}", table);
+    Ok(())
+}
+
+/// Bind one loopback listener per node on `first_port` and the two
+/// following ports, or on free ports if `first_port` is 0
+async fn bind_loopback(first_port: u16) -> Result<[TcpListener; 3]> {
+    let mut listeners = Vec::new();
+    for i in 0..3 {
+        let port = if first_port == 0 { 0 } else { first_port + i };
+        let listener = TcpListener::bind(("127.0.0.1", port))
+            .await
+            .with_context(|| format!("Failed to bind port {}", port))?;
+        listeners.push(listener);
+    }
+    listeners.try_into().map_err(|_| anyhow!("Expected three listeners"))
+}
+
+/// Ports the listeners are bound to
+fn local_ports(listeners: &[TcpListener; 3]) -> Result<[u16; 3]> {
+    Ok([
+        listeners[0].local_addr()?.port(),
+        listeners[1].local_addr()?.port(),
+        listeners[2].local_addr()?.port(),
+    ])
+}
+
+/// Wait until a server accepts connections on the given loopback port. The
+/// servers only return on failure, e.g. when another process took a reserved
+/// port before the server bound it, so this fails as soon as one has exited.
+async fn wait_for_port(port: u16, servers: &mut [JoinHandle<Result<()>>]) -> Result<()> {
+    for _ in 0..STARTUP_ATTEMPTS {
+        if let Some(server) = servers.iter_mut().find(|server| server.is_finished()) {
+            return Err(match server.await {
+                Ok(Err(e)) => e.context("Computing node server failed to start"),
+                Ok(Ok(())) => anyhow!("Computing node server stopped during start-up"),
+                Err(e) => anyhow!("Computing node server panicked: {}", e),
+            });
+        }
+        if TcpStream::connect(("127.0.0.1", port)).await.is_ok() {
+            return Ok(());
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    Err(anyhow!("Computing node on port {} did not start", port))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use computing_node::receive::storage::extract_column_bits;
+    use computing_node::BinaryShareStorage;
+    use data_analyst::binder::bind;
+    use data_analyst::planner::plan_sql;
+    use data_analyst::run_query;
+    use data_analyst::result::{ResultTable, Value};
+    use data_owner::encode::decode_value;
+    use data_owner::types::{BitVector, Sharing, TableSchema};
+
+    /// Reconstruct all rows of a stored table from the share files
+    fn select_all(cluster: &LocalCluster, owner_id: &str, schema: &TableSchema) -> Result<Vec<Vec<String>>> {
+        let party_file = |node: usize| {
+            cluster
+                .storage_path(node)
+                .join(owner_id)
+                .join(&schema.table_name)
+                .join(format!("party{}_data.bin", node))
+        };
+
+        // Party 0 holds shares (x_0, x_2), party 1 holds (x_1, x_0), XOR or additive per column
+        let party0 = BinaryShareStorage::read_party_data(&party_file(0), 0, schema.table_id)?.rows;
+        let party1 = BinaryShareStorage::read_party_data(&party_file(1), 1, schema.table_id)?.rows;
+        if party0.len() != party1.len() {
+            return Err(anyhow!("Parties disagree on row count"));
+        }
+
+        let mut rows = Vec::new();
+        for (row0, row1) in party0.iter().zip(&party1) {
+            // Rows with fewer fields than the schema only store their leading columns
+            let mut values = Vec::new();
+            for (col_idx, column) in schema.columns.iter().enumerate().take(row0.column_bit_offsets.len()) {
+                let shares0 = extract_column_bits(row0, col_idx)?;
+                let shares1 = extract_column_bits(row1, col_idx)?;
+                let mut bits = BitVector::new();
+                match column.sharing {
+                    Sharing::Boolean => {
+                        for ((x0, x2), x1) in shares0.bits_a.iter().zip(&shares0.bits_b).zip(&shares1.bits_a) {
+                            bits.push(x0 ^ x2 ^ x1);
+                        }
+                    }
+                    Sharing::Arithmetic => {
+                        let value = [&shares0.bits_a, &shares0.bits_b, &shares1.bits_a]
+                            .iter()
+                            .fold(0u64, |acc, share| acc.wrapping_add(to_u64(share)));
+                        bits.extend((0..64).map(|j| (value >> j) & 1 == 1));
+                    }
+                }
+                values.push(decode_value(&bits, column));
+            }
+            rows.push(values);
+        }
+        Ok(rows)
+    }
+
+    /// Read LSB-first share bits as an integer
+    fn to_u64(bits: &[bool]) -> u64 {
+        bits.iter().take(64).enumerate().fold(0, |acc, (j, bit)| acc | ((*bit as u64) << j))
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_local_pipeline_reconstructs_table() {
+        let storage_root = std::env::temp_dir().join(format!("fesca_local_test_{}", std::process::id()));
+        let config = load_data_owner_config("data_owner/config_data_owner.json").unwrap();
+        let (records, schema) = load_table_data(&config.data_path).unwrap();
+
+        let cluster = LocalCluster::start(0, &storage_root).await.unwrap();
+        cluster.load_table(config.clone()).await.unwrap();
+        let rows = select_all(&cluster, &config.data_owner.owner_id, &schema).unwrap();
+        cluster.shutdown();
+        fs::remove_dir_all(&storage_root).ok();
+
+        assert_eq!(rows.len(), records.len());
+        for (row, record) in rows.iter().zip(&records) {
+            // Numeric columns reconstruct exactly
+            assert_eq!(row.len(), record.len());
+            assert_eq!(row[..4], record[..4]);
+            // Strings are truncated to their max_chars
+            assert!(record[4].starts_with(&row[4]));
+        }
+    }
+
+    /// Cluster with the data owner's table shared, to run queries on
+    struct LoadedCluster {
+        cluster: LocalCluster,
+        catalog: Catalog,
+        storage_root: PathBuf,
+        /// Rows of the shared table as in the data owner's file
+        records: Vec<Vec<String>>,
+    }
+
+    impl LoadedCluster {
+        async fn start(name: &str) -> Self {
+            let storage_root = std::env::temp_dir().join(format!("fesca_{}_test_{}", name, std::process::id()));
+            let config = load_data_owner_config("data_owner/config_data_owner.json").unwrap();
+            let (records, _) = load_table_data(&config.data_path).unwrap();
+
+            let cluster = LocalCluster::start(0, &storage_root).await.unwrap();
+            cluster.load_table(config).await.unwrap();
+            let catalog = Catalog::load_dir(cluster.storage_path(0)).unwrap();
+            Self { cluster, catalog, storage_root, records }
+        }
+
+        /// Share another table, as a second data owner, and return its rows
+        async fn add_table(&mut self, data_path: &str, owner_id: &str) -> Vec<Vec<String>> {
+            let mut config = load_data_owner_config("data_owner/config_data_owner.json").unwrap();
+            config.data_owner.owner_id = owner_id.to_string();
+            config.data_path = data_path.to_string();
+            let (records, _) = load_table_data(&config.data_path).unwrap();
+            self.cluster.load_table(config).await.unwrap();
+            self.catalog = Catalog::load_dir(self.cluster.storage_path(0)).unwrap();
+            records
+        }
+
+        async fn query(&self, sql: &str) -> Result<ResultTable> {
+            execute_query(sql, &self.catalog, &self.cluster.query_client()).await
+        }
+
+        fn shutdown(self) {
+            self.cluster.shutdown();
+            fs::remove_dir_all(&self.storage_root).ok();
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_local_cluster_runs_query() {
+        let loaded = LoadedCluster::start("query").await;
+        let result = loaded
+            .query("SELECT part_key * available_qty AS product, available_qty > 5000 AS large, extra_code FROM partsupp")
+            .await;
+        let records = loaded.records.clone();
+        loaded.shutdown();
+
+        let table = result.unwrap();
+        assert_eq!(table.rows.len(), records.len());
+        for (row, record) in table.rows.iter().zip(&records) {
+            let part_key: u64 = record[0].parse().unwrap();
+            let available_qty: u64 = record[2].parse().unwrap();
+            assert_eq!(row[0], Value::UInt(part_key * available_qty));
+            assert_eq!(row[1], Value::Bool(available_qty > 5000));
+            assert_eq!(row[2], Value::Text(record[4].chars().take(8).collect()));
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_local_cluster_filters_rows() {
+        let loaded = LoadedCluster::start("filter").await;
+        let filtered = loaded
+            .query("SELECT part_key, available_qty FROM partsupp WHERE available_qty > 5000")
+            .await;
+        let records = loaded.records.clone();
+        loaded.shutdown();
+
+        let expected: Vec<Vec<Value>> = records
+            .iter()
+            .map(|record| [0, 2].map(|i| record[i].parse::<u64>().unwrap()))
+            .filter(|[_, available_qty]| *available_qty > 5000)
+            .map(|row| row.map(Value::UInt).to_vec())
+            .collect();
+        assert!(!expected.is_empty() && expected.len() < records.len());
+        assert_eq!(filtered.unwrap().rows, expected);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_local_cluster_aggregates() {
+        let loaded = LoadedCluster::start("aggregate").await;
+        let aggregated = loaded
+            .query(
+                "SELECT COUNT(*), SUM(available_qty), AVG(available_qty), MIN(part_key), MAX(part_key) \
+                 FROM partsupp WHERE available_qty > 5000",
+            )
+            .await;
+        let records = loaded.records.clone();
+        loaded.shutdown();
+
+        let rows: Vec<[u64; 2]> = records
+            .iter()
+            .map(|record| [0, 2].map(|i| record[i].parse::<u64>().unwrap()))
+            .filter(|[_, available_qty]| *available_qty > 5000)
+            .collect();
+        let count = rows.len() as u64;
+        let sum: u64 = rows.iter().map(|[_, available_qty]| available_qty).sum();
+        let keys = || rows.iter().map(|[part_key, _]| *part_key);
+        assert_eq!(
+            aggregated.unwrap().rows,
+            vec![vec![
+                Value::UInt(count),
+                Value::UInt(sum),
+                Value::Float(sum as f64 / count as f64),
+                Value::UInt(keys().min().unwrap()),
+                Value::UInt(keys().max().unwrap()),
+            ]]
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_local_cluster_sorts_rows() {
+        let loaded = LoadedCluster::start("sort").await;
+        let sorted = loaded
+            .query(
+                "SELECT part_key, available_qty FROM partsupp WHERE available_qty > 3500 \
+                 ORDER BY available_qty DESC, part_key",
+            )
+            .await;
+        let limited = loaded
+            .query("SELECT part_key, available_qty FROM partsupp WHERE available_qty > 3500 LIMIT 2")
+            .await;
+        let records = loaded.records.clone();
+        loaded.shutdown();
+
+        let mut expected: Vec<[u64; 2]> = records
+            .iter()
+            .map(|record| [0, 2].map(|i| record[i].parse::<u64>().unwrap()))
+            .filter(|[_, available_qty]| *available_qty > 3500)
+            .collect();
+        let to_values = |rows: &[[u64; 2]]| -> Vec<Vec<Value>> {
+            rows.iter().map(|row| row.map(Value::UInt).to_vec()).collect()
+        };
+        let filtered = to_values(&expected);
+        expected.sort_by_key(|[part_key, available_qty]| (std::cmp::Reverse(*available_qty), *part_key));
+        assert_eq!(sorted.unwrap().rows, to_values(&expected));
+
+        // LIMIT keeps two of the three valid rows
+        let limited = limited.unwrap().rows;
+        assert_eq!(limited.len(), 2);
+        assert!(limited.iter().all(|row| filtered.contains(row)), "{:?}", limited);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_local_cluster_deduplicates_rows() {
+        let loaded = LoadedCluster::start("distinct").await;
+        let sql = "SELECT DISTINCT supplier_key FROM partsupp ORDER BY supplier_key";
+        let distinct = loaded.query(sql).await;
+        let query = bind(&plan_sql(sql).unwrap(), &loaded.catalog).unwrap();
+        let compacted = run_query(&query, &loaded.catalog, &loaded.cluster.query_client(), true).await;
+        let records = loaded.records.clone();
+        loaded.shutdown();
+
+        let mut supplier_keys: Vec<u64> = records.iter().map(|record| record[1].parse().unwrap()).collect();
+        supplier_keys.sort();
+        supplier_keys.dedup();
+        let supplier_keys: Vec<Vec<Value>> = supplier_keys.into_iter().map(|key| vec![Value::UInt(key)]).collect();
+        assert_eq!(distinct.unwrap().rows, supplier_keys);
+        // Revealing the number of distinct rows returns just as many
+        assert_eq!(compacted.unwrap().rows, supplier_keys);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_local_cluster_matches_patterns() {
+        let loaded = LoadedCluster::start("like").await;
+        let matched = loaded
+            .query("SELECT available_qty FROM partsupp WHERE extra_code LIKE '%even%' OR extra_code LIKE 'a_%'")
+            .await;
+        let records = loaded.records.clone();
+        loaded.shutdown();
+
+        let expected: Vec<Vec<Value>> = records
+            .iter()
+            .filter(|record| {
+                let extra_code: String = record[4].chars().take(8).collect();
+                extra_code.contains("even") || (extra_code.starts_with('a') && extra_code.len() > 1)
+            })
+            .map(|record| vec![Value::UInt(record[2].parse().unwrap())])
+            .collect();
+        assert!(!expected.is_empty() && expected.len() < records.len());
+        assert_eq!(matched.unwrap().rows, expected);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_local_cluster_computes_on_fixed_point() {
+        let loaded = LoadedCluster::start("fixed_point").await;
+        let costs = loaded
+            .query(
+                "SELECT SUM(supply_cost), AVG(supply_cost), MAX(supply_cost) FROM partsupp \
+                 WHERE supply_cost > 500.5",
+            )
+            .await;
+        let records = loaded.records.clone();
+        loaded.shutdown();
+
+        // supply_cost is stored in cents
+        let cents: Vec<u64> = records
+            .iter()
+            .map(|record| record[3].replace('.', "").parse().unwrap())
+            .filter(|cents| *cents > 50050)
+            .collect();
+        let total: u64 = cents.iter().sum();
+        assert_eq!(
+            costs.unwrap().rows,
+            vec![vec![
+                Value::Float(total as f64 / 100.0),
+                Value::Float(total as f64 / cents.len() as f64 / 100.0),
+                Value::Float(*cents.iter().max().unwrap() as f64 / 100.0),
+            ]]
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_local_cluster_joins_tables_of_two_owners() {
+        let mut loaded = LoadedCluster::start("join").await;
+        let part_records = loaded.add_table("data_owner/data/part.tbl", "owner_002").await;
+        // part.part_key is unique, so this runs as a sort-merge join
+        let joined = loaded
+            .query(
+                "SELECT partsupp.part_key, available_qty, size FROM partsupp \
+                 JOIN part ON partsupp.part_key = part.part_key WHERE size > 5",
+            )
+            .await;
+        let partsupp_records = loaded.records.clone();
+        loaded.shutdown();
+
+        let mut expected: Vec<Vec<u64>> = Vec::new();
+        for ps in &partsupp_records {
+            for p in part_records.iter().filter(|p| p[0] == ps[0] && p[5].parse::<u64>().unwrap() > 5) {
+                expected.push([&ps[0], &ps[2], &p[5]].map(|v| v.parse().unwrap()).to_vec());
+            }
+        }
+        assert!(!expected.is_empty());
+        // Rows come out ordered by the join key, in any order within a key
+        let mut rows: Vec<Vec<u64>> = joined
+            .unwrap()
+            .rows
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|value| match value {
+                        Value::UInt(value) => *value,
+                        other => panic!("unexpected value {:?}", other),
+                    })
+                    .collect()
+            })
+            .collect();
+        rows.sort();
+        expected.sort();
+        assert_eq!(rows, expected);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_local_cluster_groups_rows() {
+        let mut loaded = LoadedCluster::start("group").await;
+        let part_records = loaded.add_table("data_owner/data/part.tbl", "owner_002").await;
+        let grouped = loaded
+            .query(
+                "SELECT part.part_key, COUNT(*), MAX(available_qty) FROM partsupp \
+                 JOIN part ON partsupp.part_key = part.part_key GROUP BY part.part_key",
+            )
+            .await;
+        let averages = loaded
+            .query("SELECT supplier_key, AVG(available_qty) FROM partsupp GROUP BY supplier_key")
+            .await;
+        // Averages are only divided by the analyst, so rows cannot be sorted by them
+        let sorted_by_average = loaded
+            .query(
+                "SELECT supplier_key, AVG(available_qty) FROM partsupp GROUP BY supplier_key \
+                 ORDER BY AVG(available_qty)",
+            )
+            .await;
+        let records = loaded.records.clone();
+        loaded.shutdown();
+
+        let mut expected: Vec<Vec<Value>> = Vec::new();
+        for p in &part_records {
+            let quantities: Vec<u64> = records
+                .iter()
+                .filter(|ps| ps[0] == p[0])
+                .map(|ps| ps[2].parse().unwrap())
+                .collect();
+            if let Some(max) = quantities.iter().max() {
+                let row = [p[0].parse().unwrap(), quantities.len() as u64, *max];
+                expected.push(row.map(Value::UInt).to_vec());
+            }
+        }
+        assert!(!expected.is_empty());
+        let by_key = |mut rows: Vec<Vec<Value>>| {
+            rows.sort_by_key(|row| match row[0] {
+                Value::UInt(key) => key,
+                _ => unreachable!(),
+            });
+            rows
+        };
+        assert_eq!(by_key(grouped.unwrap().rows), expected);
+
+        let mut groups: BTreeMap<u64, (u64, u64)> = BTreeMap::new();
+        for record in &records {
+            let group = groups.entry(record[1].parse().unwrap()).or_default();
+            group.0 += record[2].parse::<u64>().unwrap();
+            group.1 += 1;
+        }
+        let expected: Vec<Vec<Value>> = groups
+            .into_iter()
+            .map(|(key, (sum, count))| vec![Value::UInt(key), Value::Float(sum as f64 / count as f64)])
+            .collect();
+        assert_eq!(by_key(averages.unwrap().rows), expected);
+        let error = sorted_by_average.unwrap_err().to_string();
+        assert!(error.starts_with("Unsupported query: ORDER BY AVG(available_qty)"), "{}", error);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_wait_for_port_fails_when_server_exits() {
+        // The storage directory cannot be created below a file, so the server exits
+        let port = local_ports(&bind_loopback(0).await.unwrap()).unwrap()[0];
+        let mut servers = vec![tokio::spawn(start_server(port, "/dev/null/fesca".to_string()))];
+
+        let error = wait_for_port(port, &mut servers).await.unwrap_err();
+        assert!(error.to_string().contains("failed to start"), "{}", error);
+    }
+}
@@ -2,29 +2,59 @@
 The main entry point for the FESCA framework.
 This file sets up the command-line interface and starts the appropriate role based on user input.
 Example usage:
-    cargo run -- --role DataOwner
+    cargo run -- data_owner
+    cargo run -- data_analyst                                   (interactive prompt)
+    cargo run -- data_analyst --query "SELECT * FROM partsupp"
+    cargo run -- data_analyst --file queries.sql
+    cargo run -- data_analyst --reveal-cardinality --query "SELECT DISTINCT supplier_key FROM partsupp"
+    cargo run -- local    (all three computing nodes, the data owner and the analyst in one process)
+    cargo run -- local --query "SELECT supplier_key, COUNT(*) FROM partsupp GROUP BY supplier_key"
  */
-use std::{error::Error, process};
-use clap::{Parser, ValueEnum, error::ErrorKind};
+use std::{error::Error, path::PathBuf, process};
+use clap::{Args, Parser, Subcommand, error::ErrorKind};
 use env_logger::{Builder, Env};
 use log::{error, info};
 
 use data_owner::run_data_owner;
-use data_analyst::run as run_data_analyst;
+use data_analyst::{run as run_data_analyst, AnalystOptions};
 use computing_node::run_computing_node;
+use fesca::local::{run_local, LocalOptions};
 
-#[derive(Clone, ValueEnum, Debug)]
-#[clap(rename_all = "snake_case")]
+#[derive(Subcommand, Debug)]
+#[command(rename_all = "snake_case")]
 enum Role {
     DataOwner,
-    DataAnalyst,
+    DataAnalyst(AnalystArgs),
     ComputingNode,
+    Local(LocalArgs),
+}
+
+// Data analyst arguments; without --query or --file an interactive prompt starts
+#[derive(Args, Debug)]
+struct AnalystArgs {
+    /// SQL query to run
+    #[arg(long, conflicts_with = "file")]
+    query: Option<String>,
+    /// File with SQL statements to run
+    #[arg(long)]
+    file: Option<PathBuf>,
+    /// Reveal the number of rows SELECT DISTINCT produces, making the rest of the query cheaper
+    #[arg(long)]
+    reveal_cardinality: bool,
+}
+
+// Local simulation arguments; without --query the whole table is selected
+#[derive(Args, Debug)]
+struct LocalArgs {
+    /// SQL query to run on the shared table
+    #[arg(long)]
+    query: Option<String>,
 }
 
 // CLI arguments
 #[derive(Parser, Debug)]
 struct Cli {
-    #[arg(value_enum)]
+    #[command(subcommand)]
     role: Role,
 }
 
@@ -36,10 +66,13 @@ fn main() -> Result<(), Box<dyn Error>> {
     let args = match Cli::try_parse() {
         Ok(a) => a,
 
-        Err(e) if e.kind() == ErrorKind::MissingRequiredArgument => {
+        Err(e) if matches!(
+            e.kind(),
+            ErrorKind::MissingSubcommand | ErrorKind::DisplayHelpOnMissingArgumentOrSubcommand
+        ) => {
             eprintln!(
                 "Error: no role specified.\n\
-                 Please run with one of: data_owner, data_analyst, computing_node\n\n\
+                 Please run with one of: data_owner, data_analyst, computing_node, local\n\n\
                  Example:\n  cargo run -- data_analyst"
             );
             process::exit(1);
@@ -56,9 +89,14 @@ fn main() -> Result<(), Box<dyn Error>> {
                 process::exit(1);
             }
         }
-        Role::DataAnalyst => {
+        Role::DataAnalyst(analyst) => {
             info!("Running as Data Analyst...");
-            if let Err(e) = run_data_analyst() {
+            let options = AnalystOptions {
+                query: analyst.query,
+                file: analyst.file,
+                reveal_cardinality: analyst.reveal_cardinality,
+            };
+            if let Err(e) = run_data_analyst(options) {
                 error!("Error running as data analyst: {}", e);
                 process::exit(1);
             }
@@ -70,6 +108,17 @@ fn main() -> Result<(), Box<dyn Error>> {
                 process::exit(1);
             }
         }
+        Role::Local(local) => {
+            info!("Running local three-party simulation...");
+            let options = LocalOptions {
+                query: local.query,
+                ..LocalOptions::default()
+            };
+            if let Err(e) = run_local(options) {
+                error!("Error running local simulation: {}", e);
+                process::exit(1);
+            }
+        }
     }
 
     Ok(())